@@ -1,3 +1,36 @@
+// the simulation steps at a fixed rate and frames are drawn as often as the
+// backend allows, so it runs the same everywhere. Examples set `TICK_RATE`,
+// `update(dt)` runs once per tick and `render` gets how far the frame is into
+// the next tick, for blending the last two states
+#[cfg(not(feature = "wasm"))]
+struct FixedTimestep {
+    tick: f32,
+    // a stall (dragging the window, a breakpoint) is cut to this, rather than
+    // queueing up more ticks than can be caught up on
+    max_frame_time: f32,
+    accumulator: f32,
+}
+
+#[cfg(not(feature = "wasm"))]
+impl FixedTimestep {
+    fn new(tick_rate: f32) -> Self {
+        Self {
+            tick: 1.0 / tick_rate,
+            max_frame_time: 0.25,
+            accumulator: 0.0,
+        }
+    }
+    // runs the ticks `frame_time` is worth, returns the interpolation alpha in [0, 1)
+    fn advance(&mut self, frame_time: f32, mut update: impl FnMut(f32)) -> f32 {
+        self.accumulator += frame_time.min(self.max_frame_time);
+        while self.accumulator >= self.tick {
+            update(self.tick);
+            self.accumulator -= self.tick;
+        }
+        self.accumulator / self.tick
+    }
+}
+
 #[cfg(not(feature = "wasm"))]
 fn get_timer() -> impl FnMut() -> f32 {
     use std::thread;
@@ -8,12 +41,13 @@ fn get_timer() -> impl FnMut() -> f32 {
     const CAP_FPS: bool = false;
     #[cfg(feature = "term")]
     const CAP_FPS: bool = true;
+    #[cfg(feature = "fbdev")]
+    const CAP_FPS: bool = true;
+    // page flips already wait for vblank
+    #[cfg(feature = "kms")]
+    const CAP_FPS: bool = false;
     let mut last_loop_start_timepoint = Instant::now();
     let mut calculation_start_timepoint = Instant::now();
-    #[cfg(feature = "sdl")]
-    let mut accumulated_time = 0f32;
-    #[cfg(feature = "sdl")]
-    let mut frame_count = 0;
     move || {
         let loop_start_timepoint = Instant::now();
         let dt = (loop_start_timepoint - last_loop_start_timepoint).as_secs_f32();
@@ -29,17 +63,6 @@ fn get_timer() -> impl FnMut() -> f32 {
             calculation_start_timepoint = Instant::now();
         }
 
-        #[cfg(feature = "sdl")]
-        {
-            accumulated_time += dt;
-            frame_count += 1;
-            if accumulated_time > 1f32 {
-                println!("FPS: {:.2}", frame_count as f32 / accumulated_time);
-                accumulated_time = 0f32;
-                frame_count = 0;
-            }
-        }
-
         // println!("dt: {}", dt);
         // println!("FPS: {}", 1.0 / dt);
         // println!();
@@ -47,28 +70,208 @@ fn get_timer() -> impl FnMut() -> f32 {
     }
 }
 
+// a window of the app. Examples list theirs in `VIEWS` and `render` draws the
+// one it is given, e.g. the scene and a light's-eye debug view. Backends with a
+// single screen show the first, which is WIDTH x HEIGHT, and have no title
+#[cfg_attr(not(feature = "sdl"), allow(dead_code))]
+struct View {
+    title: &'static str,
+    width: u32,
+    height: u32,
+}
+
+// the last two seconds or so, FRAME_STATS_CSV=<path> also dumps every frame
+#[cfg(not(feature = "wasm"))]
+fn frame_stats() -> olive3d::timing::FrameStats {
+    let mut stats = olive3d::timing::FrameStats::new(120);
+    if let Ok(path) = std::env::var("FRAME_STATS_CSV") {
+        stats.record_csv(path).unwrap();
+    }
+    stats
+}
+
+// where F12 saves the window, CAPTURE_DIR=<dir> and CAPTURE_FORMAT=<ppm | bmp>
+// override ./screenshots and ppm
+#[cfg(not(feature = "wasm"))]
+fn capture() -> olive3d::capture::Capture {
+    use olive3d::capture::{Capture, Format};
+    let dir = std::env::var("CAPTURE_DIR").unwrap_or("screenshots".to_string());
+    let format = std::env::var("CAPTURE_FORMAT").map_or(Format::Ppm, |name| {
+        Format::from_name(&name).unwrap_or_else(|| panic!("unknown capture format {name}"))
+    });
+    Capture::new(dir, format)
+}
+
+// the dt and input of each frame, from the clock and the devices or from a
+// recording. REPLAY_RECORD=<path> records a run and REPLAY=<path> plays one back,
+// as fast as it renders, to get the same frames again, e.g. from a bug report.
+// REPLAY_EXPORT=<dir> saves each replayed frame of the first view as well
+#[cfg(not(feature = "wasm"))]
+struct Session {
+    timer: Box<dyn FnMut() -> f32>,
+    recorder: Option<olive3d::replay::Recorder<std::io::BufWriter<std::fs::File>>>,
+    replay: Option<olive3d::replay::Replay>,
+    export: Option<olive3d::capture::Capture>,
+    frame: u32,
+}
+
+#[cfg(not(feature = "wasm"))]
+impl Session {
+    fn from_env() -> Self {
+        use olive3d::{capture::Capture, replay::{Recorder, Replay}};
+        let var = |name| std::env::var(name).ok();
+        Self {
+            timer: Box::new(get_timer()),
+            recorder: var("REPLAY_RECORD").map(|path| Recorder::create(path).unwrap()),
+            replay: var("REPLAY").map(|path| Replay::open(path).unwrap()),
+            export: var("REPLAY_EXPORT").map(|dir| Capture { dir: dir.into(), ..capture() }),
+            frame: 0,
+        }
+    }
+    fn replaying(&self) -> bool {
+        self.replay.is_some()
+    }
+    // live input is dropped while replaying, None once the replay is over
+    fn next(&mut self, live: Vec<olive3d::replay::Input>) -> Option<olive3d::replay::Frame> {
+        let frame = match self.replay.as_mut() {
+            Some(replay) => replay.next()?,
+            None => olive3d::replay::Frame { dt: (self.timer)(), inputs: live },
+        };
+        if let Some(recorder) = self.recorder.as_mut() {
+            recorder.record(&frame).unwrap();
+        }
+        Some(frame)
+    }
+    // as frame_00000, frame_00001, ... to make a video of
+    fn export(&mut self, buffer: &mut [u32], z_buffer: &mut [f32], width: u32, height: u32) {
+        if let (Some(export), true) = (&self.export, self.replaying()) {
+            let image = olive3d::renderer::Renderer::new(buffer, z_buffer, width, height).capture();
+            export.save_as(&format!("frame_{:05}", self.frame), &image).unwrap();
+        }
+        self.frame += 1;
+    }
+}
+
+// the half of the pixels `render` may draw this frame, None for all of them.
+// An example that can leave the other half out takes it as its renderer's
+// `field`, one whose passes need every pixel leaves it and draws all of them
+static FIELD: std::sync::Mutex<Option<olive3d::interlace::Field>> = std::sync::Mutex::new(None);
+
+fn take_field() -> Option<olive3d::interlace::Field> {
+    FIELD.lock().unwrap().take()
+}
+
+// RENDER_SCALE=<0..1> draws the first view at that fraction of its width and
+// height and scales it up to the screen, RENDER_SCALE=auto picks the fraction
+// every frame to draw in time for 60 fps. RENDER_FILTER=nearest scales up
+// blocky instead of smooth. INTERLACE=scanlines or checkerboard draws half of
+// its pixels a frame and makes up the rest from the frame before
+#[cfg(not(feature = "wasm"))]
+struct Resolution {
+    scale: Option<olive3d::resolution::RenderScale>,
+    interlace: Option<olive3d::interlace::Interlacer>,
+    buffer: Vec<u32>,
+    z_buffer: Vec<f32>,
+}
+
+#[cfg(not(feature = "wasm"))]
+impl Resolution {
+    fn from_env() -> Self {
+        use olive3d::interlace::{Interlace, Interlacer};
+        use olive3d::resolution::{RenderScale, Upscale};
+        let filter = match std::env::var("RENDER_FILTER").as_deref() {
+            Ok("nearest") => Upscale::Nearest,
+            Ok("bilinear") | Err(_) => Upscale::Bilinear,
+            Ok(name) => panic!("unknown render filter {name}"),
+        };
+        let scale = std::env::var("RENDER_SCALE").ok().map(|scale| match scale.as_str() {
+            "auto" => RenderScale::dynamic(1.0 / 60.0, filter),
+            _ => {
+                let fraction: f32 = scale
+                    .parse()
+                    .unwrap_or_else(|_| panic!("RENDER_SCALE={scale} is not a number or auto"));
+                RenderScale::new(fraction.clamp(0.05, 1.0), filter)
+            }
+        });
+        let interlace = std::env::var("INTERLACE").ok().map(|pattern| {
+            let pattern = match pattern.as_str() {
+                "scanlines" => Interlace::Scanlines,
+                "checkerboard" => Interlace::Checkerboard,
+                _ => panic!("unknown interlace pattern {pattern}"),
+            };
+            Interlacer::new(0, 0, pattern)
+        });
+        Self { scale, interlace, buffer: Vec::new(), z_buffer: Vec::new() }
+    }
+    // `render` for `view`, filling all of `buffer`
+    fn render(
+        &mut self,
+        view: usize,
+        buffer: &mut [u32],
+        z_buffer: &mut [f32],
+        alpha: f32,
+        stats: &mut olive3d::timing::FrameStats,
+    ) {
+        use olive3d::timing::Pass;
+        let View { width, height, .. } = VIEWS[view];
+        let start = std::time::Instant::now();
+        let scale = self.scale.as_mut().filter(|_| view == 0);
+        let (w, h) = scale.as_ref().map_or((width, height), |scale| scale.size(width, height));
+        let (target, target_z) = match scale {
+            Some(_) => {
+                self.buffer.resize((w * h) as usize, 0);
+                self.z_buffer.resize((w * h) as usize, 0.0);
+                (&mut self.buffer[..], &mut self.z_buffer[..])
+            }
+            None => (&mut *buffer, &mut *z_buffer),
+        };
+        match self.interlace.as_mut().filter(|_| view == 0) {
+            Some(interlacer) => {
+                interlacer.resize(w, h);
+                *FIELD.lock().unwrap() = interlacer.field();
+                render(view, target, target_z, w, h, alpha, stats);
+                if take_field().is_some() {
+                    // drawn whole, starting over draws the next one whole too
+                    interlacer.reset();
+                } else {
+                    let mut renderer = olive3d::renderer::Renderer::new(target, target_z, w, h);
+                    stats.time(Pass::Raster, || interlacer.reconstruct(&mut renderer));
+                }
+            }
+            None => render(view, target, target_z, w, h, alpha, stats),
+        }
+        if let Some(scale) = scale {
+            let filter = scale.filter;
+            stats.time(Pass::Raster, || {
+                olive3d::resolution::upscale(&self.buffer, w, h, buffer, width, height, filter)
+            });
+            scale.adjust(start.elapsed().as_secs_f32());
+        }
+    }
+}
+
 #[cfg(feature = "sdl")]
 fn main() {
     init();
-    use sdl3::event::Event;
+    use olive3d::renderer::Renderer;
+    use olive3d::replay::{Button, Frame, Input};
+    use sdl3::event::{Event, WindowEvent};
     use sdl3::keyboard::Keycode;
-    use sdl3::{render::Canvas, video::Window, Sdl};
-    fn create_canvase(sdl: &Sdl) -> Canvas<Window> {
-        let video_subsystem = sdl.video().unwrap();
-
+    use sdl3::{render::Canvas, video::Window, VideoSubsystem};
+    fn create_canvase(video_subsystem: &VideoSubsystem, view: &View) -> Canvas<Window> {
         let window = video_subsystem
-            .window("rust-sdl3 demo", WIDTH, HEIGHT)
+            .window(view.title, view.width, view.height)
             .position_centered()
             .build()
             .unwrap();
 
         window.into_canvas()
     }
-    fn show(buffer: &[u32], canvas: &mut Canvas<Window>) {
+    fn show(buffer: &[u32], view: &View, canvas: &mut Canvas<Window>) {
         use bytemuck::cast_slice;
         use sdl3::pixels::PixelFormat;
         use sdl3::pixels::PixelMasks;
-        let pitch = (WIDTH * 4) as usize;
+        let pitch = (view.width * 4) as usize;
         let texture_creator = canvas.texture_creator();
         let mut texture = texture_creator
             .create_texture_target(PixelFormat::from_masks(PixelMasks {
@@ -77,7 +280,7 @@ fn main() {
                 gmask: 0x0000ff00,
                 bmask: 0x00ff0000,
                 amask: 0xff000000,
-            }), WIDTH, HEIGHT)
+            }), view.width, view.height)
             .unwrap();
         texture.update(None, cast_slice(buffer), pitch).unwrap();
         canvas.copy(&texture, None, None).unwrap();
@@ -85,33 +288,153 @@ fn main() {
     }
     sdl3::hint::set("SDL_VIDEO_DRIVER", "wayland,x11");
     let sdl_context = sdl3::init().unwrap();
-    let mut canvas = create_canvase(&sdl_context);
+    let video_subsystem = sdl_context.video().unwrap();
 
-    let mut buffer = [0u32; WIDTH as usize * HEIGHT as usize];
-    let mut z_buffer = [0f32; WIDTH as usize * HEIGHT as usize];
-    let mut timer = get_timer();
+    // a window and buffers per view, None once the window is closed
+    let mut windows: Vec<_> = VIEWS
+        .iter()
+        .map(|view| {
+            let size = (view.width * view.height) as usize;
+            Some((create_canvase(&video_subsystem, view), vec![0u32; size], vec![0f32; size]))
+        })
+        .collect();
+    let mut timestep = FixedTimestep::new(TICK_RATE);
+    let mut stats = frame_stats();
+    let capture = capture();
+    let mut session = Session::from_env();
+    let mut resolution = Resolution::from_env();
+    // the example's `ui` over the first window, fed that window's mouse
+    #[cfg(feature = "egui")]
+    let mut overlay = olive3d::egui::Overlay::new();
+    fn button(button: sdl3::mouse::MouseButton) -> Option<Button> {
+        use sdl3::mouse::MouseButton;
+        match button {
+            MouseButton::Left => Some(Button::Left),
+            MouseButton::Right => Some(Button::Right),
+            MouseButton::Middle => Some(Button::Middle),
+            _ => None,
+        }
+    }
+    #[cfg(feature = "egui")]
+    fn egui_button(button: Button) -> egui::PointerButton {
+        match button {
+            Button::Left => egui::PointerButton::Primary,
+            Button::Right => egui::PointerButton::Secondary,
+            Button::Middle => egui::PointerButton::Middle,
+        }
+    }
 
     let mut event_pump = sdl_context.event_pump().unwrap();
     'running: loop {
-        // handle time, fps cap
-        let dt = timer();
-
-        // handle event
+        // handle event, as input of the window's view
+        let mut inputs = Vec::new();
         for event in event_pump.poll_iter() {
-            match event {
+            let view_of = |window_id| {
+                windows.iter().position(|window| {
+                    window
+                        .as_ref()
+                        .is_some_and(|(canvas, ..)| canvas.window().id() == window_id)
+                })
+            };
+            let input = match event {
                 Event::Quit { .. }
                 | Event::KeyDown {
                     keycode: Some(Keycode::Escape),
                     ..
                 } => break 'running,
+                Event::KeyDown {
+                    window_id,
+                    keycode: Some(Keycode::F12),
+                    ..
+                } => view_of(window_id).map(|view| Input::Screenshot { view }),
+                Event::Window {
+                    window_id,
+                    win_event: WindowEvent::CloseRequested,
+                    ..
+                } => view_of(window_id).map(|view| Input::Close { view }),
+                Event::MouseMotion { window_id, x, y, .. } => {
+                    view_of(window_id).map(|view| Input::PointerMoved { view, x, y })
+                }
+                Event::MouseButtonDown { window_id, mouse_btn, x, y, .. }
+                | Event::MouseButtonUp { window_id, mouse_btn, x, y, .. } => {
+                    let pressed = matches!(event, Event::MouseButtonDown { .. });
+                    view_of(window_id).zip(button(mouse_btn)).map(|(view, button)| {
+                        Input::PointerButton { view, x, y, button, pressed }
+                    })
+                }
+                Event::MouseWheel { window_id, x, y, .. } => {
+                    view_of(window_id).map(|view| Input::Scroll { view, dx: x, dy: y })
+                }
+                _ => None,
+            };
+            inputs.extend(input);
+        }
+
+        // handle time, fps cap
+        let Some(Frame { dt, inputs }) = session.next(inputs) else {
+            break;
+        };
+        let alpha = timestep.advance(dt, update);
+
+        for input in inputs {
+            match input {
+                // the first window is the app, the others can come and go
+                Input::Close { view: 0 } => break 'running,
+                Input::Close { view } => windows[view] = None,
+                // the window as last shown, overlays and all
+                Input::Screenshot { view } => {
+                    let Some((_, buffer, z_buffer)) = windows[view].as_mut() else {
+                        continue;
+                    };
+                    let View { width, height, .. } = VIEWS[view];
+                    let image = Renderer::new(buffer, z_buffer, width, height).capture();
+                    match capture.save(&image) {
+                        Ok(path) => eprintln!("saved {}", path.display()),
+                        Err(err) => eprintln!("capture failed: {err}"),
+                    }
+                }
+                #[cfg(feature = "egui")]
+                Input::PointerMoved { view: 0, x, y } => overlay.pointer_moved(x, y),
+                #[cfg(feature = "egui")]
+                Input::PointerButton { view: 0, x, y, button, pressed } => {
+                    overlay.pointer_button(x, y, egui_button(button), pressed)
+                }
+                #[cfg(feature = "egui")]
+                Input::Scroll { view: 0, dx, dy } => overlay.scroll(dx, dy),
                 _ => {}
             }
         }
 
-        render(&mut buffer, &mut z_buffer, dt);
+        for (view, window) in windows.iter_mut().enumerate() {
+            let Some((canvas, buffer, z_buffer)) = window else {
+                continue;
+            };
+            resolution.render(view, buffer, z_buffer, alpha, &mut stats);
+            if view == 0 {
+                let View { width, height, .. } = VIEWS[0];
+                #[cfg(feature = "egui")]
+                {
+                    let mut renderer = Renderer::new(buffer, z_buffer, width, height);
+                    overlay.run(dt, width, height, ui);
+                    stats.time(olive3d::timing::Pass::Raster, || overlay.paint(&mut renderer));
+                }
+                session.export(buffer, z_buffer, width, height);
+                // frame times aren't part of a replay, they are left out of its frames
+                if !session.replaying() {
+                    Renderer::new(buffer, z_buffer, width, height).draw_text(
+                        4,
+                        4,
+                        &stats.summary(),
+                        0xff00ff00,
+                        1,
+                    );
+                }
+            }
 
-        // show on screen
-        show(&buffer, &mut canvas);
+            // show on screen
+            stats.time(olive3d::timing::Pass::Present, || show(buffer, &VIEWS[view], canvas));
+        }
+        stats.end_frame();
     }
 }
 
@@ -152,14 +475,79 @@ fn main() {
         table.as_bytes()[i] as char
     }
     let mut buffer = [0u32; WIDTH as usize * HEIGHT as usize];
-    let mut timer = get_timer();
-    loop {
+    let mut z_buffer = [0f32; WIDTH as usize * HEIGHT as usize];
+    let mut timestep = FixedTimestep::new(TICK_RATE);
+    let mut stats = frame_stats();
+    let mut session = Session::from_env();
+    let mut resolution = Resolution::from_env();
+    // no input here, until a replay runs out
+    while let Some(frame) = session.next(Vec::new()) {
+        // handle time, fps cap
+        let alpha = timestep.advance(frame.dt, update);
+
+        resolution.render(0, &mut buffer, &mut z_buffer, alpha, &mut stats);
+        session.export(&mut buffer, &mut z_buffer, WIDTH, HEIGHT);
+
+        // show on screen
+        stats.time(olive3d::timing::Pass::Present, || show(&buffer));
+        stats.end_frame();
+    }
+}
+
+#[cfg(feature = "fbdev")]
+fn main() {
+    init();
+    use olive3d::fbdev::Framebuffer;
+    let path = std::env::var("FRAMEBUFFER").unwrap_or("/dev/fb0".to_string());
+    let mut fb = Framebuffer::open(&path).unwrap();
+    // one screen, the first view
+    let View { width, height, .. } = VIEWS[0];
+    let mut buffer = vec![0u32; (width * height) as usize];
+    let mut z_buffer = vec![0f32; (width * height) as usize];
+    let mut timestep = FixedTimestep::new(TICK_RATE);
+    let mut stats = frame_stats();
+    let mut session = Session::from_env();
+    let mut resolution = Resolution::from_env();
+    // no input here, until a replay runs out
+    while let Some(frame) = session.next(Vec::new()) {
         // handle time, fps cap
-        let dt = timer();
+        let alpha = timestep.advance(frame.dt, update);
+
+        resolution.render(0, &mut buffer, &mut z_buffer, alpha, &mut stats);
+        session.export(&mut buffer, &mut z_buffer, width, height);
+
+        // show on screen
+        stats.time(olive3d::timing::Pass::Present, || fb.present(&buffer, width, height));
+        stats.end_frame();
+    }
+}
+
+#[cfg(feature = "kms")]
+fn main() {
+    init();
+    use olive3d::kms::Kms;
+    let path = std::env::var("DRM_DEVICE").unwrap_or("/dev/dri/card0".to_string());
+    let mut kms = Kms::open(&path).unwrap();
+    // one screen, the first view
+    let View { width, height, .. } = VIEWS[0];
+    let mut buffer = vec![0u32; (width * height) as usize];
+    let mut z_buffer = vec![0f32; (width * height) as usize];
+    let mut timestep = FixedTimestep::new(TICK_RATE);
+    let mut stats = frame_stats();
+    let mut session = Session::from_env();
+    let mut resolution = Resolution::from_env();
+    // no input here, until a replay runs out
+    while let Some(frame) = session.next(Vec::new()) {
+        // handle time
+        let alpha = timestep.advance(frame.dt, update);
 
-        render(&mut buffer, dt);
+        resolution.render(0, &mut buffer, &mut z_buffer, alpha, &mut stats);
+        session.export(&mut buffer, &mut z_buffer, width, height);
 
         // show on screen
-        show(&buffer);
+        stats
+            .time(olive3d::timing::Pass::Present, || kms.present(&buffer, width, height))
+            .unwrap();
+        stats.end_frame();
     }
 }
@@ -1,31 +1,61 @@
-use std::ops::Neg;
+use std::{f32::consts::PI, ops::Neg, sync::Mutex};
 
 use lazy_static::lazy_static;
 use olive3d::{
-    geometry::{m2v, v2m, Matrix, Matrix4, Vector3},
+    animation::{Animation, AnimationPlayer, Interpolation, LoopMode, Track},
+    geometry::{m2v, v2m, Matrix, Matrix4, Vector2, Vector3},
     model::Model,
-    renderer::{self, lookat, viewport, Renderer, Shader},
+    renderer::{self, lookat, viewport, DebugView, OverdrawStats, Renderer, Shader},
+    scene::Scene,
+    timing::{FrameStats, Pass},
 };
 
 const WIDTH: u32 = 800;
 const HEIGHT: u32 = 800;
 const DEPTH: u32 = 255;
+const TICK_RATE: f32 = 60.0;
+const VIEWS: &[View] = &[View {
+    title: "camera",
+    width: WIDTH,
+    height: HEIGHT,
+}];
 
 struct GouraudShader<'a> {
     model: &'a Model,
     transform: Matrix4,
+    // of the frame before, for the motion blur
+    previous: Matrix4,
     light_dir: Vector3,
     camera_dir: Vector3,
     varying_uv: Matrix<3, 2>,
+    varying_previous: Matrix<3, 2>,
 }
 impl Shader for GouraudShader<'_> {
     fn vertex(&mut self, iface: usize, nthvert: usize) -> Vector3 {
         self.varying_uv
             .set_row(nthvert, self.model.uv(iface, nthvert));
         let v = self.model.vert(iface, nthvert);
+        let previous = m2v(&(&self.previous * v2m(&v)));
+        self.varying_previous
+            .set_row(nthvert, Vector2::new(previous.x(), previous.y()));
         m2v(&(&self.transform * v2m(&v)))
     }
 
+    fn previous_position(&mut self, bc: &Vector3) -> Option<Vector2> {
+        let bc = Matrix::from_row_vector(bc.clone());
+        Some((bc * &self.varying_previous).to_row_vector())
+    }
+
+    // the normal map's, in model space
+    fn normal(&mut self, bc: &Vector3) -> Option<Vector3> {
+        let uv = self.uv(bc)?;
+        Some(self.model.normal_uv(&uv).normalize())
+    }
+
+    fn uv(&mut self, bc: &Vector3) -> Option<Vector2> {
+        Some((Matrix::from_row_vector(bc.clone()) * &self.varying_uv).to_row_vector())
+    }
+
     fn fregment(&mut self, bc: &Vector3) -> Option<u32> {
         let uv = (Matrix::from_row_vector(bc.clone()) * &self.varying_uv).to_row_vector();
         let n = self.model.normal_uv(&uv).normalize();
@@ -57,14 +87,78 @@ lazy_static! {
     };
 }
 
-static mut T: f32 = 0.0;
+// the eye circles in front of the head once every 2 pi seconds
+fn orbit() -> (Scene, AnimationPlayer) {
+    let mut scene = Scene::new();
+    let eye = scene.add_node("eye", None);
+    let keys = (0..=8)
+        .map(|i| {
+            let t = i as f32 * PI / 4.0;
+            let eye = Matrix4::rotation_z(t) * v2m(&Vector3::new(1.0, 0.0, 3.0));
+            (t, m2v(&eye))
+        })
+        .collect();
+    let mut track = Track::translation(eye, keys);
+    track.interpolation = Interpolation::Cubic;
+    let mut animation = Animation::new("orbit");
+    animation.add_track(track);
+    (scene, AnimationPlayer::new(animation, LoopMode::Loop))
+}
 
-pub fn render(buffer: &mut [u32], z_buffer: &mut [f32], dt: f32) {
-    let mut renderer = Renderer::new(buffer, z_buffer, WIDTH, HEIGHT);
+lazy_static! {
+    // and where the eye was a tick before
+    static ref ORBIT: Mutex<(Scene, AnimationPlayer, Vector3)> = {
+        let (mut scene, mut player) = orbit();
+        player.advance(0.0, &mut scene);
+        let eye = scene.node(0).transform.translation.clone();
+        Mutex::new((scene, player, eye))
+    };
+}
+
+// in degrees, set from the debug panel
+static FOV: Mutex<f32> = Mutex::new(60.0);
+static MOTION_BLUR: Mutex<bool> = Mutex::new(true);
+static DEBUG_VIEW: Mutex<DebugView> = Mutex::new(DebugView::Shaded);
+// of the last frame drawn with `DebugView::Overdraw`
+static OVERDRAW: Mutex<Option<OverdrawStats>> = Mutex::new(None);
+// the transform of the frame drawn last and the velocity attachment
+static PREVIOUS: Mutex<Option<Matrix4>> = Mutex::new(None);
+static VELOCITY: Mutex<Vec<(f32, f32)>> = Mutex::new(Vec::new());
+
+pub fn update(dt: f32) {
+    let (scene, player, previous) = &mut *ORBIT.lock().unwrap();
+    *previous = scene.node(0).transform.translation.clone();
+    player.advance(dt, scene);
+}
+
+pub fn render(
+    _view: usize,
+    buffer: &mut [u32],
+    z_buffer: &mut [f32],
+    width: u32,
+    height: u32,
+    alpha: f32,
+    stats: &mut FrameStats,
+) {
+    let mut velocity = VELOCITY.lock().unwrap();
+    velocity.resize((width * height) as usize, (0.0, 0.0));
+    let mut renderer = Renderer::new(buffer, z_buffer, width, height);
+    let debug_view = *DEBUG_VIEW.lock().unwrap();
+    renderer.debug_view(debug_view);
+    renderer.field = take_field();
+    // the blur would smear the debug colors, or the gaps between the field
+    let motion_blur = *MOTION_BLUR.lock().unwrap()
+        && debug_view == DebugView::Shaded
+        && renderer.field.is_none();
+    if motion_blur {
+        renderer.attach_velocity(&mut velocity);
+    }
     let light_dir = Vector3::new(0.0, 0.0, -1.0).normalize();
-    let eye = unsafe {
-        T += dt;
-        Vector3::new(T.cos(), T.sin(), 3.0)
+    // between the last two ticks
+    let eye = {
+        let (scene, _, previous) = &*ORBIT.lock().unwrap();
+        let eye = &scene.node(0).transform.translation;
+        previous + &((eye - previous) * alpha)
     };
     let center = Vector3::new(0.0, 0.0, 0.0);
     let camera_dir = (&center - &eye).normalize();
@@ -73,34 +167,68 @@ pub fn render(buffer: &mut [u32], z_buffer: &mut [f32], dt: f32) {
 
     let projection = renderer::projection(-1.0 / (&eye - &center).length());
 
+    // 60 degrees frames the head like the other examples, narrower zooms in
+    let fov = *FOV.lock().unwrap();
+    let zoom = 30f32.to_radians().tan() / (fov.to_radians() / 2.0).tan();
+    let (w, h) = (width as f32 * 3.0 / 4.0 * zoom, height as f32 * 3.0 / 4.0 * zoom);
     let viewport = viewport(
-        WIDTH as f32 / 8.0,
-        HEIGHT as f32 / 8.0,
-        WIDTH as f32 * 3.0 / 4.0,
-        HEIGHT as f32 * 3.0 / 4.0,
+        (width as f32 - w) / 2.0,
+        (height as f32 - h) / 2.0,
+        w,
+        h,
         DEPTH as f32,
     );
 
     let transform = viewport * projection * model_view;
+    let previous = PREVIOUS.lock().unwrap().replace(transform.clone());
 
     let mut shader = GouraudShader {
         model: &MODEL,
         light_dir,
         camera_dir,
+        previous: previous.unwrap_or_else(|| transform.clone()),
         transform,
         varying_uv: Matrix::zero(),
+        varying_previous: Matrix::zero(),
     };
 
-    renderer.fill(0xff000000);
+    stats.time(Pass::Clear, || renderer.fill(0xff000000));
     for i in 0..MODEL.nfaces() {
-        let mut screen_coords = Vec::with_capacity(3);
-        for j in 0..3 {
-            screen_coords.push(shader.vertex(i, j));
-        }
-        renderer.fill_triangle(&screen_coords, &mut shader);
+        let screen_coords = stats.time(Pass::Vertex, || [0, 1, 2].map(|j| shader.vertex(i, j)));
+        stats.time(Pass::Raster, || renderer.fill_triangle(&screen_coords, &mut shader));
+    }
+    *OVERDRAW.lock().unwrap() = renderer.overdraw_stats();
+    // the eye moves a few pixels a frame, at 60 Hz that strobes without a blur
+    if motion_blur {
+        stats.time(Pass::Raster, || renderer.motion_blur(8, 0.5));
     }
 }
 
+#[cfg(feature = "egui")]
+pub fn ui(ctx: &egui::Context) {
+    use egui::{Align2, Slider, Window};
+    Window::new("camera")
+        .anchor(Align2::RIGHT_TOP, [-8.0, 8.0])
+        .show(ctx, |ui| {
+            ui.add(Slider::new(&mut *FOV.lock().unwrap(), 20.0..=120.0).text("fov"));
+            ui.checkbox(&mut MOTION_BLUR.lock().unwrap(), "motion blur");
+            let debug_view = &mut *DEBUG_VIEW.lock().unwrap();
+            for (view, name) in [
+                (DebugView::Shaded, "shaded"),
+                (DebugView::Depth, "depth"),
+                (DebugView::Normals, "normals"),
+                (DebugView::Overdraw, "overdraw"),
+                (DebugView::Uv, "uv"),
+                (DebugView::BarycentricWireframe, "wireframe"),
+            ] {
+                ui.radio_value(debug_view, view, name);
+            }
+            if let Some(stats) = *OVERDRAW.lock().unwrap() {
+                ui.label(stats.to_string());
+            }
+        });
+}
+
 pub fn init() {}
 
 include!("../common/main.rs");
@@ -0,0 +1,233 @@
+use std::sync::Mutex;
+
+use lazy_static::lazy_static;
+use olive3d::{
+    cloth::Cloth,
+    geometry::{m2v, v2m, Matrix, Matrix3, Matrix4, Vector2, Vector3},
+    model::Model,
+    renderer::{self, lookat, viewport, Renderer, Shader},
+    shadow::ShadowMap,
+    timing::{FrameStats, Pass},
+};
+
+const WIDTH: u32 = 800;
+const HEIGHT: u32 = 800;
+const DEPTH: u32 = 255;
+const SUBSTEPS: u32 = 4;
+const TICK_RATE: f32 = 60.0;
+const SHADOW_SIZE: u32 = 512;
+const VIEWS: &[View] = &[
+    View {
+        title: "cloth",
+        width: WIDTH,
+        height: HEIGHT,
+    },
+    View {
+        title: "light's eye",
+        width: SHADOW_SIZE,
+        height: SHADOW_SIZE,
+    },
+];
+
+#[derive(Clone)]
+struct LitShader<'a> {
+    model: &'a Model,
+    shadow: &'a ShadowMap,
+    shadows: bool,
+    transform: Matrix4,
+    light_dir: Vector3,
+    camera_dir: Vector3,
+    color: u32,
+    varying_pos: Matrix3,
+    varying_nrm: Matrix3,
+}
+impl Shader for LitShader<'_> {
+    fn vertex(&mut self, iface: usize, nthvert: usize) -> Vector3 {
+        let v = self.model.vert(iface, nthvert);
+        self.varying_pos.set_row(nthvert, v.clone());
+        self.varying_nrm
+            .set_row(nthvert, self.model.normal_vert(iface, nthvert));
+        m2v(&(&self.transform * v2m(&v)))
+    }
+
+    fn fregment(&mut self, bc: &Vector3) -> Option<u32> {
+        let bc = Matrix::from_row_vector(bc.clone());
+        let pos = (&bc * &self.varying_pos).to_row_vector();
+        let mut n = (&bc * &self.varying_nrm).to_row_vector().normalize();
+        // two-sided: light the side facing the camera
+        if n.dot(&self.camera_dir) > 0.0 {
+            n = -n;
+        }
+        let visibility = if self.shadows {
+            self.shadow.visibility(&pos)
+        } else {
+            1.0
+        };
+        let diffuse = (-n.dot(&self.light_dir)).max(0.0) * visibility;
+        let intensity = 0.2 + 0.8 * diffuse;
+        let mut new_pixel = 0xff000000;
+        for i in 0..3 {
+            let part = ((self.color >> (8 * i)) & 0xff) as f32 * intensity;
+            new_pixel |= ((part.clamp(0.0, 255.0) as u32) & 0xff) << (8 * i)
+        }
+        Some(new_pixel)
+    }
+    fn fork<'f>(&self) -> Option<Box<dyn Shader + Send + 'f>>
+    where
+        Self: 'f,
+    {
+        Some(Box::new(self.clone()))
+    }
+}
+
+fn floor(y: f32, size: f32, cells: usize) -> Model {
+    let mut verts = Vec::new();
+    let mut facets = Vec::new();
+    for r in 0..=cells {
+        for c in 0..=cells {
+            let x = -size + 2.0 * size * c as f32 / cells as f32;
+            let z = -size + 2.0 * size * r as f32 / cells as f32;
+            verts.push(Vector3::new(x, y, z));
+        }
+    }
+    for r in 0..cells {
+        for c in 0..cells {
+            let i = c + r * (cells + 1);
+            facets.extend([i, i + cells + 1, i + 1, i + 1, i + cells + 1, i + cells + 2]);
+        }
+    }
+    let n = facets.len();
+    let tex_coord = vec![Vector2::zero()];
+    let norms = vec![Vector3::new(0.0, 1.0, 0.0)];
+    Model::from_parts(verts, tex_coord, norms, facets, vec![0; n], vec![0; n])
+}
+
+lazy_static! {
+    static ref CLOTH: Mutex<(Cloth, Model)> = {
+        let mut cloth = Cloth::new(24, 24, 1.6, 1.6, Vector3::new(-0.8, 1.0, 0.0));
+        cloth.pin(0, 0);
+        cloth.pin(23, 0);
+        let model = cloth.to_model();
+        Mutex::new((cloth, model))
+    };
+    static ref FLOOR: Model = floor(-1.0, 2.0, 16);
+}
+
+// what the debug panel changes
+#[derive(Clone, Copy)]
+struct Settings {
+    wind: f32,
+    shadows: bool,
+}
+
+static SETTINGS: Mutex<Settings> = Mutex::new(Settings {
+    wind: 1.0,
+    shadows: true,
+});
+
+static T: Mutex<f32> = Mutex::new(0.0);
+
+pub fn update(dt: f32) {
+    let t = {
+        let mut t = T.lock().unwrap();
+        *t += dt;
+        *t
+    };
+    let mut state = CLOTH.lock().unwrap();
+    let (cloth, model) = &mut *state;
+    let wind = Vector3::new(1.5 * (t * 0.7).sin(), 0.0, -2.5 - 2.0 * (t * 1.3).sin());
+    cloth.wind = wind * SETTINGS.lock().unwrap().wind;
+    for _ in 0..SUBSTEPS {
+        cloth.step(dt / SUBSTEPS as f32);
+    }
+    cloth.update_model(model);
+}
+
+// the cloth as of the last tick, it moves too little per tick to need blending
+pub fn render(
+    view: usize,
+    buffer: &mut [u32],
+    z_buffer: &mut [f32],
+    width: u32,
+    height: u32,
+    _alpha: f32,
+    stats: &mut FrameStats,
+) {
+    let state = CLOTH.lock().unwrap();
+    let (_, model) = &*state;
+
+    let light_dir = Vector3::new(0.6, -1.0, -0.8).normalize();
+    let mut shadow = ShadowMap::new(
+        SHADOW_SIZE,
+        SHADOW_SIZE,
+        &light_dir,
+        &Vector3::zero(),
+        2.5,
+        DEPTH as f32,
+    );
+    // the shadow map is a whole draw of its own
+    stats.time(Pass::Raster, || shadow.render(model));
+    if view == 1 {
+        // the depth the light sees, nearer is brighter
+        for (pixel, &depth) in buffer.iter_mut().zip(shadow.depth_buffer()) {
+            let shade = (depth.clamp(0.0, DEPTH as f32) / DEPTH as f32 * 255.0) as u32;
+            *pixel = 0xff000000 | shade << 16 | shade << 8 | shade;
+        }
+        return;
+    }
+
+    let eye = Vector3::new(1.5, 0.8, 3.0);
+    let center = Vector3::new(0.0, 0.0, 0.0);
+    let camera_dir = (&center - &eye).normalize();
+    let model_view = lookat(&eye, &center, &Vector3::new(0.0, 1.0, 0.0));
+    let projection = renderer::projection(-1.0 / (&eye - &center).length());
+    let viewport = viewport(
+        width as f32 / 8.0,
+        height as f32 / 8.0,
+        width as f32 * 3.0 / 4.0,
+        height as f32 * 3.0 / 4.0,
+        DEPTH as f32,
+    );
+    let transform = viewport * projection * model_view;
+
+    let mut renderer = Renderer::new(buffer, z_buffer, width, height);
+    renderer.field = take_field();
+    // the floor's two triangles cover most of the screen
+    renderer.threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    stats.time(Pass::Clear, || renderer.fill(0xff201810));
+    for (model, color) in [(&*FLOOR, 0xff909090), (model, 0xffd06030)] {
+        let mut shader = LitShader {
+            model,
+            shadow: &shadow,
+            shadows: SETTINGS.lock().unwrap().shadows,
+            transform: transform.clone(),
+            light_dir: light_dir.clone(),
+            camera_dir: camera_dir.clone(),
+            color,
+            varying_pos: Matrix::zero(),
+            varying_nrm: Matrix::zero(),
+        };
+        for i in 0..model.nfaces() {
+            let screen_coords = stats.time(Pass::Vertex, || [0, 1, 2].map(|j| shader.vertex(i, j)));
+            stats.time(Pass::Raster, || {
+                renderer.fill_triangle(&screen_coords, &mut shader)
+            });
+        }
+    }
+}
+
+#[cfg(feature = "egui")]
+pub fn ui(ctx: &egui::Context) {
+    use egui::{Align2, Slider, Window};
+    let settings = &mut *SETTINGS.lock().unwrap();
+    Window::new("cloth")
+        .anchor(Align2::RIGHT_TOP, [-8.0, 8.0])
+        .show(ctx, |ui| {
+            ui.add(Slider::new(&mut settings.wind, 0.0..=3.0).text("wind"));
+            ui.checkbox(&mut settings.shadows, "shadows");
+        });
+}
+
+pub fn init() {}
+
+include!("../common/main.rs");
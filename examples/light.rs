@@ -1,21 +1,29 @@
-use std::ops::Neg;
+use std::{ops::Neg, sync::Mutex};
 
 use lazy_static::lazy_static;
 use olive3d::{
     geometry::{m2v, v2m, Matrix, Matrix4, Vector3},
     model::Model,
     renderer::{self, viewport, Renderer, Shader},
+    timing::{FrameStats, Pass},
 };
 
 const WIDTH: u32 = 800;
 const HEIGHT: u32 = 800;
 const DEPTH: u32 = 255;
+const TICK_RATE: f32 = 60.0;
+const VIEWS: &[View] = &[View {
+    title: "light",
+    width: WIDTH,
+    height: HEIGHT,
+}];
 
 struct GouraudShader<'a> {
     model: &'a Model,
     transform: Matrix4,
     light_dir: Vector3,
     camera_dir: Vector3,
+    specular: bool,
     varying_uv: Matrix<3, 2>,
 }
 impl Shader for GouraudShader<'_> {
@@ -33,7 +41,11 @@ impl Shader for GouraudShader<'_> {
         let r = ((2.0 * n.dot(&l) * &n) - &l).normalize();
         let ambient_component = 5.0;
         let diffuse_compoent = n.dot(&l).neg().max(0.0);
-        let specular_compoent = (r.dot(&self.camera_dir)).max(0.0).powf(self.model.specular(&uv));
+        let specular_compoent = if self.specular {
+            (r.dot(&self.camera_dir)).max(0.0).powf(self.model.specular(&uv))
+        } else {
+            0.0
+        };
         let pixel: u32 = self.model.diffuse(&uv);
         // let pixel: u32 = 0xffffffff;
         let mut new_pixel = 0xff000000;
@@ -55,49 +67,97 @@ lazy_static! {
         model.load_specular_map("./obj/african_head_spec.ppm");
         model
     };
-    static ref transform: Matrix4 = {
-        let camera = Vector3::new(0.0, 0.0, 3.0);
-
-        let projection = renderer::projection(-1.0 / camera.z());
-        let viewport = viewport(
-            WIDTH as f32 / 8.0,
-            HEIGHT as f32 / 8.0,
-            WIDTH as f32 * 3.0 / 4.0,
-            HEIGHT as f32 * 3.0 / 4.0,
-            DEPTH as f32,
-        );
-        viewport * projection
-    };
 }
 
-static mut T: f32 = 0.0;
+// the head seen from 3 units in front, framed in a `width` x `height` target
+fn transform(width: u32, height: u32) -> Matrix4 {
+    let camera = Vector3::new(0.0, 0.0, 3.0);
 
-pub fn render(buffer: &mut [u32], z_buffer: &mut [f32], dt: f32) {
-    let mut renderer = Renderer::new(buffer, z_buffer, WIDTH, HEIGHT);
-    let light_dir = unsafe {
-        T += dt;
-        let light_dir = Vector3::new(T.cos(), T.sin(), -1.0);
-        light_dir.normalize()
-    };
+    let projection = renderer::projection(-1.0 / camera.z());
+    let viewport = viewport(
+        width as f32 / 8.0,
+        height as f32 / 8.0,
+        width as f32 * 3.0 / 4.0,
+        height as f32 * 3.0 / 4.0,
+        DEPTH as f32,
+    );
+    viewport * projection
+}
+
+// what the debug panel changes
+#[derive(Clone, Copy)]
+struct Settings {
+    spin: bool,
+    specular: bool,
+}
+
+static SETTINGS: Mutex<Settings> = Mutex::new(Settings {
+    spin: true,
+    specular: true,
+});
+
+static T: Mutex<f32> = Mutex::new(0.0);
+
+pub fn update(dt: f32) {
+    if SETTINGS.lock().unwrap().spin {
+        *T.lock().unwrap() += dt;
+    }
+}
+
+pub fn render(
+    _view: usize,
+    buffer: &mut [u32],
+    z_buffer: &mut [f32],
+    width: u32,
+    height: u32,
+    alpha: f32,
+    stats: &mut FrameStats,
+) {
+    let mut renderer = Renderer::new(buffer, z_buffer, width, height);
+    renderer.field = take_field();
+    let settings = *SETTINGS.lock().unwrap();
+    // between the last two ticks, unless it is held still
+    let mut t = *T.lock().unwrap();
+    if settings.spin {
+        t -= (1.0 - alpha) / TICK_RATE;
+    }
+    let light_dir = Vector3::new(t.cos(), t.sin(), -1.0).normalize();
 
     let mut shader = GouraudShader {
         model: &MODEL,
         light_dir,
         camera_dir: Vector3::new(0.0, 0.0, -1.0).normalize(),
-        transform: transform.to_owned(),
+        specular: settings.specular,
+        transform: transform(width, height),
         varying_uv: Matrix::zero(),
     };
 
-    renderer.fill(0xff000000);
+    stats.time(Pass::Clear, || renderer.fill(0xff000000));
     for i in 0..MODEL.nfaces() {
-        let mut screen_coords = Vec::with_capacity(3);
-        for j in 0..3 {
-            screen_coords.push(shader.vertex(i, j));
-        }
-        renderer.fill_triangle(&screen_coords, &mut shader);
+        let screen_coords = stats.time(Pass::Vertex, || [0, 1, 2].map(|j| shader.vertex(i, j)));
+        stats.time(Pass::Raster, || renderer.fill_triangle(&screen_coords, &mut shader));
     }
 }
 
+#[cfg(feature = "egui")]
+pub fn ui(ctx: &egui::Context) {
+    use egui::{Align2, Slider, Window};
+    use std::f32::consts::TAU;
+    let settings = &mut *SETTINGS.lock().unwrap();
+    // the light circles the head, the slider moves it along
+    let mut angle = T.lock().unwrap().rem_euclid(TAU);
+    Window::new("light")
+        .anchor(Align2::RIGHT_TOP, [-8.0, 8.0])
+        .show(ctx, |ui| {
+            let slider = ui.add(Slider::new(&mut angle, 0.0..=TAU).text("light"));
+            if slider.changed() {
+                *T.lock().unwrap() = angle;
+            }
+            ui.checkbox(&mut settings.spin, "spin");
+            ui.checkbox(&mut settings.specular, "specular");
+        });
+}
+
 pub fn init() {}
 
 include!("../common/main.rs");
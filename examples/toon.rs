@@ -0,0 +1,155 @@
+use std::sync::Mutex;
+
+use lazy_static::lazy_static;
+use olive3d::{
+    geometry::Vector3,
+    model::Model,
+    renderer::{self, lookat, viewport, EdgeDetect, Renderer},
+    shaders::ToonShader,
+    timing::{FrameStats, Pass},
+};
+
+const WIDTH: u32 = 800;
+const HEIGHT: u32 = 800;
+const DEPTH: u32 = 255;
+const TICK_RATE: f32 = 60.0;
+const VIEWS: &[View] = &[View {
+    title: "toon",
+    width: WIDTH,
+    height: HEIGHT,
+}];
+
+lazy_static! {
+    static ref MODEL: Model = {
+        let mut model = Model::new("./obj/african_head.obj");
+        model.load_diffuse_map("./obj/african_head_diffuse.ppm");
+        model
+    };
+}
+
+// what the debug panel changes, angles in degrees
+#[derive(Clone, Copy)]
+struct Settings {
+    light_yaw: f32,
+    light_pitch: f32,
+    fov: f32,
+    bands: u32,
+    outline: bool,
+    // lines where the surface folds too, from a normal attachment
+    creases: bool,
+    line_width: u32,
+}
+
+static SETTINGS: Mutex<Settings> = Mutex::new(Settings {
+    light_yaw: -135.0,
+    light_pitch: -35.26,
+    fov: 60.0,
+    bands: 4,
+    outline: true,
+    creases: false,
+    line_width: 1,
+});
+static NORMALS: Mutex<Vec<[f32; 3]>> = Mutex::new(Vec::new());
+
+static T: Mutex<f32> = Mutex::new(0.0);
+
+pub fn update(dt: f32) {
+    *T.lock().unwrap() += dt;
+}
+
+pub fn render(
+    _view: usize,
+    buffer: &mut [u32],
+    z_buffer: &mut [f32],
+    width: u32,
+    height: u32,
+    alpha: f32,
+    stats: &mut FrameStats,
+) {
+    let settings = *SETTINGS.lock().unwrap();
+    let mut normals = NORMALS.lock().unwrap();
+    normals.resize((width * height) as usize, [0.0; 3]);
+    let mut renderer = Renderer::new(buffer, z_buffer, width, height);
+    if settings.outline && settings.creases {
+        renderer.attach_normals(&mut normals);
+    }
+    // the edges need the depth of every pixel
+    if !settings.outline {
+        renderer.field = take_field();
+    }
+    let (yaw, pitch) = (
+        settings.light_yaw.to_radians(),
+        settings.light_pitch.to_radians(),
+    );
+    let light_dir = Vector3::new(
+        pitch.cos() * yaw.sin(),
+        pitch.sin(),
+        pitch.cos() * yaw.cos(),
+    );
+    // between the last two ticks
+    let t = *T.lock().unwrap() - (1.0 - alpha) / TICK_RATE;
+    let eye = Vector3::new(3.0 * t.sin(), 0.0, 3.0 * t.cos());
+    let center = Vector3::new(0.0, 0.0, 0.0);
+
+    let model_view = lookat(&eye, &center, &Vector3::new(0.0, 1.0, 0.0));
+    let projection = renderer::projection(-1.0 / (&eye - &center).length());
+    // 60 degrees frames the head like the other examples, narrower zooms in
+    let zoom = 30f32.to_radians().tan() / (settings.fov.to_radians() / 2.0).tan();
+    let (w, h) = (
+        width as f32 * 3.0 / 4.0 * zoom,
+        height as f32 * 3.0 / 4.0 * zoom,
+    );
+    let viewport = viewport(
+        (width as f32 - w) / 2.0,
+        (height as f32 - h) / 2.0,
+        w,
+        h,
+        DEPTH as f32,
+    );
+
+    let transform = viewport * projection * model_view;
+    let mut shader = ToonShader::new(&MODEL, transform, light_dir, settings.bands);
+
+    // a dark gradient behind the head, brighter at the top
+    stats.time(Pass::Clear, || {
+        renderer.clear_with(|_, y| {
+            let shade = 0x50 - 0x30 * y / height;
+            0xff000000 | shade << 16 | shade << 8 | shade
+        });
+        renderer.clear_depth();
+        renderer.clear_normals();
+    });
+    // indexed draws shade the vertices as the triangles need them, so that
+    // counts as raster here
+    stats.time(Pass::Raster, || {
+        renderer.draw_model(&MODEL, &mut shader);
+        if settings.outline {
+            renderer.draw_edges(&EdgeDetect {
+                depth_threshold: DEPTH as f32 / 16.0,
+                width: settings.line_width,
+                ..EdgeDetect::new(0xff000000)
+            });
+        }
+    });
+}
+
+#[cfg(feature = "egui")]
+pub fn ui(ctx: &egui::Context) {
+    use egui::{Align2, Slider, Window};
+    let settings = &mut *SETTINGS.lock().unwrap();
+    Window::new("toon")
+        .anchor(Align2::RIGHT_TOP, [-8.0, 8.0])
+        .show(ctx, |ui| {
+            ui.add(Slider::new(&mut settings.light_yaw, -180.0..=180.0).text("light yaw"));
+            ui.add(Slider::new(&mut settings.light_pitch, -90.0..=90.0).text("light pitch"));
+            ui.add(Slider::new(&mut settings.fov, 20.0..=120.0).text("fov"));
+            ui.add(Slider::new(&mut settings.bands, 1..=8).text("bands"));
+            ui.checkbox(&mut settings.outline, "outline");
+            ui.checkbox(&mut settings.creases, "creases");
+            ui.add(Slider::new(&mut settings.line_width, 1..=5).text("line width"));
+        });
+}
+
+pub fn init() {}
+
+include!("../common/main.rs");
@@ -0,0 +1,91 @@
+#[cfg(not(feature = "std"))]
+use crate::compat::*;
+
+// how an `Accumulator` combines the frames it is given
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Accumulation {
+    // the mean of every frame since the last reset, a static scene converges to
+    // the anti-aliased image
+    Average,
+    // each frame weighs `alpha`, the history the rest: moving things leave a
+    // short trail instead of smearing over everything since the reset
+    Exponential(f32),
+}
+
+// frames rendered with sub pixel jitter, added up into one anti-aliased image.
+// Per frame: set the renderer's `jitter` from `jitter`, draw, `add` the colors
+// and `resolve` into what is shown
+pub struct Accumulator {
+    pub width: u32,
+    pub height: u32,
+    pub mode: Accumulation,
+    // rgb per pixel, 0..=255
+    history: Vec<[f32; 3]>,
+    frames: u32,
+}
+
+impl Accumulator {
+    pub fn new(width: u32, height: u32, mode: Accumulation) -> Self {
+        Self {
+            width,
+            height,
+            mode,
+            history: vec![[0.0; 3]; (width * height) as usize],
+            frames: 0,
+        }
+    }
+    // start over, e.g. when the camera moves in `Accumulation::Average`
+    pub fn reset(&mut self) {
+        self.frames = 0;
+    }
+    // added since the last reset
+    pub fn frames(&self) -> u32 {
+        self.frames
+    }
+    // the sub pixel offset for the next frame, in -0.5..0.5 from the Halton
+    // (2, 3) sequence, which covers the pixel evenly for any number of frames
+    pub fn jitter(&self) -> (f32, f32) {
+        // 1 based, index 0 of the sequence is the corner (0, 0)
+        let i = self.frames % 1024 + 1;
+        (halton(i, 2) - 0.5, halton(i, 3) - 0.5)
+    }
+    pub fn add(&mut self, buffer: &[u32]) {
+        assert_eq!(buffer.len(), self.history.len());
+        // the first frame after a reset replaces the history in either mode
+        let weight = match self.mode {
+            _ if self.frames == 0 => 1.0,
+            Accumulation::Average => 1.0 / (self.frames + 1) as f32,
+            Accumulation::Exponential(alpha) => alpha,
+        };
+        for (history, &pixel) in self.history.iter_mut().zip(buffer) {
+            for (i, channel) in history.iter_mut().enumerate() {
+                let value = ((pixel >> (8 * i)) & 0xff) as f32;
+                *channel += (value - *channel) * weight;
+            }
+        }
+        self.frames += 1;
+    }
+    // the accumulated colors, opaque
+    pub fn resolve(&self, buffer: &mut [u32]) {
+        assert_eq!(buffer.len(), self.history.len());
+        for (pixel, history) in buffer.iter_mut().zip(self.history.iter()) {
+            let mut new_pixel = 0xff000000;
+            for (i, channel) in history.iter().enumerate() {
+                new_pixel |= ((channel.round().clamp(0.0, 255.0) as u32) & 0xff) << (8 * i);
+            }
+            *pixel = new_pixel;
+        }
+    }
+}
+
+// the radical inverse of `i` in `base`, in 0..1
+pub fn halton(mut i: u32, base: u32) -> f32 {
+    let mut f = 1.0;
+    let mut r = 0.0;
+    while i > 0 {
+        f /= base as f32;
+        r += f * (i % base) as f32;
+        i /= base;
+    }
+    r
+}
@@ -0,0 +1,257 @@
+use crate::{
+    geometry::{Quaternion, Vector, Vector3, Vector4},
+    scene::Scene,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Interpolation {
+    // hold each key until the next one
+    Step,
+    Linear,
+    // catmull-rom through the keys, tangents from the neighbouring keys
+    Cubic,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LoopMode {
+    // stop on the last frame
+    Once,
+    Loop,
+    // forward then backward
+    PingPong,
+}
+
+#[derive(Clone, Debug)]
+pub enum Keys {
+    Translation(Vec<Vector3>),
+    Rotation(Vec<Quaternion>),
+    Scale(Vec<Vector3>),
+}
+
+// one property of one node over time, `times` sorted and as long as the keys
+#[derive(Clone, Debug)]
+pub struct Track {
+    pub node: usize,
+    pub interpolation: Interpolation,
+    pub times: Vec<f32>,
+    pub keys: Keys,
+}
+
+impl Track {
+    pub fn translation(node: usize, keys: Vec<(f32, Vector3)>) -> Self {
+        let (times, values) = keys.into_iter().unzip();
+        Self {
+            node,
+            interpolation: Interpolation::Linear,
+            times,
+            keys: Keys::Translation(values),
+        }
+    }
+    pub fn rotation(node: usize, keys: Vec<(f32, Quaternion)>) -> Self {
+        let (times, mut values): (Vec<f32>, Vec<Quaternion>) = keys.into_iter().unzip();
+        // q and -q are the same rotation, keep neighbours on the same side so
+        // blending takes the short way around
+        for i in 1..values.len() {
+            if values[i].dot(&values[i - 1]) < 0.0 {
+                values[i] = -values[i];
+            }
+        }
+        Self {
+            node,
+            interpolation: Interpolation::Linear,
+            times,
+            keys: Keys::Rotation(values),
+        }
+    }
+    pub fn scale(node: usize, keys: Vec<(f32, Vector3)>) -> Self {
+        let (times, values) = keys.into_iter().unzip();
+        Self {
+            node,
+            interpolation: Interpolation::Linear,
+            times,
+            keys: Keys::Scale(values),
+        }
+    }
+    pub fn duration(&self) -> f32 {
+        self.times.last().copied().unwrap_or(0.0)
+    }
+    // write the value at `time` into the node. With `looped` the cubic tangents
+    // wrap around, which expects the last key to repeat the first
+    pub fn apply(&self, time: f32, looped: bool, scene: &mut Scene) {
+        if self.times.is_empty() {
+            return;
+        }
+        let transform = &mut scene.node_mut(self.node).transform;
+        match self.keys {
+            Keys::Translation(ref values) => {
+                transform.translation = self.sample(values, time, looped);
+            }
+            Keys::Rotation(ref values) => {
+                let q = self.sample(values, time, looped);
+                transform.rotation = Quaternion::from_vector4(&q).normalize();
+            }
+            Keys::Scale(ref values) => {
+                transform.scale = self.sample(values, time, looped);
+            }
+        }
+    }
+    fn sample<const D: usize>(&self, values: &[impl Key<D>], time: f32, looped: bool) -> Vector<D> {
+        let key = |i: usize| values[i].components();
+        let times = &self.times;
+        let n = times.len();
+        if n == 1 || time <= times[0] {
+            return key(0);
+        }
+        if time >= times[n - 1] {
+            return key(n - 1);
+        }
+        // the segment [i, i + 1] holding `time`
+        let i = times.partition_point(|&t| t <= time) - 1;
+        let dt = times[i + 1] - times[i];
+        let s = if dt > 0.0 {
+            (time - times[i]) / dt
+        } else {
+            0.0
+        };
+        match self.interpolation {
+            Interpolation::Step => key(i),
+            Interpolation::Linear => key(i) * (1.0 - s) + key(i + 1) * s,
+            Interpolation::Cubic => {
+                let m0 = self.tangent(values, i, looped) * dt;
+                let m1 = self.tangent(values, i + 1, looped) * dt;
+                let (s2, s3) = (s * s, s * s * s);
+                key(i) * (2.0 * s3 - 3.0 * s2 + 1.0)
+                    + m0 * (s3 - 2.0 * s2 + s)
+                    + key(i + 1) * (-2.0 * s3 + 3.0 * s2)
+                    + m1 * (s3 - s2)
+            }
+        }
+    }
+    // slope at key `i` from its neighbours, one sided at open ends
+    fn tangent<const D: usize>(&self, values: &[impl Key<D>], i: usize, looped: bool) -> Vector<D> {
+        let times = &self.times;
+        let n = times.len();
+        let period = times[n - 1] - times[0];
+        let (prev, prev_t) = if i > 0 {
+            (values[i - 1].components(), times[i - 1])
+        } else if looped && n > 2 {
+            (values[n - 2].components(), times[n - 2] - period)
+        } else {
+            (values[i].components(), times[i])
+        };
+        let (next, next_t) = if i + 1 < n {
+            (values[i + 1].components(), times[i + 1])
+        } else if looped && n > 2 {
+            (values[1].components(), times[1] + period)
+        } else {
+            (values[i].components(), times[i])
+        };
+        if next_t > prev_t {
+            (next - prev) / (next_t - prev_t)
+        } else {
+            Vector::zero()
+        }
+    }
+}
+
+// keys blend component-wise, rotations as their (x, y, z, w) followed by a
+// normalize
+trait Key<const D: usize> {
+    fn components(&self) -> Vector<D>;
+}
+
+impl<const D: usize> Key<D> for Vector<D> {
+    fn components(&self) -> Vector<D> {
+        self.clone()
+    }
+}
+
+impl Key<4> for Quaternion {
+    fn components(&self) -> Vector4 {
+        self.to_vector4()
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Animation {
+    pub name: String,
+    pub tracks: Vec<Track>,
+}
+
+impl Animation {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            tracks: Vec::new(),
+        }
+    }
+    pub fn add_track(&mut self, track: Track) -> usize {
+        self.tracks.push(track);
+        self.tracks.len() - 1
+    }
+    // up to the last key of the longest track
+    pub fn duration(&self) -> f32 {
+        self.tracks.iter().map(Track::duration).fold(0.0, f32::max)
+    }
+    pub fn apply(&self, time: f32, looped: bool, scene: &mut Scene) {
+        for track in self.tracks.iter() {
+            track.apply(time, looped, scene);
+        }
+    }
+}
+
+// plays one animation on a scene, `advance` once per frame
+pub struct AnimationPlayer {
+    pub animation: Animation,
+    pub mode: LoopMode,
+    pub speed: f32,
+    pub playing: bool,
+    // seconds played, wrapped to the duration (a round trip for PingPong)
+    pub time: f32,
+}
+
+impl AnimationPlayer {
+    pub fn new(animation: Animation, mode: LoopMode) -> Self {
+        Self {
+            animation,
+            mode,
+            speed: 1.0,
+            playing: true,
+            time: 0.0,
+        }
+    }
+    // a Once animation that reached its end
+    pub fn finished(&self) -> bool {
+        self.mode == LoopMode::Once && self.time >= self.animation.duration()
+    }
+    pub fn advance(&mut self, dt: f32, scene: &mut Scene) {
+        let duration = self.animation.duration();
+        let mut local = 0.0;
+        if self.playing {
+            self.time += dt * self.speed;
+        }
+        if duration > 0.0 {
+            match self.mode {
+                LoopMode::Once => {
+                    self.time = self.time.clamp(0.0, duration);
+                    local = self.time;
+                }
+                LoopMode::Loop => {
+                    self.time = self.time.rem_euclid(duration);
+                    local = self.time;
+                }
+                LoopMode::PingPong => {
+                    // time runs over one round trip, played back mirrored on the way back
+                    self.time = self.time.rem_euclid(2.0 * duration);
+                    local = if self.time > duration {
+                        2.0 * duration - self.time
+                    } else {
+                        self.time
+                    };
+                }
+            }
+        }
+        self.animation
+            .apply(local, self.mode == LoopMode::Loop, scene);
+    }
+}
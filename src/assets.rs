@@ -0,0 +1,238 @@
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    hash::{DefaultHasher, Hash, Hasher},
+    marker::PhantomData,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+#[cfg(feature = "ppm")]
+use crate::ppm::load_ppm_file_to_buffer;
+use crate::{image::Image, model::Model};
+
+// refers to an asset in `Assets`, cheap to copy around. Once the asset is
+// unloaded the handle looks up nothing, even if its slot is reused
+pub struct Handle<T> {
+    index: u32,
+    generation: u32,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Handle<T> {}
+
+impl<T> PartialEq for Handle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        (self.index, self.generation) == (other.index, other.generation)
+    }
+}
+
+impl<T> Eq for Handle<T> {}
+
+impl<T> Hash for Handle<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (self.index, self.generation).hash(state);
+    }
+}
+
+impl<T> fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Handle({}v{})", self.index, self.generation)
+    }
+}
+
+// what an asset was found by, so another load of it is the same asset
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum Key {
+    Path(PathBuf),
+    // of the file or the image's pixels
+    Content(u64),
+}
+
+struct Slot<T> {
+    generation: u32,
+    value: Option<T>,
+    keys: Vec<Key>,
+}
+
+struct Pool<T> {
+    slots: Vec<Slot<T>>,
+    free: Vec<u32>,
+    keys: HashMap<Key, Handle<T>>,
+}
+
+impl<T> Pool<T> {
+    fn new() -> Self {
+        Self {
+            slots: Vec::new(),
+            free: Vec::new(),
+            keys: HashMap::new(),
+        }
+    }
+    fn find(&self, key: &Key) -> Option<Handle<T>> {
+        self.keys.get(key).copied()
+    }
+    // by path, then by the file's bytes, and only then from disk
+    fn load(&mut self, path: &Path, load: impl FnOnce(&Path) -> T) -> Handle<T> {
+        let path = path.canonicalize().unwrap();
+        let by_path = Key::Path(path.clone());
+        if let Some(handle) = self.find(&by_path) {
+            return handle;
+        }
+        let by_content = Key::Content(hash(&fs::read(&path).unwrap()));
+        if let Some(handle) = self.find(&by_content) {
+            // remembered for the next load
+            self.keys.insert(by_path.clone(), handle);
+            self.slots[handle.index as usize].keys.push(by_path);
+            return handle;
+        }
+        let value = load(&path);
+        self.insert(value, vec![by_path, by_content])
+    }
+    fn insert(&mut self, value: T, keys: Vec<Key>) -> Handle<T> {
+        let index = match self.free.pop() {
+            Some(index) => index,
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    value: None,
+                    keys: Vec::new(),
+                });
+                self.slots.len() as u32 - 1
+            }
+        };
+        let slot = &mut self.slots[index as usize];
+        slot.value = Some(value);
+        let handle = Handle {
+            index,
+            generation: slot.generation,
+            marker: PhantomData,
+        };
+        for key in keys.iter() {
+            self.keys.insert(key.clone(), handle);
+        }
+        slot.keys = keys;
+        handle
+    }
+    fn slot(&self, handle: Handle<T>) -> Option<&Slot<T>> {
+        self.slots
+            .get(handle.index as usize)
+            .filter(|slot| slot.generation == handle.generation)
+    }
+    fn get(&self, handle: Handle<T>) -> Option<&T> {
+        self.slot(handle)?.value.as_ref()
+    }
+    fn get_mut(&mut self, handle: Handle<T>) -> Option<&mut T> {
+        self.slots
+            .get_mut(handle.index as usize)
+            .filter(|slot| slot.generation == handle.generation)?
+            .value
+            .as_mut()
+    }
+    fn remove(&mut self, handle: Handle<T>) -> Option<T> {
+        self.slot(handle)?;
+        let slot = &mut self.slots[handle.index as usize];
+        let value = slot.value.take()?;
+        for key in slot.keys.drain(..) {
+            self.keys.remove(&key);
+        }
+        slot.generation += 1;
+        self.free.push(handle.index);
+        Some(value)
+    }
+    fn len(&self) -> usize {
+        self.slots.len() - self.free.len()
+    }
+}
+
+// models and images loaded once and looked up by handle. Loading a path again,
+// or another file with the same bytes, gives back the handle of the first load.
+// Images are shared with the materials they are put in, see `image`
+pub struct Assets {
+    models: Pool<Model>,
+    images: Pool<Arc<Image>>,
+}
+
+impl Default for Assets {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Assets {
+    pub fn new() -> Self {
+        Self {
+            models: Pool::new(),
+            images: Pool::new(),
+        }
+    }
+    pub fn load_model(&mut self, path: impl AsRef<Path>) -> Handle<Model> {
+        self.models.load(path.as_ref(), |path| Model::new(path))
+    }
+    // stored bottom row first like material maps
+    #[cfg(feature = "ppm")]
+    pub fn load_image(&mut self, path: impl AsRef<Path>) -> Handle<Image> {
+        cast(self.images.load(path.as_ref(), |path| {
+            let mut image = load_ppm_file_to_buffer(path);
+            image.vflip();
+            Arc::new(image)
+        }))
+    }
+    // built in code, never shared with a load
+    pub fn add_model(&mut self, model: Model) -> Handle<Model> {
+        self.models.insert(model, Vec::new())
+    }
+    // the same pixels added twice are one image
+    pub fn add_image(&mut self, image: Image) -> Handle<Image> {
+        let content = Key::Content(hash(&(image.width, image.height, &image.buffer)));
+        match self.images.find(&content) {
+            Some(handle) => cast(handle),
+            None => cast(self.images.insert(Arc::new(image), vec![content])),
+        }
+    }
+    pub fn model(&self, handle: Handle<Model>) -> Option<&Model> {
+        self.models.get(handle)
+    }
+    pub fn model_mut(&mut self, handle: Handle<Model>) -> Option<&mut Model> {
+        self.models.get_mut(handle)
+    }
+    // for a material map, e.g. `material.diffuse_map = assets.image(handle)`.
+    // Every material given it shares the one copy of the pixels
+    pub fn image(&self, handle: Handle<Image>) -> Option<Arc<Image>> {
+        self.images.get(cast(handle)).cloned()
+    }
+    pub fn unload_model(&mut self, handle: Handle<Model>) -> Option<Model> {
+        self.models.remove(handle)
+    }
+    // the pixels are freed once no material holds on to them any more
+    pub fn unload_image(&mut self, handle: Handle<Image>) -> bool {
+        self.images.remove(cast(handle)).is_some()
+    }
+    pub fn model_count(&self) -> usize {
+        self.models.len()
+    }
+    pub fn image_count(&self) -> usize {
+        self.images.len()
+    }
+}
+
+// images are stored as `Arc<Image>`, handed out as `Handle<Image>`
+fn cast<T, U>(handle: Handle<T>) -> Handle<U> {
+    Handle {
+        index: handle.index,
+        generation: handle.generation,
+        marker: PhantomData,
+    }
+}
+
+fn hash(value: &impl Hash) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
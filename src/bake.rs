@@ -0,0 +1,438 @@
+use std::f32::consts::PI;
+
+use crate::{
+    bvh::Bvh,
+    geometry::{Vector2, Vector3},
+    image::Image,
+    model::Model,
+    raytrace::{hits_sphere, Ray},
+    renderer::interpolate,
+};
+
+// directional light, `dir` is the direction the light travels in
+#[derive(Clone)]
+pub struct BakeLight {
+    pub dir: Vector3,
+    pub intensity: f32,
+}
+
+impl BakeLight {
+    pub fn new(dir: Vector3, intensity: f32) -> Self {
+        Self {
+            dir: dir.normalize(),
+            intensity,
+        }
+    }
+}
+
+// what one vertex sees, kept so a change only recomputes the parts it can affect
+#[derive(Clone, Default)]
+struct Sample {
+    ao: f32,
+    // visibility towards each light
+    lit: Vec<f32>,
+    // objects whose bounds a ray of this sample crossed before it was blocked
+    deps: Vec<usize>,
+    dirty: bool,
+}
+
+struct BakeObject {
+    model: Model,
+    bvh: Bvh,
+    center: Vector3,
+    radius: f32,
+    normals: Vec<Vector3>,
+    samples: Vec<Sample>,
+}
+
+impl BakeObject {
+    fn new(model: Model) -> Self {
+        let (center, radius) = model.bounding_sphere();
+        let mut normals = vec![Vector3::zero(); model.nverts()];
+        for i in 0..model.nfaces() {
+            for j in 0..3 {
+                let v = model.vert_index(i, j);
+                normals[v] = &normals[v] + &model.normal_vert(i, j).normalize();
+            }
+        }
+        let normals = normals
+            .into_iter()
+            .map(|n| {
+                if n.length_square() > 0.0 {
+                    n.normalize()
+                } else {
+                    n
+                }
+            })
+            .collect();
+        let samples = vec![
+            Sample {
+                dirty: true,
+                ..Default::default()
+            };
+            model.nverts()
+        ];
+        Self {
+            bvh: Bvh::new(&model),
+            model,
+            center,
+            radius,
+            normals,
+            samples,
+        }
+    }
+    // start rays slightly off the surface so they don't hit their own triangles
+    fn ray_origin(&self, v: usize) -> Vector3 {
+        self.offset(&self.model.verts()[v], &self.normals[v])
+    }
+    fn offset(&self, p: &Vector3, n: &Vector3) -> Vector3 {
+        p + &(n * (1e-3 * self.radius.max(1e-3)))
+    }
+}
+
+// per vertex ambient occlusion and shadowed direct light, written into the vertex
+// colors of static models. `bake` only redoes the vertices a change can reach,
+// `lightmap` bakes the same per texel
+pub struct Baker {
+    pub ao_samples: u32,
+    pub ao_distance: f32,
+    pub ambient: f32,
+    objects: Vec<BakeObject>,
+    lights: Vec<BakeLight>,
+    dirty_lights: Vec<bool>,
+}
+
+impl Default for Baker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Baker {
+    pub fn new() -> Self {
+        Self {
+            ao_samples: 16,
+            ao_distance: 1.0,
+            ambient: 0.4,
+            objects: Vec::new(),
+            lights: Vec::new(),
+            dirty_lights: Vec::new(),
+        }
+    }
+    // `model` in world space
+    pub fn add_object(&mut self, model: Model) -> usize {
+        let object = BakeObject::new(model);
+        self.invalidate_region(self.objects.len(), &object.center, object.radius);
+        self.objects.push(object);
+        self.objects.len() - 1
+    }
+    // replace an object, e.g. after moving it: its own vertices, every vertex that
+    // saw it before and every vertex whose rays reach where it is now get rebaked
+    pub fn set_object(&mut self, id: usize, model: Model) {
+        let object = BakeObject::new(model);
+        self.invalidate_region(id, &object.center, object.radius);
+        self.objects[id] = object;
+    }
+    pub fn model(&self, id: usize) -> &Model {
+        &self.objects[id].model
+    }
+    pub fn add_light(&mut self, light: BakeLight) -> usize {
+        self.lights.push(light);
+        self.dirty_lights.push(true);
+        self.lights.len() - 1
+    }
+    // only the term of this light is recomputed
+    pub fn set_light(&mut self, id: usize, light: BakeLight) {
+        self.lights[id] = light;
+        self.dirty_lights[id] = true;
+    }
+    // e.g. after changing the ao settings
+    pub fn invalidate_all(&mut self) {
+        for object in self.objects.iter_mut() {
+            for sample in object.samples.iter_mut() {
+                sample.dirty = true;
+            }
+        }
+    }
+    // vertices that still need baking
+    pub fn pending(&self) -> usize {
+        self.objects
+            .iter()
+            .flat_map(|o| o.samples.iter())
+            .filter(|s| s.dirty)
+            .count()
+    }
+    fn invalidate_region(&mut self, id: usize, center: &Vector3, radius: f32) {
+        let directions: Vec<Vector3> = self.lights.iter().map(|l| -&l.dir).collect();
+        for (o, object) in self.objects.iter_mut().enumerate() {
+            if o == id {
+                continue;
+            }
+            for v in 0..object.samples.len() {
+                if object.samples[v].dirty {
+                    continue;
+                }
+                // a vertex only cares if it saw the old object or one of its ao or
+                // shadow rays passes through the new one
+                let seen = object.samples[v].deps.binary_search(&id).is_ok();
+                let reaches = || {
+                    let origin = object.ray_origin(v);
+                    let n = &object.normals[v];
+                    let ray = |d: &Vector3| Ray::new(origin.clone(), d.clone());
+                    hemisphere(n, self.ao_samples)
+                        .iter()
+                        .any(|d| hits_sphere(&ray(d), self.ao_distance, center, radius))
+                        || directions.iter().any(|d| {
+                            n.dot(d) > 0.0 && hits_sphere(&ray(d), f32::MAX, center, radius)
+                        })
+                };
+                if seen || reaches() {
+                    object.samples[v].dirty = true;
+                }
+            }
+        }
+    }
+    // bring every dirty vertex and light term up to date, returns how many vertices
+    // were rebaked
+    pub fn bake(&mut self) -> usize {
+        let mut rebaked = 0;
+        let mut updates = Vec::new();
+        for (o, object) in self.objects.iter().enumerate() {
+            let mut samples = object.samples.clone();
+            let mut changed = false;
+            for (v, sample) in samples.iter_mut().enumerate() {
+                let origin = object.ray_origin(v);
+                let n = &object.normals[v];
+                if sample.dirty {
+                    let mut deps = Vec::new();
+                    let directions = hemisphere(n, self.ao_samples);
+                    let open = directions
+                        .iter()
+                        .filter(|d| !self.occluded(&origin, d, self.ao_distance, &mut deps))
+                        .count();
+                    sample.ao = open as f32 / directions.len().max(1) as f32;
+                    sample.lit = self
+                        .lights
+                        .iter()
+                        .map(|l| self.visibility(&origin, n, l, &mut deps))
+                        .collect();
+                    deps.sort_unstable();
+                    deps.dedup();
+                    sample.deps = deps;
+                    sample.dirty = false;
+                    rebaked += 1;
+                    changed = true;
+                    continue;
+                }
+                sample.lit.resize(self.lights.len(), 0.0);
+                for (l, light) in self.lights.iter().enumerate() {
+                    if self.dirty_lights[l] {
+                        let mut deps = std::mem::take(&mut sample.deps);
+                        sample.lit[l] = self.visibility(&origin, n, light, &mut deps);
+                        deps.sort_unstable();
+                        deps.dedup();
+                        sample.deps = deps;
+                        changed = true;
+                    }
+                }
+            }
+            if changed {
+                updates.push((o, samples));
+            }
+        }
+        for (o, samples) in updates {
+            let normals = &self.objects[o].normals;
+            let colors = samples
+                .iter()
+                .zip(normals.iter())
+                .map(|(sample, n)| gray(self.light(n, sample.ao, &sample.lit)))
+                .collect();
+            let object = &mut self.objects[o];
+            object.samples = samples;
+            object.model.set_colors(colors);
+        }
+        self.dirty_lights.fill(false);
+        rebaked
+    }
+    fn light(&self, n: &Vector3, ao: f32, lit: &[f32]) -> f32 {
+        let mut light = self.ambient * ao;
+        for (l, lit) in self.lights.iter().zip(lit.iter()) {
+            let diffuse = (-n.dot(&l.dir)).max(0.0);
+            light += l.intensity * diffuse * lit;
+        }
+        light
+    }
+    // ambient occlusion and shadowed light of an object per texel of its second uv
+    // layout (the first one if it has none), as a gray map to use as its
+    // `Material::lightmap` or multiply into its diffuse map. Stored
+    // bottom row first like the maps of a `Material`. Texels no triangle covers
+    // take the value of a covered neighbour `padding` texels out, so filtering
+    // and mip levels don't bleed in black at the uv seams
+    pub fn lightmap(&self, id: usize, width: u32, height: u32, padding: u32) -> Image {
+        let object = &self.objects[id];
+        let model = &object.model;
+        let mut buffer = vec![0xff000000; (width * height) as usize];
+        let mut covered = vec![false; buffer.len()];
+        let size = Vector2::new(width as f32, height as f32);
+        for i in 0..model.nfaces() {
+            let uvs = [0, 1, 2].map(|j| {
+                let uv = model.uv1(i, j);
+                Vector2::new(uv.x() * size.x(), uv.y() * size.y())
+            });
+            let area = edge(&uvs[0], &uvs[1], &uvs[2]);
+            if area.abs() < 1e-12 {
+                continue;
+            }
+            let verts = [0, 1, 2].map(|j| model.vert(i, j));
+            let normals = [0, 1, 2].map(|j| model.normal_vert(i, j));
+            let lo = |k: usize| uvs.iter().map(|p| p[k]).fold(f32::MAX, f32::min);
+            let hi = |k: usize| uvs.iter().map(|p| p[k]).fold(f32::MIN, f32::max);
+            let x0 = (lo(0) - 0.5).ceil().max(0.0) as u32;
+            let y0 = (lo(1) - 0.5).ceil().max(0.0) as u32;
+            let x1 = ((hi(0) - 0.5).floor() + 1.0).clamp(0.0, width as f32) as u32;
+            let y1 = ((hi(1) - 0.5).floor() + 1.0).clamp(0.0, height as f32) as u32;
+            for y in y0..y1 {
+                for x in x0..x1 {
+                    // texel centers, either winding of the uv triangle
+                    let p = Vector2::new(x as f32 + 0.5, y as f32 + 0.5);
+                    let bc = Vector3::new(
+                        edge(&uvs[1], &uvs[2], &p) / area,
+                        edge(&uvs[2], &uvs[0], &p) / area,
+                        edge(&uvs[0], &uvs[1], &p) / area,
+                    );
+                    if bc[0] < 0.0 || bc[1] < 0.0 || bc[2] < 0.0 {
+                        continue;
+                    }
+                    let position = interpolate(&bc, [&verts[0], &verts[1], &verts[2]]);
+                    let n = interpolate(&bc, [&normals[0], &normals[1], &normals[2]]);
+                    if n.length_square() == 0.0 {
+                        continue;
+                    }
+                    let n = n.normalize();
+                    let origin = object.offset(&position, &n);
+                    let mut deps = Vec::new();
+                    let directions = hemisphere(&n, self.ao_samples);
+                    let open = directions
+                        .iter()
+                        .filter(|d| !self.occluded(&origin, d, self.ao_distance, &mut deps))
+                        .count();
+                    let ao = open as f32 / directions.len().max(1) as f32;
+                    let lit: Vec<f32> = self
+                        .lights
+                        .iter()
+                        .map(|l| self.visibility(&origin, &n, l, &mut deps))
+                        .collect();
+                    let index = (x + y * width) as usize;
+                    buffer[index] = gray(self.light(&n, ao, &lit));
+                    covered[index] = true;
+                }
+            }
+        }
+        dilate(&mut buffer, &mut covered, width, height, padding);
+        Image {
+            buffer,
+            width,
+            height,
+        }
+    }
+    fn visibility(
+        &self,
+        origin: &Vector3,
+        n: &Vector3,
+        light: &BakeLight,
+        deps: &mut Vec<usize>,
+    ) -> f32 {
+        let towards = -&light.dir;
+        if n.dot(&towards) <= 0.0 || self.occluded(origin, &towards, f32::MAX, deps) {
+            0.0
+        } else {
+            1.0
+        }
+    }
+    // records every object whose bounds the ray crosses until it is blocked
+    fn occluded(&self, origin: &Vector3, dir: &Vector3, max_t: f32, deps: &mut Vec<usize>) -> bool {
+        let ray = Ray::new(origin.clone(), dir.clone());
+        for (o, object) in self.objects.iter().enumerate() {
+            if !hits_sphere(&ray, max_t, &object.center, object.radius) {
+                continue;
+            }
+            deps.push(o);
+            if object.bvh.occluded(&ray, max_t) {
+                return true;
+            }
+        }
+        false
+    }
+}
+
+// cosine distributed directions around `n`, the same for every call
+fn hemisphere(n: &Vector3, count: u32) -> Vec<Vector3> {
+    let helper = if n.x().abs() < 0.9 {
+        Vector3::new(1.0, 0.0, 0.0)
+    } else {
+        Vector3::new(0.0, 1.0, 0.0)
+    };
+    let t = helper.cross(n).normalize();
+    let b = n.cross(&t);
+    let golden = PI * (3.0 - 5f32.sqrt());
+    (0..count)
+        .map(|i| {
+            let r = ((i as f32 + 0.5) / count as f32).sqrt();
+            let phi = i as f32 * golden;
+            let z = (1.0 - r * r).max(0.0).sqrt();
+            &(&(&t * (r * phi.cos())) + &(&b * (r * phi.sin()))) + &(n * z)
+        })
+        .collect()
+}
+
+// twice the signed area of abc
+fn edge(a: &Vector2, b: &Vector2, c: &Vector2) -> f32 {
+    (b.x() - a.x()) * (c.y() - a.y()) - (b.y() - a.y()) * (c.x() - a.x())
+}
+
+// grow the covered texels outwards one ring per pass, each new texel averages
+// its covered neighbours
+fn dilate(buffer: &mut [u32], covered: &mut [bool], width: u32, height: u32, passes: u32) {
+    for _ in 0..passes {
+        let mut grown = Vec::new();
+        for y in 0..height as i32 {
+            for x in 0..width as i32 {
+                if covered[(x + y * width as i32) as usize] {
+                    continue;
+                }
+                let mut sum = [0; 4];
+                let mut n = 0;
+                for (dx, dy) in [(-1, 0), (1, 0), (0, -1), (0, 1)] {
+                    let (nx, ny) = (x + dx, y + dy);
+                    if nx < 0 || ny < 0 || nx >= width as i32 || ny >= height as i32 {
+                        continue;
+                    }
+                    let i = (nx + ny * width as i32) as usize;
+                    if covered[i] {
+                        for (c, s) in sum.iter_mut().enumerate() {
+                            *s += (buffer[i] >> (8 * c)) & 0xff;
+                        }
+                        n += 1;
+                    }
+                }
+                if n > 0 {
+                    let pixel = (0..4).fold(0, |p, c| p | (sum[c] / n) << (8 * c));
+                    grown.push(((x + y * width as i32) as usize, pixel));
+                }
+            }
+        }
+        if grown.is_empty() {
+            break;
+        }
+        for (i, pixel) in grown {
+            buffer[i] = pixel;
+            covered[i] = true;
+        }
+    }
+}
+
+// linear light to an sRGB gray vertex color
+fn gray(light: f32) -> u32 {
+    let c = (light.clamp(0.0, 1.0).powf(1.0 / 2.2) * 255.0).round() as u32;
+    0xff000000 | c << 16 | c << 8 | c
+}
@@ -0,0 +1,313 @@
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    io::{self, Write},
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    geometry::{Matrix, Vector2, Vector3},
+    model::Model,
+    renderer::{Renderer, Shader},
+    scene::{Camera, Light, Scene, Transform},
+};
+
+// how long `run` spends on a benchmark: a warmup, which also picks how many
+// iterations make a sample, then `samples` samples of about `sample_time` each.
+// Workloads fill their triangles with `threads` threads
+#[derive(Clone, Debug)]
+pub struct Bench {
+    pub warmup: Duration,
+    pub samples: usize,
+    pub sample_time: Duration,
+    pub threads: usize,
+}
+
+impl Default for Bench {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Bench {
+    pub fn new() -> Self {
+        Self {
+            warmup: Duration::from_secs(1),
+            samples: 20,
+            sample_time: Duration::from_millis(200),
+            threads: 1,
+        }
+    }
+    pub fn run(&self, name: &str, mut f: impl FnMut()) -> Measurement {
+        let start = Instant::now();
+        let mut warmup_iterations = 0u32;
+        while warmup_iterations == 0 || start.elapsed() < self.warmup {
+            f();
+            warmup_iterations += 1;
+        }
+        let estimate = start.elapsed() / warmup_iterations;
+        let iterations = (self.sample_time.as_secs_f64() / estimate.as_secs_f64().max(1e-9))
+            .clamp(1.0, u32::MAX as f64) as u32;
+        let samples = (0..self.samples)
+            .map(|_| {
+                let start = Instant::now();
+                for _ in 0..iterations {
+                    f();
+                }
+                start.elapsed() / iterations
+            })
+            .collect();
+        Measurement {
+            name: name.to_string(),
+            iterations,
+            samples,
+        }
+    }
+}
+
+// the time of one iteration in each sample
+#[derive(Clone, Debug)]
+pub struct Measurement {
+    pub name: String,
+    // per sample
+    pub iterations: u32,
+    pub samples: Vec<Duration>,
+}
+
+impl Measurement {
+    pub fn mean(&self) -> Duration {
+        self.samples.iter().sum::<Duration>() / self.samples.len().max(1) as u32
+    }
+    pub fn median(&self) -> Duration {
+        let mut sorted = self.samples.clone();
+        sorted.sort();
+        sorted.get(sorted.len() / 2).copied().unwrap_or_default()
+    }
+    pub fn min(&self) -> Duration {
+        self.samples.iter().min().copied().unwrap_or_default()
+    }
+    pub fn max(&self) -> Duration {
+        self.samples.iter().max().copied().unwrap_or_default()
+    }
+    pub fn std_dev(&self) -> Duration {
+        let mean = self.mean().as_secs_f64();
+        let variance = self
+            .samples
+            .iter()
+            .map(|s| (s.as_secs_f64() - mean).powi(2))
+            .sum::<f64>()
+            / self.samples.len().max(1) as f64;
+        Duration::from_secs_f64(variance.sqrt())
+    }
+    // of the median against a baseline's, -0.1 is 10% faster
+    pub fn change(&self, baseline: Duration) -> f64 {
+        self.median().as_secs_f64() / baseline.as_secs_f64() - 1.0
+    }
+}
+
+impl fmt::Display for Measurement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ms = |d: Duration| d.as_secs_f64() * 1e3;
+        write!(
+            f,
+            "{:<20} median {:>8.3} ms  mean {:>8.3} ms ± {:.3}  [{:.3} .. {:.3}]",
+            self.name,
+            ms(self.median()),
+            ms(self.mean()),
+            ms(self.std_dev()),
+            ms(self.min()),
+            ms(self.max()),
+        )
+    }
+}
+
+// medians by name as `name,nanoseconds` lines, to compare a later run with
+pub fn save_baseline(path: impl AsRef<Path>, measurements: &[Measurement]) -> io::Result<()> {
+    let mut out = fs::File::create(path)?;
+    for measurement in measurements {
+        writeln!(
+            out,
+            "{},{}",
+            measurement.name,
+            measurement.median().as_nanos()
+        )?;
+    }
+    Ok(())
+}
+
+pub fn load_baseline(path: impl AsRef<Path>) -> io::Result<HashMap<String, Duration>> {
+    let text = fs::read_to_string(path)?;
+    let mut baseline = HashMap::new();
+    for line in text.lines() {
+        let parsed = line
+            .rsplit_once(',')
+            .and_then(|(name, nanos)| Some((name, nanos.trim().parse().ok()?)));
+        let Some((name, nanos)) = parsed else {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, line.to_string()));
+        };
+        baseline.insert(name.to_string(), Duration::from_nanos(nanos));
+    }
+    Ok(baseline)
+}
+
+// the african head with its maps from `obj_dir`, e.g. "./obj"
+pub fn load_head(obj_dir: impl AsRef<Path>) -> Model {
+    let dir = obj_dir.as_ref();
+    let mut model = Model::new(dir.join("african_head.obj"));
+    model.load_diffuse_map(dir.join("african_head_diffuse.ppm"));
+    model.load_normal_map(dir.join("african_head_nm.ppm"));
+    model.load_specular_map(dir.join("african_head_spec.ppm"));
+    model
+}
+
+// a standard frame for tracking the rasterizer's speed, the same on every
+// machine and every run
+pub struct Workload {
+    pub name: &'static str,
+    // None for the full screen quad
+    scene: Option<(Scene, Camera)>,
+}
+
+impl Workload {
+    // the head filling most of the screen
+    pub fn head(head: Model) -> Self {
+        let mut scene = lit_scene();
+        let model = scene.add_model(head);
+        let node = scene.add_node("head", None);
+        scene.nodes[node].model = Some(model);
+        Self {
+            name: "head",
+            scene: Some((scene, front_camera())),
+        }
+    }
+    // `count` nodes sharing one head, on a square grid across the screen
+    pub fn instanced_heads(head: Model, count: usize) -> Self {
+        let mut scene = lit_scene();
+        let model = scene.add_model(head);
+        let side = (count as f32).sqrt().ceil() as usize;
+        let spacing = 2.4 / side as f32;
+        for i in 0..count {
+            let (x, y) = ((i % side) as f32, (i / side) as f32);
+            let offset = (side - 1) as f32 / 2.0;
+            let node = scene.add_node(&format!("head {i}"), None);
+            scene.nodes[node].model = Some(model);
+            scene.nodes[node].transform = Transform {
+                scale: Vector3::new(1.0, 1.0, 1.0) * (spacing / 2.2),
+                ..Transform::from_translation(Vector3::new(
+                    (x - offset) * spacing,
+                    (y - offset) * spacing,
+                    0.0,
+                ))
+            };
+        }
+        Self {
+            name: "instanced heads",
+            scene: Some((scene, front_camera())),
+        }
+    }
+    // every pixel once through a fragment shader that does a lot of math, so
+    // the time is all in the fragment stage
+    pub fn fullscreen_quad() -> Self {
+        Self {
+            name: "fullscreen quad",
+            scene: None,
+        }
+    }
+    // drawn into a `width` x `height` buffer over and over
+    pub fn measure(&self, bench: &Bench, width: u32, height: u32) -> Measurement {
+        let size = (width * height) as usize;
+        let mut buffer = vec![0u32; size];
+        let mut z_buffer = vec![0f32; size];
+        bench.run(self.name, || {
+            let mut renderer = Renderer::new(&mut buffer, &mut z_buffer, width, height);
+            renderer.threads = bench.threads;
+            self.draw(&mut renderer);
+        })
+    }
+    // clears and draws one frame
+    pub fn draw(&self, renderer: &mut Renderer) {
+        renderer.fill(0xff000000);
+        match &self.scene {
+            Some((scene, camera)) => {
+                renderer.draw_scene(scene, camera);
+            }
+            None => {
+                let (w, h) = (renderer.width as f32, renderer.height as f32);
+                let mut shader = HeavyShader {
+                    corners: [
+                        Vector3::new(0.0, 0.0, 0.0),
+                        Vector3::new(w, 0.0, 0.0),
+                        Vector3::new(w, h, 0.0),
+                        Vector3::new(0.0, h, 0.0),
+                    ],
+                    varying_uv: Matrix::zero(),
+                };
+                for face in 0..2 {
+                    let verts = [0, 1, 2].map(|j| shader.vertex(face, j));
+                    renderer.fill_triangle(&verts, &mut shader);
+                }
+            }
+        }
+    }
+}
+
+// the head, 100 instanced heads and the full screen quad, with the head
+// loaded from `obj_dir`
+pub fn standard_workloads(obj_dir: impl AsRef<Path>) -> Vec<Workload> {
+    let dir = obj_dir.as_ref();
+    vec![
+        Workload::head(load_head(dir)),
+        Workload::instanced_heads(load_head(dir), 100),
+        Workload::fullscreen_quad(),
+    ]
+}
+
+fn lit_scene() -> Scene {
+    let mut scene = Scene::new();
+    let light = scene.add_node("light", None);
+    scene.nodes[light].light = Some(Light::new(Vector3::new(-1.0, -1.0, -1.0), 1.0));
+    scene
+}
+
+fn front_camera() -> Camera {
+    Camera::new(Vector3::new(0.0, 0.0, 3.0), Vector3::zero())
+}
+
+const OCTAVES: u32 = 6;
+
+#[derive(Clone)]
+struct HeavyShader {
+    // in pixels, counter-clockwise from the top left
+    corners: [Vector3; 4],
+    varying_uv: Matrix<3, 2>,
+}
+
+impl Shader for HeavyShader {
+    fn vertex(&mut self, iface: usize, nthvert: usize) -> Vector3 {
+        let corner = [[0, 1, 2], [0, 2, 3]][iface][nthvert];
+        let uv = Vector2::new([0.0, 1.0, 1.0, 0.0][corner], [0.0, 0.0, 1.0, 1.0][corner]);
+        self.varying_uv.set_row(nthvert, uv);
+        self.corners[corner].clone()
+    }
+
+    fn fregment(&mut self, bc: &Vector3) -> Option<u32> {
+        let uv = (Matrix::from_row_vector(bc.clone()) * &self.varying_uv).to_row_vector();
+        let (mut u, mut v) = (uv.x(), uv.y());
+        let mut value = 0.0;
+        let mut amplitude = 0.5;
+        for _ in 0..OCTAVES {
+            value += amplitude * (u * 7.0 + (v * 5.0).sin()).sin() * (v * 6.0 - u).cos();
+            (u, v) = (u * 1.9 + 0.3 * v, v * 1.9 - 0.3 * u);
+            amplitude *= 0.55;
+        }
+        let shade = ((value * 0.5 + 0.5).clamp(0.0, 1.0) * 255.0) as u32;
+        Some(0xff000000 | shade << 16 | (255 - shade) << 8 | shade)
+    }
+    fn fork<'f>(&self) -> Option<Box<dyn Shader + Send + 'f>>
+    where
+        Self: 'f,
+    {
+        Some(Box::new(self.clone()))
+    }
+}
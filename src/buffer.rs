@@ -0,0 +1,63 @@
+#[cfg(not(feature = "std"))]
+use crate::compat::*;
+use crate::geometry::{Vector2, Vector3};
+#[cfg(feature = "model")]
+use crate::{compat::HashMap, model::Model};
+
+// one entry per distinct corner of a mesh, so a vertex shared by several
+// triangles is only shaded once by `Renderer::draw_indexed`
+#[derive(Default)]
+pub struct VertexBuffer {
+    pub positions: Vec<Vector3>,
+    pub uvs: Vec<Vector2>,
+    pub uvs1: Vec<Vector2>,
+    pub normals: Vec<Vector3>,
+    pub tangents: Vec<Vector3>,
+    pub bitangents: Vec<Vector3>,
+    pub colors: Vec<u32>,
+}
+
+// three indices into a vertex buffer per triangle
+#[derive(Default)]
+pub struct IndexBuffer {
+    pub indices: Vec<usize>,
+}
+
+impl IndexBuffer {
+    pub fn ntriangles(&self) -> usize {
+        self.indices.len() / 3
+    }
+}
+
+impl VertexBuffer {
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+    // corners sharing position, uv and normal become one vertex; rebuild after the
+    // model changes
+    #[cfg(feature = "model")]
+    pub fn from_model(model: &Model) -> (Self, IndexBuffer) {
+        let mut vertices = Self::default();
+        let mut indices = IndexBuffer::default();
+        let mut seen = HashMap::new();
+        for i in 0..model.nfaces() {
+            for j in 0..3 {
+                let index = *seen.entry(model.corner(i, j)).or_insert_with(|| {
+                    vertices.positions.push(model.vert(i, j));
+                    vertices.uvs.push(model.uv(i, j));
+                    vertices.uvs1.push(model.uv1(i, j));
+                    vertices.normals.push(model.normal_vert(i, j));
+                    vertices.tangents.push(model.tangent(i, j));
+                    vertices.bitangents.push(model.bitangent(i, j));
+                    vertices.colors.push(model.color(i, j));
+                    vertices.positions.len() - 1
+                });
+                indices.indices.push(index);
+            }
+        }
+        (vertices, indices)
+    }
+}
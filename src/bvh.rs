@@ -0,0 +1,261 @@
+use crate::{
+    geometry::Vector3,
+    model::Model,
+    raytrace::{intersect_triangle, Hit, Ray},
+};
+
+// leaves stop splitting at this many triangles
+const LEAF_SIZE: usize = 4;
+// centroid buckets tried for each split
+const BINS: usize = 12;
+
+#[derive(Clone, Debug)]
+struct Node {
+    min: Vector3,
+    max: Vector3,
+    // a leaf holds `count` faces from `start` of the face order, an inner node
+    // has its children at `start` and `start + 1`
+    start: usize,
+    count: usize,
+}
+
+// bounding volume hierarchy over triangles, split with the surface area
+// heuristic over binned centroids
+pub struct Bvh {
+    nodes: Vec<Node>,
+    // face ids in leaf order
+    order: Vec<usize>,
+    triangles: Vec<[Vector3; 3]>,
+}
+
+impl Bvh {
+    // in the model's own space
+    pub fn new(model: &Model) -> Self {
+        Self::from_triangles(
+            (0..model.nfaces())
+                .map(|i| [model.vert(i, 0), model.vert(i, 1), model.vert(i, 2)])
+                .collect(),
+        )
+    }
+    // hits report the index into `triangles` as their face
+    pub fn from_triangles(triangles: Vec<[Vector3; 3]>) -> Self {
+        let mut bvh = Self {
+            nodes: Vec::new(),
+            order: (0..triangles.len()).collect(),
+            triangles,
+        };
+        let centroids: Vec<Vector3> = bvh
+            .triangles
+            .iter()
+            .map(|[a, b, c]| (a + b + c) / 3.0)
+            .collect();
+        bvh.nodes.push(bvh.leaf(0, bvh.triangles.len()));
+        bvh.split(0, &centroids);
+        bvh
+    }
+    pub fn triangle(&self, face: usize) -> &[Vector3; 3] {
+        &self.triangles[face]
+    }
+    pub fn bounds(&self) -> (Vector3, Vector3) {
+        (self.nodes[0].min.clone(), self.nodes[0].max.clone())
+    }
+    fn leaf(&self, start: usize, count: usize) -> Node {
+        let (min, max) = bounds(
+            self.order[start..start + count]
+                .iter()
+                .flat_map(|&f| &self.triangles[f]),
+        );
+        Node {
+            min,
+            max,
+            start,
+            count,
+        }
+    }
+    fn split(&mut self, node: usize, centroids: &[Vector3]) {
+        let Node { start, count, .. } = self.nodes[node];
+        if count <= LEAF_SIZE {
+            return;
+        }
+        let faces = &self.order[start..start + count];
+        let (lo, hi) = bounds(faces.iter().map(|&f| &centroids[f]));
+        let extent = &hi - &lo;
+        let axis = (0..3)
+            .max_by(|&a, &b| extent[a].total_cmp(&extent[b]))
+            .unwrap();
+        if extent[axis] <= 0.0 {
+            return;
+        }
+        let bin = |f: usize| {
+            (((centroids[f][axis] - lo[axis]) / extent[axis] * BINS as f32) as usize).min(BINS - 1)
+        };
+        // cost of splitting after each bin, area times triangle count on both sides
+        let mut boxes: Vec<Option<(Vector3, Vector3)>> = vec![None; BINS];
+        let mut counts = [0; BINS];
+        for &f in faces {
+            let b = bin(f);
+            counts[b] += 1;
+            let (min, max) = bounds(&self.triangles[f]);
+            boxes[b] = Some(match boxes[b].take() {
+                Some(bx) => union(&bx, &(min, max)),
+                None => (min, max),
+            });
+        }
+        let sweep = |range: &mut dyn Iterator<Item = usize>| {
+            let mut acc: Option<(Vector3, Vector3)> = None;
+            let mut n = 0;
+            let mut out = vec![(0.0, 0); BINS];
+            for b in range {
+                if let Some(bx) = &boxes[b] {
+                    acc = Some(match acc {
+                        Some(a) => union(&a, bx),
+                        None => bx.clone(),
+                    });
+                }
+                n += counts[b];
+                out[b] = (acc.as_ref().map_or(0.0, area), n);
+            }
+            out
+        };
+        let left = sweep(&mut (0..BINS));
+        let right = sweep(&mut (0..BINS).rev());
+        let best = (0..BINS - 1)
+            .filter(|&b| left[b].1 > 0 && right[b + 1].1 > 0)
+            .map(|b| {
+                (
+                    b,
+                    left[b].0 * left[b].1 as f32 + right[b + 1].0 * right[b + 1].1 as f32,
+                )
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1));
+        let Some((best, cost)) = best else {
+            return;
+        };
+        // not worth it when testing every triangle is cheaper
+        let parent = &self.nodes[node];
+        if cost >= area(&(parent.min.clone(), parent.max.clone())) * count as f32
+            && count <= 4 * LEAF_SIZE
+        {
+            return;
+        }
+        let faces = &mut self.order[start..start + count];
+        let mut mid = 0;
+        for i in 0..faces.len() {
+            if bin(faces[i]) <= best {
+                faces.swap(i, mid);
+                mid += 1;
+            }
+        }
+        let children = self.nodes.len();
+        let left = self.leaf(start, mid);
+        let right = self.leaf(start + mid, count - mid);
+        self.nodes.push(left);
+        self.nodes.push(right);
+        self.nodes[node].start = children;
+        self.nodes[node].count = 0;
+        self.split(children, centroids);
+        self.split(children + 1, centroids);
+    }
+    // the closest triangle along the ray
+    pub fn intersect(&self, ray: &Ray) -> Option<Hit> {
+        let mut closest: Option<Hit> = None;
+        self.walk(ray, f32::MAX, |face, t, u, v| {
+            closest = Some(Hit {
+                t,
+                face,
+                barycentric: Vector3::new(1.0 - u - v, u, v),
+            });
+            Some(t)
+        });
+        closest
+    }
+    // any triangle closer than `max_t`
+    pub fn occluded(&self, ray: &Ray, max_t: f32) -> bool {
+        let mut hit = false;
+        self.walk(ray, max_t, |_, _, _, _| {
+            hit = true;
+            None
+        });
+        hit
+    }
+    // `found` gets every triangle hit closer than the current limit and returns
+    // the new limit, None stops the walk
+    fn walk(
+        &self,
+        ray: &Ray,
+        max_t: f32,
+        mut found: impl FnMut(usize, f32, f32, f32) -> Option<f32>,
+    ) {
+        if self.triangles.is_empty() {
+            return;
+        }
+        let inv = Vector3::new(1.0 / ray.dir.x(), 1.0 / ray.dir.y(), 1.0 / ray.dir.z());
+        let mut limit = max_t;
+        let mut stack = vec![0];
+        while let Some(i) = stack.pop() {
+            let node = &self.nodes[i];
+            if slab(ray, &inv, &node.min, &node.max).map_or(true, |t| t >= limit) {
+                continue;
+            }
+            if node.count == 0 {
+                // nearer child on top
+                let (a, b) = (node.start, node.start + 1);
+                let ta = slab(ray, &inv, &self.nodes[a].min, &self.nodes[a].max);
+                let tb = slab(ray, &inv, &self.nodes[b].min, &self.nodes[b].max);
+                if ta.unwrap_or(f32::MAX) < tb.unwrap_or(f32::MAX) {
+                    stack.push(b);
+                    stack.push(a);
+                } else {
+                    stack.push(a);
+                    stack.push(b);
+                }
+                continue;
+            }
+            for &face in &self.order[node.start..node.start + node.count] {
+                let [a, b, c] = &self.triangles[face];
+                if let Some((t, u, v)) = intersect_triangle(ray, a, b, c) {
+                    if t < limit {
+                        match found(face, t, u, v) {
+                            Some(t) => limit = t,
+                            None => return,
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+// where the ray enters the box, 0 if it starts inside
+fn slab(ray: &Ray, inv: &Vector3, min: &Vector3, max: &Vector3) -> Option<f32> {
+    let (mut near, mut far) = (0.0f32, f32::MAX);
+    for i in 0..3 {
+        let t0 = (min[i] - ray.origin[i]) * inv[i];
+        let t1 = (max[i] - ray.origin[i]) * inv[i];
+        // NaN from 0 * inf, a ray in the plane of a face, leaves the bounds alone
+        near = near.max(t0.min(t1));
+        far = far.min(t0.max(t1));
+    }
+    (near <= far).then_some(near)
+}
+
+fn bounds<'a>(points: impl IntoIterator<Item = &'a Vector3>) -> (Vector3, Vector3) {
+    let mut min = Vector3::new(f32::MAX, f32::MAX, f32::MAX);
+    let mut max = Vector3::new(f32::MIN, f32::MIN, f32::MIN);
+    for p in points {
+        for i in 0..3 {
+            min[i] = min[i].min(p[i]);
+            max[i] = max[i].max(p[i]);
+        }
+    }
+    (min, max)
+}
+
+fn union(a: &(Vector3, Vector3), b: &(Vector3, Vector3)) -> (Vector3, Vector3) {
+    bounds([&a.0, &a.1, &b.0, &b.1])
+}
+
+fn area((min, max): &(Vector3, Vector3)) -> f32 {
+    let d = max - min;
+    2.0 * (d.x() * d.y() + d.y() * d.z() + d.z() * d.x())
+}
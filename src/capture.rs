@@ -0,0 +1,148 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{image::Image, ppm::write_ppm, renderer::Renderer};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Ppm,
+    // 32 bit uncompressed, opens in about any image viewer
+    Bmp,
+}
+
+impl Format {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Ppm => "ppm",
+            Format::Bmp => "bmp",
+        }
+    }
+    // by extension, e.g. "bmp"
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "ppm" => Some(Format::Ppm),
+            "bmp" => Some(Format::Bmp),
+            _ => None,
+        }
+    }
+    pub fn write(self, image: &Image, out: &mut impl Write) -> io::Result<()> {
+        match self {
+            Format::Ppm => write_ppm(&image.buffer, image.width, image.height, image.width, out),
+            Format::Bmp => write_bmp(image, out),
+        }
+    }
+}
+
+// where screenshots go, `save` names them after the time they were taken, e.g.
+// `screenshot_20240131_235959_123.ppm`
+#[derive(Clone, Debug)]
+pub struct Capture {
+    pub dir: PathBuf,
+    pub prefix: String,
+    pub format: Format,
+}
+
+impl Capture {
+    pub fn new(dir: impl AsRef<Path>, format: Format) -> Self {
+        Self {
+            dir: dir.as_ref().to_path_buf(),
+            prefix: "screenshot".to_string(),
+            format,
+        }
+    }
+    // returns the file written. Two in the same millisecond get a counter
+    // rather than overwriting each other
+    pub fn save(&self, image: &Image) -> io::Result<PathBuf> {
+        let name = format!("{}_{}", self.prefix, timestamp(SystemTime::now()));
+        let mut unique = name.clone();
+        let mut n = 1;
+        while self.path(&unique).exists() {
+            unique = format!("{name}_{n}");
+            n += 1;
+        }
+        self.save_as(&unique, image)
+    }
+    // as `dir/<name>.<extension>`, replacing what is there. `dir` is created if
+    // missing
+    pub fn save_as(&self, name: &str, image: &Image) -> io::Result<PathBuf> {
+        fs::create_dir_all(&self.dir)?;
+        let path = self.path(name);
+        let mut out = BufWriter::new(File::create(&path)?);
+        self.format.write(image, &mut out)?;
+        out.flush()?;
+        Ok(path)
+    }
+    fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(format!("{name}.{}", self.format.extension()))
+    }
+}
+
+impl Renderer<'_> {
+    // a copy of the colors as they are now, top row first like the screen.
+    // Unlike the material maps, which are stored bottom row first
+    pub fn capture(&self) -> Image {
+        let mut buffer = Vec::with_capacity((self.width * self.height) as usize);
+        for y in 0..self.height {
+            buffer.extend((0..self.width).map(|x| self.pixel(x, y)));
+        }
+        Image {
+            buffer,
+            width: self.width,
+            height: self.height,
+        }
+    }
+}
+
+// BITMAPINFOHEADER with a negative height, so the rows go top to bottom
+fn write_bmp(image: &Image, out: &mut impl Write) -> io::Result<()> {
+    const HEADER: u32 = 14 + 40;
+    let size = image.width * image.height * 4;
+    out.write_all(b"BM")?;
+    out.write_all(&(HEADER + size).to_le_bytes())?;
+    out.write_all(&0u32.to_le_bytes())?;
+    out.write_all(&HEADER.to_le_bytes())?;
+    out.write_all(&40u32.to_le_bytes())?;
+    out.write_all(&(image.width as i32).to_le_bytes())?;
+    out.write_all(&(-(image.height as i32)).to_le_bytes())?;
+    // planes, bits per pixel
+    out.write_all(&1u16.to_le_bytes())?;
+    out.write_all(&32u16.to_le_bytes())?;
+    // uncompressed, then size, resolution and palette left to the reader
+    out.write_all(&0u32.to_le_bytes())?;
+    out.write_all(&size.to_le_bytes())?;
+    out.write_all(&[0; 16])?;
+    for &pixel in image.buffer.iter() {
+        // 0xAABBGGRR to b, g, r, a
+        let [r, g, b, a] = pixel.to_le_bytes();
+        out.write_all(&[b, g, r, a])?;
+    }
+    Ok(())
+}
+
+// `YYYYMMDD_HHMMSS_mmm` in UTC
+fn timestamp(time: SystemTime) -> String {
+    let since = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since.as_secs();
+    let (days, rest) = ((secs / 86400) as i64, secs % 86400);
+    // days to a civil date, from Howard Hinnant's date algorithms
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    format!(
+        "{year:04}{month:02}{day:02}_{:02}{:02}{:02}_{:03}",
+        rest / 3600,
+        rest / 60 % 60,
+        rest % 60,
+        since.subsec_millis()
+    )
+}
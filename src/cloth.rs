@@ -0,0 +1,203 @@
+use crate::{
+    geometry::{Vector2, Vector3},
+    model::Model,
+};
+
+struct Spring {
+    a: usize,
+    b: usize,
+    rest: f32,
+    stiffness: f32,
+}
+
+// mass-spring cloth integrated with Verlet, springs are resolved as distance constraints
+pub struct Cloth {
+    pub cols: usize,
+    pub rows: usize,
+    pub positions: Vec<Vector3>,
+    previous: Vec<Vector3>,
+    pinned: Vec<bool>,
+    springs: Vec<Spring>,
+    pub gravity: Vector3,
+    pub wind: Vector3,
+    pub damping: f32,
+    pub iterations: usize,
+}
+
+impl Cloth {
+    // a `width` x `height` sheet hanging in the xy plane, `origin` is the top left corner
+    pub fn new(cols: usize, rows: usize, width: f32, height: f32, origin: Vector3) -> Self {
+        assert!(cols >= 2 && rows >= 2);
+        let mut positions = Vec::with_capacity(cols * rows);
+        for r in 0..rows {
+            for c in 0..cols {
+                let x = c as f32 / (cols - 1) as f32 * width;
+                let y = r as f32 / (rows - 1) as f32 * height;
+                positions.push(&origin + Vector3::new(x, -y, 0.0));
+            }
+        }
+        let mut cloth = Self {
+            cols,
+            rows,
+            previous: positions.clone(),
+            pinned: vec![false; positions.len()],
+            positions,
+            springs: Vec::new(),
+            gravity: Vector3::new(0.0, -9.8, 0.0),
+            wind: Vector3::zero(),
+            damping: 0.01,
+            iterations: 8,
+        };
+        for r in 0..rows {
+            for c in 0..cols {
+                // structural
+                cloth.add_spring((c, r), (c + 1, r), 1.0);
+                cloth.add_spring((c, r), (c, r + 1), 1.0);
+                // shear
+                cloth.add_spring((c, r), (c + 1, r + 1), 0.5);
+                cloth.add_spring((c + 1, r), (c, r + 1), 0.5);
+                // bend
+                cloth.add_spring((c, r), (c + 2, r), 0.2);
+                cloth.add_spring((c, r), (c, r + 2), 0.2);
+            }
+        }
+        cloth
+    }
+    fn add_spring(&mut self, (c0, r0): (usize, usize), (c1, r1): (usize, usize), stiffness: f32) {
+        if c0 >= self.cols || c1 >= self.cols || r0 >= self.rows || r1 >= self.rows {
+            return;
+        }
+        let a = self.index(c0, r0);
+        let b = self.index(c1, r1);
+        let rest = (&self.positions[a] - &self.positions[b]).length();
+        self.springs.push(Spring {
+            a,
+            b,
+            rest,
+            stiffness,
+        });
+    }
+    #[inline]
+    pub fn index(&self, col: usize, row: usize) -> usize {
+        col + row * self.cols
+    }
+    pub fn pin(&mut self, col: usize, row: usize) {
+        let i = self.index(col, row);
+        self.pinned[i] = true;
+    }
+    pub fn unpin(&mut self, col: usize, row: usize) {
+        let i = self.index(col, row);
+        self.pinned[i] = false;
+    }
+    pub fn step(&mut self, dt: f32) {
+        let mut forces = vec![self.gravity.clone(); self.positions.len()];
+        self.apply_wind(&mut forces);
+        for (i, force) in forces.iter().enumerate() {
+            if self.pinned[i] {
+                continue;
+            }
+            let velocity = (&self.positions[i] - &self.previous[i]) * (1.0 - self.damping);
+            let next = &self.positions[i] + velocity + force * (dt * dt);
+            self.previous[i] = std::mem::replace(&mut self.positions[i], next);
+        }
+        for _ in 0..self.iterations {
+            for spring in self.springs.iter() {
+                let (a, b) = (spring.a, spring.b);
+                let delta = &self.positions[b] - &self.positions[a];
+                let length = delta.length();
+                if length < f32::EPSILON {
+                    continue;
+                }
+                let correction = delta * ((length - spring.rest) / length * spring.stiffness);
+                match (self.pinned[a], self.pinned[b]) {
+                    (true, true) => {}
+                    (false, true) => self.positions[a] = &self.positions[a] + correction,
+                    (true, false) => self.positions[b] = &self.positions[b] - correction,
+                    (false, false) => {
+                        let half = correction * 0.5;
+                        self.positions[a] = &self.positions[a] + &half;
+                        self.positions[b] = &self.positions[b] - &half;
+                    }
+                }
+            }
+        }
+    }
+    // wind pushes each triangle along its normal, the per-vertex mass scales with
+    // the area as well so the resulting acceleration is resolution independent
+    fn apply_wind(&self, forces: &mut [Vector3]) {
+        if self.wind.length_square() == 0.0 {
+            return;
+        }
+        for [a, b, c] in self.triangles() {
+            let n = (&self.positions[b] - &self.positions[a])
+                .cross(&(&self.positions[c] - &self.positions[a]));
+            let length = n.length();
+            if length < f32::EPSILON {
+                continue;
+            }
+            let n = n / length;
+            let force = &n * (n.dot(&self.wind) / 3.0);
+            for i in [a, b, c] {
+                forces[i] = &forces[i] + &force;
+            }
+        }
+    }
+    fn triangles(&self) -> impl Iterator<Item = [usize; 3]> + '_ {
+        (0..self.rows - 1).flat_map(move |r| {
+            (0..self.cols - 1).flat_map(move |c| {
+                let i00 = self.index(c, r);
+                let i10 = self.index(c + 1, r);
+                let i01 = self.index(c, r + 1);
+                let i11 = self.index(c + 1, r + 1);
+                [[i00, i01, i10], [i10, i01, i11]]
+            })
+        })
+    }
+    // area weighted vertex normals of the current pose
+    pub fn normals(&self) -> Vec<Vector3> {
+        let mut normals = vec![Vector3::zero(); self.positions.len()];
+        for [a, b, c] in self.triangles() {
+            let n = (&self.positions[b] - &self.positions[a])
+                .cross(&(&self.positions[c] - &self.positions[a]));
+            for i in [a, b, c] {
+                normals[i] = &normals[i] + &n;
+            }
+        }
+        normals
+            .into_iter()
+            .map(|n| {
+                if n.length_square() > 0.0 {
+                    n.normalize()
+                } else {
+                    Vector3::new(0.0, 0.0, 1.0)
+                }
+            })
+            .collect()
+    }
+    pub fn to_model(&self) -> Model {
+        let mut tex_coord = Vec::with_capacity(self.positions.len());
+        for r in 0..self.rows {
+            for c in 0..self.cols {
+                let u = c as f32 / (self.cols - 1) as f32;
+                let v = 1.0 - r as f32 / (self.rows - 1) as f32;
+                tex_coord.push(Vector2::new(u, v));
+            }
+        }
+        let facets: Vec<usize> = self.triangles().flatten().collect();
+        Model::from_parts(
+            self.positions.clone(),
+            tex_coord,
+            self.normals(),
+            facets.clone(),
+            facets.clone(),
+            facets,
+        )
+    }
+    // write the current pose into a model built by `to_model`
+    pub fn update_model(&self, model: &mut Model) {
+        model
+            .verts_mut(0..self.positions.len())
+            .clone_from_slice(&self.positions);
+        model.refresh();
+    }
+}
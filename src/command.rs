@@ -0,0 +1,305 @@
+use std::{cmp::Ordering, thread};
+
+use crate::{
+    geometry::{m2v, v2m, Aabb, Matrix4, Vector4},
+    material::Material,
+    model::Model,
+    occlusion::OcclusionBuffer,
+    renderer::{Blend, DepthState, Renderer},
+    scene::{Camera, Light, Scene, SceneShader},
+};
+
+// a model drawn with `material`, placed in the world by `transform`. `object` is
+// what picking reports for its pixels
+#[derive(Clone)]
+pub struct Draw<'a> {
+    pub model: &'a Model,
+    pub material: &'a Material,
+    pub transform: Matrix4,
+    pub object: usize,
+}
+
+#[derive(Clone)]
+pub enum Command<'a> {
+    // colors, depth, stencil and ids, like `Renderer::fill`
+    Clear(u32),
+    // to the renderer's `depth.clear`, the colors are kept
+    ClearDepth,
+    Camera(Camera),
+    Lights(Vec<Light>, f32),
+    Depth(DepthState),
+    Blend(Blend),
+    ClipPlane(Option<Vector4>),
+    Draw(Draw<'a>),
+}
+
+// a frame recorded up front and executed by `Renderer::submit`, any number of
+// times and into any number of targets, e.g. once with an id attachment for
+// picking and once for the colors. State set by a command stays on the renderer
+// until the next command changes it, also after the submit
+#[derive(Clone, Default)]
+pub struct CommandBuffer<'a> {
+    pub commands: Vec<Command<'a>>,
+}
+
+impl<'a> CommandBuffer<'a> {
+    pub fn new() -> Self {
+        Self {
+            commands: Vec::new(),
+        }
+    }
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+    pub fn clear(&mut self, pixel: u32) {
+        self.commands.push(Command::Clear(pixel));
+    }
+    pub fn clear_depth(&mut self) {
+        self.commands.push(Command::ClearDepth);
+    }
+    pub fn set_camera(&mut self, camera: &Camera) {
+        self.commands.push(Command::Camera(camera.clone()));
+    }
+    pub fn set_lights(&mut self, lights: Vec<Light>, ambient: f32) {
+        self.commands.push(Command::Lights(lights, ambient));
+    }
+    pub fn set_depth(&mut self, depth: DepthState) {
+        self.commands.push(Command::Depth(depth));
+    }
+    pub fn set_blend(&mut self, blend: Blend) {
+        self.commands.push(Command::Blend(blend));
+    }
+    pub fn set_clip_plane(&mut self, plane: Option<Vector4>) {
+        self.commands.push(Command::ClipPlane(plane));
+    }
+    pub fn draw(
+        &mut self,
+        model: &'a Model,
+        material: &'a Material,
+        transform: Matrix4,
+        object: usize,
+    ) {
+        self.commands.push(Command::Draw(Draw {
+            model,
+            material,
+            transform,
+            object,
+        }));
+    }
+    // the scene's lights and a draw per visible node with a model, with the node
+    // id as the object. Unlike `draw_scene` there is no lod and translucent
+    // materials are drawn in node order as the blend set at the time
+    pub fn record_scene(&mut self, scene: &'a Scene) {
+        self.set_lights(scene.lights(), scene.ambient);
+        for (id, node) in scene.nodes.iter().enumerate() {
+            let Some(model) = node.model else {
+                continue;
+            };
+            if !scene.visible(id) {
+                continue;
+            }
+            let model = &scene.models[model];
+            let material = match node.material {
+                Some(material) => &scene.materials[material],
+                None => model.material(),
+            };
+            let transform = if node.skin.is_some() {
+                Matrix4::identity()
+            } else {
+                scene.world_transform(id)
+            };
+            self.draw(model, material, transform, id);
+        }
+    }
+    // draws between two other commands grouped by material then model, so the
+    // same maps are sampled one after the other. The order of everything else
+    // stays as recorded
+    pub fn sort_by_state(&mut self) {
+        self.sort_draws(
+            |_, draw| (draw.material as *const Material, draw.model as *const Model),
+            Ord::cmp,
+        );
+    }
+    // draws between two other commands ordered by the distance of their bounds
+    // from the camera in effect: front to back to skip hidden fragments early,
+    // back to front for alpha blending. Draws before any camera keep their order
+    pub fn sort_by_depth(&mut self, front_to_back: bool) {
+        self.sort_draws(
+            |camera, draw| {
+                let camera = camera?;
+                let (min, max) = draw.model.aabb();
+                let center = m2v(&(&draw.transform * v2m(&((min + max) * 0.5))));
+                let distance = (&center - &camera.eye).length();
+                Some(if front_to_back { distance } else { -distance })
+            },
+            |a, b| match (a, b) {
+                (Some(a), Some(b)) => a.total_cmp(b),
+                _ => Ordering::Equal,
+            },
+        );
+    }
+    // drops the draws `occlusion` has behind its occluders, returns how many.
+    // The occlusion buffer should be seen from the camera the draws are
+    pub fn cull_occluded(&mut self, occlusion: &OcclusionBuffer) -> usize {
+        let before = self.commands.len();
+        self.commands.retain(|command| match command {
+            Command::Draw(draw) => {
+                let (min, max) = draw.model.aabb();
+                occlusion.is_visible(&Aabb::new(min, max), &draw.transform)
+            }
+            _ => true,
+        });
+        before - self.commands.len()
+    }
+    // `parts` buffers, each with every command but the draws, which are dealt
+    // out in turn. Rendered separately, e.g. on threads into targets of their
+    // own, they add up to the whole frame with `Renderer::composite` as long as
+    // nothing is blended
+    pub fn split(&self, parts: usize) -> Vec<CommandBuffer<'a>> {
+        let mut split = vec![CommandBuffer::new(); parts.max(1)];
+        let mut next = 0;
+        for command in self.commands.iter() {
+            match command {
+                Command::Draw(_) => {
+                    split[next].commands.push(command.clone());
+                    next = (next + 1) % split.len();
+                }
+                _ => {
+                    for part in split.iter_mut() {
+                        part.commands.push(command.clone());
+                    }
+                }
+            }
+        }
+        split
+    }
+    // stable sort of each run of draws by `key`, which gets the camera set
+    // before the run
+    fn sort_draws<K>(
+        &mut self,
+        key: impl Fn(Option<&Camera>, &Draw) -> K,
+        compare: impl Fn(&K, &K) -> Ordering,
+    ) {
+        let mut camera = None;
+        let mut start = 0;
+        for i in 0..=self.commands.len() {
+            if let Some(Command::Draw(_)) = self.commands.get(i) {
+                continue;
+            }
+            let draw = |command: &Command<'a>| match command {
+                Command::Draw(draw) => draw.clone(),
+                _ => unreachable!(),
+            };
+            let mut keyed: Vec<_> = self.commands[start..i]
+                .iter()
+                .map(|command| {
+                    let draw = draw(command);
+                    (key(camera.as_ref(), &draw), draw)
+                })
+                .collect();
+            keyed.sort_by(|a, b| compare(&a.0, &b.0));
+            for (command, (_, draw)) in self.commands[start..i].iter_mut().zip(keyed) {
+                *command = Command::Draw(draw);
+            }
+            if let Some(Command::Camera(c)) = self.commands.get(i) {
+                camera = Some(c.clone());
+            }
+            start = i + 1;
+        }
+    }
+}
+
+impl Renderer<'_> {
+    // runs the commands in order, returns how many draws were inside the view.
+    // Draws need a camera set before them
+    pub fn submit(&mut self, commands: &CommandBuffer) -> usize {
+        let mut camera = None;
+        let mut lights = Vec::new();
+        let mut ambient = 0.0;
+        let mut drawn = 0;
+        for command in commands.commands.iter() {
+            match command {
+                Command::Clear(pixel) => self.fill(*pixel),
+                Command::ClearDepth => self.clear_depth(),
+                Command::Camera(c) => camera = Some(c),
+                Command::Lights(l, a) => (lights, ambient) = (l.clone(), *a),
+                Command::Depth(depth) => self.depth = *depth,
+                Command::Blend(blend) => self.blend = *blend,
+                Command::ClipPlane(plane) => self.clip_plane = plane.clone(),
+                Command::Draw(draw) => {
+                    let camera: &Camera = camera.expect("a draw before any camera");
+                    let (min, max) = draw.model.aabb();
+                    let corners = Aabb::new(min, max)
+                        .corners()
+                        .map(|c| m2v(&(&draw.transform * v2m(&c))));
+                    if !camera
+                        .frustum(self.width, self.height)
+                        .intersects_box(&corners)
+                    {
+                        continue;
+                    }
+                    drawn += 1;
+                    let view = camera.transform(self.width, self.height, 255.0);
+                    let mut shader = SceneShader::new(
+                        draw.model,
+                        draw.material,
+                        &view,
+                        &draw.transform,
+                        &lights,
+                        ambient,
+                    );
+                    self.set_object(draw.object);
+                    self.draw_faces(&mut shader);
+                }
+            }
+        }
+        drawn
+    }
+    // `submit` with the draws split over `threads` threads like `split`, each
+    // drawing into a target of its own from the state this renderer has, then
+    // composited into this one. The rest of the commands run here, so the
+    // state after is the same as with `submit`. For frames where nothing is
+    // blended and the depth isn't cleared between draws; only colors and depth
+    // come back, and the stats of every thread add up on this renderer
+    pub fn submit_parallel(&mut self, commands: &CommandBuffer, threads: usize) -> usize {
+        let state = self.state();
+        let mut rest = commands.clone();
+        rest.commands
+            .retain(|command| !matches!(command, Command::Draw(_)));
+        self.submit(&rest);
+        let (width, height) = (self.width, self.height);
+        let size = (width * height) as usize;
+        let parts = commands.split(threads);
+        let targets: Vec<_> = thread::scope(|scope| {
+            let workers: Vec<_> = parts
+                .iter()
+                .map(|part| {
+                    let state = &state;
+                    scope.spawn(move || {
+                        let mut buffer = vec![0xff000000; size];
+                        let mut z_buffer = vec![state.depth.clear; size];
+                        let mut renderer = Renderer::new(&mut buffer, &mut z_buffer, width, height);
+                        renderer.set_state(state);
+                        let drawn = renderer.submit(part);
+                        let stats = renderer.stats;
+                        (buffer, z_buffer, stats, drawn)
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .map(|worker| worker.join().unwrap())
+                .collect()
+        });
+        let mut drawn = 0;
+        for (buffer, z_buffer, stats, part_drawn) in targets {
+            self.composite(&buffer, &z_buffer);
+            self.stats += stats;
+            drawn += part_drawn;
+        }
+        drawn
+    }
+}
@@ -0,0 +1,102 @@
+// what the core modules take from alloc and libm when built without std. Some
+// of it only the model uses
+#![cfg_attr(not(feature = "model"), allow(unused_imports, dead_code))]
+
+#[cfg(not(feature = "std"))]
+pub(crate) use crate::geometry::Scalar;
+#[cfg(not(feature = "std"))]
+pub(crate) use alloc::{
+    collections::BTreeMap as HashMap,
+    format,
+    string::{String, ToString},
+    sync::Arc,
+    vec,
+    vec::Vec,
+};
+#[cfg(not(feature = "std"))]
+pub(crate) use core::cell::OnceCell as OnceLock;
+#[cfg(feature = "std")]
+pub(crate) use std::{
+    collections::HashMap,
+    sync::{Arc, OnceLock},
+};
+
+// the float functions core leaves out, same names as the inherent ones so call
+// sites don't change. `sqrt` comes with `Scalar`
+#[cfg(not(feature = "std"))]
+pub(crate) trait Float: Sized {
+    fn floor(self) -> Self;
+    fn round(self) -> Self;
+    fn sin_cos(self) -> (Self, Self);
+    fn sin(self) -> Self;
+    fn cos(self) -> Self;
+    fn acos(self) -> Self;
+    fn atan2(self, other: Self) -> Self;
+    fn powi(self, n: i32) -> Self;
+}
+
+#[cfg(not(feature = "std"))]
+impl Float for f32 {
+    fn floor(self) -> Self {
+        libm::floorf(self)
+    }
+    fn round(self) -> Self {
+        libm::roundf(self)
+    }
+    fn sin_cos(self) -> (Self, Self) {
+        libm::sincosf(self)
+    }
+    fn sin(self) -> Self {
+        libm::sinf(self)
+    }
+    fn cos(self) -> Self {
+        libm::cosf(self)
+    }
+    fn acos(self) -> Self {
+        libm::acosf(self)
+    }
+    fn atan2(self, other: Self) -> Self {
+        libm::atan2f(self, other)
+    }
+    fn powi(self, n: i32) -> Self {
+        let x = (0..n.unsigned_abs()).fold(1.0, |x, _| x * self);
+        if n < 0 {
+            1.0 / x
+        } else {
+            x
+        }
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl Float for f64 {
+    fn floor(self) -> Self {
+        libm::floor(self)
+    }
+    fn round(self) -> Self {
+        libm::round(self)
+    }
+    fn sin_cos(self) -> (Self, Self) {
+        libm::sincos(self)
+    }
+    fn sin(self) -> Self {
+        libm::sin(self)
+    }
+    fn cos(self) -> Self {
+        libm::cos(self)
+    }
+    fn acos(self) -> Self {
+        libm::acos(self)
+    }
+    fn atan2(self, other: Self) -> Self {
+        libm::atan2(self, other)
+    }
+    fn powi(self, n: i32) -> Self {
+        let x = (0..n.unsigned_abs()).fold(1.0, |x, _| x * self);
+        if n < 0 {
+            1.0 / x
+        } else {
+            x
+        }
+    }
+}
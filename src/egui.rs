@@ -0,0 +1,247 @@
+use std::{collections::HashMap, mem};
+
+use ::egui::{
+    epaint::{ImageDelta, Primitive, Vertex},
+    vec2, ClippedPrimitive, Color32, Context, Event, ImageData, MouseWheelUnit, PointerButton,
+    Pos2, RawInput, Rect, TextureFilter, TextureId,
+};
+
+use crate::{
+    geometry::Vector3,
+    renderer::{Blend, Compare, Renderer, Shader},
+};
+
+// an egui UI drawn into the framebuffer over the scene, its meshes filled by the
+// renderer like any other triangles. Feed it the window's input, `run` the UI
+// once a frame and `paint` what it made after the scene is drawn
+pub struct Overlay {
+    pub ctx: Context,
+    events: Vec<Event>,
+    textures: HashMap<TextureId, Texture>,
+    // egui frees textures once the frame that last used them is painted
+    free: Vec<TextureId>,
+    primitives: Vec<ClippedPrimitive>,
+    pixels_per_point: f32,
+    // the sum of the frames' dt, so a replayed run animates the same
+    time: f64,
+}
+
+impl Default for Overlay {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Overlay {
+    pub fn new() -> Self {
+        Self {
+            ctx: Context::default(),
+            events: Vec::new(),
+            textures: HashMap::new(),
+            free: Vec::new(),
+            primitives: Vec::new(),
+            pixels_per_point: 1.0,
+            time: 0.0,
+        }
+    }
+    // queued for the next `run`, the pointer ones below are the common cases
+    pub fn event(&mut self, event: Event) {
+        self.events.push(event);
+    }
+    // positions are in pixels of the framebuffer
+    pub fn pointer_moved(&mut self, x: f32, y: f32) {
+        let pos = self.point(x, y);
+        self.event(Event::PointerMoved(pos));
+    }
+    pub fn pointer_button(&mut self, x: f32, y: f32, button: PointerButton, pressed: bool) {
+        let pos = self.point(x, y);
+        self.event(Event::PointerButton {
+            pos,
+            button,
+            pressed,
+            modifiers: Default::default(),
+        });
+    }
+    pub fn pointer_gone(&mut self) {
+        self.event(Event::PointerGone);
+    }
+    // in lines, positive moves the content right and down
+    pub fn scroll(&mut self, dx: f32, dy: f32) {
+        self.event(Event::MouseWheel {
+            unit: MouseWheelUnit::Line,
+            delta: vec2(dx, dy),
+            modifiers: Default::default(),
+        });
+    }
+    // whether the last input went to the UI rather than the scene, e.g. to skip
+    // orbiting the camera while a slider is dragged
+    pub fn wants_pointer(&self) -> bool {
+        self.ctx.wants_pointer_input() || self.ctx.is_pointer_over_area()
+    }
+    // lays out the UI for a `width` x `height` framebuffer and tessellates it,
+    // `dt` seconds after the last run
+    pub fn run(&mut self, dt: f32, width: u32, height: u32, ui: impl FnMut(&Context)) {
+        for id in self.free.drain(..) {
+            self.textures.remove(&id);
+        }
+        self.time += dt as f64;
+        let size = vec2(width as f32, height as f32) / self.ctx.pixels_per_point();
+        let input = RawInput {
+            screen_rect: Some(Rect::from_min_size(Pos2::ZERO, size)),
+            time: Some(self.time),
+            predicted_dt: dt,
+            events: mem::take(&mut self.events),
+            ..Default::default()
+        };
+        let output = self.ctx.run(input, ui);
+        for (id, delta) in output.textures_delta.set {
+            self.set_texture(id, delta);
+        }
+        self.free = output.textures_delta.free;
+        self.pixels_per_point = output.pixels_per_point;
+        self.primitives = self.ctx.tessellate(output.shapes, output.pixels_per_point);
+    }
+    // over whatever is in the buffer, in the order egui gave. The renderer's
+    // depth, blend and clip plane are put back afterwards
+    pub fn paint(&self, renderer: &mut Renderer) {
+        let (depth, blend) = (renderer.depth, renderer.blend);
+        let clip_plane = renderer.clip_plane.take();
+        renderer.depth.compare = Compare::Always;
+        renderer.depth.write = false;
+        renderer.blend = Blend::Alpha;
+        for ClippedPrimitive {
+            clip_rect,
+            primitive,
+        } in self.primitives.iter()
+        {
+            // callbacks are for gpu backends
+            let Primitive::Mesh(mesh) = primitive else {
+                continue;
+            };
+            let Some(texture) = self.textures.get(&mesh.texture_id) else {
+                continue;
+            };
+            let clip = *clip_rect * self.pixels_per_point;
+            for triangle in mesh.indices.chunks_exact(3) {
+                let vertices = [0, 1, 2].map(|i| &mesh.vertices[triangle[i] as usize]);
+                let verts = vertices.map(|v| {
+                    let pos = v.pos * self.pixels_per_point;
+                    Vector3::new(pos.x, pos.y, 0.0)
+                });
+                let mut shader = MeshShader {
+                    texture,
+                    vertices,
+                    verts: &verts,
+                    clip,
+                };
+                renderer.fill_triangle(&verts, &mut shader);
+            }
+        }
+        renderer.depth = depth;
+        renderer.blend = blend;
+        renderer.clip_plane = clip_plane;
+    }
+    fn point(&self, x: f32, y: f32) -> Pos2 {
+        Pos2::new(x, y) / self.pixels_per_point
+    }
+    fn set_texture(&mut self, id: TextureId, delta: ImageDelta) {
+        let ImageData::Color(image) = delta.image;
+        let [width, height] = image.size;
+        match delta.pos {
+            // a patch of a texture egui already sent, e.g. new glyphs in the font atlas
+            Some([x, y]) => {
+                let texture = self.textures.get_mut(&id).unwrap();
+                for row in 0..height {
+                    let start = (y + row) * texture.width + x;
+                    texture.pixels[start..start + width]
+                        .copy_from_slice(&image.pixels[row * width..(row + 1) * width]);
+                }
+            }
+            None => {
+                let texture = Texture {
+                    width,
+                    height,
+                    pixels: image.pixels.clone(),
+                    filter: delta.options.magnification,
+                };
+                self.textures.insert(id, texture);
+            }
+        }
+    }
+}
+
+struct Texture {
+    width: usize,
+    height: usize,
+    // premultiplied, like all of egui's colors
+    pixels: Vec<Color32>,
+    filter: TextureFilter,
+}
+
+impl Texture {
+    // rgba in 0..=1, clamped at the edges
+    fn sample(&self, u: f32, v: f32) -> [f32; 4] {
+        let texel = |x: i64, y: i64| {
+            let x = x.clamp(0, self.width as i64 - 1) as usize;
+            let y = y.clamp(0, self.height as i64 - 1) as usize;
+            self.pixels[y * self.width + x]
+                .to_array()
+                .map(|c| c as f32 / 255.0)
+        };
+        let x = u * self.width as f32;
+        let y = v * self.height as f32;
+        match self.filter {
+            TextureFilter::Nearest => texel(x as i64, y as i64),
+            TextureFilter::Linear => {
+                let (x, y) = (x - 0.5, y - 0.5);
+                let (x0, y0) = (x.floor() as i64, y.floor() as i64);
+                let (fx, fy) = (x - x.floor(), y - y.floor());
+                let [a, b, c, d] =
+                    [(0, 0), (1, 0), (0, 1), (1, 1)].map(|(i, j)| texel(x0 + i, y0 + j));
+                [0, 1, 2, 3].map(|i| {
+                    let top = a[i] + (b[i] - a[i]) * fx;
+                    let bottom = c[i] + (d[i] - c[i]) * fx;
+                    top + (bottom - top) * fy
+                })
+            }
+        }
+    }
+}
+
+// vertex color times texel, outside the clip rect is discarded
+struct MeshShader<'a> {
+    texture: &'a Texture,
+    vertices: [&'a Vertex; 3],
+    verts: &'a [Vector3; 3],
+    clip: Rect,
+}
+
+impl Shader for MeshShader<'_> {
+    fn vertex(&mut self, _iface: usize, nthvert: usize) -> Vector3 {
+        self.verts[nthvert].clone()
+    }
+    fn fregment(&mut self, bc: &Vector3) -> Option<u32> {
+        // z is 0 so there is no perspective correction, `bc` weighs the pixel center
+        let lerp = |f: &dyn Fn(usize) -> f32| bc[0] * f(0) + bc[1] * f(1) + bc[2] * f(2);
+        let pos = Pos2::new(lerp(&|i| self.verts[i].x()), lerp(&|i| self.verts[i].y()));
+        if !self.clip.contains(pos) {
+            return None;
+        }
+        let u = lerp(&|i| self.vertices[i].uv.x);
+        let v = lerp(&|i| self.vertices[i].uv.y);
+        let texel = self.texture.sample(u, v);
+        let color = [0, 1, 2, 3]
+            .map(|c| lerp(&|i| self.vertices[i].color.to_array()[c] as f32 / 255.0) * texel[c]);
+        let alpha = color[3];
+        if alpha <= 0.0 {
+            return None;
+        }
+        // the renderer blends with straight alpha
+        let mut pixel = ((alpha * 255.0).round() as u32) << 24;
+        for (i, &c) in color[..3].iter().enumerate() {
+            let c = (c / alpha).clamp(0.0, 1.0);
+            pixel |= ((c * 255.0).round() as u32) << (8 * i);
+        }
+        Some(pixel)
+    }
+}
@@ -0,0 +1,83 @@
+use core::convert::Infallible;
+
+use embedded_graphics_core::{
+    pixelcolor::{Rgb888, RgbColor},
+    prelude::{DrawTarget, OriginDimensions, Point, Size},
+    primitives::Rectangle,
+    Pixel,
+};
+
+use crate::renderer::Renderer;
+
+// the color buffer as an embedded-graphics target, so text and widgets can be
+// drawn over a rendered frame. Only the colors change, depth and stencil are
+// left alone
+impl OriginDimensions for Renderer<'_> {
+    fn size(&self) -> Size {
+        Size::new(self.width, self.height)
+    }
+}
+
+impl DrawTarget for Renderer<'_> {
+    type Color = Rgb888;
+    type Error = Infallible;
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(point, color) in pixels {
+            let (x, y) = (point.x, point.y);
+            if x >= 0 && y >= 0 && (x as u32) < self.width && (y as u32) < self.height {
+                self.draw_pixel_unchecked(x as u32, y as u32, pack(color));
+            }
+        }
+        Ok(())
+    }
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        self.clear_color(pack(color));
+        Ok(())
+    }
+}
+
+// 0xAABBGGRR like the rest of the buffer, opaque
+fn pack(color: Rgb888) -> u32 {
+    0xff000000 | (color.b() as u32) << 16 | (color.g() as u32) << 8 | color.r() as u32
+}
+
+fn unpack(pixel: u32) -> Rgb888 {
+    Rgb888::new(pixel as u8, (pixel >> 8) as u8, (pixel >> 16) as u8)
+}
+
+// sends a buffer to any display driver, row by row in one call so drivers that
+// stream a window (most spi panels) only set it up once. Colors are converted
+// to the display's own format, e.g. Rgb565
+pub fn present<D>(
+    buffer: &[u32],
+    width: u32,
+    height: u32,
+    stride: u32,
+    target: &mut D,
+) -> Result<(), D::Error>
+where
+    D: DrawTarget,
+    D::Color: From<Rgb888>,
+{
+    let area = Rectangle::new(Point::zero(), Size::new(width, height));
+    let colors = (0..height)
+        .flat_map(|y| (0..width).map(move |x| unpack(buffer[(x + y * stride) as usize]).into()));
+    target.fill_contiguous(&area, colors)
+}
+
+impl Renderer<'_> {
+    // the frame drawn so far to a display, see `present`
+    pub fn present<D>(&self, target: &mut D) -> Result<(), D::Error>
+    where
+        D: DrawTarget,
+        D::Color: From<Rgb888>,
+    {
+        let area = Rectangle::new(Point::zero(), Size::new(self.width, self.height));
+        let colors = (0..self.height)
+            .flat_map(|y| (0..self.width).map(move |x| unpack(self.pixel(x, y)).into()));
+        target.fill_contiguous(&area, colors)
+    }
+}
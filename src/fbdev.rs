@@ -0,0 +1,182 @@
+use std::{
+    fs::{File, OpenOptions},
+    io,
+    os::fd::AsRawFd,
+    path::Path,
+};
+
+// linux/fb.h
+const FBIOGET_VSCREENINFO: libc::c_ulong = 0x4600;
+const FBIOGET_FSCREENINFO: libc::c_ulong = 0x4602;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Bitfield {
+    pub offset: u32,
+    pub length: u32,
+    pub msb_right: u32,
+}
+
+#[repr(C)]
+#[derive(Default)]
+#[allow(dead_code)]
+struct VarScreenInfo {
+    xres: u32,
+    yres: u32,
+    xres_virtual: u32,
+    yres_virtual: u32,
+    xoffset: u32,
+    yoffset: u32,
+    bits_per_pixel: u32,
+    grayscale: u32,
+    red: Bitfield,
+    green: Bitfield,
+    blue: Bitfield,
+    transp: Bitfield,
+    nonstd: u32,
+    activate: u32,
+    height: u32,
+    width: u32,
+    accel_flags: u32,
+    pixclock: u32,
+    left_margin: u32,
+    right_margin: u32,
+    upper_margin: u32,
+    lower_margin: u32,
+    hsync_len: u32,
+    vsync_len: u32,
+    sync: u32,
+    vmode: u32,
+    rotate: u32,
+    colorspace: u32,
+    reserved: [u32; 4],
+}
+
+#[repr(C)]
+#[derive(Default)]
+#[allow(dead_code)]
+struct FixScreenInfo {
+    id: [u8; 16],
+    smem_start: libc::c_ulong,
+    smem_len: u32,
+    kind: u32,
+    type_aux: u32,
+    visual: u32,
+    xpanstep: u16,
+    ypanstep: u16,
+    ywrapstep: u16,
+    line_length: u32,
+    mmio_start: libc::c_ulong,
+    mmio_len: u32,
+    accel: u32,
+    capabilities: u16,
+    reserved: [u16; 2],
+}
+
+// the linux framebuffer device (/dev/fb0) mapped into memory, for drawing full
+// screen on a console without SDL or X11. Pixels are converted to whatever
+// layout the device reports, e.g. RGB565 on small panels or XRGB8888
+pub struct Framebuffer {
+    pub width: u32,
+    pub height: u32,
+    pub bits_per_pixel: u32,
+    pub red: Bitfield,
+    pub green: Bitfield,
+    pub blue: Bitfield,
+    // bytes from one row to the next
+    line_length: u32,
+    // where the visible part starts, the virtual screen may be larger
+    offset: usize,
+    map: *mut u8,
+    len: usize,
+    _file: File,
+}
+
+impl Framebuffer {
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        let fd = file.as_raw_fd();
+        let mut var = VarScreenInfo::default();
+        let mut fix = FixScreenInfo::default();
+        // SAFETY: both structs match the kernel's layout for these requests
+        unsafe {
+            if libc::ioctl(fd, FBIOGET_VSCREENINFO as _, &mut var) < 0
+                || libc::ioctl(fd, FBIOGET_FSCREENINFO as _, &mut fix) < 0
+            {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        if !matches!(var.bits_per_pixel, 16 | 24 | 32) {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("{} bits per pixel", var.bits_per_pixel),
+            ));
+        }
+        let len = (fix.line_length * var.yres_virtual) as usize;
+        // SAFETY: a fresh shared mapping of the device, checked below
+        let map = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                fd,
+                0,
+            )
+        };
+        if map == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self {
+            width: var.xres,
+            height: var.yres,
+            bits_per_pixel: var.bits_per_pixel,
+            red: var.red,
+            green: var.green,
+            blue: var.blue,
+            line_length: fix.line_length,
+            offset: (var.yoffset * fix.line_length + var.xoffset * var.bits_per_pixel / 8) as usize,
+            map: map as *mut u8,
+            len,
+            _file: file,
+        })
+    }
+    // a 0xAABBGGRR pixel in the device's layout
+    pub fn convert(&self, pixel: u32) -> u32 {
+        let channel = |value: u32, field: &Bitfield| {
+            let value = value & 0xff;
+            if field.length >= 8 {
+                value << (field.length - 8) << field.offset
+            } else {
+                value >> (8 - field.length) << field.offset
+            }
+        };
+        channel(pixel, &self.red)
+            | channel(pixel >> 8, &self.green)
+            | channel(pixel >> 16, &self.blue)
+    }
+    // copies the top left of `buffer` that fits on the screen
+    pub fn present(&mut self, buffer: &[u32], width: u32, height: u32) {
+        assert_eq!((width * height) as usize, buffer.len());
+        let bytes = (self.bits_per_pixel / 8) as usize;
+        // SAFETY: `map` is `len` bytes long until drop
+        let screen = unsafe { std::slice::from_raw_parts_mut(self.map, self.len) };
+        for y in 0..height.min(self.height) {
+            let row = self.offset + (y * self.line_length) as usize;
+            for x in 0..width.min(self.width) {
+                let pixel = self.convert(buffer[(x + y * width) as usize]).to_le_bytes();
+                let at = row + x as usize * bytes;
+                screen[at..at + bytes].copy_from_slice(&pixel[..bytes]);
+            }
+        }
+    }
+}
+
+impl Drop for Framebuffer {
+    fn drop(&mut self) {
+        // SAFETY: unmaps what `open` mapped, nothing points into it anymore
+        unsafe {
+            libc::munmap(self.map as *mut libc::c_void, self.len);
+        }
+    }
+}
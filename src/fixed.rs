@@ -0,0 +1,136 @@
+use core::{
+    cmp::Ordering,
+    fmt::Display,
+    ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign},
+};
+
+#[cfg(not(feature = "std"))]
+use crate::compat::*;
+use crate::geometry::Scalar;
+
+const FRACTION_BITS: u32 = 16;
+const ONE: i32 = 1 << FRACTION_BITS;
+
+// 16.16 fixed point: integer math only, so the same on every target, with or
+// without an fpu. Covers about +-32768 in steps of 1/65536, results past that
+// wrap
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Fixed(pub i32);
+
+impl Fixed {
+    pub fn from_int(n: i32) -> Self {
+        Self(n << FRACTION_BITS)
+    }
+    pub fn from_f32(x: f32) -> Self {
+        Self((x * ONE as f32).round() as i32)
+    }
+    pub fn to_f32(self) -> f32 {
+        self.0 as f32 / ONE as f32
+    }
+    // a / b without going through a float, rounded towards zero
+    pub fn ratio(a: i64, b: i64) -> Self {
+        Self((((a as i128) << FRACTION_BITS) / b as i128) as i32)
+    }
+}
+
+impl Add for Fixed {
+    type Output = Self;
+    fn add(self, rhs: Self) -> Self::Output {
+        Self(self.0.wrapping_add(rhs.0))
+    }
+}
+
+impl Sub for Fixed {
+    type Output = Self;
+    fn sub(self, rhs: Self) -> Self::Output {
+        Self(self.0.wrapping_sub(rhs.0))
+    }
+}
+
+impl Mul for Fixed {
+    type Output = Self;
+    fn mul(self, rhs: Self) -> Self::Output {
+        Self(((self.0 as i64 * rhs.0 as i64) >> FRACTION_BITS) as i32)
+    }
+}
+
+impl Div for Fixed {
+    type Output = Self;
+    fn div(self, rhs: Self) -> Self::Output {
+        Self::ratio(self.0 as i64, rhs.0 as i64)
+    }
+}
+
+impl Neg for Fixed {
+    type Output = Self;
+    fn neg(self) -> Self::Output {
+        Self(self.0.wrapping_neg())
+    }
+}
+
+impl AddAssign for Fixed {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+impl SubAssign for Fixed {
+    fn sub_assign(&mut self, rhs: Self) {
+        *self = *self - rhs;
+    }
+}
+
+impl MulAssign for Fixed {
+    fn mul_assign(&mut self, rhs: Self) {
+        *self = *self * rhs;
+    }
+}
+
+impl DivAssign for Fixed {
+    fn div_assign(&mut self, rhs: Self) {
+        *self = *self / rhs;
+    }
+}
+
+impl Display for Fixed {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.to_f64())
+    }
+}
+
+impl Scalar for Fixed {
+    const ZERO: Self = Self(0);
+    const ONE: Self = Self(ONE);
+    // integer square root of the value shifted up once more, negatives give 0
+    fn sqrt(self) -> Self {
+        let mut n = (self.0.max(0) as u64) << FRACTION_BITS;
+        // digit by digit, two bits of `n` per bit of the root
+        let mut root = 0u64;
+        let mut bit = 1u64 << 62;
+        while bit > n {
+            bit >>= 2;
+        }
+        while bit != 0 {
+            if n >= root + bit {
+                n -= root + bit;
+                root = (root >> 1) + bit;
+            } else {
+                root >>= 1;
+            }
+            bit >>= 2;
+        }
+        Self(root as i32)
+    }
+    fn abs(self) -> Self {
+        Self(self.0.wrapping_abs())
+    }
+    fn total_cmp(&self, other: &Self) -> Ordering {
+        self.cmp(other)
+    }
+    fn from_f64(x: f64) -> Self {
+        Self((x * ONE as f64).round() as i32)
+    }
+    fn to_f64(self) -> f64 {
+        self.0 as f64 / ONE as f64
+    }
+}
@@ -1,8 +1,12 @@
-use std::{
-    fmt::Display,
-    ops::{Add, Div, Index, IndexMut, Mul, Sub},
+use core::{
+    cmp::Ordering,
+    fmt::{Debug, Display},
+    ops::{Add, AddAssign, Div, DivAssign, Index, IndexMut, Mul, MulAssign, Neg, Sub, SubAssign},
 };
 
+#[cfg(not(feature = "std"))]
+use crate::compat::*;
+
 #[derive(Clone)]
 pub struct Line2D {
     pub x0: f32,
@@ -105,92 +109,561 @@ impl Line2D {
             }
         }
     }
+    // Cyrus–Beck: the part of the line inside `polygon`, which must be convex,
+    // either winding. `box_clip` is the special case of a rectangle
+    pub fn clip_to_convex_polygon(&self, polygon: &[Vector2]) -> Option<Self> {
+        let sign = sign(signed_area(polygon));
+        if sign == 0.0 {
+            return None;
+        }
+        let start = Vector2::new(self.x0, self.y0);
+        let end = Vector2::new(self.x1, self.y1);
+        let n = polygon.len();
+        let (t0, t1) = clip_range(polygon.iter().enumerate().map(|(i, a)| {
+            let b = &polygon[(i + 1) % n];
+            (cross2(a, b, &start) * sign, cross2(a, b, &end) * sign)
+        }))?;
+        let at = |t: f32| {
+            (
+                self.x0 + (self.x1 - self.x0) * t,
+                self.y0 + (self.y1 - self.y0) * t,
+            )
+        };
+        let ((x0, y0), (x1, y1)) = (at(t0), at(t1));
+        Some(Self { x0, y0, x1, y1 })
+    }
+}
+
+// a segment in 3D, e.g. a debug line in world space
+#[derive(Clone, Debug, PartialEq)]
+pub struct Line3D {
+    pub start: Vector3,
+    pub end: Vector3,
+}
+impl Line3D {
+    pub fn new(start: Vector3, end: Vector3) -> Self {
+        Self { start, end }
+    }
+    // the part of the line on the inner side of every plane, e.g. the planes of a
+    // `Frustum`
+    pub fn clip_to_planes(&self, planes: &[Plane]) -> Option<Self> {
+        let (t0, t1) = clip_range(
+            planes
+                .iter()
+                .map(|p| (p.distance(&self.start), p.distance(&self.end))),
+        )?;
+        let delta = &self.end - &self.start;
+        Some(Self {
+            start: &self.start + &(&delta * t0),
+            end: &self.start + &(&delta * t1),
+        })
+    }
+}
+
+// the range of t in 0..=1 where a segment is inside every one of a set of half
+// spaces, given the signed distances of its ends to each, inside >= 0
+fn clip_range(distances: impl Iterator<Item = (f32, f32)>) -> Option<(f32, f32)> {
+    let (mut t0, mut t1) = (0.0f32, 1.0f32);
+    for (d0, d1) in distances {
+        if d0 < 0.0 && d1 < 0.0 {
+            return None;
+        }
+        if d0 < 0.0 {
+            t0 = t0.max(d0 / (d0 - d1));
+        } else if d1 < 0.0 {
+            t1 = t1.min(d0 / (d0 - d1));
+        }
+        if t0 > t1 {
+            return None;
+        }
+    }
+    Some((t0, t1))
+}
+
+// a closed polygon, the last point joins the first
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Polygon2D {
+    pub points: Vec<Vector2>,
+}
+impl Polygon2D {
+    pub fn new(points: Vec<Vector2>) -> Self {
+        Self { points }
+    }
+    // positive counter-clockwise with y up
+    pub fn signed_area(&self) -> f32 {
+        signed_area(&self.points)
+    }
+    // every corner turns the same way, straight corners allowed
+    pub fn is_convex(&self) -> bool {
+        let n = self.points.len();
+        let mut turn = 0.0;
+        for i in 0..n {
+            let [a, b, c] = [i, i + 1, i + 2].map(|j| &self.points[j % n]);
+            let t = cross2(a, b, c);
+            if t * turn < 0.0 {
+                return false;
+            }
+            if t != 0.0 {
+                turn = t;
+            }
+        }
+        true
+    }
+    // Sutherland–Hodgman: the part of this polygon inside `clipper`, which must
+    // be convex, either winding. A concave polygon cut in several pieces comes
+    // back as one, joined along the clipper's edges
+    pub fn clip(&self, clipper: &Polygon2D) -> Option<Self> {
+        let sign = sign(clipper.signed_area());
+        if sign == 0.0 {
+            return None;
+        }
+        let mut output = self.points.clone();
+        let n = clipper.points.len();
+        for i in 0..n {
+            let (a, b) = (&clipper.points[i], &clipper.points[(i + 1) % n]);
+            let side = |p: &Vector2| cross2(a, b, p) * sign;
+            let input = core::mem::take(&mut output);
+            for j in 0..input.len() {
+                let (p, q) = (&input[j], &input[(j + 1) % input.len()]);
+                let (sp, sq) = (side(p), side(q));
+                if sp >= 0.0 {
+                    output.push(p.clone());
+                }
+                // a point on the edge is kept as it is, not crossed again
+                if (sp > 0.0 && sq < 0.0) || (sp < 0.0 && sq > 0.0) {
+                    output.push(p + &((q - p) * (sp / (sp - sq))));
+                }
+            }
+        }
+        (output.len() >= 3).then(|| Self::new(output))
+    }
+    // `clip` to an axis aligned box
+    pub fn box_clip(&self, x_min: f32, y_min: f32, x_max: f32, y_max: f32) -> Option<Self> {
+        if x_max < x_min || y_max < y_min {
+            return None;
+        }
+        self.clip(&Polygon2D::new(vec![
+            Vector2::new(x_min, y_min),
+            Vector2::new(x_max, y_min),
+            Vector2::new(x_max, y_max),
+            Vector2::new(x_min, y_max),
+        ]))
+    }
+    // the part of this polygon inside `clipper`, any simple polygon. A concave
+    // clipper is cut into triangles and the pieces come back unmerged, they
+    // share edges but don't overlap
+    pub fn intersect(&self, clipper: &Polygon2D) -> Vec<Self> {
+        if clipper.is_convex() {
+            return self.clip(clipper).into_iter().collect();
+        }
+        clipper
+            .triangulate()
+            .iter()
+            .filter_map(|t| {
+                self.clip(&Polygon2D::new(
+                    t.map(|i| clipper.points[i].clone()).to_vec(),
+                ))
+            })
+            .collect()
+    }
+    // ear clipping, indices into `points` wound like the polygon. A self
+    // intersecting polygon can run out of ears, what is left then is dropped
+    pub fn triangulate(&self) -> Vec<[usize; 3]> {
+        let sign = sign(self.signed_area());
+        let mut remaining: Vec<usize> = (0..self.points.len()).collect();
+        let mut triangles = Vec::new();
+        if sign == 0.0 {
+            return triangles;
+        }
+        while remaining.len() > 3 {
+            let m = remaining.len();
+            let corner = |i: usize| [(i + m - 1) % m, i, (i + 1) % m].map(|j| remaining[j]);
+            let turn = |[a, b, c]: [usize; 3]| {
+                cross2(&self.points[a], &self.points[b], &self.points[c]) * sign
+            };
+            let is_ear = |i: usize| {
+                let [a, b, c] = corner(i);
+                turn([a, b, c]) > 0.0
+                    && remaining.iter().all(|&j| {
+                        [a, b, c].contains(&j)
+                            || [[a, b, j], [b, c, j], [c, a, j]]
+                                .iter()
+                                .any(|&t| turn(t) < 0.0)
+                    })
+            };
+            if let Some(i) = (0..m).find(|&i| is_ear(i)) {
+                triangles.push(corner(i));
+                remaining.remove(i);
+            } else if let Some(i) = (0..m).find(|&i| turn(corner(i)) == 0.0) {
+                // a straight corner adds no triangle
+                remaining.remove(i);
+            } else {
+                return triangles;
+            }
+        }
+        if let [a, b, c] = remaining[..] {
+            if cross2(&self.points[a], &self.points[b], &self.points[c]) != 0.0 {
+                triangles.push([a, b, c]);
+            }
+        }
+        triangles
+    }
+}
+
+fn signed_area(points: &[Vector2]) -> f32 {
+    let n = points.len();
+    let twice: f32 = (0..n)
+        .map(|i| {
+            let (a, b) = (&points[i], &points[(i + 1) % n]);
+            a.x() * b.y() - b.x() * a.y()
+        })
+        .sum();
+    twice / 2.0
+}
+
+// twice the signed area of the triangle, positive when `c` is left of a->b
+fn cross2(a: &Vector2, b: &Vector2, c: &Vector2) -> f32 {
+    (b.x() - a.x()) * (c.y() - a.y()) - (b.y() - a.y()) * (c.x() - a.x())
+}
+
+fn sign(x: f32) -> f32 {
+    if x > 0.0 {
+        1.0
+    } else if x < 0.0 {
+        -1.0
+    } else {
+        0.0
+    }
+}
+
+// the pixels of the line from (x0, y0) to (x1, y1), bresenham, both ends
+// included. A line of one point yields it once
+#[derive(Clone, Debug)]
+pub struct LineIter {
+    x: i32,
+    y: i32,
+    sx: i32,
+    sy: i32,
+    dx: i64,
+    // negative, the error term steps y when it goes past it
+    dy: i64,
+    error: i64,
+    remaining: usize,
+}
+impl LineIter {
+    pub fn new(x0: i32, y0: i32, x1: i32, y1: i32) -> Self {
+        let dx = (x1 as i64 - x0 as i64).abs();
+        let dy = -(y1 as i64 - y0 as i64).abs();
+        Self {
+            x: x0,
+            y: y0,
+            sx: if x1 < x0 { -1 } else { 1 },
+            sy: if y1 < y0 { -1 } else { 1 },
+            dx,
+            dy,
+            error: dx + dy,
+            remaining: dx.max(-dy) as usize + 1,
+        }
+    }
+}
+impl Iterator for LineIter {
+    type Item = (i32, i32);
+    fn next(&mut self) -> Option<(i32, i32)> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        let point = (self.x, self.y);
+        if self.remaining > 0 {
+            let e2 = 2 * self.error;
+            if e2 >= self.dy {
+                self.error += self.dy;
+                self.x += self.sx;
+            }
+            if e2 <= self.dx {
+                self.error += self.dx;
+                self.y += self.sy;
+            }
+        }
+        Some(point)
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+impl ExactSizeIterator for LineIter {}
+
+// up to eight mirror images of an octant or quadrant point, each pixel once
+#[derive(Clone, Debug, Default)]
+struct Mirrored {
+    points: [(i32, i32); 8],
+    len: usize,
+    at: usize,
+}
+impl Mirrored {
+    fn new(center: (i32, i32), images: &[(i32, i32)]) -> Self {
+        let mut mirrored = Self::default();
+        for &(x, y) in images {
+            let point = (center.0 + x, center.1 + y);
+            if !mirrored.points[..mirrored.len].contains(&point) {
+                mirrored.points[mirrored.len] = point;
+                mirrored.len += 1;
+            }
+        }
+        mirrored
+    }
+    fn next(&mut self) -> Option<(i32, i32)> {
+        (self.at < self.len).then(|| {
+            self.at += 1;
+            self.points[self.at - 1]
+        })
+    }
+}
+
+// the outline of the circle of `radius` around (cx, cy), midpoint, each pixel
+// once. The octants are walked together, the points don't come in order around
+#[derive(Clone, Debug)]
+pub struct CircleIter {
+    center: (i32, i32),
+    x: i32,
+    y: i32,
+    d: i32,
+    pending: Mirrored,
+}
+impl CircleIter {
+    pub fn new(cx: i32, cy: i32, radius: u32) -> Self {
+        let radius = radius as i32;
+        Self {
+            center: (cx, cy),
+            x: 0,
+            y: radius,
+            d: 1 - radius,
+            pending: Mirrored::default(),
+        }
+    }
+    // the points between the angles `start` and `end`, see `ArcIter`
+    pub fn arc(self, start: f32, end: f32) -> ArcIter<Self> {
+        ArcIter::new(self.center, self, start, end)
+    }
+}
+impl Iterator for CircleIter {
+    type Item = (i32, i32);
+    fn next(&mut self) -> Option<(i32, i32)> {
+        loop {
+            if let Some(point) = self.pending.next() {
+                return Some(point);
+            }
+            // the octant from the top going right, x <= y
+            if self.x > self.y {
+                return None;
+            }
+            let (x, y) = (self.x, self.y);
+            self.pending = Mirrored::new(
+                self.center,
+                &[
+                    (x, y),
+                    (y, x),
+                    (-x, y),
+                    (-y, x),
+                    (x, -y),
+                    (y, -x),
+                    (-x, -y),
+                    (-y, -x),
+                ],
+            );
+            self.x += 1;
+            if self.d < 0 {
+                self.d += 2 * self.x + 1;
+            } else {
+                self.y -= 1;
+                self.d += 2 * (self.x - self.y) + 1;
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum EllipseRegion {
+    // slope under 1, a step right every point
+    Flat,
+    // a step up every point
+    Steep,
+    Done,
+}
+
+// the outline of the axis aligned ellipse with radii `rx` and `ry` around (cx,
+// cy), midpoint, each pixel once. The quadrants are walked together like
+// `CircleIter`
+#[derive(Clone, Debug)]
+pub struct EllipseIter {
+    center: (i32, i32),
+    rx2: i64,
+    ry2: i64,
+    x: i64,
+    y: i64,
+    // the decision term, four times the midpoint's distance to stay in integers
+    d: i64,
+    region: EllipseRegion,
+    pending: Mirrored,
+}
+impl EllipseIter {
+    pub fn new(cx: i32, cy: i32, rx: u32, ry: u32) -> Self {
+        let (rx, ry) = (rx as i64, ry as i64);
+        let (rx2, ry2) = (rx * rx, ry * ry);
+        let mut ellipse = Self {
+            center: (cx, cy),
+            rx2,
+            ry2,
+            x: 0,
+            y: ry,
+            d: 4 * ry2 - 4 * rx2 * ry + rx2,
+            region: EllipseRegion::Flat,
+            pending: Mirrored::default(),
+        };
+        // a flat ellipse is a line, its walk never turns steep. A thin one is
+        // steep from the start
+        if ry > 0 && rx == 0 {
+            ellipse.steep();
+        }
+        ellipse
+    }
+    // the points between the angles `start` and `end`, see `ArcIter`
+    pub fn arc(self, start: f32, end: f32) -> ArcIter<Self> {
+        ArcIter::new(self.center, self, start, end)
+    }
+    fn steep(&mut self) {
+        let (x, y) = (self.x, self.y);
+        self.region = EllipseRegion::Steep;
+        self.d =
+            self.ry2 * (2 * x + 1).pow(2) + 4 * self.rx2 * (y - 1).pow(2) - 4 * self.rx2 * self.ry2;
+    }
+}
+impl Iterator for EllipseIter {
+    type Item = (i32, i32);
+    fn next(&mut self) -> Option<(i32, i32)> {
+        loop {
+            if let Some(point) = self.pending.next() {
+                return Some(point);
+            }
+            if self.region == EllipseRegion::Done {
+                return None;
+            }
+            let (x, y) = (self.x as i32, self.y as i32);
+            self.pending = Mirrored::new(self.center, &[(x, y), (-x, y), (x, -y), (-x, -y)]);
+            let (rx2, ry2) = (self.rx2, self.ry2);
+            match self.region {
+                EllipseRegion::Flat => {
+                    // only a flat ellipse walks the axis here, a line out to rx
+                    if self.y == 0 {
+                        if self.x * self.x >= rx2 {
+                            self.region = EllipseRegion::Done;
+                        }
+                        self.x += 1;
+                        continue;
+                    }
+                    self.x += 1;
+                    if self.d < 0 {
+                        self.d += 4 * (2 * ry2 * self.x + ry2);
+                    } else {
+                        self.y -= 1;
+                        self.d += 4 * (2 * ry2 * self.x - 2 * rx2 * self.y + ry2);
+                    }
+                    if ry2 * self.x >= rx2 * self.y {
+                        self.steep();
+                    }
+                }
+                EllipseRegion::Steep => {
+                    if self.y == 0 {
+                        self.region = EllipseRegion::Done;
+                        continue;
+                    }
+                    self.y -= 1;
+                    if self.d > 0 {
+                        self.d += 4 * (rx2 - 2 * rx2 * self.y);
+                    } else {
+                        self.x += 1;
+                        self.d += 4 * (2 * ry2 * self.x - 2 * rx2 * self.y + rx2);
+                    }
+                }
+                EllipseRegion::Done => unreachable!(),
+            }
+        }
+    }
 }
 
+// the points of a circle or ellipse around `center` whose angle is between
+// `start` and `end`, in either order, in radians from +x towards +y: clockwise
+// on a y down screen. A range of a full turn or more keeps every point
+#[derive(Clone, Debug)]
+pub struct ArcIter<I> {
+    center: (i32, i32),
+    points: I,
+    start: f32,
+    sweep: f32,
+}
+impl<I> ArcIter<I> {
+    pub fn new(center: (i32, i32), points: I, start: f32, end: f32) -> Self {
+        let (start, end) = if end < start {
+            (end, start)
+        } else {
+            (start, end)
+        };
+        Self {
+            center,
+            points,
+            start,
+            sweep: end - start,
+        }
+    }
+}
+impl<I: Iterator<Item = (i32, i32)>> Iterator for ArcIter<I> {
+    type Item = (i32, i32);
+    fn next(&mut self) -> Option<(i32, i32)> {
+        use core::f32::consts::TAU;
+        let (center, start, sweep) = (self.center, self.start, self.sweep);
+        self.points.find(|&(x, y)| {
+            if sweep >= TAU {
+                return true;
+            }
+            let angle = ((y - center.1) as f32).atan2((x - center.0) as f32);
+            let mut from_start = (angle - start) % TAU;
+            if from_start < 0.0 {
+                from_start += TAU;
+            }
+            from_start <= sweep
+        })
+    }
+}
+
+// the walk `LineIter` replaced: `next_xy` until `reached`, which leaves out the
+// end point
 pub struct Ray {
     pub x0: i32,
     pub y0: i32,
     pub x1: i32,
     pub y1: i32,
     pub reached: bool,
-    sx: i32,
-    sy: i32,
-    dx: i32,
-    dy: i32,
-    error: i32,
-    x: i32,
-    y: i32,
-    next: fn(&mut Self) -> (i32, i32),
+    points: LineIter,
 }
 impl Ray {
+    #[deprecated(note = "use `LineIter`, it ends by itself and includes the end point")]
     pub fn new(x0: i32, y0: i32, x1: i32, y1: i32) -> Self {
-        // bresenham
-        let sx = if x1 < x0 { -1 } else { 1 };
-        let sy = if y1 < y0 { -1 } else { 1 };
-
-        let dx = (x1 - x0).abs();
-        let dy = (y1 - y0).abs();
-
-        let x = x0;
-        let y = y0;
-
-        let error;
-        let next: fn(&mut Self) -> (i32, i32);
-        let reached;
-        if dy < dx {
-            reached = x == x1;
-            error = -dx;
-            next = Self::iter_x;
-        } else {
-            reached = y == y1;
-            error = -dy;
-            next = Self::iter_y;
-        }
-
+        let points = LineIter::new(x0, y0, x1, y1);
         Self {
             x0,
             y0,
             x1,
             y1,
-            reached,
-            sx,
-            sy,
-            dx,
-            dy,
-            error,
-            x,
-            y,
-            next,
+            reached: points.len() <= 1,
+            points,
         }
     }
+    // past the end it stays on the end point
+    #[deprecated(note = "use `LineIter`, it ends by itself and includes the end point")]
     pub fn next_xy(&mut self) -> (i32, i32) {
-        (self.next)(self)
-    }
-    fn iter_x(&mut self) -> (i32, i32) {
-        let result = (self.x, self.y);
-        self.error += self.dy + self.dy;
-        if self.error >= 0 {
-            self.y += self.sy;
-            self.error -= self.dx + self.dx;
-        }
-        self.x += self.sx;
-        if self.x == self.x1 {
-            self.reached = true;
-        }
-        result
-    }
-    fn iter_y(&mut self) -> (i32, i32) {
-        let result = (self.x, self.y);
-        self.error += self.dx + self.dx;
-        if self.error >= 0 {
-            self.x += self.sx;
-            self.error -= self.dy + self.dy;
-        }
-        self.y += self.sy;
-        if self.y == self.y1 {
-            self.reached = true;
-        }
-        result
+        let point = self.points.next().unwrap_or((self.x1, self.y1));
+        self.reached = self.points.len() <= 1;
+        point
     }
 }
 
@@ -204,17 +677,80 @@ pub trait Cross<Rhs = Self> {
     fn cross(self, rhs: Rhs) -> Self::Output;
 }
 
+// what vectors and matrices are made of: f32 everywhere in the renderer, f64 as
+// a double precision reference when chasing precision problems
+pub trait Scalar:
+    Copy
+    + Debug
+    + Display
+    + PartialOrd
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + Neg<Output = Self>
+    + AddAssign
+    + SubAssign
+    + MulAssign
+    + DivAssign
+{
+    const ZERO: Self;
+    const ONE: Self;
+    fn sqrt(self) -> Self;
+    fn abs(self) -> Self;
+    fn total_cmp(&self, other: &Self) -> Ordering;
+    fn from_f64(x: f64) -> Self;
+    fn to_f64(self) -> f64;
+}
+
+macro_rules! impl_scalar {
+    ($($t:ty, $sqrt:ident);+) => {
+        $(impl Scalar for $t {
+            const ZERO: Self = 0.0;
+            const ONE: Self = 1.0;
+            #[cfg(feature = "std")]
+            fn sqrt(self) -> Self {
+                <$t>::sqrt(self)
+            }
+            #[cfg(not(feature = "std"))]
+            fn sqrt(self) -> Self {
+                libm::$sqrt(self)
+            }
+            fn abs(self) -> Self {
+                <$t>::abs(self)
+            }
+            fn total_cmp(&self, other: &Self) -> Ordering {
+                <$t>::total_cmp(self, other)
+            }
+            fn from_f64(x: f64) -> Self {
+                x as $t
+            }
+            fn to_f64(self) -> f64 {
+                self as f64
+            }
+        })+
+    };
+}
+
+impl_scalar!(f32, sqrtf; f64, sqrt);
+
 #[derive(Debug, Clone, PartialEq)]
-pub struct Vector<const D: usize> {
-    data: [f32; D],
+pub struct Vector<const D: usize, T = f32> {
+    data: [T; D],
 }
 
-impl<const D: usize> Vector<D> {
+impl<const D: usize, T: Scalar> Vector<D, T> {
     pub fn zero() -> Self {
-        Self { data: [0f32; D] }
+        Self { data: [T::ZERO; D] }
+    }
+    // the same vector in another precision
+    pub fn cast<U: Scalar>(&self) -> Vector<D, U> {
+        Vector {
+            data: self.data.map(|x| U::from_f64(x.to_f64())),
+        }
     }
     #[inline]
-    fn get(&self, name: &str) -> Option<f32> {
+    fn get(&self, name: &str) -> Option<T> {
         match name {
             "x" => self.data.first(),
             "y" => self.data.get(1),
@@ -224,17 +760,17 @@ impl<const D: usize> Vector<D> {
         }
         .copied()
     }
-    pub fn length_square(&self) -> f32 {
+    pub fn length_square(&self) -> T {
         self.dot(self)
     }
-    pub fn length(&self) -> f32 {
+    pub fn length(&self) -> T {
         self.length_square().sqrt()
     }
     pub fn normalize(&self) -> Self {
         self / self.length()
     }
-    pub fn dot(&self, rhs: &Vector<D>) -> f32 {
-        let mut result = 0.0;
+    pub fn dot(&self, rhs: &Vector<D, T>) -> T {
+        let mut result = T::ZERO;
         for i in 0..D {
             result += self.data[i] * rhs.data[i];
         }
@@ -242,34 +778,34 @@ impl<const D: usize> Vector<D> {
     }
 }
 
-impl<const D: usize> Index<usize> for Vector<D> {
-    type Output = f32;
+impl<const D: usize, T> Index<usize> for Vector<D, T> {
+    type Output = T;
     fn index(&self, index: usize) -> &Self::Output {
         &self.data[index]
     }
 }
 
-impl<const D: usize> IndexMut<usize> for Vector<D> {
+impl<const D: usize, T> IndexMut<usize> for Vector<D, T> {
     fn index_mut(&mut self, index: usize) -> &mut Self::Output {
         &mut self.data[index]
     }
 }
 
 macro_rules! impl_bin_op {
-    (impl$(<$(const $T:ident: $t:ty),+>)? $Op:ident<$rhs:ty> for $lhs:ty, $op:ident, $output:ty) => {
-        impl$(<$(const $T: $t),+>)? $Op<$rhs> for $lhs {
+    (impl<$($g:ident $($b:ident)?: $t:path),+> $Op:ident<$rhs:ty> for $lhs:ty, $op:ident, $output:ty) => {
+        impl<$($g $($b)?: $t),+> $Op<$rhs> for $lhs {
             type Output = $output;
             fn $op(self, rhs: $rhs) -> Self::Output {
                 (&self).$op(&rhs)
             }
         }
-        impl$(<$(const $T: $t),+>)? $Op<&$rhs> for $lhs {
+        impl<$($g $($b)?: $t),+> $Op<&$rhs> for $lhs {
             type Output = $output;
             fn $op(self, rhs: &$rhs) -> Self::Output {
                 (&self).$op(rhs)
             }
         }
-        impl$(<$(const $T: $t),+>)? $Op<$rhs> for &$lhs {
+        impl<$($g $($b)?: $t),+> $Op<$rhs> for &$lhs {
             type Output = $output;
             fn $op(self, rhs: $rhs) -> Self::Output {
                 (self).$op(&rhs)
@@ -278,9 +814,9 @@ macro_rules! impl_bin_op {
     };
 }
 
-impl<const D: usize> Add<&Vector<D>> for &Vector<D> {
-    type Output = Vector<D>;
-    fn add(self, rhs: &Vector<D>) -> Self::Output {
+impl<const D: usize, T: Scalar> Add<&Vector<D, T>> for &Vector<D, T> {
+    type Output = Vector<D, T>;
+    fn add(self, rhs: &Vector<D, T>) -> Self::Output {
         let mut vector = self.clone();
         for i in 0..D {
             vector.data[i] += rhs.data[i];
@@ -288,11 +824,11 @@ impl<const D: usize> Add<&Vector<D>> for &Vector<D> {
         vector
     }
 }
-impl_bin_op!(impl<const D: usize> Add<Vector<D>> for Vector<D>, add, Vector<D>);
+impl_bin_op!(impl<const D: usize, T: Scalar> Add<Vector<D, T>> for Vector<D, T>, add, Vector<D, T>);
 
-impl<const D: usize> Sub<&Vector<D>> for &Vector<D> {
-    type Output = Vector<D>;
-    fn sub(self, rhs: &Vector<D>) -> Self::Output {
+impl<const D: usize, T: Scalar> Sub<&Vector<D, T>> for &Vector<D, T> {
+    type Output = Vector<D, T>;
+    fn sub(self, rhs: &Vector<D, T>) -> Self::Output {
         let mut vector = self.clone();
         for i in 0..D {
             vector.data[i] -= rhs.data[i];
@@ -300,50 +836,74 @@ impl<const D: usize> Sub<&Vector<D>> for &Vector<D> {
         vector
     }
 }
-impl_bin_op!(impl<const D: usize> Sub<Vector<D>> for Vector<D>, sub, Vector<D>);
+impl_bin_op!(impl<const D: usize, T: Scalar> Sub<Vector<D, T>> for Vector<D, T>, sub, Vector<D, T>);
 
-impl<const D: usize> Div<&f32> for &Vector<D> {
-    type Output = Vector<D>;
-    fn div(self, rhs: &f32) -> Self::Output {
+impl<const D: usize, T: Scalar> Div<&T> for &Vector<D, T> {
+    type Output = Vector<D, T>;
+    fn div(self, rhs: &T) -> Self::Output {
         let mut vector = self.clone();
         vector.data.iter_mut().for_each(|n| *n /= *rhs);
         vector
     }
 }
-impl_bin_op!(impl<const D: usize> Div<f32> for Vector<D>, div, Vector<D>);
+impl_bin_op!(impl<const D: usize, T: Scalar> Div<T> for Vector<D, T>, div, Vector<D, T>);
 
-impl<const D: usize> Mul<&f32> for &Vector<D> {
-    type Output = Vector<D>;
-    fn mul(self, rhs: &f32) -> Self::Output {
+impl<const D: usize, T: Scalar> Mul<&T> for &Vector<D, T> {
+    type Output = Vector<D, T>;
+    fn mul(self, rhs: &T) -> Self::Output {
         let mut vector = self.clone();
         vector.data.iter_mut().for_each(|n| *n *= *rhs);
         vector
     }
 }
-impl_bin_op!(impl<const D: usize> Mul<f32> for Vector<D>, mul, Vector<D>);
+impl_bin_op!(impl<const D: usize, T: Scalar> Mul<T> for Vector<D, T>, mul, Vector<D, T>);
 
-impl<const D: usize> Mul<&Vector<D>> for &f32 {
-    type Output = Vector<D>;
-    fn mul(self, rhs: &Vector<D>) -> Self::Output {
-        let mut vector = rhs.clone();
-        vector.data.iter_mut().for_each(|n| *n *= *self);
+// a generic scalar can't be on the left, so one impl per scalar type
+macro_rules! impl_scalar_mul {
+    ($($t:ty),+) => {
+        $(impl<const D: usize> Mul<&Vector<D, $t>> for &$t {
+            type Output = Vector<D, $t>;
+            fn mul(self, rhs: &Vector<D, $t>) -> Self::Output {
+                rhs * self
+            }
+        }
+        impl_bin_op!(impl<const D: usize> Mul<Vector<D, $t>> for $t, mul, Vector<D, $t>);)+
+    };
+}
+
+impl_scalar_mul!(f32, f64);
+
+impl<const D: usize, T: Scalar> Neg for &Vector<D, T> {
+    type Output = Vector<D, T>;
+    fn neg(self) -> Self::Output {
+        let mut vector = self.clone();
+        vector.data.iter_mut().for_each(|n| *n = -*n);
         vector
     }
 }
-impl_bin_op!(impl<const D: usize> Mul<Vector<D>> for f32, mul, Vector<D>);
+impl<const D: usize, T: Scalar> Neg for Vector<D, T> {
+    type Output = Vector<D, T>;
+    fn neg(self) -> Self::Output {
+        (&self).neg()
+    }
+}
 
 pub type Vector2 = Vector<2>;
 pub type Vector3 = Vector<3>;
 pub type Vector4 = Vector<4>;
+// double precision, see `Scalar`
+pub type DVector2 = Vector<2, f64>;
+pub type DVector3 = Vector<3, f64>;
+pub type DVector4 = Vector<4, f64>;
 
 macro_rules! impl_vector_methods {
-    ($ty:ident, $($name:ident),+) => {
-        impl $ty {
-            $(pub fn $name(&self) -> f32 {
-                self.get(stringify!($name)).unwrap_or(0.0)
+    ($n:literal, $($name:ident),+) => {
+        impl<T: Scalar> Vector<$n, T> {
+            $(pub fn $name(&self) -> T {
+                self.get(stringify!($name)).unwrap_or(T::ZERO)
             })+
             #[allow(unused_assignments)]
-            pub fn new($($name: f32),+) -> Self {
+            pub fn new($($name: T),+) -> Self {
                 let mut v = Self::zero();
                 let mut i = 0;
                 $(
@@ -356,12 +916,12 @@ macro_rules! impl_vector_methods {
     };
 }
 
-impl_vector_methods!(Vector2, x, y);
-impl_vector_methods!(Vector3, x, y, z);
-impl_vector_methods!(Vector4, x, y, z, w);
+impl_vector_methods!(2, x, y);
+impl_vector_methods!(3, x, y, z);
+impl_vector_methods!(4, x, y, z, w);
 
-impl Vector3 {
-    pub fn cross(&self, rhs: &Vector3) -> Self {
+impl<T: Scalar> Vector<3, T> {
+    pub fn cross(&self, rhs: &Vector<3, T>) -> Self {
         let x = self.y() * rhs.z() - self.z() * rhs.y();
         let y = self.z() * rhs.x() - self.x() * rhs.z();
         let z = self.x() * rhs.y() - self.y() * rhs.x();
@@ -370,53 +930,339 @@ impl Vector3 {
 }
 
 #[derive(Debug, Clone, PartialEq)]
-pub struct Matrix<const R: usize, const C: usize> {
-    pub rows: [[f32; C]; R],
+pub struct Matrix<const R: usize, const C: usize, T = f32> {
+    pub rows: [[T; C]; R],
 }
 
-impl<const R: usize, const C: usize> Matrix<R, C> {
+impl<const R: usize, const C: usize, T: Scalar> Matrix<R, C, T> {
     #[inline]
     pub fn zero() -> Self {
         Self {
-            rows: [[0f32; C]; R],
+            rows: [[T::ZERO; C]; R],
         }
     }
     #[inline]
-    pub fn from_rows(rows: [[f32; C]; R]) -> Self {
+    pub fn from_rows(rows: [[T; C]; R]) -> Self {
         Self::from(rows)
     }
     #[inline]
-    pub fn set_row(&mut self, r: usize, row_vec: Vector<C>) {
+    pub fn set_row(&mut self, r: usize, row_vec: Vector<C, T>) {
         self.rows[r] = row_vec.data;
     }
+    #[inline]
+    pub fn row(&self, r: usize) -> Vector<C, T> {
+        Vector { data: self.rows[r] }
+    }
+    #[inline]
+    pub fn set_col(&mut self, c: usize, col_vec: Vector<R, T>) {
+        for (row, x) in self.rows.iter_mut().zip(col_vec.data) {
+            row[c] = x;
+        }
+    }
+    #[inline]
+    pub fn col(&self, c: usize) -> Vector<R, T> {
+        Vector {
+            data: self.rows.map(|row| row[c]),
+        }
+    }
+    // the same matrix in another precision
+    pub fn cast<U: Scalar>(&self) -> Matrix<R, C, U> {
+        Matrix {
+            rows: self.rows.map(|row| row.map(|x| U::from_f64(x.to_f64()))),
+        }
+    }
 }
-impl<const C: usize> Matrix<1, C> {
+impl<const C: usize, T: Scalar> Matrix<1, C, T> {
     #[inline]
-    pub fn from_row_vector(row_vector: Vector<C>) -> Matrix<1, C> {
+    pub fn from_row_vector(row_vector: Vector<C, T>) -> Matrix<1, C, T> {
         Matrix::from([row_vector.data])
     }
-    pub fn to_row_vector(self) -> Vector<C> {
+    pub fn to_row_vector(self) -> Vector<C, T> {
         Vector { data: self[0] }
     }
 }
-impl<const N: usize> Matrix<N, N> {
+// a vector standing as a column, e.g. for a matrix applied from the left
+impl<const R: usize, T: Scalar> Matrix<R, 1, T> {
+    #[inline]
+    pub fn from_col_vector(col_vector: Vector<R, T>) -> Matrix<R, 1, T> {
+        Matrix::from(col_vector.data.map(|x| [x]))
+    }
+    pub fn to_col_vector(self) -> Vector<R, T> {
+        Vector {
+            data: self.rows.map(|[x]| x),
+        }
+    }
+}
+impl<const N: usize, T: Scalar> Matrix<N, N, T> {
     #[inline]
     pub fn identity() -> Self {
         let mut mat = Self::zero();
         for i in 0..N {
-            mat[i][i] = 1f32;
+            mat[i][i] = T::ONE;
         }
         mat
     }
+    // gauss-jordan with partial pivoting, None when singular
+    pub fn inverse(&self) -> Option<Self> {
+        let mut m = self.clone();
+        let mut inv = Self::identity();
+        for c in 0..N {
+            let pivot = (c..N).max_by(|&a, &b| m[a][c].abs().total_cmp(&m[b][c].abs()))?;
+            if m[pivot][c].abs().to_f64() < 1e-12 {
+                return None;
+            }
+            m.rows.swap(c, pivot);
+            inv.rows.swap(c, pivot);
+            let scale = T::ONE / m[c][c];
+            for k in 0..N {
+                m[c][k] *= scale;
+                inv[c][k] *= scale;
+            }
+            for r in 0..N {
+                let f = m[r][c];
+                if r == c || f == T::ZERO {
+                    continue;
+                }
+                let (row, inv_row) = (m[c], inv[c]);
+                for k in 0..N {
+                    m[r][k] -= f * row[k];
+                    inv[r][k] -= f * inv_row[k];
+                }
+            }
+        }
+        Some(inv)
+    }
 }
 
-impl<const R: usize, const C: usize> From<[[f32; C]; R]> for Matrix<R, C> {
-    fn from(rows: [[f32; C]; R]) -> Self {
+// rotations in radians, counter-clockwise looking down the axis towards the origin
+impl Matrix4 {
+    #[rustfmt::skip]
+    pub fn rotation_x(angle: f32) -> Self {
+        let (s, c) = angle.sin_cos();
+        Matrix::from_rows([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0,   c,  -s, 0.0],
+            [0.0,   s,   c, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+    #[rustfmt::skip]
+    pub fn rotation_y(angle: f32) -> Self {
+        let (s, c) = angle.sin_cos();
+        Matrix::from_rows([
+            [  c, 0.0,   s, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [ -s, 0.0,   c, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+    #[rustfmt::skip]
+    pub fn rotation_z(angle: f32) -> Self {
+        let (s, c) = angle.sin_cos();
+        Matrix::from_rows([
+            [  c,  -s, 0.0, 0.0],
+            [  s,   c, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+    #[rustfmt::skip]
+    pub fn translation(v: &Vector3) -> Self {
+        Matrix::from_rows([
+            [1.0, 0.0, 0.0, v.x()],
+            [0.0, 1.0, 0.0, v.y()],
+            [0.0, 0.0, 1.0, v.z()],
+            [0.0, 0.0, 0.0,   1.0],
+        ])
+    }
+    #[rustfmt::skip]
+    pub fn scaling(v: &Vector3) -> Self {
+        Matrix::from_rows([
+            [v.x(),   0.0,   0.0, 0.0],
+            [  0.0, v.y(),   0.0, 0.0],
+            [  0.0,   0.0, v.z(), 0.0],
+            [  0.0,   0.0,   0.0, 1.0],
+        ])
+    }
+    // translation * rotation * scale
+    #[rustfmt::skip]
+    pub fn trs(t: &Vector3, r: &Quaternion, s: &Vector3) -> Self {
+        let (x, y, z, w) = (r.x, r.y, r.z, r.w);
+        let (sx, sy, sz) = (s.x(), s.y(), s.z());
+        Matrix::from_rows([
+            [(1.0 - 2.0*(y*y + z*z))*sx,       2.0*(x*y - z*w)*sy,       2.0*(x*z + y*w)*sz, t.x()],
+            [      2.0*(x*y + z*w)*sx, (1.0 - 2.0*(x*x + z*z))*sy,       2.0*(y*z - x*w)*sz, t.y()],
+            [      2.0*(x*z - y*w)*sx,       2.0*(y*z + x*w)*sy, (1.0 - 2.0*(x*x + y*y))*sz, t.z()],
+            [                     0.0,                      0.0,                        0.0,   1.0],
+        ])
+    }
+    // back into what `trs` takes, for affine matrices without shear. A mirroring
+    // matrix gets a negative x scale
+    pub fn decompose(&self) -> (Vector3, Quaternion, Vector3) {
+        let m = self;
+        let translation = Vector3::new(m[0][3], m[1][3], m[2][3]);
+        let mut columns = [0, 1, 2].map(|c| Vector3::new(m[0][c], m[1][c], m[2][c]));
+        let mut scale = Vector3::new(
+            columns[0].length(),
+            columns[1].length(),
+            columns[2].length(),
+        );
+        if columns[0].cross(&columns[1]).dot(&columns[2]) < 0.0 {
+            scale[0] = -scale[0];
+        }
+        for (column, s) in columns.iter_mut().zip([scale.x(), scale.y(), scale.z()]) {
+            if s != 0.0 {
+                *column = &*column / s;
+            }
+        }
+        let r = |row: usize, col: usize| columns[col][row];
+        // shepperd's method, from the largest of w, x, y and z
+        let trace = r(0, 0) + r(1, 1) + r(2, 2);
+        let rotation = if trace > 0.0 {
+            let s = (trace + 1.0).sqrt() * 2.0;
+            Quaternion::new(
+                (r(2, 1) - r(1, 2)) / s,
+                (r(0, 2) - r(2, 0)) / s,
+                (r(1, 0) - r(0, 1)) / s,
+                s / 4.0,
+            )
+        } else if r(0, 0) > r(1, 1) && r(0, 0) > r(2, 2) {
+            let s = (1.0 + r(0, 0) - r(1, 1) - r(2, 2)).sqrt() * 2.0;
+            Quaternion::new(
+                s / 4.0,
+                (r(0, 1) + r(1, 0)) / s,
+                (r(0, 2) + r(2, 0)) / s,
+                (r(2, 1) - r(1, 2)) / s,
+            )
+        } else if r(1, 1) > r(2, 2) {
+            let s = (1.0 + r(1, 1) - r(0, 0) - r(2, 2)).sqrt() * 2.0;
+            Quaternion::new(
+                (r(0, 1) + r(1, 0)) / s,
+                s / 4.0,
+                (r(1, 2) + r(2, 1)) / s,
+                (r(0, 2) - r(2, 0)) / s,
+            )
+        } else {
+            let s = (1.0 + r(2, 2) - r(0, 0) - r(1, 1)).sqrt() * 2.0;
+            Quaternion::new(
+                (r(0, 2) + r(2, 0)) / s,
+                (r(1, 2) + r(2, 1)) / s,
+                s / 4.0,
+                (r(1, 0) - r(0, 1)) / s,
+            )
+        };
+        (translation, rotation.normalize(), scale)
+    }
+    // rodrigues' formula, `axis` needn't be normalized
+    #[rustfmt::skip]
+    pub fn from_axis_angle(axis: &Vector3, angle: f32) -> Self {
+        let a = axis.normalize();
+        let (x, y, z) = (a.x(), a.y(), a.z());
+        let (s, c) = angle.sin_cos();
+        let t = 1.0 - c;
+        Matrix::from_rows([
+            [t*x*x + c,   t*x*y - s*z, t*x*z + s*y, 0.0],
+            [t*x*y + s*z, t*y*y + c,   t*y*z - s*x, 0.0],
+            [t*x*z - s*y, t*y*z + s*x, t*z*z + c,   0.0],
+            [0.0,         0.0,         0.0,         1.0],
+        ])
+    }
+    // yaw about y, pitch about x and roll about z, roll applied first
+    pub fn from_euler(yaw: f32, pitch: f32, roll: f32) -> Self {
+        Self::rotation_y(yaw) * Self::rotation_x(pitch) * Self::rotation_z(roll)
+    }
+    // the shortest rotation turning direction `from` into direction `to`
+    pub fn rotation_from_to(from: &Vector3, to: &Vector3) -> Self {
+        let (a, b) = (from.normalize(), to.normalize());
+        let axis = a.cross(&b);
+        let cos = a.dot(&b);
+        if cos < -1.0 + 1e-6 {
+            // opposite: half a turn about anything perpendicular to them
+            let other = if a.x().abs() < 0.9 {
+                Vector3::new(1.0, 0.0, 0.0)
+            } else {
+                Vector3::new(0.0, 1.0, 0.0)
+            };
+            return Self::from_axis_angle(&a.cross(&other), core::f32::consts::PI);
+        }
+        // I + [v]x + [v]x^2 / (1 + cos), v = a x b
+        let k = 1.0 / (1.0 + cos);
+        let (x, y, z) = (axis.x(), axis.y(), axis.z());
+        let cross = [[0.0, -z, y], [z, 0.0, -x], [-y, x, 0.0]];
+        let mut m = Self::identity();
+        for r in 0..3 {
+            for c in 0..3 {
+                let square: f32 = (0..3).map(|i| cross[r][i] * cross[i][c]).sum();
+                m[r][c] += cross[r][c] + square * k;
+            }
+        }
+        m
+    }
+}
+
+// 2D affine transforms of (x, y, 1) columns, angles in radians from +x towards
+// +y: clockwise on a y down screen
+impl Matrix3 {
+    #[rustfmt::skip]
+    pub fn translation_2d(x: f32, y: f32) -> Self {
+        Matrix::from_rows([
+            [1.0, 0.0,   x],
+            [0.0, 1.0,   y],
+            [0.0, 0.0, 1.0],
+        ])
+    }
+    #[rustfmt::skip]
+    pub fn rotation_2d(angle: f32) -> Self {
+        let (s, c) = angle.sin_cos();
+        Matrix::from_rows([
+            [  c,  -s, 0.0],
+            [  s,   c, 0.0],
+            [0.0, 0.0, 1.0],
+        ])
+    }
+    #[rustfmt::skip]
+    pub fn scaling_2d(x: f32, y: f32) -> Self {
+        Matrix::from_rows([
+            [  x, 0.0, 0.0],
+            [0.0,   y, 0.0],
+            [0.0, 0.0, 1.0],
+        ])
+    }
+    // x leans by `x` towards +x going down +y, y by `y` towards +y going along +x
+    #[rustfmt::skip]
+    pub fn skew_2d(x: f32, y: f32) -> Self {
+        let tan = |angle: f32| {
+            let (s, c) = angle.sin_cos();
+            s / c
+        };
+        Matrix::from_rows([
+            [    1.0, tan(x), 0.0],
+            [ tan(y),    1.0, 0.0],
+            [    0.0,    0.0, 1.0],
+        ])
+    }
+    pub fn apply_2d(&self, x: f32, y: f32) -> (f32, f32) {
+        let m = &self.rows;
+        (
+            m[0][0] * x + m[0][1] * y + m[0][2],
+            m[1][0] * x + m[1][1] * y + m[1][2],
+        )
+    }
+    // how much the transform grows areas, e.g. to scale a stroke width by its
+    // square root
+    pub fn area_scale_2d(&self) -> f32 {
+        let m = &self.rows;
+        (m[0][0] * m[1][1] - m[0][1] * m[1][0]).abs()
+    }
+}
+
+impl<const R: usize, const C: usize, T> From<[[T; C]; R]> for Matrix<R, C, T> {
+    fn from(rows: [[T; C]; R]) -> Self {
         Self { rows }
     }
 }
-impl<const R: usize, const C: usize> Display for Matrix<R, C> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl<const R: usize, const C: usize, T: Debug> Display for Matrix<R, C, T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         let mut output = String::new();
         output += "[\n";
         for row in self.rows.iter() {
@@ -426,20 +1272,22 @@ impl<const R: usize, const C: usize> Display for Matrix<R, C> {
         write!(f, "{output}")
     }
 }
-impl<const R: usize, const C: usize> Index<usize> for Matrix<R, C> {
-    type Output = [f32; C];
+impl<const R: usize, const C: usize, T> Index<usize> for Matrix<R, C, T> {
+    type Output = [T; C];
     fn index(&self, index: usize) -> &Self::Output {
         &self.rows[index]
     }
 }
-impl<const R: usize, const C: usize> IndexMut<usize> for Matrix<R, C> {
+impl<const R: usize, const C: usize, T> IndexMut<usize> for Matrix<R, C, T> {
     fn index_mut(&mut self, index: usize) -> &mut Self::Output {
         &mut self.rows[index]
     }
 }
-impl<const A: usize, const B: usize, const C: usize> Mul<&Matrix<B, C>> for &Matrix<A, B> {
-    type Output = Matrix<A, C>;
-    fn mul(self, rhs: &Matrix<B, C>) -> Self::Output {
+impl<const A: usize, const B: usize, const C: usize, T: Scalar> Mul<&Matrix<B, C, T>>
+    for &Matrix<A, B, T>
+{
+    type Output = Matrix<A, C, T>;
+    fn mul(self, rhs: &Matrix<B, C, T>) -> Self::Output {
         let mut result = Self::Output::zero();
         for a in 0..A {
             for c in 0..C {
@@ -451,22 +1299,424 @@ impl<const A: usize, const B: usize, const C: usize> Mul<&Matrix<B, C>> for &Mat
         result
     }
 }
-impl_bin_op!(impl<const A: usize, const B: usize, const C: usize> Mul<Matrix<B, C>> for Matrix<A, B>, mul, Matrix<A, C>);
+impl_bin_op!(impl<const A: usize, const B: usize, const C: usize, T: Scalar> Mul<Matrix<B, C, T>> for Matrix<A, B, T>, mul, Matrix<A, C, T>);
 
 pub type Matrix2 = Matrix<2, 2>;
 pub type Matrix3 = Matrix<3, 3>;
 pub type Matrix4 = Matrix<4, 4>;
+pub type DMatrix3 = Matrix<3, 3, f64>;
+pub type DMatrix4 = Matrix<4, 4, f64>;
 
-pub fn m2v(m: &Matrix<4, 1>) -> Vector3 {
-    Vector3::new(m[0][0] / m[3][0], m[1][0] / m[3][0], m[2][0] / m[3][0])
+// a rotation as a unit quaternion: (x, y, z) is the axis times sin(angle / 2)
+// and w is cos(angle / 2)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quaternion {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub w: f32,
+}
+
+impl Quaternion {
+    pub fn new(x: f32, y: f32, z: f32, w: f32) -> Self {
+        Self { x, y, z, w }
+    }
+    pub fn identity() -> Self {
+        Self::new(0.0, 0.0, 0.0, 1.0)
+    }
+    // `angle` radians about `axis`, which needn't be normalized
+    pub fn from_axis_angle(axis: &Vector3, angle: f32) -> Self {
+        let axis = axis.normalize();
+        let (s, c) = (angle * 0.5).sin_cos();
+        Self::new(axis.x() * s, axis.y() * s, axis.z() * s, c)
+    }
+    pub fn from_vector4(v: &Vector4) -> Self {
+        Self::new(v.x(), v.y(), v.z(), v.w())
+    }
+    pub fn to_vector4(&self) -> Vector4 {
+        Vector4::new(self.x, self.y, self.z, self.w)
+    }
+    pub fn dot(&self, other: &Quaternion) -> f32 {
+        self.x * other.x + self.y * other.y + self.z * other.z + self.w * other.w
+    }
+    pub fn normalize(&self) -> Self {
+        let length = self.dot(self).sqrt();
+        Self::new(
+            self.x / length,
+            self.y / length,
+            self.z / length,
+            self.w / length,
+        )
+    }
+    // the inverse rotation for unit quaternions
+    pub fn conjugate(&self) -> Self {
+        Self::new(-self.x, -self.y, -self.z, self.w)
+    }
+    pub fn rotate(&self, v: &Vector3) -> Vector3 {
+        let u = Vector3::new(self.x, self.y, self.z);
+        let t = u.cross(v) * 2.0;
+        &(v + &(&t * self.w)) + &u.cross(&t)
+    }
+    // along the shorter arc, so the result may come out as -q
+    pub fn slerp(&self, other: &Quaternion, t: f32) -> Self {
+        let mut cos = self.dot(other);
+        // q and -q are the same rotation
+        let other = if cos < 0.0 {
+            cos = -cos;
+            -*other
+        } else {
+            *other
+        };
+        let blend = |a: f32, b: f32| Self {
+            x: self.x * a + other.x * b,
+            y: self.y * a + other.y * b,
+            z: self.z * a + other.z * b,
+            w: self.w * a + other.w * b,
+        };
+        if cos > 0.9995 {
+            return blend(1.0 - t, t).normalize();
+        }
+        let angle = cos.acos();
+        let sin = angle.sin();
+        blend(((1.0 - t) * angle).sin() / sin, (t * angle).sin() / sin).normalize()
+    }
+    pub fn to_matrix4(&self) -> Matrix4 {
+        Matrix4::trs(&Vector3::zero(), self, &Vector3::new(1.0, 1.0, 1.0))
+    }
+}
+
+impl Default for Quaternion {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+// hamilton product, `rhs` rotates first
+impl Mul for Quaternion {
+    type Output = Quaternion;
+    fn mul(self, b: Quaternion) -> Self::Output {
+        let a = self;
+        Quaternion::new(
+            a.w * b.x + a.x * b.w + a.y * b.z - a.z * b.y,
+            a.w * b.y - a.x * b.z + a.y * b.w + a.z * b.x,
+            a.w * b.z + a.x * b.y - a.y * b.x + a.z * b.w,
+            a.w * b.w - a.x * b.x - a.y * b.y - a.z * b.z,
+        )
+    }
+}
+impl Neg for Quaternion {
+    type Output = Quaternion;
+    fn neg(self) -> Self::Output {
+        Quaternion::new(-self.x, -self.y, -self.z, -self.w)
+    }
+}
+
+pub fn m2v<T: Scalar>(m: &Matrix<4, 1, T>) -> Vector<3, T> {
+    Vector::<3, T>::new(m[0][0] / m[3][0], m[1][0] / m[3][0], m[2][0] / m[3][0])
 }
 
 #[rustfmt::skip]
-pub fn v2m(v: &Vector3) -> Matrix<4, 1> {
+pub fn v2m<T: Scalar>(v: &Vector<3, T>) -> Matrix<4, 1, T> {
     [
         [v.x()],
         [v.y()],
         [v.z()],
-        [  1.0],
+        [T::ONE],
     ].into()
 }
+
+// `p` through `transform`, e.g. viewport * projection * view, perspective
+// divide included
+pub fn project<T: Scalar>(transform: &Matrix<4, 4, T>, p: &Vector<3, T>) -> Vector<3, T> {
+    m2v(&(transform * v2m(p)))
+}
+
+// back from where `project` put a point, x and y in pixels and z its depth, to
+// the space `transform` starts from. None if `transform` can't be inverted
+pub fn unproject<T: Scalar>(
+    transform: &Matrix<4, 4, T>,
+    screen: &Vector<3, T>,
+) -> Option<Vector<3, T>> {
+    Some(m2v(&(&transform.inverse()? * v2m(screen))))
+}
+
+// 3x3 matrix times column vector
+pub fn m3v<T: Scalar>(m: &Matrix<3, 3, T>, v: &Vector<3, T>) -> Vector<3, T> {
+    let mut result = Vector::zero();
+    for i in 0..3 {
+        result[i] = m[i][0] * v.x() + m[i][1] * v.y() + m[i][2] * v.z();
+    }
+    result
+}
+
+// takes normals through the upper 3x3 of `m`: its cofactor matrix is the inverse
+// transpose times the determinant, so nothing has to be inverted but mirroring
+// must be flipped back. Results still need normalizing
+pub fn normal_matrix(m: &Matrix4) -> Matrix3 {
+    let column = |c: usize| Vector3::new(m[0][c], m[1][c], m[2][c]);
+    let (a, b, c) = (column(0), column(1), column(2));
+    let sign = if a.cross(&b).dot(&c) < 0.0 { -1.0 } else { 1.0 };
+    let cofactor = [b.cross(&c), c.cross(&a), a.cross(&b)];
+    let mut result = Matrix3::zero();
+    for (col, v) in cofactor.iter().enumerate() {
+        for row in 0..3 {
+            result[row][col] = v[row] * sign;
+        }
+    }
+    result
+}
+
+// points p with normal.dot(p) + d >= 0 are on the inner side
+#[derive(Debug, Clone)]
+pub struct Plane {
+    pub normal: Vector3,
+    pub d: f32,
+}
+
+impl Plane {
+    // through `point`, `normal` pointing to the inner side
+    pub fn from_point_normal(point: &Vector3, normal: &Vector3) -> Self {
+        let normal = normal.normalize();
+        let d = -normal.dot(point);
+        Self { normal, d }
+    }
+    // the inner side is the one the triangle is counter-clockwise from, None for
+    // points on a line
+    pub fn from_points(a: &Vector3, b: &Vector3, c: &Vector3) -> Option<Self> {
+        let n = (b - a).cross(&(c - a));
+        (n.length_square() > 0.0).then(|| Self::from_point_normal(a, &n))
+    }
+    // signed, in units of the normal's length
+    pub fn distance(&self, p: &Vector3) -> f32 {
+        self.normal.dot(p) + self.d
+    }
+    // how far along the ray it meets the plane, from either side
+    #[cfg(all(feature = "std", feature = "model"))]
+    pub fn intersect_ray(&self, ray: &crate::raytrace::Ray) -> Option<f32> {
+        let along = self.normal.dot(&ray.dir);
+        if along.abs() < 1e-12 {
+            return None;
+        }
+        let t = -self.distance(&ray.origin) / along;
+        (t >= 0.0).then_some(t)
+    }
+    // mirrors points about the plane, `normal` has to be unit length
+    pub fn reflection(&self) -> Matrix4 {
+        let n = &self.normal;
+        let mut m = Matrix4::identity();
+        for r in 0..3 {
+            for c in 0..3 {
+                m[r][c] -= 2.0 * n[r] * n[c];
+            }
+            m[r][3] = -2.0 * self.d * n[r];
+        }
+        m
+    }
+}
+
+// axis aligned box, `empty()` has min above max until something is added
+#[derive(Debug, Clone, PartialEq)]
+pub struct Aabb {
+    pub min: Vector3,
+    pub max: Vector3,
+}
+
+impl Aabb {
+    pub fn new(min: Vector3, max: Vector3) -> Self {
+        Self { min, max }
+    }
+    pub fn empty() -> Self {
+        Self {
+            min: Vector3::new(f32::MAX, f32::MAX, f32::MAX),
+            max: Vector3::new(f32::MIN, f32::MIN, f32::MIN),
+        }
+    }
+    pub fn from_points<'a>(points: impl IntoIterator<Item = &'a Vector3>) -> Self {
+        let mut aabb = Self::empty();
+        for p in points {
+            aabb.extend(p);
+        }
+        aabb
+    }
+    pub fn is_empty(&self) -> bool {
+        (0..3).any(|i| self.min[i] > self.max[i])
+    }
+    pub fn extend(&mut self, p: &Vector3) {
+        for i in 0..3 {
+            self.min[i] = self.min[i].min(p[i]);
+            self.max[i] = self.max[i].max(p[i]);
+        }
+    }
+    pub fn merge(&self, other: &Self) -> Self {
+        Self::from_points([&self.min, &self.max, &other.min, &other.max])
+    }
+    pub fn center(&self) -> Vector3 {
+        (&self.min + &self.max) * 0.5
+    }
+    pub fn contains(&self, p: &Vector3) -> bool {
+        (0..3).all(|i| self.min[i] <= p[i] && p[i] <= self.max[i])
+    }
+    // bit i of the index picks max over min along axis i
+    pub fn corners(&self) -> [Vector3; 8] {
+        core::array::from_fn(|i| {
+            let pick = |axis: usize| {
+                if i >> axis & 1 == 0 {
+                    self.min[axis]
+                } else {
+                    self.max[axis]
+                }
+            };
+            Vector3::new(pick(0), pick(1), pick(2))
+        })
+    }
+    // the box around the transformed corners, so it grows under rotation
+    pub fn transform(&self, m: &Matrix4) -> Self {
+        if self.is_empty() {
+            return self.clone();
+        }
+        let corners = self.corners().map(|c| m2v(&(m * v2m(&c))));
+        Self::from_points(&corners)
+    }
+    // slab test, where the ray enters the box, 0 if it starts inside
+    #[cfg(all(feature = "std", feature = "model"))]
+    pub fn intersect_ray(&self, ray: &crate::raytrace::Ray) -> Option<f32> {
+        let (mut near, mut far) = (0.0f32, f32::MAX);
+        for i in 0..3 {
+            let inv = 1.0 / ray.dir[i];
+            let t0 = (self.min[i] - ray.origin[i]) * inv;
+            let t1 = (self.max[i] - ray.origin[i]) * inv;
+            // NaN from 0 * inf, a ray in the plane of a face, leaves the bounds alone
+            near = near.max(t0.min(t1));
+            far = far.min(t0.max(t1));
+        }
+        (near <= far).then_some(near)
+    }
+}
+
+// the planes bounding a view volume, normals pointing in
+#[derive(Debug, Clone)]
+pub struct Frustum {
+    pub planes: Vec<Plane>,
+}
+
+impl Frustum {
+    // left, right, bottom, top, near and far from a projection * view matrix
+    // mapping the visible volume to -w <= x, y, z <= w
+    pub fn from_matrix(m: &Matrix4) -> Self {
+        let z = Vector4::new(m[2][0], m[2][1], m[2][2], m[2][3]);
+        let w = Vector4::new(m[3][0], m[3][1], m[3][2], m[3][3]);
+        let mut frustum = Self::sides(m);
+        frustum
+            .planes
+            .extend([&w + &z, &w - &z].iter().map(plane_from_row));
+        frustum
+    }
+    // the four sides and the plane through the eye, for matrices that say
+    // nothing useful about depth, like the camera's: the rasterizer clips
+    // nothing by depth so culling shouldn't either
+    pub fn from_matrix_without_depth(m: &Matrix4) -> Self {
+        let w = Vector4::new(m[3][0], m[3][1], m[3][2], m[3][3]);
+        let mut frustum = Self::sides(m);
+        frustum.planes.push(plane_from_row(&w));
+        frustum
+    }
+    fn sides(m: &Matrix4) -> Self {
+        let row = |r: usize| Vector4::new(m[r][0], m[r][1], m[r][2], m[r][3]);
+        let (x, y, w) = (row(0), row(1), row(3));
+        let planes = [&w + &x, &w - &x, &w + &y, &w - &y]
+            .iter()
+            .map(plane_from_row)
+            .collect();
+        Self { planes }
+    }
+    pub fn contains_point(&self, p: &Vector3) -> bool {
+        self.planes.iter().all(|plane| plane.distance(p) >= 0.0)
+    }
+    pub fn intersects_sphere(&self, center: &Vector3, radius: f32) -> bool {
+        self.planes.iter().all(|p| p.distance(center) >= -radius)
+    }
+    // a box along the axes of the space the planes are in: per plane only the
+    // corner farthest along its normal needs checking
+    pub fn intersects_aabb(&self, min: &Vector3, max: &Vector3) -> bool {
+        self.planes.iter().all(|p| {
+            let mut corner = Vector3::zero();
+            for i in 0..3 {
+                corner[i] = if p.normal[i] >= 0.0 { max[i] } else { min[i] };
+            }
+            p.distance(&corner) >= 0.0
+        })
+    }
+    // the box is culled only when all its corners are outside the same plane
+    pub fn intersects_box(&self, corners: &[Vector3]) -> bool {
+        self.planes
+            .iter()
+            .all(|p| corners.iter().any(|c| p.distance(c) >= 0.0))
+    }
+}
+
+// a combination of matrix rows as a plane with a unit normal
+fn plane_from_row(p: &Vector4) -> Plane {
+    let normal = Vector3::new(p.x(), p.y(), p.z());
+    let length = normal.length();
+    Plane {
+        normal: normal / length,
+        d: p.w() / length,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn row_vector_round_trip() {
+        let v = Vector3::new(1.0, 2.0, 3.0);
+        let m = Matrix::from_row_vector(v.clone());
+        assert_eq!(m, Matrix::from([[1.0, 2.0, 3.0]]));
+        assert_eq!(m.to_row_vector(), v);
+    }
+
+    #[test]
+    fn col_vector_round_trip() {
+        let v = Vector3::new(1.0, 2.0, 3.0);
+        let m = Matrix::from_col_vector(v.clone());
+        assert_eq!(m, Matrix::from([[1.0], [2.0], [3.0]]));
+        assert_eq!(m.to_col_vector(), v);
+    }
+
+    #[test]
+    fn set_row_then_row() {
+        let mut m = Matrix::<3, 2>::zero();
+        m.set_row(1, Vector2::new(4.0, 5.0));
+        assert_eq!(m.row(1), Vector2::new(4.0, 5.0));
+        assert_eq!(m.row(0), Vector2::zero());
+    }
+
+    #[test]
+    fn set_col_then_col() {
+        let mut m = Matrix::<3, 2>::zero();
+        m.set_col(1, Vector3::new(4.0, 5.0, 6.0));
+        assert_eq!(m.col(1), Vector3::new(4.0, 5.0, 6.0));
+        assert_eq!(m.col(0), Vector3::zero());
+    }
+
+    #[test]
+    fn m2v_inverts_v2m() {
+        let v = Vector3::new(-1.5, 2.0, 0.25);
+        assert_eq!(m2v(&v2m(&v)), v);
+    }
+
+    #[test]
+    fn barycentric_times_varying() {
+        // a uv per vertex in the rows, the way the shaders keep varyings
+        let uvs = [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)].map(|(u, v)| Vector2::new(u, v));
+        let mut varying = Matrix::<3, 2>::zero();
+        for (i, uv) in uvs.iter().enumerate() {
+            varying.set_row(i, uv.clone());
+        }
+        let bc = Vector3::new(0.25, 0.5, 0.25);
+        let uv = (Matrix::from_row_vector(bc.clone()) * &varying).to_row_vector();
+        let expected = &(&(&uvs[0] * bc[0]) + &(&uvs[1] * bc[1])) + &(&uvs[2] * bc[2]);
+        assert_eq!(uv, expected);
+    }
+}
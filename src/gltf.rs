@@ -0,0 +1,531 @@
+use std::{collections::HashMap, path::Path, sync::Arc};
+
+use ::gltf::{
+    animation::{util::ReadOutputs, Interpolation as GltfInterpolation},
+    image::Format,
+    mesh::Mode,
+    texture::MagFilter,
+    Document, Node,
+};
+
+use crate::{
+    animation::{Animation, Interpolation, Track},
+    geometry::{m2v, m3v, normal_matrix, v2m, Matrix4, Quaternion, Vector2, Vector3},
+    image::Image,
+    material::Material,
+    model::Model,
+    sampler::Filter,
+    scene::Scene,
+    skin::Skin,
+};
+
+// one primitive of a mesh instanced by a node, vertices stay in mesh space
+pub struct GltfMesh {
+    pub name: Option<String>,
+    // node to world, parents included
+    pub transform: Matrix4,
+    // carries the primitive's material, base color factors are in the vertex colors
+    pub model: Model,
+}
+
+// everything in the default scene, one entry per primitive
+pub fn load_meshes(path: impl AsRef<Path>) -> Vec<GltfMesh> {
+    let (document, buffers, images) = ::gltf::import(path).unwrap();
+    let images = to_images(&images);
+    let mut meshes = Vec::new();
+    for (node, transform) in scene_nodes(&document) {
+        let Some(mesh) = node.mesh() else {
+            continue;
+        };
+        for primitive in mesh.primitives() {
+            let Some(mut data) = read_primitive(&primitive, &buffers) else {
+                continue;
+            };
+            let material = primitive.material();
+            data.tint(material.pbr_metallic_roughness().base_color_factor());
+            let mut model = data.into_model();
+            model.set_material(to_material(&material, &images));
+            meshes.push(GltfMesh {
+                name: mesh.name().map(str::to_string),
+                transform: transform.clone(),
+                model,
+            });
+        }
+    }
+    eprintln!("# gltf meshes# {}", meshes.len());
+    meshes
+}
+
+// every node as a scene node with the same index, meshes as models (a mesh's
+// extra primitives hang below its node), skins as scene skins with the same index
+// and the animations targeting those nodes. Nodes outside the default scene are
+// hidden
+pub fn load_scene(path: impl AsRef<Path>) -> (Scene, Vec<Animation>) {
+    let (document, buffers, images) = ::gltf::import(path).unwrap();
+    let mut scene = Scene::new();
+    for node in document.nodes() {
+        let name = match node.name() {
+            Some(name) => name.to_string(),
+            None => format!("node{}", node.index()),
+        };
+        let id = scene.add_node(&name, None);
+        let (t, r, s) = node.transform().decomposed();
+        let transform = &mut scene.node_mut(id).transform;
+        transform.translation = Vector3::new(t[0], t[1], t[2]);
+        transform.rotation = Quaternion::new(r[0], r[1], r[2], r[3]);
+        transform.scale = Vector3::new(s[0], s[1], s[2]);
+    }
+    for node in document.nodes() {
+        for child in node.children() {
+            scene.set_parent(child.index(), Some(node.index()));
+        }
+    }
+    let roots: Vec<usize> = document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+        .map(|s| s.nodes().map(|n| n.index()).collect())
+        .unwrap_or_default();
+    for (id, node) in scene.nodes.iter_mut().enumerate() {
+        if node.parent.is_none() && !roots.contains(&id) {
+            node.visible = false;
+        }
+    }
+
+    for skin in document.skins() {
+        let joints: Vec<usize> = skin.joints().map(|j| j.index()).collect();
+        let reader = skin.reader(|buffer| Some(&buffers[buffer.index()]));
+        let inverse_bind = match reader.read_inverse_bind_matrices() {
+            Some(matrices) => matrices.map(|m| to_matrix(&m)).collect(),
+            None => vec![Matrix4::identity(); joints.len()],
+        };
+        scene.add_skin(Skin::new(
+            skin.name().unwrap_or("skin"),
+            joints,
+            inverse_bind,
+        ));
+    }
+
+    let images = to_images(&images);
+    // nodes instancing a mesh share its models, unless they pose it with
+    // different skins
+    let mut instanced = HashMap::new();
+    for node in document.nodes() {
+        let Some(mesh) = node.mesh() else {
+            continue;
+        };
+        let skin = node.skin().map(|s| s.index());
+        let mut target = node.index();
+        for primitive in mesh.primitives() {
+            let key = (mesh.index(), primitive.index(), skin);
+            let model = match instanced.get(&key) {
+                Some(&model) => model,
+                None => {
+                    let Some(mut data) = read_primitive(&primitive, &buffers) else {
+                        continue;
+                    };
+                    let material = primitive.material();
+                    data.tint(material.pbr_metallic_roughness().base_color_factor());
+                    let mut model = data.into_model();
+                    model.set_material(to_material(&material, &images));
+                    let model = scene.add_model(model);
+                    instanced.insert(key, model);
+                    model
+                }
+            };
+            if scene.node(target).model.is_some() {
+                target = scene.add_node(mesh.name().unwrap_or("primitive"), Some(node.index()));
+            }
+            let target = scene.node_mut(target);
+            target.model = Some(model);
+            target.skin = skin;
+        }
+    }
+
+    let animations = document
+        .animations()
+        .map(|animation| {
+            let mut result = Animation::new(animation.name().unwrap_or("animation"));
+            for channel in animation.channels() {
+                let reader = channel.reader(|buffer| Some(&buffers[buffer.index()]));
+                let (Some(inputs), Some(outputs)) = (reader.read_inputs(), reader.read_outputs())
+                else {
+                    continue;
+                };
+                let times: Vec<f32> = inputs.collect();
+                let node = channel.target().node().index();
+                let interpolation = channel.sampler().interpolation();
+                let mut track = match outputs {
+                    ReadOutputs::Translations(values) => {
+                        let values = values.map(|[x, y, z]| Vector3::new(x, y, z));
+                        Track::translation(node, keys(&times, values, interpolation))
+                    }
+                    ReadOutputs::Rotations(values) => {
+                        let values = values
+                            .into_f32()
+                            .map(|[x, y, z, w]| Quaternion::new(x, y, z, w));
+                        Track::rotation(node, keys(&times, values, interpolation))
+                    }
+                    ReadOutputs::Scales(values) => {
+                        let values = values.map(|[x, y, z]| Vector3::new(x, y, z));
+                        Track::scale(node, keys(&times, values, interpolation))
+                    }
+                    ReadOutputs::MorphTargetWeights(_) => continue,
+                };
+                track.interpolation = match interpolation {
+                    GltfInterpolation::Step => Interpolation::Step,
+                    GltfInterpolation::Linear => Interpolation::Linear,
+                    GltfInterpolation::CubicSpline => Interpolation::Cubic,
+                };
+                result.add_track(track);
+            }
+            result
+        })
+        .collect();
+    eprintln!(
+        "# gltf nodes# {} skins# {}",
+        scene.nodes.len(),
+        scene.skins.len()
+    );
+    (scene, animations)
+}
+
+// cubic spline samplers store (in tangent, value, out tangent) per key, only the
+// values are kept and tracks derive their own tangents
+fn keys<T>(
+    times: &[f32],
+    values: impl Iterator<Item = T>,
+    interpolation: GltfInterpolation,
+) -> Vec<(f32, T)> {
+    let values: Vec<T> = if interpolation == GltfInterpolation::CubicSpline {
+        values.skip(1).step_by(3).collect()
+    } else {
+        values.collect()
+    };
+    times.iter().copied().zip(values).collect()
+}
+
+// gltf matrices are column major
+fn to_matrix(m: &[[f32; 4]; 4]) -> Matrix4 {
+    let mut result = Matrix4::zero();
+    for r in 0..4 {
+        for c in 0..4 {
+            result[r][c] = m[c][r];
+        }
+    }
+    result
+}
+
+impl Model {
+    // the whole default scene flattened into world space. Model has a single
+    // material, the first textured one, while base color factors are baked into
+    // the vertex colors
+    pub fn from_gltf(path: impl AsRef<Path>) -> Self {
+        let (document, buffers, images) = ::gltf::import(path).unwrap();
+        let mut merged = MeshData::default();
+        let mut textured = None;
+        for (node, transform) in scene_nodes(&document) {
+            let Some(mesh) = node.mesh() else {
+                continue;
+            };
+            for primitive in mesh.primitives() {
+                let Some(mut data) = read_primitive(&primitive, &buffers) else {
+                    continue;
+                };
+                let material = primitive.material();
+                data.tint(material.pbr_metallic_roughness().base_color_factor());
+                // flattened meshes can't be posed any more
+                data.joints.clear();
+                data.weights.clear();
+                data.transform(&transform);
+                merged.append(data);
+                if textured.is_none()
+                    && material
+                        .pbr_metallic_roughness()
+                        .base_color_texture()
+                        .is_some()
+                {
+                    textured = Some(material);
+                }
+            }
+        }
+        eprintln!(
+            "# v# {} f# {}",
+            merged.positions.len(),
+            merged.indices.len() / 3
+        );
+        let mut model = merged.into_model();
+        if let Some(material) = textured {
+            model.set_material(to_material(&material, &to_images(&images)));
+        }
+        model
+    }
+}
+
+// nodes of the default (or first) scene with their world transforms
+fn scene_nodes(document: &Document) -> Vec<(Node<'_>, Matrix4)> {
+    fn visit<'a>(node: Node<'a>, parent: &Matrix4, out: &mut Vec<(Node<'a>, Matrix4)>) {
+        let world = parent * &to_matrix(&node.transform().matrix());
+        for child in node.children() {
+            visit(child, &world, out);
+        }
+        out.push((node, world));
+    }
+    let scene = document
+        .default_scene()
+        .or_else(|| document.scenes().next());
+    let mut nodes = Vec::new();
+    if let Some(scene) = scene {
+        for node in scene.nodes() {
+            visit(node, &Matrix4::identity(), &mut nodes);
+        }
+    }
+    nodes
+}
+
+#[derive(Default)]
+struct MeshData {
+    positions: Vec<Vector3>,
+    normals: Vec<Vector3>,
+    uvs: Vec<Vector2>,
+    // TEXCOORD_1, a copy of the first set where a primitive has none
+    uvs1: Vec<Vector2>,
+    has_uvs1: bool,
+    // linear rgba
+    colors: Vec<[f32; 4]>,
+    // empty unless the primitive is skinned
+    joints: Vec<[usize; 4]>,
+    weights: Vec<[f32; 4]>,
+    indices: Vec<usize>,
+}
+
+fn read_primitive(
+    primitive: &::gltf::Primitive,
+    buffers: &[::gltf::buffer::Data],
+) -> Option<MeshData> {
+    if primitive.mode() != Mode::Triangles {
+        return None;
+    }
+    let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+    let positions: Vec<Vector3> = reader
+        .read_positions()?
+        .map(|[x, y, z]| Vector3::new(x, y, z))
+        .collect();
+    let n = positions.len();
+    let indices: Vec<usize> = match reader.read_indices() {
+        Some(indices) => indices.into_u32().map(|i| i as usize).collect(),
+        None => (0..n).collect(),
+    };
+    let normals = match reader.read_normals() {
+        Some(normals) => normals.map(|[x, y, z]| Vector3::new(x, y, z)).collect(),
+        None => smooth_normals(&positions, &indices),
+    };
+    // gltf puts the uv origin at the top left, models use the obj convention
+    let uvs = match reader.read_tex_coords(0) {
+        Some(uvs) => uvs
+            .into_f32()
+            .map(|[u, v]| Vector2::new(u, 1.0 - v))
+            .collect(),
+        None => vec![Vector2::zero(); n],
+    };
+    let uvs1 = reader.read_tex_coords(1).map(|uvs| {
+        uvs.into_f32()
+            .map(|[u, v]| Vector2::new(u, 1.0 - v))
+            .collect()
+    });
+    let colors = match reader.read_colors(0) {
+        Some(colors) => colors.into_rgba_f32().collect(),
+        None => vec![[1.0; 4]; n],
+    };
+    let joints = match reader.read_joints(0) {
+        Some(joints) => joints.into_u16().map(|j| j.map(|x| x as usize)).collect(),
+        None => Vec::new(),
+    };
+    let weights = match reader.read_weights(0) {
+        Some(weights) => weights.into_f32().collect(),
+        None => Vec::new(),
+    };
+    Some(MeshData {
+        positions,
+        normals,
+        has_uvs1: uvs1.is_some(),
+        uvs1: uvs1.unwrap_or_else(|| uvs.clone()),
+        uvs,
+        colors,
+        joints,
+        weights,
+        indices,
+    })
+}
+
+fn smooth_normals(positions: &[Vector3], indices: &[usize]) -> Vec<Vector3> {
+    let mut normals = vec![Vector3::zero(); positions.len()];
+    for face in indices.chunks(3) {
+        let n = (&positions[face[1]] - &positions[face[0]])
+            .cross(&(&positions[face[2]] - &positions[face[0]]));
+        for &i in face {
+            normals[i] = &normals[i] + &n;
+        }
+    }
+    normals
+        .into_iter()
+        .map(|n| {
+            if n.length_square() > 0.0 {
+                n.normalize()
+            } else {
+                Vector3::new(0.0, 0.0, 1.0)
+            }
+        })
+        .collect()
+}
+
+impl MeshData {
+    fn tint(&mut self, factor: [f32; 4]) {
+        for color in self.colors.iter_mut() {
+            for i in 0..4 {
+                color[i] *= factor[i];
+            }
+        }
+    }
+    fn transform(&mut self, m: &Matrix4) {
+        for p in self.positions.iter_mut() {
+            *p = m2v(&(m * &v2m(p)));
+        }
+        let normal = normal_matrix(m);
+        for n in self.normals.iter_mut() {
+            let t = m3v(&normal, n);
+            *n = if t.length_square() > 0.0 {
+                t.normalize()
+            } else {
+                n.clone()
+            };
+        }
+        let column = |c: usize| Vector3::new(m[0][c], m[1][c], m[2][c]);
+        let mirrored = column(0).cross(&column(1)).dot(&column(2)) < 0.0;
+        // mirroring flips the winding
+        if mirrored {
+            for face in self.indices.chunks_mut(3) {
+                face.swap(1, 2);
+            }
+        }
+    }
+    fn append(&mut self, other: MeshData) {
+        let offset = self.positions.len();
+        self.positions.extend(other.positions);
+        self.normals.extend(other.normals);
+        self.uvs.extend(other.uvs);
+        self.uvs1.extend(other.uvs1);
+        self.has_uvs1 |= other.has_uvs1;
+        self.colors.extend(other.colors);
+        self.joints.extend(other.joints);
+        self.weights.extend(other.weights);
+        self.indices
+            .extend(other.indices.into_iter().map(|i| i + offset));
+    }
+    fn into_model(self) -> Model {
+        let tinted = self.colors.iter().any(|c| c.iter().any(|&x| x != 1.0));
+        // vertex colors are stored like texels, sRGB encoded
+        let colors = self
+            .colors
+            .iter()
+            .map(|c| {
+                let mut pixel = ((c[3].clamp(0.0, 1.0) * 255.0).round() as u32) << 24;
+                for (i, &x) in c[..3].iter().enumerate() {
+                    let x = x.clamp(0.0, 1.0).powf(1.0 / 2.2);
+                    pixel |= ((x * 255.0).round() as u32) << (8 * i);
+                }
+                pixel
+            })
+            .collect();
+        let mut model = Model::from_parts(
+            self.positions,
+            self.uvs,
+            self.normals,
+            self.indices.clone(),
+            self.indices.clone(),
+            self.indices,
+        );
+        if tinted {
+            model.set_colors(colors);
+        }
+        if self.has_uvs1 {
+            model.set_uv1(self.uvs1);
+        }
+        let n = model.nverts();
+        if self.joints.len() == n && self.weights.len() == n {
+            model.set_skin(self.joints, self.weights);
+        }
+        model
+    }
+}
+
+// each image once, materials using the same one share it
+fn to_images(images: &[::gltf::image::Data]) -> Vec<Arc<Image>> {
+    images.iter().map(|data| Arc::new(to_image(data))).collect()
+}
+
+fn to_material(material: &::gltf::Material, images: &[Arc<Image>]) -> Material {
+    let pbr = material.pbr_metallic_roughness();
+    let image = |index: usize| Some(images[index].clone());
+    let mut result = Material::new(material.name().unwrap_or("default"));
+    result.metallic = pbr.metallic_factor();
+    result.roughness = pbr.roughness_factor();
+    if let Some(info) = pbr.base_color_texture() {
+        result.diffuse_map = image(info.texture().source().index());
+        if let Some(MagFilter::Linear) = info.texture().sampler().mag_filter() {
+            result.filter = Filter::Bilinear;
+        }
+    }
+    if let Some(info) = pbr.metallic_roughness_texture() {
+        result.metallic_roughness_map = image(info.texture().source().index());
+    }
+    if let Some(normal) = material.normal_texture() {
+        result.normal_map = image(normal.texture().source().index());
+    }
+    result
+}
+
+fn to_image(data: &::gltf::image::Data) -> Image {
+    let channels = match data.format {
+        Format::R8 => 1,
+        Format::R8G8 => 2,
+        Format::R8G8B8 => 3,
+        Format::R8G8B8A8 => 4,
+        Format::R16 => 1,
+        Format::R16G16 => 2,
+        Format::R16G16B16 => 3,
+        Format::R16G16B16A16 => 4,
+        Format::R32G32B32FLOAT => 3,
+        Format::R32G32B32A32FLOAT => 4,
+    };
+    let bytes_per_channel = data.pixels.len() / (data.width * data.height) as usize / channels;
+    let buffer = data
+        .pixels
+        .chunks(channels * bytes_per_channel)
+        .map(|texel| {
+            // keep the most significant byte of wider channels
+            let channel = |i: usize| match bytes_per_channel {
+                1 => texel[i] as u32,
+                2 => texel[i * 2 + 1] as u32,
+                _ => {
+                    let at = i * 4;
+                    let x = f32::from_le_bytes(texel[at..at + 4].try_into().unwrap());
+                    (x.clamp(0.0, 1.0) * 255.0).round() as u32
+                }
+            };
+            let (r, g, b, a) = match channels {
+                1 => (channel(0), channel(0), channel(0), 0xff),
+                2 => (channel(0), channel(1), 0, 0xff),
+                3 => (channel(0), channel(1), channel(2), 0xff),
+                _ => (channel(0), channel(1), channel(2), channel(3)),
+            };
+            a << 24 | b << 16 | g << 8 | r
+        })
+        .collect();
+    let mut image = Image {
+        buffer,
+        width: data.width,
+        height: data.height,
+    };
+    image.vflip();
+    image
+}
@@ -0,0 +1,61 @@
+#[cfg(not(feature = "std"))]
+use crate::compat::*;
+
+// coarse depth for early rejection: the depth range of each tile of the z
+// buffer. A triangle entirely behind the farthest depth of a tile can't pass the
+// depth test anywhere in it, so its pixels there are skipped before any shading
+pub const TILE_SIZE: u32 = 8;
+
+pub struct HiZ {
+    pub tiles_x: u32,
+    pub tiles_y: u32,
+    width: u32,
+    height: u32,
+    // (min, max) of each tile
+    range: Vec<(f32, f32)>,
+    // tiles written since their range was taken, refreshed on the next query
+    dirty: Vec<bool>,
+}
+
+impl HiZ {
+    // every tile starts dirty, the z buffer may hold an earlier pass
+    pub fn new(width: u32, height: u32) -> Self {
+        let tiles_x = width.div_ceil(TILE_SIZE);
+        let tiles_y = height.div_ceil(TILE_SIZE);
+        let n = (tiles_x * tiles_y) as usize;
+        Self {
+            tiles_x,
+            tiles_y,
+            width,
+            height,
+            range: vec![(f32::MIN, f32::MIN); n],
+            dirty: vec![true; n],
+        }
+    }
+    // after clearing the z buffer to `depth`
+    pub fn reset(&mut self, depth: f32) {
+        self.range.fill((depth, depth));
+        self.dirty.fill(false);
+    }
+    #[inline]
+    pub fn mark(&mut self, x: u32, y: u32) {
+        self.dirty[(x / TILE_SIZE + y / TILE_SIZE * self.tiles_x) as usize] = true;
+    }
+    pub fn range(&mut self, tx: u32, ty: u32, z_buffer: &[f32], stride: u32) -> (f32, f32) {
+        let i = (tx + ty * self.tiles_x) as usize;
+        if self.dirty[i] {
+            let mut range = (f32::MAX, f32::MIN);
+            for y in ty * TILE_SIZE..((ty + 1) * TILE_SIZE).min(self.height) {
+                let row = (y * stride) as usize;
+                let x0 = (tx * TILE_SIZE) as usize;
+                let x1 = ((tx + 1) * TILE_SIZE).min(self.width) as usize;
+                range = z_buffer[row + x0..row + x1]
+                    .iter()
+                    .fold(range, |(lo, hi), &z| (lo.min(z), hi.max(z)));
+            }
+            self.range[i] = range;
+            self.dirty[i] = false;
+        }
+        self.range[i]
+    }
+}
@@ -0,0 +1,170 @@
+use std::{
+    collections::HashMap,
+    io, mem,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    sync::{
+        mpsc::{channel, Receiver},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::{
+    image::Image,
+    material::Material,
+    model::{LoadOptions, Model},
+    scene::Scene,
+};
+
+// saves often come as several writes, a file is reloaded once it has been
+// left alone this long
+const SETTLE: Duration = Duration::from_millis(100);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Map {
+    Diffuse,
+    Normal,
+    Specular,
+    MetallicRoughness,
+    Lightmap,
+}
+
+// what a watched file is loaded into, indices into the scene
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Asset {
+    // an obj, the model keeps its material
+    Model(usize),
+    // a map of one of `Scene::materials`
+    Map(usize, Map),
+    // a map of a model's own material, see `Model::material`
+    ModelMap(usize, Map),
+}
+
+// reloads obj and ppm files registered with `watch` when they change on disk.
+// Changes are collected in the background and swapped into the scene by
+// `apply`, called between frames
+pub struct HotReload {
+    watcher: RecommendedWatcher,
+    // with when they happened
+    events: Receiver<(Instant, notify::Result<Event>)>,
+    assets: Vec<(PathBuf, Asset)>,
+    // directories watched so far, files are watched through their directory so
+    // editors that save by replacing the file are seen too
+    dirs: Vec<PathBuf>,
+    // changed files and when they last changed
+    pending: HashMap<PathBuf, Instant>,
+}
+
+impl HotReload {
+    pub fn new() -> notify::Result<Self> {
+        let (sender, events) = channel();
+        let watcher = notify::recommended_watcher(move |event| {
+            let _ = sender.send((Instant::now(), event));
+        })?;
+        Ok(Self {
+            watcher,
+            events,
+            assets: Vec::new(),
+            dirs: Vec::new(),
+            pending: HashMap::new(),
+        })
+    }
+    // `path` has to exist already
+    pub fn watch(&mut self, path: impl AsRef<Path>, asset: Asset) -> notify::Result<()> {
+        let path = path.as_ref().canonicalize()?;
+        let dir = path.parent().unwrap().to_path_buf();
+        if !self.dirs.contains(&dir) {
+            self.watcher.watch(&dir, RecursiveMode::NonRecursive)?;
+            self.dirs.push(dir);
+        }
+        self.assets.push((path, asset));
+        Ok(())
+    }
+    // reloads what has changed and settled, returns the assets swapped in. A
+    // file that fails to load is reported and left as it was until it changes
+    // again, e.g. when it was read halfway through a save
+    pub fn apply(&mut self, scene: &mut Scene) -> Vec<Asset> {
+        let now = Instant::now();
+        while let Ok((time, event)) = self.events.try_recv() {
+            match event {
+                Ok(event) if event.kind.is_create() || event.kind.is_modify() => {
+                    for path in event.paths {
+                        self.pending.insert(path, time);
+                    }
+                }
+                Ok(_) => {}
+                Err(err) => eprintln!("hot reload: {err}"),
+            }
+        }
+        let settled: Vec<PathBuf> = self
+            .pending
+            .iter()
+            .filter(|(_, &changed)| now - changed >= SETTLE)
+            .map(|(path, _)| path.clone())
+            .collect();
+        let mut reloaded = Vec::new();
+        for path in settled {
+            self.pending.remove(&path);
+            for (_, asset) in self.assets.iter().filter(|(p, _)| *p == path) {
+                match reload(scene, &path, *asset) {
+                    Ok(()) => reloaded.push(*asset),
+                    Err(err) => eprintln!("hot reload: {}: {err}", path.display()),
+                }
+            }
+        }
+        reloaded
+    }
+}
+
+fn reload(scene: &mut Scene, path: &Path, asset: Asset) -> io::Result<()> {
+    match asset {
+        Asset::Model(id) => {
+            let mut model = try_load(|| Model::new_with_options(path, &LoadOptions::default()))?
+                .map_err(|issues| io::Error::new(io::ErrorKind::InvalidData, issues))?;
+            let old = &mut scene.models[id];
+            *model.material_mut() = mem::take(old.material_mut());
+            *old = model;
+        }
+        Asset::Map(id, map) => {
+            let image = try_load(|| load_map(map, path))?;
+            *map_field(&mut scene.materials[id], map) = Some(image);
+        }
+        Asset::ModelMap(id, map) => {
+            let image = try_load(|| load_map(map, path))?;
+            *map_field(scene.models[id].material_mut(), map) = Some(image);
+        }
+    }
+    Ok(())
+}
+
+// the loaders panic on malformed files
+fn try_load<T>(load: impl FnOnce() -> T) -> io::Result<T> {
+    panic::catch_unwind(AssertUnwindSafe(load))
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "failed to load"))
+}
+
+// through the material's loader, so it ends up stored the same way
+fn load_map(map: Map, path: &Path) -> Arc<Image> {
+    let mut material = Material::default();
+    match map {
+        Map::Diffuse => material.load_diffuse_map(path),
+        Map::Normal => material.load_normal_map(path),
+        Map::Specular => material.load_specular_map(path),
+        Map::MetallicRoughness => material.load_metallic_roughness_map(path),
+        Map::Lightmap => material.load_lightmap(path),
+    }
+    map_field(&mut material, map).take().unwrap()
+}
+
+fn map_field(material: &mut Material, map: Map) -> &mut Option<Arc<Image>> {
+    match map {
+        Map::Diffuse => &mut material.diffuse_map,
+        Map::Normal => &mut material.normal_map,
+        Map::Specular => &mut material.specular_map,
+        Map::MetallicRoughness => &mut material.metallic_roughness_map,
+        Map::Lightmap => &mut material.lightmap,
+    }
+}
@@ -0,0 +1,224 @@
+use crate::geometry::Vector3;
+#[cfg(feature = "model")]
+use crate::{geometry::Quaternion, scene::Scene};
+
+// analytic two-bone solver (hip/knee/foot, shoulder/elbow/hand)
+// returns the new (mid, end) positions, the root stays in place and the joint
+// bends towards `pole`
+pub fn solve_two_bone(
+    root: &Vector3,
+    mid: &Vector3,
+    end: &Vector3,
+    target: &Vector3,
+    pole: &Vector3,
+) -> (Vector3, Vector3) {
+    let a = (mid - root).length();
+    let b = (end - mid).length();
+    let to_target = target - root;
+    let distance = to_target.length();
+    if distance < f32::EPSILON {
+        return (mid.clone(), end.clone());
+    }
+    let dir = to_target / distance;
+    // a bone of no length can't bend, the other one points at the target
+    if a.min(b) < f32::EPSILON {
+        return (root + &dir * a, root + &dir * (a + b));
+    }
+    // keep the triangle valid: a fully stretched or folded limb has no bend plane
+    let d = distance.clamp((a - b).abs() + 1e-4, a + b - 1e-4);
+
+    let bend = bend_direction(root, &dir, pole).or_else(|| bend_direction(root, &dir, mid));
+    let bend = match bend {
+        Some(bend) => bend,
+        None => return (root + &dir * a, root + &dir * (a + b)),
+    };
+
+    // law of cosines for the angle at the root
+    let cos_root = ((a * a + d * d - b * b) / (2.0 * a * d)).clamp(-1.0, 1.0);
+    let sin_root = (1.0 - cos_root * cos_root).sqrt();
+    let new_mid = root + &dir * (a * cos_root) + &bend * (a * sin_root);
+    let new_end = root + &dir * d;
+    (new_mid, new_end)
+}
+
+fn bend_direction(root: &Vector3, dir: &Vector3, hint: &Vector3) -> Option<Vector3> {
+    let to_hint = hint - root;
+    let perpendicular = &to_hint - dir * to_hint.dot(dir);
+    let length = perpendicular.length();
+    if length < 1e-6 {
+        None
+    } else {
+        Some(perpendicular / length)
+    }
+}
+
+// the unit vector from `from` to `to`, `fallback` where they meet
+fn direction(from: &Vector3, to: &Vector3, fallback: &Vector3) -> Vector3 {
+    let v = to - from;
+    let length = v.length();
+    if length < 1e-6 {
+        fallback.clone()
+    } else {
+        v / length
+    }
+}
+
+// a chain of joint positions connected by rigid bones, joints[0] is the root
+pub struct IkChain {
+    pub joints: Vec<Vector3>,
+    lengths: Vec<f32>,
+}
+
+impl IkChain {
+    pub fn new(joints: Vec<Vector3>) -> Self {
+        assert!(joints.len() >= 2, "an ik chain needs at least one bone");
+        let lengths = joints
+            .windows(2)
+            .map(|w| (&w[1] - &w[0]).length())
+            .collect();
+        Self { joints, lengths }
+    }
+    pub fn total_length(&self) -> f32 {
+        self.lengths.iter().sum()
+    }
+    pub fn end(&self) -> &Vector3 {
+        self.joints.last().unwrap()
+    }
+    // FABRIK: alternate backward (end -> root) and forward (root -> end) passes
+    // until the end effector is within `tolerance` of the target.
+    // returns whether the target was reached
+    pub fn solve_fabrik(
+        &mut self,
+        target: &Vector3,
+        tolerance: f32,
+        max_iterations: usize,
+    ) -> bool {
+        let root = self.joints[0].clone();
+        let n = self.joints.len();
+        // where each bone points now, kept for joints the passes put on top of
+        // each other
+        let up = Vector3::new(0.0, 1.0, 0.0);
+        let rest: Vec<Vector3> = self
+            .joints
+            .windows(2)
+            .map(|w| direction(&w[0], &w[1], &up))
+            .collect();
+
+        let distance = (target - &root).length();
+        if self.total_length() < f32::EPSILON {
+            return distance <= tolerance;
+        }
+        if distance >= self.total_length() {
+            // unreachable: stretch the chain straight towards the target
+            let dir = direction(&root, target, &rest[0]);
+            for i in 1..n {
+                self.joints[i] = &self.joints[i - 1] + &dir * self.lengths[i - 1];
+            }
+            return false;
+        }
+
+        for _ in 0..max_iterations {
+            if (self.end() - target).length() <= tolerance {
+                return true;
+            }
+            self.joints[n - 1] = target.clone();
+            for i in (0..n - 1).rev() {
+                let dir = direction(&self.joints[i + 1], &self.joints[i], &-&rest[i]);
+                self.joints[i] = &self.joints[i + 1] + dir * self.lengths[i];
+            }
+            self.joints[0] = root.clone();
+            for i in 1..n {
+                let dir = direction(&self.joints[i - 1], &self.joints[i], &rest[i - 1]);
+                self.joints[i] = &self.joints[i - 1] + dir * self.lengths[i - 1];
+            }
+        }
+        (self.end() - target).length() <= tolerance
+    }
+    // the joints of a skeleton at their world positions, `nodes` from the root
+    // down with each an ancestor of the next, e.g. shoulder, elbow and hand
+    #[cfg(feature = "model")]
+    pub fn from_nodes(scene: &Scene, nodes: &[usize]) -> Self {
+        Self::new(nodes.iter().map(|&id| world_position(scene, id)).collect())
+    }
+    // rotates the skeleton's `nodes`, as given to `from_nodes`, to where the
+    // chain has its joints
+    #[cfg(feature = "model")]
+    pub fn pose_nodes(&self, scene: &mut Scene, nodes: &[usize]) {
+        pose_nodes(scene, nodes, &self.joints);
+    }
+}
+
+// `solve_two_bone` on three joints of a skeleton, e.g. hip, knee and foot, and
+// the nodes rotated to the result. Bones keep the lengths they have in the scene
+#[cfg(feature = "model")]
+pub fn solve_two_bone_nodes(
+    scene: &mut Scene,
+    nodes: [usize; 3],
+    target: &Vector3,
+    pole: &Vector3,
+) {
+    let [root, mid, end] = nodes.map(|id| world_position(scene, id));
+    let (mid, end) = solve_two_bone(&root, &mid, &end, target, pole);
+    pose_nodes(scene, &nodes, &[root, mid, end]);
+}
+
+// each of `nodes` but the last rotated so the bone to the next one points the
+// way it does between `joints`. Only the rotations change, so the bones keep
+// their lengths and a root out of place stays where it is
+#[cfg(feature = "model")]
+pub fn pose_nodes(scene: &mut Scene, nodes: &[usize], joints: &[Vector3]) {
+    assert_eq!(nodes.len(), joints.len());
+    for i in 0..nodes.len().saturating_sub(1) {
+        let from = world_position(scene, nodes[i + 1]) - world_position(scene, nodes[i]);
+        let to = &joints[i + 1] - &joints[i];
+        if from.length() < 1e-6 || to.length() < 1e-6 {
+            continue;
+        }
+        // the turn in world space, taken into the space of the node's parent
+        let turn = rotation_between(&from.normalize(), &to.normalize());
+        let parent = match scene.nodes[nodes[i]].parent {
+            Some(parent) => world_rotation(scene, parent),
+            None => Quaternion::identity(),
+        };
+        let transform = &mut scene.nodes[nodes[i]].transform;
+        transform.rotation = (parent.conjugate() * turn * parent * transform.rotation).normalize();
+    }
+}
+
+#[cfg(feature = "model")]
+fn world_position(scene: &Scene, id: usize) -> Vector3 {
+    let m = scene.world_transform(id);
+    Vector3::new(m[0][3], m[1][3], m[2][3])
+}
+
+// the rotations of the node and its ancestors, exact with uniform scales
+#[cfg(feature = "model")]
+fn world_rotation(scene: &Scene, id: usize) -> Quaternion {
+    let mut rotation = scene.nodes[id].transform.rotation;
+    let mut parent = scene.nodes[id].parent;
+    while let Some(p) = parent {
+        rotation = scene.nodes[p].transform.rotation * rotation;
+        parent = scene.nodes[p].parent;
+    }
+    rotation
+}
+
+// the shortest rotation taking unit vector `from` to unit vector `to`
+#[cfg(feature = "model")]
+fn rotation_between(from: &Vector3, to: &Vector3) -> Quaternion {
+    let cos = from.dot(to).clamp(-1.0, 1.0);
+    let axis = from.cross(to);
+    if axis.length() > 1e-6 {
+        return Quaternion::from_axis_angle(&axis, cos.acos());
+    }
+    if cos > 0.0 {
+        return Quaternion::identity();
+    }
+    // half a turn about any axis across `from`
+    let across = if from.x().abs() < 0.9 {
+        Vector3::new(1.0, 0.0, 0.0)
+    } else {
+        Vector3::new(0.0, 1.0, 0.0)
+    };
+    Quaternion::from_axis_angle(&from.cross(&across), core::f32::consts::PI)
+}
@@ -0,0 +1,21 @@
+#[cfg(not(feature = "std"))]
+use crate::compat::*;
+
+// colors 0xAABBGGRR, row by row
+pub struct Image {
+    pub buffer: Vec<u32>,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Image {
+    pub fn vflip(&mut self) {
+        for y in 0..self.height / 2 {
+            for x in 0..self.width {
+                let a = x + y * self.width;
+                let b = x + (self.height - 1 - y) * self.width;
+                self.buffer.swap(a as usize, b as usize);
+            }
+        }
+    }
+}
@@ -0,0 +1,91 @@
+#[cfg(not(feature = "std"))]
+use crate::compat::*;
+
+use crate::renderer::Renderer;
+
+// which half of the pixels a frame draws
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Interlace {
+    // every other row, like an interlaced tv signal
+    Scanlines,
+    // the black or the white squares of a checkerboard, every missing pixel
+    // has four drawn ones around it instead of two
+    Checkerboard,
+}
+
+// the pixels of `pattern` a frame draws, `parity` 0 or 1 picks the half
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Field {
+    pub pattern: Interlace,
+    pub parity: u32,
+}
+
+impl Field {
+    pub fn new(pattern: Interlace, parity: u32) -> Self {
+        Self { pattern, parity }
+    }
+    #[inline]
+    pub fn covers(&self, x: u32, y: u32) -> bool {
+        match self.pattern {
+            Interlace::Scanlines => (y + self.parity) % 2 == 0,
+            Interlace::Checkerboard => (x + y + self.parity) % 2 == 0,
+        }
+    }
+    // the other half
+    pub fn next(&self) -> Self {
+        Self::new(self.pattern, 1 - self.parity)
+    }
+}
+
+// half the pixels drawn per frame, the halves taking turns, with the other half
+// made up from the frame before. Per frame: set the renderer's `field` from
+// `field`, draw and `reconstruct`. The first frame after a reset is drawn whole
+pub struct Interlacer {
+    pub width: u32,
+    pub height: u32,
+    pub pattern: Interlace,
+    // the last frame as shown
+    history: Vec<u32>,
+    frames: u32,
+}
+
+impl Interlacer {
+    pub fn new(width: u32, height: u32, pattern: Interlace) -> Self {
+        Self {
+            width,
+            height,
+            pattern,
+            history: vec![0; (width * height) as usize],
+            frames: 0,
+        }
+    }
+    // start over, e.g. on a cut where the frame before has nothing to give
+    pub fn reset(&mut self) {
+        self.frames = 0;
+    }
+    // for a target that changed size, resets when it did
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if (width, height) != (self.width, self.height) {
+            *self = Self::new(width, height, self.pattern);
+        }
+    }
+    // None to draw every pixel
+    pub fn field(&self) -> Option<Field> {
+        (self.frames > 0).then(|| Field::new(self.pattern, self.frames % 2))
+    }
+    // fills what the frame didn't draw, see `Renderer::reconstruct`, and keeps
+    // the result for the next one. The renderer's `field` is None after
+    pub fn reconstruct(&mut self, renderer: &mut Renderer) {
+        assert_eq!((renderer.width, renderer.height), (self.width, self.height));
+        renderer.field = None;
+        if let Some(field) = self.field() {
+            renderer.reconstruct(field, &self.history);
+        }
+        for y in 0..self.height {
+            for x in 0..self.width {
+                self.history[(x + y * self.width) as usize] = renderer.pixel(x, y);
+            }
+        }
+        self.frames += 1;
+    }
+}
@@ -0,0 +1,153 @@
+use std::{
+    fs::{File, OpenOptions},
+    io,
+    os::fd::{AsFd, BorrowedFd},
+    path::Path,
+};
+
+use drm::{
+    buffer::{Buffer, DrmFourcc},
+    control::{
+        connector, crtc, dumbbuffer::DumbBuffer, framebuffer, Device as ControlDevice, Event, Mode,
+        PageFlipFlags,
+    },
+    Device,
+};
+
+struct Card(File);
+
+impl AsFd for Card {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.0.as_fd()
+    }
+}
+
+impl Device for Card {}
+impl ControlDevice for Card {}
+
+// direct scanout through DRM/KMS (e.g. /dev/dri/card0), for kiosks and boards
+// without a display server. The first connected output is set to its preferred
+// mode and frames are page flipped between two dumb buffers on vblank. The
+// output is given back as it was on drop
+pub struct Kms {
+    pub width: u32,
+    pub height: u32,
+    card: Card,
+    connector: connector::Handle,
+    crtc: crtc::Handle,
+    // what the crtc showed before, e.g. the console
+    previous: crtc::Info,
+    buffers: Vec<(DumbBuffer, framebuffer::Handle)>,
+    // the buffer on screen, the other one is drawn into
+    front: usize,
+    flip_pending: bool,
+}
+
+impl Kms {
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        let card = Card(file);
+        let resources = card.resource_handles()?;
+        let connector = resources
+            .connectors()
+            .iter()
+            .filter_map(|&handle| card.get_connector(handle, true).ok())
+            .find(|info| info.state() == connector::State::Connected)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no connected output"))?;
+        let mode: Mode = *connector
+            .modes()
+            .first()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "output has no modes"))?;
+        // the crtc already driving the output, or the first one
+        let crtc = connector
+            .current_encoder()
+            .and_then(|encoder| card.get_encoder(encoder).ok()?.crtc())
+            .or_else(|| resources.crtcs().first().copied())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no crtc"))?;
+        let previous = card.get_crtc(crtc)?;
+        let (width, height) = mode.size();
+        let (width, height) = (width as u32, height as u32);
+        let mut buffers = Vec::new();
+        for _ in 0..2 {
+            let mut buffer = card.create_dumb_buffer((width, height), DrmFourcc::Xrgb8888, 32)?;
+            card.map_dumb_buffer(&mut buffer)?.as_mut().fill(0);
+            let fb = card.add_framebuffer(&buffer, 24, 32)?;
+            buffers.push((buffer, fb));
+        }
+        card.set_crtc(
+            crtc,
+            Some(buffers[0].1),
+            (0, 0),
+            &[connector.handle()],
+            Some(mode),
+        )?;
+        Ok(Self {
+            width,
+            height,
+            card,
+            connector: connector.handle(),
+            crtc,
+            previous,
+            buffers,
+            front: 0,
+            flip_pending: false,
+        })
+    }
+    // blocks until the last flip has happened, so its buffer is free again
+    fn wait_for_flip(&mut self) -> io::Result<()> {
+        while self.flip_pending {
+            for event in self.card.receive_events()? {
+                if matches!(event, Event::PageFlip(_)) {
+                    self.flip_pending = false;
+                }
+            }
+        }
+        Ok(())
+    }
+    // draws the top left of `buffer` that fits into the back buffer and flips it
+    // on the next vblank. At most one flip is in flight, so this paces the
+    // caller to the refresh rate
+    pub fn present(&mut self, buffer: &[u32], width: u32, height: u32) -> io::Result<()> {
+        assert_eq!((width * height) as usize, buffer.len());
+        self.wait_for_flip()?;
+        let back = 1 - self.front;
+        let (dumb, fb) = &mut self.buffers[back];
+        let pitch = dumb.pitch() as usize;
+        let mut map = self.card.map_dumb_buffer(dumb)?;
+        let screen = map.as_mut();
+        for y in 0..height.min(self.height) {
+            let row = y as usize * pitch;
+            for x in 0..width.min(self.width) {
+                // 0xAABBGGRR to xrgb, little endian in memory
+                let pixel = buffer[(x + y * width) as usize];
+                let xrgb = (pixel & 0xff) << 16 | (pixel & 0xff00) | (pixel >> 16) & 0xff;
+                let at = row + x as usize * 4;
+                screen[at..at + 4].copy_from_slice(&xrgb.to_le_bytes());
+            }
+        }
+        let fb = *fb;
+        drop(map);
+        self.card
+            .page_flip(self.crtc, fb, PageFlipFlags::EVENT, None)?;
+        self.flip_pending = true;
+        self.front = back;
+        Ok(())
+    }
+}
+
+impl Drop for Kms {
+    fn drop(&mut self) {
+        let _ = self.wait_for_flip();
+        let _ = self.card.set_crtc(
+            self.crtc,
+            self.previous.framebuffer(),
+            self.previous.position(),
+            &[self.connector],
+            self.previous.mode(),
+        );
+        for (dumb, fb) in self.buffers.drain(..) {
+            let _ = self.card.destroy_framebuffer(fb);
+            let _ = self.card.destroy_dumb_buffer(dumb);
+        }
+    }
+}
@@ -1,4 +1,100 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
 pub mod renderer;
 pub mod geometry;
+pub mod image;
+pub mod sampler;
+pub mod lut;
+pub mod resolution;
+#[cfg(feature = "model")]
 pub mod model;
+#[cfg(feature = "ppm")]
 pub mod ppm;
+#[cfg(all(feature = "std", feature = "model"))]
+pub mod shaders;
+#[cfg(feature = "std")]
+pub mod ik;
+#[cfg(all(feature = "std", feature = "model"))]
+pub mod cloth;
+#[cfg(all(feature = "std", feature = "model"))]
+pub mod shadow;
+#[cfg(feature = "std")]
+pub mod text;
+#[cfg(all(feature = "std", feature = "model"))]
+pub mod primitives;
+#[cfg(all(feature = "std", feature = "model"))]
+pub mod stl;
+#[cfg(feature = "std")]
+pub mod path;
+#[cfg(all(feature = "std", feature = "model"))]
+pub mod ply;
+#[cfg(feature = "std")]
+pub mod plot;
+#[cfg(all(feature = "std", feature = "model"))]
+pub mod probe;
+#[cfg(all(feature = "std", feature = "model"))]
+pub mod bake;
+#[cfg(all(feature = "std", feature = "model"))]
+pub mod scene;
+#[cfg(all(feature = "std", feature = "model"))]
+pub mod command;
+#[cfg(all(feature = "std", feature = "model"))]
+pub mod occlusion;
+#[cfg(all(feature = "std", feature = "model"))]
+pub mod stereo;
+#[cfg(all(feature = "std", feature = "model"))]
+pub mod panorama;
+#[cfg(all(feature = "std", feature = "model"))]
+pub mod reference;
+pub mod material;
+#[cfg(all(feature = "std", feature = "model"))]
+pub mod animation;
+#[cfg(all(feature = "std", feature = "model"))]
+pub mod skin;
+pub mod buffer;
+pub mod hiz;
+pub mod oit;
+pub mod accumulate;
+pub mod interlace;
+pub mod noise;
+#[cfg(all(feature = "std", feature = "model"))]
+pub mod raytrace;
+#[cfg(all(feature = "std", feature = "model"))]
+pub mod bvh;
+#[cfg(all(feature = "std", feature = "model"))]
+pub mod terrain;
+#[cfg(all(feature = "std", feature = "model"))]
+pub mod simplify;
+#[cfg(all(feature = "std", feature = "model"))]
+pub mod subdivide;
+#[cfg(all(feature = "model", feature = "ppm"))]
+pub mod turntable;
+#[cfg(feature = "std")]
+pub mod timing;
+#[cfg(all(feature = "std", feature = "model"))]
+pub mod assets;
+#[cfg(feature = "ppm")]
+pub mod capture;
+#[cfg(feature = "std")]
+pub mod replay;
+#[cfg(all(feature = "model", feature = "ppm"))]
+pub mod bench;
+mod compat;
+#[cfg(feature = "std")]
+mod pool;
+#[cfg(feature = "fixed")]
+pub mod fixed;
+#[cfg(feature = "embedded")]
+pub mod embedded;
+#[cfg(all(feature = "fbdev", target_os = "linux"))]
+pub mod fbdev;
+#[cfg(all(feature = "kms", target_os = "linux"))]
+pub mod kms;
+#[cfg(feature = "hot-reload")]
+pub mod hot_reload;
+#[cfg(feature = "egui")]
+pub mod egui;
+#[cfg(feature = "gltf")]
+pub mod gltf;
@@ -0,0 +1,150 @@
+#[cfg(feature = "std")]
+use std::{fs, path::Path};
+
+#[cfg(not(feature = "std"))]
+use crate::compat::*;
+use crate::image::Image;
+#[cfg(feature = "ppm")]
+use crate::ppm::load_ppm_file_to_buffer;
+
+// a 3D color lookup table for grading the final image, see
+// `Renderer::color_grade`. Made in an image editor or grading tool by editing
+// an identity table, saved as a HALD CLUT image or an Adobe/Resolve .cube file
+#[derive(Clone, Debug)]
+pub struct Lut {
+    // entries along each axis
+    pub size: u32,
+    // rgb in 0..1, red changing fastest then green then blue
+    pub table: Vec<[f32; 3]>,
+    // the input colors mapped onto the corners of the table
+    pub domain_min: [f32; 3],
+    pub domain_max: [f32; 3],
+}
+
+impl Lut {
+    // maps every color to itself
+    pub fn identity(size: u32) -> Self {
+        assert!(size >= 2, "a lut needs at least 2 entries per axis");
+        let step = 1.0 / (size - 1) as f32;
+        let mut table = Vec::with_capacity((size * size * size) as usize);
+        for b in 0..size {
+            for g in 0..size {
+                for r in 0..size {
+                    table.push([r as f32 * step, g as f32 * step, b as f32 * step]);
+                }
+            }
+        }
+        Self {
+            size,
+            table,
+            domain_min: [0.0; 3],
+            domain_max: [1.0; 3],
+        }
+    }
+    // a HALD CLUT of level `l` is an l^3 x l^3 image holding an l^2 sized table
+    // row by row, e.g. 512x512 for level 8
+    pub fn from_hald(image: &Image) -> Self {
+        let level = (1..=16)
+            .find(|l| l * l * l == image.width)
+            .filter(|_| image.width == image.height)
+            .unwrap_or_else(|| {
+                panic!(
+                    "{}x{} isn't the size of a hald clut",
+                    image.width, image.height
+                )
+            });
+        let size = level * level;
+        let table = image
+            .buffer
+            .iter()
+            .map(|pixel| [0, 1, 2].map(|i| ((pixel >> (8 * i)) & 0xff) as f32 / 255.0))
+            .collect();
+        Self {
+            size,
+            table,
+            domain_min: [0.0; 3],
+            domain_max: [1.0; 3],
+        }
+    }
+    // the text of a .cube file. Only 3D tables, TITLE is ignored
+    pub fn from_cube(text: &str) -> Self {
+        let mut size = 0;
+        let mut table = Vec::new();
+        let mut domain_min = [0.0; 3];
+        let mut domain_max = [1.0; 3];
+        let floats = |parts: &[&str], line: &str| -> [f32; 3] {
+            let parsed: Vec<f32> = parts.iter().filter_map(|p| p.parse().ok()).collect();
+            match parsed[..] {
+                [r, g, b] => [r, g, b],
+                _ => panic!("expected three numbers: {line}"),
+            }
+        };
+        for line in text.lines() {
+            let line = line.trim();
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            match parts.as_slice() {
+                [] => {}
+                [first, ..] if first.starts_with('#') => {}
+                ["TITLE", ..] => {}
+                ["LUT_3D_SIZE", n] => size = n.parse().unwrap(),
+                ["LUT_1D_SIZE", ..] => panic!("1D luts are not supported"),
+                ["DOMAIN_MIN", rest @ ..] => domain_min = floats(rest, line),
+                ["DOMAIN_MAX", rest @ ..] => domain_max = floats(rest, line),
+                _ => table.push(floats(&parts, line)),
+            }
+        }
+        assert!(size >= 2, "missing LUT_3D_SIZE");
+        assert_eq!(
+            table.len(),
+            (size * size * size) as usize,
+            "a {size}^3 lut with {} entries",
+            table.len()
+        );
+        Self {
+            size,
+            table,
+            domain_min,
+            domain_max,
+        }
+    }
+    #[cfg(feature = "ppm")]
+    pub fn load_hald(path: impl AsRef<Path>) -> Self {
+        Self::from_hald(&load_ppm_file_to_buffer(path))
+    }
+    #[cfg(feature = "std")]
+    pub fn load_cube(path: impl AsRef<Path>) -> Self {
+        Self::from_cube(&fs::read_to_string(path).unwrap())
+    }
+    // trilinear between the eight entries around `rgb`, clamped to the domain
+    pub fn sample(&self, rgb: [f32; 3]) -> [f32; 3] {
+        let last = (self.size - 1) as f32;
+        let mut lo = [0; 3];
+        let mut hi = [0; 3];
+        let mut t = [0.0; 3];
+        for i in 0..3 {
+            let range = self.domain_max[i] - self.domain_min[i];
+            let x = ((rgb[i] - self.domain_min[i]) / range).clamp(0.0, 1.0) * last;
+            let floor = x.floor().min(last - 1.0);
+            lo[i] = floor as u32;
+            hi[i] = lo[i] + 1;
+            t[i] = x - floor;
+        }
+        let at =
+            |r: u32, g: u32, b: u32| &self.table[(r + (g + b * self.size) * self.size) as usize];
+        let lerp = |a: [f32; 3], b: [f32; 3], t: f32| [0, 1, 2].map(|i| a[i] + (b[i] - a[i]) * t);
+        let along_r = |g, b| lerp(*at(lo[0], g, b), *at(hi[0], g, b), t[0]);
+        let along_g = |b| lerp(along_r(lo[1], b), along_r(hi[1], b), t[1]);
+        lerp(along_g(lo[2]), along_g(hi[2]), t[2])
+    }
+    // the graded color of an sRGB pixel, alpha kept
+    pub fn apply(&self, pixel: u32) -> u32 {
+        let rgb = [0, 1, 2].map(|i| ((pixel >> (8 * i)) & 0xff) as f32 / 255.0);
+        let graded = self.sample(rgb);
+        let mut new_pixel = pixel & 0xff000000;
+        for (i, value) in graded.iter().enumerate() {
+            let part = (value * 255.0).round().clamp(0.0, 255.0) as u32;
+            new_pixel |= part << (8 * i);
+        }
+        new_pixel
+    }
+}
@@ -1,9 +1,14 @@
 use std::ops::Neg;
 
 use olive3d::{
+    bench::{self, Bench},
     geometry::{m2v, v2m, Matrix, Matrix4, Vector3},
     model::Model,
+    panorama::Panorama,
+    ppm, reference,
     renderer::{self, lookat, viewport, Renderer, Shader},
+    scene::{Camera, Light, Scene},
+    turntable::{Motion, Turntable},
 };
 
 const WIDTH: u32 = 800;
@@ -46,7 +51,113 @@ impl Shader for GouraudShader {
     }
 }
 
+// olive3d turntable <model.obj> <frames> <out_dir | -> [orbit | spin]
+// a turn around the model as numbered ppm files, or a ppm stream on stdout for "-"
+fn turntable(args: &[String]) {
+    let [path, frames, out, rest @ ..] = args else {
+        eprintln!("usage: olive3d turntable <model.obj> <frames> <out_dir | -> [orbit | spin]");
+        std::process::exit(1);
+    };
+    let mut scene = Scene::new();
+    let model = scene.add_model(Model::new(path));
+    let node = scene.add_node("model", None);
+    scene.nodes[node].model = Some(model);
+    let light = scene.add_node("light", None);
+    scene.nodes[light].light = Some(Light::new(Vector3::new(-1.0, -1.0, -1.0), 1.0));
+    let camera = Camera::new(Vector3::new(0.0, 0.0, 3.0), Vector3::new(0.0, 0.0, 0.0));
+    let motion = match rest.first().map(String::as_str) {
+        Some("spin") => Motion::RotateNode(node),
+        _ => Motion::OrbitCamera,
+    };
+    let turntable = Turntable::new(frames.parse().unwrap(), WIDTH, HEIGHT, motion);
+    let progress = |done, total| eprint!("\rframe {done}/{total}");
+    if out == "-" {
+        turntable
+            .export_stream(&mut scene, &camera, std::io::stdout().lock(), progress)
+            .unwrap();
+    } else {
+        turntable
+            .export_frames(&mut scene, &camera, out, progress)
+            .unwrap();
+    }
+    eprintln!();
+}
+
+// olive3d panorama <model.obj> <width> <out.ppm>
+// a 360 degree equirectangular view from the default camera, width x width / 2
+fn panorama(args: &[String]) {
+    let [path, width, out] = args else {
+        eprintln!("usage: olive3d panorama <model.obj> <width> <out.ppm>");
+        std::process::exit(1);
+    };
+    let mut scene = Scene::new();
+    let model = scene.add_model(Model::new(path));
+    let node = scene.add_node("model", None);
+    scene.nodes[node].model = Some(model);
+    let light = scene.add_node("light", None);
+    scene.nodes[light].light = Some(Light::new(Vector3::new(-1.0, -1.0, -1.0), 1.0));
+    let camera = Camera::new(Vector3::new(0.0, 0.0, 3.0), Vector3::new(0.0, 0.0, 0.0));
+    let width: u32 = width.parse().unwrap();
+    let image = Panorama::new((width / 4).max(1), 0xff000000).render(&scene, &camera, width);
+    ppm::save_buffer_to_ppm_file(&image.buffer, image.width, image.height, image.width, out)
+        .unwrap();
+}
+
+// olive3d reference <width> <height> <out.ppm>
+// the built in scene CI and the snapshot test compare, see `render_reference_scene`
+fn reference(args: &[String]) {
+    let [width, height, out] = args else {
+        eprintln!("usage: olive3d reference <width> <height> <out.ppm>");
+        std::process::exit(1);
+    };
+    let image = reference::render_reference_scene(width.parse().unwrap(), height.parse().unwrap());
+    ppm::save_buffer_to_ppm_file(&image.buffer, image.width, image.height, image.width, out)
+        .unwrap();
+}
+
+// olive3d bench [--baseline <csv>] [--save <csv>] [--threads <n>]
+// times the standard workloads, against the medians of an earlier --save
+fn bench(args: &[String]) {
+    let mut baseline = None;
+    let mut save = None;
+    let mut bench = Bench::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match (arg.as_str(), args.next()) {
+            ("--baseline", Some(path)) => baseline = Some(bench::load_baseline(path).unwrap()),
+            ("--save", Some(path)) => save = Some(path),
+            ("--threads", Some(n)) => bench.threads = n.parse().unwrap(),
+            _ => {
+                eprintln!("usage: olive3d bench [--baseline <csv>] [--save <csv>] [--threads <n>]");
+                std::process::exit(1);
+            }
+        }
+    }
+    let workloads = bench::standard_workloads("./obj");
+    let mut measurements = Vec::new();
+    for workload in workloads.iter() {
+        let measurement = workload.measure(&bench, WIDTH, HEIGHT);
+        print!("{measurement}");
+        match baseline.as_ref().and_then(|b| b.get(&measurement.name)) {
+            Some(&median) => println!("  {:+.1}%", measurement.change(median) * 100.0),
+            None => println!(),
+        }
+        measurements.push(measurement);
+    }
+    if let Some(path) = save {
+        bench::save_baseline(path, &measurements).unwrap();
+    }
+}
+
 fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    match args.get(1).map(String::as_str) {
+        Some("turntable") => return turntable(&args[2..]),
+        Some("bench") => return bench(&args[2..]),
+        Some("panorama") => return panorama(&args[2..]),
+        Some("reference") => return reference(&args[2..]),
+        _ => {}
+    }
     let mut model = Model::new("./obj/african_head.obj");
     model.load_diffuse_map("./obj/african_head_diffuse.ppm");
     model.load_normal_map("./obj/african_head_nm.ppm");
@@ -88,10 +199,7 @@ fn main() {
 
     renderer.fill(0xff000000);
     for i in 0..nfaces {
-        let mut screen_coords = Vec::with_capacity(3);
-        for j in 0..3 {
-            screen_coords.push(shader.vertex(i, j));
-        }
+        let screen_coords = [0, 1, 2].map(|j| shader.vertex(i, j));
         renderer.fill_triangle(&screen_coords, &mut shader);
     }
     renderer.save_to_ppm_file("output/black.ppm").unwrap();
@@ -0,0 +1,166 @@
+#[cfg(feature = "ppm")]
+use std::path::Path;
+
+#[cfg(not(feature = "std"))]
+use crate::compat::*;
+#[cfg(feature = "ppm")]
+use crate::ppm::load_ppm_file_to_buffer;
+use crate::{
+    compat::Arc,
+    geometry::{Vector2, Vector3},
+    image::Image,
+    sampler::{Filter, Sampler},
+};
+
+// how a surface looks, independent of the mesh it is drawn on. Maps are stored
+// bottom row first so v = 0 is the bottom of the image like in obj files, and
+// shared between the materials using the same image (see `Assets`)
+pub struct Material {
+    pub name: String,
+    // multiplies the diffuse map, 0xAABBGGRR in sRGB like the texels
+    pub color: u32,
+    // multiply the metallic-roughness map
+    pub metallic: f32,
+    pub roughness: f32,
+    pub diffuse_map: Option<Arc<Image>>,
+    pub normal_map: Option<Arc<Image>>,
+    pub specular_map: Option<Arc<Image>>,
+    pub metallic_roughness_map: Option<Arc<Image>>,
+    // baked light or ambient occlusion multiplying the lit color, looked up with
+    // the model's second uv set (see `Model::uv1`)
+    pub lightmap: Option<Arc<Image>>,
+    // how the diffuse map is sampled, the other maps take the nearest texel
+    pub filter: Filter,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self::new("default")
+    }
+}
+
+macro_rules! load_map {
+    ($func_name:ident, $map_field:ident) => {
+        #[cfg(feature = "ppm")]
+        pub fn $func_name(&mut self, path: impl AsRef<Path>) {
+            let mut img = load_ppm_file_to_buffer(path);
+            img.vflip();
+            self.$map_field = Some(Arc::new(img));
+        }
+    };
+}
+
+fn texel(map: &Image, uv: &Vector2) -> u32 {
+    Sampler::new(map, Filter::Nearest).sample(uv)
+}
+
+impl Material {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            color: 0xffffffff,
+            metallic: 1.0,
+            roughness: 1.0,
+            diffuse_map: None,
+            normal_map: None,
+            specular_map: None,
+            metallic_roughness_map: None,
+            lightmap: None,
+            filter: Filter::Nearest,
+        }
+    }
+    load_map!(load_diffuse_map, diffuse_map);
+    load_map!(load_normal_map, normal_map);
+    load_map!(load_specular_map, specular_map);
+    load_map!(load_metallic_roughness_map, metallic_roughness_map);
+    load_map!(load_lightmap, lightmap);
+    pub fn has_maps(&self) -> bool {
+        self.diffuse_map.is_some()
+            || self.normal_map.is_some()
+            || self.specular_map.is_some()
+            || self.metallic_roughness_map.is_some()
+            || self.lightmap.is_some()
+    }
+    pub fn has_normal_map(&self) -> bool {
+        self.normal_map.is_some()
+    }
+    pub fn normal_uv(&self, uv: &Vector2) -> Vector3 {
+        if let Some(ref normal_map) = self.normal_map {
+            let pixel = texel(normal_map, uv);
+            if pixel == 0xff000000 {
+                Vector3::zero()
+            } else {
+                let r = (pixel & 0xff) as f32;
+                let g = ((pixel >> 8) & 0xff) as f32;
+                let b = ((pixel >> 16) & 0xff) as f32;
+                Vector3::new(r, g, b) * 2.0 / 255.0 - Vector3::new(1.0, 1.0, 1.0)
+            }
+        } else {
+            Vector3::zero()
+        }
+    }
+    // drawn after the opaque surfaces, see `Transparency`
+    pub fn is_transparent(&self) -> bool {
+        self.color >> 24 < 0xff
+    }
+    pub fn diffuse(&self, uv: &Vector2) -> u32 {
+        let pixel = match self.diffuse_map {
+            Some(ref diffuse_map) => Sampler::new(diffuse_map, self.filter).sample(uv),
+            None => 0xffffffff,
+        };
+        self.tint(pixel)
+    }
+    // `diffuse` for four pixels at once, see `Sampler::sample4`
+    pub fn diffuse4(&self, uvs: &[Vector2; 4]) -> [u32; 4] {
+        let pixels = match self.diffuse_map {
+            Some(ref diffuse_map) => Sampler::new(diffuse_map, self.filter).sample4(uvs),
+            None => [0xffffffff; 4],
+        };
+        pixels.map(|pixel| self.tint(pixel))
+    }
+    fn tint(&self, pixel: u32) -> u32 {
+        if self.color == 0xffffffff {
+            return pixel;
+        }
+        let mut tinted = 0;
+        for i in 0..4 {
+            let a = (pixel >> (8 * i)) & 0xff;
+            let b = (self.color >> (8 * i)) & 0xff;
+            tinted |= (a * b / 255) << (8 * i);
+        }
+        tinted
+    }
+    pub fn specular(&self, uv: &Vector2) -> f32 {
+        let pixel = match self.specular_map {
+            Some(ref specular_map) => texel(specular_map, uv),
+            None => 0xffffffff,
+        };
+        (pixel & 0xff) as f32
+    }
+    // glTF layout: roughness in green, metallic in blue, scaled by the factors
+    // returns (metallic, roughness)
+    pub fn metallic_roughness(&self, uv: &Vector2) -> (f32, f32) {
+        if let Some(ref map) = self.metallic_roughness_map {
+            let pixel = texel(map, uv);
+            let roughness = ((pixel >> 8) & 0xff) as f32 / 255.0;
+            let metallic = ((pixel >> 16) & 0xff) as f32 / 255.0;
+            (metallic * self.metallic, roughness * self.roughness)
+        } else {
+            (self.metallic, self.roughness)
+        }
+    }
+    // rgb in [0, 1], white without a lightmap
+    pub fn lightmap(&self, uv1: &Vector2) -> Vector3 {
+        match self.lightmap {
+            Some(ref lightmap) => {
+                let pixel = texel(lightmap, uv1);
+                let mut light = Vector3::zero();
+                for i in 0..3 {
+                    light[i] = ((pixel >> (8 * i)) & 0xff) as f32 / 255.0;
+                }
+                light
+            }
+            None => Vector3::new(1.0, 1.0, 1.0),
+        }
+    }
+}
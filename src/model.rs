@@ -1,46 +1,148 @@
+use core::ops::Range;
+#[cfg(feature = "ppm")]
+use std::io::{BufWriter, Write};
+#[cfg(feature = "std")]
 use std::{
     fs::File,
     io::{BufRead, BufReader},
     path::Path,
 };
 
+#[cfg(not(feature = "std"))]
+use crate::compat::*;
+#[cfg(feature = "ppm")]
+use crate::ppm::save_buffer_to_ppm_file;
 use crate::{
-    geometry::{Vector2, Vector3},
-    ppm::{load_ppm_file_to_buffer, Image},
+    buffer::{IndexBuffer, VertexBuffer},
+    compat::{Arc, HashMap, OnceLock},
+    geometry::{m2v, m3v, normal_matrix, v2m, Matrix3, Matrix4, Vector2, Vector3, Vector4},
+    image::Image,
+    material::Material,
 };
 
 pub struct Model {
     verts: Vec<Vector3>,     // array of vertices
     tex_coord: Vec<Vector2>, // per-vertex array of tex coords
     norms: Vec<Vector3>,     // per-vertex array of normal vectors
+    tangents: Vec<Vector4>,  // per-normal tangents, w is the bitangent handedness
+    colors: Vec<u32>,        // per-vertex colors, empty if the mesh has none
+    uvs1: Vec<Vector2>,      // per-vertex second uv set, e.g. for lightmaps, empty if none
+    joints: Vec<[usize; 4]>, // per-vertex skin joints and weights, empty if not skinned
+    weights: Vec<[f32; 4]>,
+    bind_pose: (Vec<Vector3>, Vec<Vector3>), // verts and norms `skin` starts from
     facet_vrt: Vec<usize>,
     facet_tex: Vec<usize>, // per-triangle indices in the above arrays
     facet_nrm: Vec<usize>,
-    diffuse_map: Option<Image>,
-    normal_map: Option<Image>,
-    specular_map: Option<Image>,
+    material: Material, // used unless a draw call supplies its own
+    aabb: (Vector3, Vector3),
+    dirty: Option<Range<usize>>, // vertices modified since the last refresh
+    vert_faces: Vec<Vec<usize>>, // faces around each vertex, built on first refresh
+    buffers: OnceLock<(VertexBuffer, IndexBuffer)>, // built on first draw, reset on edits
 }
 
 macro_rules! load_map {
+    ($func_name:ident) => {
+        #[cfg(feature = "ppm")]
+        pub fn $func_name(&mut self, path: impl AsRef<Path>) {
+            self.material.$func_name(path);
+        }
+    };
+}
+
+// from before the maps moved into the material, rows must already be bottom
+// to top like a loaded map
+macro_rules! set_map {
     ($func_name:ident, $map_field:ident) => {
-        pub fn $func_name(&mut self, path: impl AsRef<std::path::Path>) {
-            let mut img = load_ppm_file_to_buffer(path);
-            img.vflip();
-            self.$map_field = Some(img);
+        #[deprecated(note = "set the map on `material_mut()` instead")]
+        pub fn $func_name(&mut self, img: Image) {
+            self.material.$map_field = Some(Arc::new(img));
         }
     };
 }
 
+pub struct LoadOptions {
+    // faces meeting at a sharper angle (radians) than this get separate normals
+    // when the normals have to be generated, PI means fully smooth
+    pub crease_angle: f32,
+    // repair the mesh by leaving out the faces `validate` complains about,
+    // otherwise loading fails on faces indexing past the end of the data
+    pub drop_degenerate: bool,
+}
+
+impl Default for LoadOptions {
+    fn default() -> Self {
+        Self {
+            crease_angle: core::f32::consts::PI,
+            drop_degenerate: false,
+        }
+    }
+}
+
+// what `Model::validate` found, face and vertex ids. Also the error loading
+// an obj returns
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MeshIssues {
+    // zero area or a corner repeated
+    pub degenerate_faces: Vec<usize>,
+    // a vertex, uv or normal index past the end of its array
+    pub out_of_range_faces: Vec<usize>,
+    pub nan_vertices: Vec<usize>,
+}
+
+impl MeshIssues {
+    pub fn is_empty(&self) -> bool {
+        self.degenerate_faces.is_empty()
+            && self.out_of_range_faces.is_empty()
+            && self.nan_vertices.is_empty()
+    }
+}
+
+impl core::fmt::Display for MeshIssues {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "{} degenerate faces, {} faces indexing out of range, {} NaN vertices",
+            self.degenerate_faces.len(),
+            self.out_of_range_faces.len(),
+            self.nan_vertices.len()
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for MeshIssues {}
+
+// obj indices are 1-based, negative ones count back from the latest element.
+// 0 and negative ones before the first element are None
+#[cfg(feature = "std")]
+fn parse_index(s: &str, len: usize) -> Option<usize> {
+    let i: i64 = s.parse().ok()?;
+    let i = if i < 0 { len as i64 + i } else { i - 1 };
+    usize::try_from(i).ok()
+}
+
 impl Model {
+    #[cfg(feature = "std")]
+    // panics on a mesh `new_with_options` would reject
     pub fn new(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+        Self::new_with_options(path, &LoadOptions::default())
+            .unwrap_or_else(|issues| panic!("{}: {issues}", path.display()))
+    }
+    #[cfg(feature = "std")]
+    pub fn new_with_options(
+        path: impl AsRef<Path>,
+        options: &LoadOptions,
+    ) -> Result<Self, MeshIssues> {
         let file = File::open(path).unwrap();
         let file = BufReader::new(file);
         let mut verts = Vec::new();
+        let mut colors = Vec::new();
         let mut norms = Vec::new();
         let mut tex_coord = Vec::new();
         let mut facet_vrt = Vec::new();
-        let mut facet_tex = Vec::new();
-        let mut facet_nrm = Vec::new();
+        let mut facet_tex: Vec<Option<usize>> = Vec::new();
+        let mut facet_nrm: Vec<Option<usize>> = Vec::new();
 
         for line in file
             .lines()
@@ -55,6 +157,15 @@ impl Model {
                         v[i] = parts[i + 1].parse().unwrap();
                     }
                     verts.push(v);
+                    // "v x y z r g b", colors in [0, 1]
+                    if parts.len() >= 7 {
+                        let mut color = 0xff000000u32;
+                        for i in 0..3 {
+                            let c: f32 = parts[i + 4].parse().unwrap();
+                            color |= ((c.clamp(0.0, 1.0) * 255.0).round() as u32) << (8 * i);
+                        }
+                        colors.push(color);
+                    }
                 }
                 "vn" => {
                     let mut n = Vector3::zero();
@@ -71,88 +182,718 @@ impl Model {
                     tex_coord.push(uv);
                 }
                 "f" => {
-                    for i in 0..3 {
-                        let v: Vec<&str> = parts[i + 1].split('/').collect();
-                        facet_vrt.push(v[0].parse::<usize>().unwrap() - 1);
-                        facet_tex.push(v[1].parse::<usize>().unwrap() - 1);
-                        facet_nrm.push(v[2].parse::<usize>().unwrap() - 1);
+                    // v, v/vt, v//vn or v/vt/vn; polygons are triangulated as a fan
+                    let corners: Vec<(usize, Option<usize>, Option<usize>)> = parts[1..]
+                        .iter()
+                        .map(|corner| {
+                            let mut v = corner.split('/');
+                            // indices that point nowhere are kept past the end so the
+                            // face shows up in `validate` as out of range
+                            let index = |s: &str, len| parse_index(s, len).unwrap_or(usize::MAX);
+                            let vrt = index(v.next().unwrap(), verts.len());
+                            let tex = v
+                                .next()
+                                .filter(|t| !t.is_empty())
+                                .map(|t| index(t, tex_coord.len()));
+                            let nrm = v
+                                .next()
+                                .filter(|n| !n.is_empty())
+                                .map(|n| index(n, norms.len()));
+                            (vrt, tex, nrm)
+                        })
+                        .collect();
+                    for i in 1..corners.len().saturating_sub(1) {
+                        for (vrt, tex, nrm) in [corners[0], corners[i], corners[i + 1]] {
+                            facet_vrt.push(vrt);
+                            facet_tex.push(tex);
+                            facet_nrm.push(nrm);
+                        }
                     }
                 }
                 _ => {}
             }
         }
-        println!(
+        eprintln!(
             "# v# {} f# {} vt# {} vn# {}",
             verts.len(),
             facet_vrt.len() / 3,
             tex_coord.len(),
             norms.len()
         );
-        Self {
+        // faces without texture coordinates all share a (0, 0) uv
+        if facet_tex.iter().any(|t| t.is_none()) {
+            tex_coord.push(Vector2::zero());
+        }
+        let facet_tex = facet_tex
+            .into_iter()
+            .map(|t| t.unwrap_or(tex_coord.len() - 1))
+            .collect();
+        let has_colors = !colors.is_empty() && colors.len() == verts.len();
+        let generate = facet_nrm.iter().any(|n| n.is_none());
+        let (norms, facet_nrm) = if generate {
+            (vec![Vector3::zero(); verts.len()], facet_vrt.clone())
+        } else {
+            (norms, facet_nrm.into_iter().map(|n| n.unwrap()).collect())
+        };
+        let mut model = Self::from_parts(verts, tex_coord, norms, facet_vrt, facet_tex, facet_nrm);
+        if options.drop_degenerate {
+            let dropped = model.drop_invalid_faces();
+            if dropped > 0 {
+                eprintln!("# dropped {dropped} invalid faces");
+            }
+        } else if let Err(issues) = model.validate() {
+            if !issues.out_of_range_faces.is_empty() {
+                return Err(issues);
+            }
+        }
+        if generate {
+            model.generate_normals(options.crease_angle);
+        }
+        if has_colors {
+            model.set_colors(colors);
+        }
+        Ok(model)
+    }
+    // v/vt/vn/f records (vertex colors as "v x y z r g b"); if the model has maps they
+    // are saved as ppm next to it and referenced from a `<name>.mtl`
+    #[cfg(feature = "ppm")]
+    pub fn save_obj(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let path = path.as_ref();
+        let mut file = BufWriter::new(File::create(path)?);
+        let maps = [
+            ("map_Kd", "diffuse", &self.material.diffuse_map),
+            ("norm", "normal", &self.material.normal_map),
+            ("map_Ns", "specular", &self.material.specular_map),
+        ];
+        let has_maps = maps.iter().any(|(_, _, map)| map.is_some());
+        if has_maps {
+            let stem = path.file_stem().unwrap().to_string_lossy();
+            let dir = path.parent().unwrap_or(Path::new(""));
+            let mut mtl = BufWriter::new(File::create(dir.join(format!("{stem}.mtl")))?);
+            writeln!(mtl, "newmtl {}", self.material.name)?;
+            for (key, suffix, map) in maps {
+                if let Some(map) = map {
+                    // maps are kept bottom to top in memory
+                    let mut img = Image {
+                        buffer: map.buffer.clone(),
+                        width: map.width,
+                        height: map.height,
+                    };
+                    img.vflip();
+                    let name = format!("{stem}_{suffix}.ppm");
+                    save_buffer_to_ppm_file(
+                        &img.buffer,
+                        img.width,
+                        img.height,
+                        img.width,
+                        dir.join(&name),
+                    )?;
+                    writeln!(mtl, "{key} {name}")?;
+                }
+            }
+            writeln!(file, "mtllib {stem}.mtl")?;
+        }
+        for (i, v) in self.verts.iter().enumerate() {
+            write!(file, "v {} {} {}", v.x(), v.y(), v.z())?;
+            if let Some(&color) = self.colors.get(i) {
+                for c in 0..3 {
+                    write!(file, " {}", ((color >> (8 * c)) & 0xff) as f32 / 255.0)?;
+                }
+            }
+            writeln!(file)?;
+        }
+        for uv in self.tex_coord.iter() {
+            writeln!(file, "vt {} {}", uv.x(), uv.y())?;
+        }
+        for n in self.norms.iter() {
+            writeln!(file, "vn {} {} {}", n.x(), n.y(), n.z())?;
+        }
+        if has_maps {
+            writeln!(file, "usemtl {}", self.material.name)?;
+        }
+        for face in 0..self.nfaces() {
+            write!(file, "f")?;
+            for i in face * 3..face * 3 + 3 {
+                write!(
+                    file,
+                    " {}/{}/{}",
+                    self.facet_vrt[i] + 1,
+                    self.facet_tex[i] + 1,
+                    self.facet_nrm[i] + 1
+                )?;
+            }
+            writeln!(file)?;
+        }
+        file.flush()
+    }
+    // build a model from already indexed data, e.g. procedurally generated meshes
+    pub fn from_parts(
+        verts: Vec<Vector3>,
+        tex_coord: Vec<Vector2>,
+        norms: Vec<Vector3>,
+        facet_vrt: Vec<usize>,
+        facet_tex: Vec<usize>,
+        facet_nrm: Vec<usize>,
+    ) -> Self {
+        assert_eq!(facet_vrt.len() % 3, 0);
+        assert_eq!(facet_vrt.len(), facet_tex.len());
+        assert_eq!(facet_vrt.len(), facet_nrm.len());
+        let mut model = Self {
             verts,
             norms,
+            tangents: Vec::new(),
+            colors: Vec::new(),
+            uvs1: Vec::new(),
+            joints: Vec::new(),
+            weights: Vec::new(),
+            bind_pose: (Vec::new(), Vec::new()),
             tex_coord,
             facet_vrt,
             facet_tex,
             facet_nrm,
-            diffuse_map: None,
-            normal_map: None,
-            specular_map: None,
+            material: Material::default(),
+            aabb: (Vector3::zero(), Vector3::zero()),
+            dirty: None,
+            vert_faces: Vec::new(),
+            buffers: OnceLock::new(),
+        };
+        model.compute_tangents();
+        model.aabb = compute_aabb(&model.verts);
+        model
+    }
+    // per-vertex tangent frames from the uv layout, stored alongside the normals:
+    // xyz is the tangent, w the handedness of the bitangent
+    pub fn compute_tangents(&mut self) {
+        self.buffers = OnceLock::new();
+        let mut tangents = vec![Vector3::zero(); self.norms.len()];
+        let mut bitangents = vec![Vector3::zero(); self.norms.len()];
+        for iface in 0..self.nfaces() {
+            // bad assets still load so `validate` can tell what is wrong
+            if !self.in_range(iface) {
+                continue;
+            }
+            let p = [0, 1, 2].map(|j| self.vert(iface, j));
+            let uv = [0, 1, 2].map(|j| self.uv(iface, j));
+            let e1 = &p[1] - &p[0];
+            let e2 = &p[2] - &p[0];
+            let (du1, dv1) = (uv[1].x() - uv[0].x(), uv[1].y() - uv[0].y());
+            let (du2, dv2) = (uv[2].x() - uv[0].x(), uv[2].y() - uv[0].y());
+            let det = du1 * dv2 - du2 * dv1;
+            if det.abs() < f32::EPSILON {
+                continue;
+            }
+            let t = (&e1 * dv2 - &e2 * dv1) / det;
+            let b = (&e2 * du1 - &e1 * du2) / det;
+            for j in 0..3 {
+                let n = self.facet_nrm[iface * 3 + j];
+                tangents[n] = &tangents[n] + &t;
+                bitangents[n] = &bitangents[n] + &b;
+            }
         }
+        self.tangents = self
+            .norms
+            .iter()
+            .zip(tangents.iter().zip(bitangents.iter()))
+            .map(|(n, (t, b))| {
+                let n = n.normalize();
+                // Gram-Schmidt against the normal
+                let mut t = t - &n * n.dot(t);
+                if t.length_square() < f32::EPSILON {
+                    // no usable uv gradient, pick any vector perpendicular to n
+                    let axis = if n.x().abs() < 0.9 {
+                        Vector3::new(1.0, 0.0, 0.0)
+                    } else {
+                        Vector3::new(0.0, 1.0, 0.0)
+                    };
+                    t = axis.cross(&n);
+                }
+                let t = t.normalize();
+                let w = if n.cross(&t).dot(b) < 0.0 { -1.0 } else { 1.0 };
+                Vector4::new(t.x(), t.y(), t.z(), w)
+            })
+            .collect();
+    }
+    load_map!(load_diffuse_map);
+    load_map!(load_normal_map);
+    load_map!(load_specular_map);
+    load_map!(load_metallic_roughness_map);
+    load_map!(load_lightmap);
+    set_map!(set_diffuse_map, diffuse_map);
+    set_map!(set_normal_map, normal_map);
+    set_map!(set_specular_map, specular_map);
+    set_map!(set_metallic_roughness_map, metallic_roughness_map);
+    // what `Renderer::draw_model` draws, one vertex per distinct corner
+    pub fn buffers(&self) -> &(VertexBuffer, IndexBuffer) {
+        self.buffers.get_or_init(|| VertexBuffer::from_model(self))
+    }
+    pub fn material(&self) -> &Material {
+        &self.material
+    }
+    pub fn material_mut(&mut self) -> &mut Material {
+        &mut self.material
+    }
+    pub fn set_material(&mut self, material: Material) {
+        self.material = material;
+    }
+    pub fn verts(&self) -> &[Vector3] {
+        &self.verts
+    }
+    // the uv and normal pools the `corner` indices point into
+    pub fn uvs(&self) -> &[Vector2] {
+        &self.tex_coord
+    }
+    pub fn normals(&self) -> &[Vector3] {
+        &self.norms
+    }
+    // mutable view for dynamic meshes (cloth, skinning, morphs), the range is
+    // recorded as dirty and normals, tangents and bounds are stale until `refresh`
+    pub fn verts_mut(&mut self, range: Range<usize>) -> &mut [Vector3] {
+        self.buffers = OnceLock::new();
+        self.dirty = Some(match self.dirty.take() {
+            Some(dirty) => dirty.start.min(range.start)..dirty.end.max(range.end),
+            None => range.clone(),
+        });
+        &mut self.verts[range]
+    }
+    pub fn set_vert(&mut self, i: usize, v: Vector3) {
+        self.verts_mut(i..i + 1)[0] = v;
+    }
+    pub fn dirty_range(&self) -> Option<Range<usize>> {
+        self.dirty.clone()
+    }
+    // recompute the derived data invalidated by `verts_mut`, normals are only
+    // recomputed around the dirty vertices
+    pub fn refresh(&mut self) {
+        let Some(range) = self.dirty.take() else {
+            return;
+        };
+        if self.facet_nrm != self.facet_vrt || self.norms.len() != self.verts.len() {
+            self.recompute_normals();
+        } else {
+            self.build_vert_faces();
+            let mut affected = vec![false; self.verts.len()];
+            for v in range {
+                for &iface in self.vert_faces[v].iter() {
+                    for j in 0..3 {
+                        affected[self.facet_vrt[iface * 3 + j]] = true;
+                    }
+                }
+            }
+            for (v, _) in affected.iter().enumerate().filter(|(_, &a)| a) {
+                self.norms[v] = self.smooth_normal(v);
+            }
+        }
+        self.compute_tangents();
+        self.aabb = compute_aabb(&self.verts);
+    }
+    // area weighted smooth normals, one per vertex; authored normals (and their
+    // hard edges) are replaced
+    pub fn recompute_normals(&mut self) {
+        self.build_vert_faces();
+        self.facet_nrm = self.facet_vrt.clone();
+        self.norms = (0..self.verts.len())
+            .map(|v| self.smooth_normal(v))
+            .collect();
+        self.compute_tangents();
+    }
+    // per-corner normals averaged (area weighted) over the faces around the vertex
+    // whose orientation is within `crease_angle` of the corner's face
+    pub fn generate_normals(&mut self, crease_angle: f32) {
+        self.build_vert_faces();
+        let face_normals: Vec<Vector3> = (0..self.nfaces()).map(|f| self.face_normal(f)).collect();
+        let unit: Vec<Vector3> = face_normals
+            .iter()
+            .map(|n| {
+                if n.length_square() > 0.0 {
+                    n.normalize()
+                } else {
+                    Vector3::zero()
+                }
+            })
+            .collect();
+        let cos_crease = crease_angle.cos();
+        let mut norms: Vec<Vector3> = Vec::new();
+        let mut facet_nrm = Vec::with_capacity(self.facet_vrt.len());
+        // normals generated for each vertex, corners with the same result share one
+        let mut per_vert: Vec<Vec<usize>> = vec![Vec::new(); self.verts.len()];
+        for (corner, &v) in self.facet_vrt.iter().enumerate() {
+            let face = corner / 3;
+            let mut n = Vector3::zero();
+            for &other in self.vert_faces[v].iter() {
+                if other == face || unit[face].dot(&unit[other]) >= cos_crease {
+                    n = n + &face_normals[other];
+                }
+            }
+            let n = if n.length_square() > 0.0 {
+                n.normalize()
+            } else {
+                Vector3::new(0.0, 0.0, 1.0)
+            };
+            let existing = per_vert[v]
+                .iter()
+                .copied()
+                .find(|&i| (&norms[i] - &n).length_square() < 1e-10);
+            let index = existing.unwrap_or_else(|| {
+                norms.push(n);
+                per_vert[v].push(norms.len() - 1);
+                norms.len() - 1
+            });
+            facet_nrm.push(index);
+        }
+        self.norms = norms;
+        self.facet_nrm = facet_nrm;
+        self.compute_tangents();
+    }
+    fn build_vert_faces(&mut self) {
+        if self.vert_faces.len() == self.verts.len() {
+            return;
+        }
+        self.vert_faces = vec![Vec::new(); self.verts.len()];
+        for (i, &v) in self.facet_vrt.iter().enumerate() {
+            self.vert_faces[v].push(i / 3);
+        }
+    }
+    // cross product of the edges, its length is twice the face area
+    fn face_normal(&self, iface: usize) -> Vector3 {
+        let a = &self.verts[self.facet_vrt[iface * 3]];
+        let b = &self.verts[self.facet_vrt[iface * 3 + 1]];
+        let c = &self.verts[self.facet_vrt[iface * 3 + 2]];
+        (b - a).cross(&(c - a))
+    }
+    fn smooth_normal(&self, v: usize) -> Vector3 {
+        let mut n = Vector3::zero();
+        for &iface in self.vert_faces[v].iter() {
+            n = n + self.face_normal(iface);
+        }
+        if n.length_square() > 0.0 {
+            n.normalize()
+        } else {
+            Vector3::new(0.0, 0.0, 1.0)
+        }
+    }
+    // (min, max) corners of the axis aligned bounding box
+    pub fn aabb(&self) -> (Vector3, Vector3) {
+        self.aabb.clone()
+    }
+    // centered on the aabb, not minimal but cheap and good enough for framing
+    pub fn bounding_sphere(&self) -> (Vector3, f32) {
+        let (min, max) = &self.aabb;
+        let center = (min + max) * 0.5;
+        let radius = self
+            .verts
+            .iter()
+            .map(|v| (v - &center).length_square())
+            .fold(0.0, f32::max)
+            .sqrt();
+        (center, radius)
+    }
+    fn in_range(&self, iface: usize) -> bool {
+        (0..3).all(|j| {
+            let (v, t, n) = self.corner(iface, j);
+            v < self.verts.len() && t < self.tex_coord.len() && n < self.norms.len()
+        })
+    }
+    // faces and vertices that would draw garbage or panic, if there are any
+    pub fn validate(&self) -> Result<(), MeshIssues> {
+        let mut issues = MeshIssues::default();
+        for (v, p) in self.verts.iter().enumerate() {
+            if (0..3).any(|i| !p[i].is_finite()) {
+                issues.nan_vertices.push(v);
+            }
+        }
+        for iface in 0..self.nfaces() {
+            if !self.in_range(iface) {
+                issues.out_of_range_faces.push(iface);
+                continue;
+            }
+            let [a, b, c] = [0, 1, 2].map(|j| self.vert_index(iface, j));
+            if a == b || b == c || c == a || self.face_normal(iface).length_square() == 0.0 {
+                issues.degenerate_faces.push(iface);
+            }
+        }
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(issues)
+        }
+    }
+    // removes the faces `validate` reports, including those touching a NaN
+    // vertex. Returns how many went
+    pub fn drop_invalid_faces(&mut self) -> usize {
+        let Err(issues) = self.validate() else {
+            return 0;
+        };
+        let mut keep = vec![true; self.nfaces()];
+        for &f in issues
+            .degenerate_faces
+            .iter()
+            .chain(issues.out_of_range_faces.iter())
+        {
+            keep[f] = false;
+        }
+        for (f, keep) in keep.iter_mut().enumerate() {
+            if *keep && (0..3).any(|j| issues.nan_vertices.contains(&self.vert_index(f, j))) {
+                *keep = false;
+            }
+        }
+        let dropped = keep.iter().filter(|&&k| !k).count();
+        if dropped == 0 {
+            return 0;
+        }
+        let filter = |facets: &[usize]| -> Vec<usize> {
+            facets
+                .chunks(3)
+                .zip(keep.iter())
+                .filter(|(_, &k)| k)
+                .flat_map(|(f, _)| f.iter().copied())
+                .collect()
+        };
+        self.facet_vrt = filter(&self.facet_vrt);
+        self.facet_tex = filter(&self.facet_tex);
+        self.facet_nrm = filter(&self.facet_nrm);
+        self.vert_faces = Vec::new();
+        self.buffers = OnceLock::new();
+        self.compute_tangents();
+        dropped
+    }
+    // merge vertices no farther apart than `epsilon`, e.g. the corners stl and
+    // flat shaded exports repeat; each group keeps its first vertex. Normals are
+    // left alone, `generate_normals` afterwards smooths across the former seams.
+    // Returns how many vertices were merged away
+    pub fn weld_vertices(&mut self, epsilon: f32) -> usize {
+        let cell = epsilon.max(1e-6);
+        let key = |p: &Vector3| [0, 1, 2].map(|i| (p[i] / cell).floor() as i64);
+        let mut grid: HashMap<[i64; 3], Vec<usize>> = HashMap::new();
+        let mut kept: Vec<usize> = Vec::new();
+        let mut remap = Vec::with_capacity(self.verts.len());
+        for (v, p) in self.verts.iter().enumerate() {
+            let k = key(p);
+            let mut found = None;
+            'search: for dx in -1..=1 {
+                for dy in -1..=1 {
+                    for dz in -1..=1 {
+                        let Some(cell) = grid.get(&[k[0] + dx, k[1] + dy, k[2] + dz]) else {
+                            continue;
+                        };
+                        if let Some(&i) = cell
+                            .iter()
+                            .find(|&&i| (&self.verts[kept[i]] - p).length() <= epsilon)
+                        {
+                            found = Some(i);
+                            break 'search;
+                        }
+                    }
+                }
+            }
+            remap.push(found.unwrap_or_else(|| {
+                kept.push(v);
+                grid.entry(k).or_default().push(kept.len() - 1);
+                kept.len() - 1
+            }));
+        }
+        let merged = self.verts.len() - kept.len();
+        if merged == 0 {
+            return 0;
+        }
+        let pick = |values: &[Vector3]| kept.iter().map(|&v| values[v].clone()).collect();
+        self.verts = pick(&self.verts);
+        if !self.bind_pose.0.is_empty() {
+            self.bind_pose.0 = pick(&self.bind_pose.0);
+        }
+        if !self.colors.is_empty() {
+            self.colors = kept.iter().map(|&v| self.colors[v]).collect();
+        }
+        if !self.uvs1.is_empty() {
+            self.uvs1 = kept.iter().map(|&v| self.uvs1[v].clone()).collect();
+        }
+        if !self.joints.is_empty() {
+            self.joints = kept.iter().map(|&v| self.joints[v]).collect();
+            self.weights = kept.iter().map(|&v| self.weights[v]).collect();
+        }
+        for v in self.facet_vrt.iter_mut() {
+            *v = remap[*v];
+        }
+        self.vert_faces = Vec::new();
+        self.dirty = None;
+        self.buffers = OnceLock::new();
+        self.aabb = compute_aabb(&self.verts);
+        merged
+    }
+    // recenter and uniformly scale the vertices so the model fits in [-1, 1]
+    pub fn normalize(&mut self) {
+        let (min, max) = self.aabb.clone();
+        let center = (&min + &max) * 0.5;
+        let half_extent = (0..3).map(|i| (max[i] - min[i]) * 0.5).fold(0.0, f32::max);
+        let scale = if half_extent > 0.0 {
+            1.0 / half_extent
+        } else {
+            1.0
+        };
+        for v in self.verts.iter_mut() {
+            *v = (&*v - &center) * scale;
+        }
+        self.buffers = OnceLock::new();
+        self.aabb = compute_aabb(&self.verts);
     }
-    load_map!(load_diffuse_map, diffuse_map);
-    load_map!(load_normal_map, normal_map);
-    load_map!(load_specular_map, specular_map);
     pub fn nverts(&self) -> usize {
         self.verts.len()
     }
     pub fn nfaces(&self) -> usize {
         self.facet_vrt.len() / 3
     }
+    // index into `verts()` of a face corner
+    pub fn vert_index(&self, iface: usize, nthvert: usize) -> usize {
+        self.facet_vrt[iface * 3 + nthvert]
+    }
+    // (vertex, uv, normal) indices of a face corner, corners with equal indices
+    // are the same vertex to a gpu style vertex buffer
+    pub fn corner(&self, iface: usize, nthvert: usize) -> (usize, usize, usize) {
+        let i = iface * 3 + nthvert;
+        (self.facet_vrt[i], self.facet_tex[i], self.facet_nrm[i])
+    }
     pub fn vert(&self, iface: usize, nthvert: usize) -> Vector3 {
         self.verts[self.facet_vrt[iface * 3 + nthvert]].clone()
     }
     pub fn uv(&self, iface: usize, nthvert: usize) -> Vector2 {
         self.tex_coord[self.facet_tex[iface * 3 + nthvert]].clone()
     }
-    pub fn normal_vert(&self, iface: usize, nthvert: usize) -> Vector3 {
-        self.norms[self.facet_nrm[iface * 3 + nthvert]].clone()
+    pub fn set_colors(&mut self, colors: Vec<u32>) {
+        assert_eq!(colors.len(), self.verts.len());
+        self.colors = colors;
+        self.buffers = OnceLock::new();
     }
-    pub fn normal_uv(&self, uv: &Vector2) -> Vector3 {
-        if let Some(ref normal_map) = self.normal_map {
-            let x = uv.x() * normal_map.width as f32;
-            let y = uv.y() * normal_map.height as f32;
-            let pixel = normal_map.buffer[x as usize + y as usize * normal_map.width as usize];
-            if pixel == 0xff000000 {
-                Vector3::zero()
-            } else {
-                let r = (pixel & 0xff) as f32;
-                let g = ((pixel >> 8) & 0xff) as f32;
-                let b = ((pixel >> 16) & 0xff) as f32;
-                Vector3::new(r, g, b) * 2.0 / 255.0 - Vector3::new(1.0, 1.0, 1.0)
+    pub fn has_colors(&self) -> bool {
+        !self.colors.is_empty()
+    }
+    // a second uv set, one per vertex like the colors, so uv seams need split
+    // vertices as in glTF. Baked maps (see `Material::lightmap`) use it
+    pub fn set_uv1(&mut self, uvs: Vec<Vector2>) {
+        assert_eq!(uvs.len(), self.verts.len());
+        self.uvs1 = uvs;
+        self.buffers = OnceLock::new();
+    }
+    pub fn has_uv1(&self) -> bool {
+        !self.uvs1.is_empty()
+    }
+    // the first uv set when the mesh has no second one
+    pub fn uv1(&self, iface: usize, nthvert: usize) -> Vector2 {
+        if self.uvs1.is_empty() {
+            self.uv(iface, nthvert)
+        } else {
+            self.uvs1[self.facet_vrt[iface * 3 + nthvert]].clone()
+        }
+    }
+    // up to four joints per vertex, indices into the matrices given to `skin`.
+    // Weights are normalized and the current vertices and normals become the
+    // bind pose
+    pub fn set_skin(&mut self, joints: Vec<[usize; 4]>, weights: Vec<[f32; 4]>) {
+        assert_eq!(joints.len(), self.verts.len());
+        assert_eq!(weights.len(), self.verts.len());
+        self.joints = joints;
+        self.weights = weights
+            .into_iter()
+            .map(|w| {
+                let sum: f32 = w.iter().sum();
+                if sum > 0.0 {
+                    w.map(|x| x / sum)
+                } else {
+                    [1.0, 0.0, 0.0, 0.0]
+                }
+            })
+            .collect();
+        self.bind_pose = (self.verts.clone(), self.norms.clone());
+    }
+    pub fn has_skin(&self) -> bool {
+        !self.joints.is_empty()
+    }
+    // linear blend skinning of the bind pose, `matrices[j]` takes joint j from
+    // bind space to its posed place
+    pub fn skin(&mut self, matrices: &[Matrix4]) {
+        assert!(self.has_skin());
+        let normals: Vec<Matrix3> = matrices.iter().map(normal_matrix).collect();
+        let (bind_verts, bind_norms) = &self.bind_pose;
+        for (v, p) in bind_verts.iter().enumerate() {
+            let mut posed = Vector3::zero();
+            for (&j, &w) in self.joints[v].iter().zip(self.weights[v].iter()) {
+                if w > 0.0 {
+                    posed = posed + m2v(&(&matrices[j] * v2m(p))) * w;
+                }
+            }
+            self.verts[v] = posed;
+        }
+        // a normal follows the joints of the first vertex using it
+        let mut done = vec![false; bind_norms.len()];
+        for (corner, &n) in self.facet_nrm.iter().enumerate() {
+            if done[n] {
+                continue;
+            }
+            done[n] = true;
+            let v = self.facet_vrt[corner];
+            let mut posed = Vector3::zero();
+            for (&j, &w) in self.joints[v].iter().zip(self.weights[v].iter()) {
+                if w > 0.0 {
+                    posed = posed + m3v(&normals[j], &bind_norms[n]) * w;
+                }
             }
+            if posed.length_square() > 0.0 {
+                self.norms[n] = posed.normalize();
+            }
+        }
+        self.dirty = None;
+        self.compute_tangents();
+        self.aabb = compute_aabb(&self.verts);
+    }
+    // white when the mesh has no vertex colors
+    pub fn color(&self, iface: usize, nthvert: usize) -> u32 {
+        if self.colors.is_empty() {
+            0xffffffff
         } else {
-            Vector3::zero()
+            self.colors[self.facet_vrt[iface * 3 + nthvert]]
         }
     }
+    pub fn normal_vert(&self, iface: usize, nthvert: usize) -> Vector3 {
+        self.norms[self.facet_nrm[iface * 3 + nthvert]].clone()
+    }
+    pub fn tangent(&self, iface: usize, nthvert: usize) -> Vector3 {
+        let t = &self.tangents[self.facet_nrm[iface * 3 + nthvert]];
+        Vector3::new(t.x(), t.y(), t.z())
+    }
+    pub fn bitangent(&self, iface: usize, nthvert: usize) -> Vector3 {
+        let t = &self.tangents[self.facet_nrm[iface * 3 + nthvert]];
+        let n = self.normal_vert(iface, nthvert).normalize();
+        n.cross(&self.tangent(iface, nthvert)) * t.w()
+    }
+    pub fn has_normal_map(&self) -> bool {
+        self.material.has_normal_map()
+    }
+    pub fn normal_uv(&self, uv: &Vector2) -> Vector3 {
+        self.material.normal_uv(uv)
+    }
     pub fn diffuse(&self, uv: &Vector2) -> u32 {
-        let pixel: u32 = if let Some(ref diffuse_map) = self.diffuse_map {
-            let x = uv.x() * diffuse_map.width as f32;
-            let y = uv.y() * diffuse_map.height as f32;
-            diffuse_map.buffer[x as usize + y as usize * diffuse_map.width as usize]
-        } else {
-            0xffffffff
-        };
-        pixel
+        self.material.diffuse(uv)
     }
     pub fn specular(&self, uv: &Vector2) -> f32 {
-        let pixel: u32 = if let Some(ref specular_map) = self.specular_map {
-            let x = uv.x() * specular_map.width as f32;
-            let y = uv.y() * specular_map.height as f32;
-            specular_map.buffer[x as usize + y as usize * specular_map.width as usize]
-        } else {
-            0xffffffff
-        };
-        ((pixel) & 0xff) as f32
+        self.material.specular(uv)
+    }
+    pub fn metallic_roughness(&self, uv: &Vector2) -> (f32, f32) {
+        self.material.metallic_roughness(uv)
+    }
+    pub fn lightmap(&self, uv1: &Vector2) -> Vector3 {
+        self.material.lightmap(uv1)
+    }
+}
+
+fn compute_aabb(verts: &[Vector3]) -> (Vector3, Vector3) {
+    if verts.is_empty() {
+        return (Vector3::zero(), Vector3::zero());
+    }
+    let mut min = verts[0].clone();
+    let mut max = verts[0].clone();
+    for v in verts.iter() {
+        for i in 0..3 {
+            min[i] = min[i].min(v[i]);
+            max[i] = max[i].max(v[i]);
+        }
     }
+    (min, max)
 }
@@ -0,0 +1,268 @@
+#[cfg(not(feature = "std"))]
+use crate::compat::*;
+
+use crate::image::Image;
+
+// gradient noise over a table of 0..=255 shuffled by a seed: the same seed gives
+// the same values everywhere. The values stay roughly in -1..1 with features
+// about a unit apart, perlin is 0 at whole coordinates
+#[derive(Clone)]
+pub struct Noise {
+    // the shuffle twice over so corner hashes never wrap
+    perm: [u8; 512],
+}
+
+impl Noise {
+    pub fn new(seed: u64) -> Self {
+        let mut table: [u8; 256] = core::array::from_fn(|i| i as u8);
+        let mut state = seed;
+        // Fisher–Yates with splitmix64 for the random numbers
+        for i in (1..256).rev() {
+            state = state.wrapping_add(0x9e3779b97f4a7c15);
+            let mut z = state;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+            z ^= z >> 31;
+            table.swap(i, (z % (i as u64 + 1)) as usize);
+        }
+        Self {
+            perm: core::array::from_fn(|i| table[i & 255]),
+        }
+    }
+    #[inline]
+    fn hash(&self, x: i32, y: i32) -> usize {
+        self.perm[self.perm[(x & 255) as usize] as usize + (y & 255) as usize] as usize
+    }
+    #[inline]
+    fn hash3(&self, x: i32, y: i32, z: i32) -> usize {
+        self.perm[self.hash(x, y) + (z & 255) as usize] as usize
+    }
+    // improved perlin noise
+    pub fn perlin2(&self, x: f32, y: f32) -> f32 {
+        let (xf, yf) = (x.floor(), y.floor());
+        let (xi, yi) = (xf as i32, yf as i32);
+        let (x, y) = (x - xf, y - yf);
+        let (u, v) = (fade(x), fade(y));
+        let corner =
+            |dx: i32, dy: i32| grad2(self.hash(xi + dx, yi + dy), x - dx as f32, y - dy as f32);
+        let bottom = lerp(corner(0, 0), corner(1, 0), u);
+        let top = lerp(corner(0, 1), corner(1, 1), u);
+        lerp(bottom, top, v)
+    }
+    pub fn perlin3(&self, x: f32, y: f32, z: f32) -> f32 {
+        let (xf, yf, zf) = (x.floor(), y.floor(), z.floor());
+        let (xi, yi, zi) = (xf as i32, yf as i32, zf as i32);
+        let (x, y, z) = (x - xf, y - yf, z - zf);
+        let (u, v, w) = (fade(x), fade(y), fade(z));
+        let corner = |dx: i32, dy: i32, dz: i32| {
+            grad3(
+                self.hash3(xi + dx, yi + dy, zi + dz),
+                x - dx as f32,
+                y - dy as f32,
+                z - dz as f32,
+            )
+        };
+        let face = |dz: i32| {
+            let bottom = lerp(corner(0, 0, dz), corner(1, 0, dz), u);
+            let top = lerp(corner(0, 1, dz), corner(1, 1, dz), u);
+            lerp(bottom, top, v)
+        };
+        lerp(face(0), face(1), w)
+    }
+    // simplex noise: fewer corners than perlin per sample and no grid aligned
+    // streaks
+    pub fn simplex2(&self, x: f32, y: f32) -> f32 {
+        // skew to the grid of squares split into triangles and back
+        const F2: f32 = 0.366_025_42; // (sqrt(3) - 1) / 2
+        const G2: f32 = 0.211_324_87; // (3 - sqrt(3)) / 6
+        let s = (x + y) * F2;
+        let (i, j) = ((x + s).floor(), (y + s).floor());
+        let t = (i + j) * G2;
+        let (x0, y0) = (x - (i - t), y - (j - t));
+        // the triangle the point is in, below or above the diagonal
+        let (i1, j1) = if x0 > y0 { (1, 0) } else { (0, 1) };
+        let (i, j) = (i as i32, j as i32);
+        let corners = [
+            (0, 0, x0, y0),
+            (i1, j1, x0 - i1 as f32 + G2, y0 - j1 as f32 + G2),
+            (1, 1, x0 - 1.0 + 2.0 * G2, y0 - 1.0 + 2.0 * G2),
+        ];
+        let sum: f32 = corners
+            .iter()
+            .map(|&(di, dj, x, y)| {
+                let t = 0.5 - x * x - y * y;
+                if t < 0.0 {
+                    return 0.0;
+                }
+                let t = t * t;
+                t * t * grad2(self.hash(i + di, j + dj), x, y)
+            })
+            .sum();
+        70.0 * sum
+    }
+    pub fn simplex3(&self, x: f32, y: f32, z: f32) -> f32 {
+        const F3: f32 = 1.0 / 3.0;
+        const G3: f32 = 1.0 / 6.0;
+        let s = (x + y + z) * F3;
+        let (i, j, k) = ((x + s).floor(), (y + s).floor(), (z + s).floor());
+        let t = (i + j + k) * G3;
+        let (x0, y0, z0) = (x - (i - t), y - (j - t), z - (k - t));
+        // the two corners between the first and the last, by the order of the
+        // coordinates in the cube
+        let (first, second) = if x0 >= y0 {
+            if y0 >= z0 {
+                ((1, 0, 0), (1, 1, 0))
+            } else if x0 >= z0 {
+                ((1, 0, 0), (1, 0, 1))
+            } else {
+                ((0, 0, 1), (1, 0, 1))
+            }
+        } else if y0 < z0 {
+            ((0, 0, 1), (0, 1, 1))
+        } else if x0 < z0 {
+            ((0, 1, 0), (0, 1, 1))
+        } else {
+            ((0, 1, 0), (1, 1, 0))
+        };
+        let (i, j, k) = (i as i32, j as i32, k as i32);
+        let offset = |(di, dj, dk): (i32, i32, i32), g: f32| {
+            (
+                di,
+                dj,
+                dk,
+                x0 - di as f32 + g,
+                y0 - dj as f32 + g,
+                z0 - dk as f32 + g,
+            )
+        };
+        let corners = [
+            offset((0, 0, 0), 0.0),
+            offset(first, G3),
+            offset(second, 2.0 * G3),
+            offset((1, 1, 1), 3.0 * G3),
+        ];
+        let sum: f32 = corners
+            .iter()
+            .map(|&(di, dj, dk, x, y, z)| {
+                let t = 0.6 - x * x - y * y - z * z;
+                if t < 0.0 {
+                    return 0.0;
+                }
+                let t = t * t;
+                t * t * grad3(self.hash3(i + di, j + dj, k + dk), x, y, z)
+            })
+            .sum();
+        32.0 * sum
+    }
+    // a grayscale image of `fbm` over `perlin2`, -1..1 mapped to black..white,
+    // `frequency` features per pixel, e.g. a heightmap for `Terrain::new`
+    pub fn heightmap(&self, width: u32, height: u32, frequency: f32, fbm: &Fbm) -> Image {
+        let buffer = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let value = fbm.sample2(
+                    |x, y| self.perlin2(x, y),
+                    x as f32 * frequency,
+                    y as f32 * frequency,
+                );
+                let gray = (((value + 1.0) / 2.0).clamp(0.0, 1.0) * 255.0) as u32;
+                0xff000000 | gray << 16 | gray << 8 | gray
+            })
+            .collect();
+        Image {
+            buffer,
+            width,
+            height,
+        }
+    }
+}
+
+impl Default for Noise {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+// fractal Brownian motion: `octaves` layers of noise, each `lacunarity` times
+// the frequency and `gain` times the amplitude of the one before, divided by
+// the amplitudes so the range stays the noise's
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Fbm {
+    pub octaves: u32,
+    pub lacunarity: f32,
+    pub gain: f32,
+}
+
+impl Fbm {
+    pub fn new(octaves: u32) -> Self {
+        Self {
+            octaves,
+            lacunarity: 2.0,
+            gain: 0.5,
+        }
+    }
+    // e.g. `fbm.sample2(|x, y| noise.simplex2(x, y), x, y)`
+    pub fn sample2(&self, noise: impl Fn(f32, f32) -> f32, x: f32, y: f32) -> f32 {
+        self.sum(|frequency| noise(x * frequency, y * frequency))
+    }
+    pub fn sample3(&self, noise: impl Fn(f32, f32, f32) -> f32, x: f32, y: f32, z: f32) -> f32 {
+        self.sum(|frequency| noise(x * frequency, y * frequency, z * frequency))
+    }
+    fn sum(&self, octave: impl Fn(f32) -> f32) -> f32 {
+        let (mut sum, mut total) = (0.0, 0.0);
+        let (mut frequency, mut amplitude) = (1.0, 1.0);
+        for _ in 0..self.octaves.max(1) {
+            sum += octave(frequency) * amplitude;
+            total += amplitude;
+            frequency *= self.lacunarity;
+            amplitude *= self.gain;
+        }
+        sum / total
+    }
+}
+
+impl Default for Fbm {
+    fn default() -> Self {
+        Self::new(4)
+    }
+}
+
+#[inline]
+fn fade(t: f32) -> f32 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+#[inline]
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+// one of eight directions, the axes and the diagonals
+#[inline]
+fn grad2(hash: usize, x: f32, y: f32) -> f32 {
+    match hash & 7 {
+        0 => x + y,
+        1 => -x + y,
+        2 => x - y,
+        3 => -x - y,
+        4 => x,
+        5 => -x,
+        6 => y,
+        _ => -y,
+    }
+}
+
+// one of the twelve cube edge directions, four of them twice
+#[inline]
+fn grad3(hash: usize, x: f32, y: f32, z: f32) -> f32 {
+    let h = hash & 15;
+    let u = if h < 8 { x } else { y };
+    let v = if h < 4 {
+        y
+    } else if h == 12 || h == 14 {
+        x
+    } else {
+        z
+    };
+    (if h & 1 == 0 { u } else { -u }) + (if h & 2 == 0 { v } else { -v })
+}
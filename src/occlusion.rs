@@ -0,0 +1,96 @@
+use crate::{
+    geometry::{m2v, v2m, Aabb, Matrix4},
+    model::Model,
+    renderer::{Renderer, Shader},
+    scene::Camera,
+    shadow::DepthShader,
+};
+
+// a small depth buffer of a few large, low poly occluders (walls, floors,
+// terrain blocks) seen from the camera, to skip whole objects hidden behind
+// them before their vertices are shaded. Depth is greater-is-nearer like
+// `DepthState::new`, independent of the renderer's depth state
+pub struct OcclusionBuffer {
+    pub width: u32,
+    pub height: u32,
+    pub transform: Matrix4,
+    camera: Camera,
+    depth: Vec<f32>,
+    color: Vec<u32>,
+}
+
+impl OcclusionBuffer {
+    // framed like `draw_scene` for a target of any size with the same aspect,
+    // e.g. a quarter of its width and height
+    pub fn new(width: u32, height: u32, camera: &Camera) -> Self {
+        let size = (width * height) as usize;
+        Self {
+            width,
+            height,
+            transform: camera.transform(width, height, 255.0),
+            camera: camera.clone(),
+            depth: vec![f32::MIN; size],
+            color: vec![0; size],
+        }
+    }
+    // empty again, seen from `camera`
+    pub fn reset(&mut self, camera: &Camera) {
+        self.transform = camera.transform(self.width, self.height, 255.0);
+        self.camera = camera.clone();
+        self.depth.fill(f32::MIN);
+    }
+    pub fn depth_buffer(&self) -> &[f32] {
+        &self.depth
+    }
+    // `model` placed by `world`. Only what is solid where the occluder is drawn
+    // should be one: a pixel it touches counts as covered
+    pub fn add_occluder(&mut self, model: &Model, world: &Matrix4) {
+        let mut renderer = Renderer::new(&mut self.color, &mut self.depth, self.width, self.height);
+        let transform = &self.transform * world;
+        let mut shader = DepthShader {
+            model,
+            transform: &transform,
+        };
+        for i in 0..model.nfaces() {
+            let screen_coords = [0, 1, 2].map(|j| shader.vertex(i, j));
+            renderer.fill_triangle(&screen_coords, &mut shader);
+        }
+    }
+    // false if `bounds` placed by `world` is behind the occluders at
+    // every pixel its screen bounds touch. Boxes reaching behind the eye count as
+    // visible, and so do the ones off the buffer: the frustum test is left to
+    // the caller
+    pub fn is_visible(&self, bounds: &Aabb, world: &Matrix4) -> bool {
+        let forward = &self.camera.center - &self.camera.eye;
+        let corners = bounds.corners().map(|c| m2v(&(world * v2m(&c))));
+        if corners
+            .iter()
+            .any(|c| (c - &self.camera.eye).dot(&forward) <= 0.0)
+        {
+            return true;
+        }
+        let screen = corners.map(|c| m2v(&(&self.transform * v2m(&c))));
+        let (mut x0, mut y0, mut x1, mut y1) = (f32::MAX, f32::MAX, f32::MIN, f32::MIN);
+        let mut nearest = f32::MIN;
+        for p in screen.iter() {
+            (x0, y0) = (x0.min(p.x()), y0.min(p.y()));
+            (x1, y1) = (x1.max(p.x()), y1.max(p.y()));
+            nearest = nearest.max(p.z());
+        }
+        let (w, h) = (self.width as f32, self.height as f32);
+        if x1 < 0.0 || y1 < 0.0 || x0 >= w || y0 >= h {
+            return true;
+        }
+        // what is off the buffer can't be seen anyway
+        let (x0, y0) = (x0.max(0.0) as u32, y0.max(0.0) as u32);
+        let (x1, y1) = (x1.min(w - 1.0) as u32, y1.min(h - 1.0) as u32);
+        for y in y0..=y1 {
+            for x in x0..=x1 {
+                if self.depth[(x + y * self.width) as usize] <= nearest {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+}
@@ -0,0 +1,72 @@
+#[cfg(not(feature = "std"))]
+use crate::compat::*;
+
+// weighted blended order independent transparency (McGuire and Bavoil 2013):
+// translucent fragments add up in a per pixel weighted sum instead of blending
+// over each other, so the draw order doesn't matter. Where they overlap the
+// result is an approximation that favours the closer ones
+pub struct WeightedOit {
+    // premultiplied rgb and alpha, times the weight
+    accum: Vec<[f32; 4]>,
+    // how much of what is behind still shows through
+    revealage: Vec<f32>,
+}
+
+impl WeightedOit {
+    pub fn new(size: usize) -> Self {
+        Self {
+            accum: vec![[0.0; 4]; size],
+            revealage: vec![1.0; size],
+        }
+    }
+    pub fn clear(&mut self) {
+        self.accum.fill([0.0; 4]);
+        self.revealage.fill(1.0);
+    }
+    // `nearness` is 0 at the far end of the depth range and 1 at the near end
+    pub fn add(&mut self, index: usize, pixel: u32, nearness: f32) {
+        accumulate(
+            &mut self.accum[index],
+            &mut self.revealage[index],
+            pixel,
+            nearness,
+        );
+    }
+    // the sums of every pixel, for the renderer to hand out a row at a time
+    pub(crate) fn sums(&mut self) -> (&mut [[f32; 4]], &mut [f32]) {
+        (&mut self.accum, &mut self.revealage)
+    }
+    // composite over `buffer`, then start over for the next frame
+    pub fn resolve(&mut self, buffer: &mut [u32]) {
+        for (index, dst) in buffer.iter_mut().enumerate() {
+            let accum = &self.accum[index];
+            let coverage = 1.0 - self.revealage[index];
+            if accum[3] <= 0.0 || coverage <= 0.0 {
+                continue;
+            }
+            let mut new_pixel = 0xff000000;
+            for i in 0..3 {
+                let s = accum[i] / accum[3];
+                let d = ((*dst >> (8 * i)) & 0xff) as f32;
+                let part = s * coverage + d * (1.0 - coverage);
+                new_pixel |= ((part.round().clamp(0.0, 255.0) as u32) & 0xff) << (8 * i);
+            }
+            *dst = new_pixel;
+        }
+        self.clear();
+    }
+}
+
+// `WeightedOit::add` on the sums of one pixel
+pub(crate) fn accumulate(accum: &mut [f32; 4], revealage: &mut f32, pixel: u32, nearness: f32) {
+    let alpha = ((pixel >> 24) & 0xff) as f32 / 255.0;
+    if alpha <= 0.0 {
+        return;
+    }
+    let weight = alpha * (3e3 * nearness.clamp(0.0, 1.0).powi(3)).max(1e-2);
+    for (i, sum) in accum.iter_mut().take(3).enumerate() {
+        *sum += ((pixel >> (8 * i)) & 0xff) as f32 * alpha * weight;
+    }
+    accum[3] += alpha * weight;
+    *revealage *= 1.0 - alpha;
+}
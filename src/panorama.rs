@@ -0,0 +1,115 @@
+use std::f32::consts::PI;
+
+use crate::{
+    geometry::{Matrix4, Vector3},
+    image::Image,
+    probe::{CubeMap, ReflectionProbe},
+    scene::{Camera, Scene, SceneShader},
+};
+
+// a 360 degree view from a camera's eye, for panorama viewers and VR players:
+// the scene rendered into the six faces of a cube map, then resampled to an
+// equirectangular image twice as wide as it is high
+pub struct Panorama {
+    // of each cube face, about a quarter of the image width keeps the detail
+    pub face_size: u32,
+    pub background: u32,
+    // triangles reaching closer to the eye than this along a face are dropped
+    pub near: f32,
+}
+
+impl Panorama {
+    pub fn new(face_size: u32, background: u32) -> Self {
+        Self {
+            face_size,
+            background,
+            near: 0.05,
+        }
+    }
+    // `capture` from the camera's eye, then `equirectangular`
+    pub fn render(&self, scene: &Scene, camera: &Camera, width: u32) -> Image {
+        let cube = self.capture(scene, &camera.eye);
+        self.equirectangular(&cube, camera, width)
+    }
+    // the scene seen from `eye` in every direction. Like `CommandBuffer::record_scene`
+    // there is no lod and translucent materials are drawn in node order
+    pub fn capture(&self, scene: &Scene, eye: &Vector3) -> CubeMap {
+        let mut probe = ReflectionProbe::new(eye.clone(), 0.0, self.face_size);
+        probe.near = self.near;
+        let lights = scene.lights();
+        probe.capture(self.background, |renderer, face| {
+            for (id, node) in scene.nodes.iter().enumerate() {
+                let Some(model) = node.model else {
+                    continue;
+                };
+                if !scene.visible(id) {
+                    continue;
+                }
+                let model = &scene.models[model];
+                let material = match node.material {
+                    Some(material) => &scene.materials[material],
+                    None => model.material(),
+                };
+                let world = if node.skin.is_some() {
+                    Matrix4::identity()
+                } else {
+                    scene.world_transform(id)
+                };
+                let mut shader = SceneShader::new(
+                    model,
+                    material,
+                    &face.transform,
+                    &world,
+                    &lights,
+                    scene.ambient,
+                );
+                renderer.set_object(id);
+                face.draw_placed(renderer, model, &world, &mut shader);
+            }
+        });
+        probe.cube
+    }
+    // `width` x `width / 2`, longitude from -180 to 180 degrees left to right
+    // with the camera's view in the middle, latitude from the camera's up at the
+    // top to its down at the bottom. The view is leveled, only its heading counts
+    pub fn equirectangular(&self, cube: &CubeMap, camera: &Camera, width: u32) -> Image {
+        let height = (width / 2).max(1);
+        let up = camera.up.normalize();
+        let view = &camera.center - &camera.eye;
+        let mut forward = &view - &(view.dot(&up) * &up);
+        if forward.length() < 1e-6 {
+            // looking straight up or down, any heading will do
+            let axis = if up.x().abs() < 0.9 {
+                Vector3::new(1.0, 0.0, 0.0)
+            } else {
+                Vector3::new(0.0, 0.0, -1.0)
+            };
+            forward = up.cross(&axis).cross(&up);
+        }
+        let forward = forward.normalize();
+        let right = forward.cross(&up);
+        let mut buffer = Vec::with_capacity((width * height) as usize);
+        for y in 0..height {
+            let latitude = PI * (0.5 - (y as f32 + 0.5) / height as f32);
+            let (sin_lat, cos_lat) = latitude.sin_cos();
+            for x in 0..width {
+                let longitude = 2.0 * PI * ((x as f32 + 0.5) / width as f32 - 0.5);
+                let (sin_lon, cos_lon) = longitude.sin_cos();
+                let dir = cos_lat * cos_lon * &forward + cos_lat * sin_lon * &right + sin_lat * &up;
+                let color = cube.sample(&dir, 0.0);
+                // back from the linear rgb the cube map keeps
+                let mut pixel = 0xff000000;
+                for i in 0..3 {
+                    let value = color[i].clamp(0.0, 1.0).powf(1.0 / 2.2) * 255.0;
+                    pixel |= (value.round() as u32 & 0xff) << (8 * i);
+                }
+                buffer.push(pixel);
+            }
+        }
+        Image {
+            buffer,
+            width,
+            height,
+        }
+    }
+}
@@ -0,0 +1,480 @@
+use crate::{geometry::Matrix3, renderer::Renderer};
+
+// vertical samples per pixel row when filling, horizontal coverage is exact
+const SUBSAMPLES: usize = 4;
+// magic number for approximating a quarter circle with a cubic
+const KAPPA: f32 = 0.552_284_8;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FillRule {
+    NonZero,
+    EvenOdd,
+}
+
+#[derive(Clone, Debug)]
+pub struct StrokeStyle {
+    pub width: f32,
+    // alternating on/off lengths, empty for a solid line
+    pub dash: Vec<f32>,
+    pub dash_offset: f32,
+}
+
+impl StrokeStyle {
+    pub fn new(width: f32) -> Self {
+        Self {
+            width,
+            dash: Vec::new(),
+            dash_offset: 0.0,
+        }
+    }
+    pub fn dashed(width: f32, dash: &[f32]) -> Self {
+        Self {
+            width,
+            dash: dash.to_vec(),
+            dash_offset: 0.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum GradientShape {
+    // 0 at `from` to 1 at `to`, the same color across the line between them
+    Linear { from: (f32, f32), to: (f32, f32) },
+    // 0 at `center` to 1 at `radius` out
+    Radial { center: (f32, f32), radius: f32 },
+}
+
+// a color per pixel from `stops`, (offset, color) sorted by offset. Colors are
+// interpolated per channel alpha included, offsets past the ends take the end
+// colors
+#[derive(Clone, Debug)]
+pub struct Gradient {
+    pub shape: GradientShape,
+    pub stops: Vec<(f32, u32)>,
+}
+
+impl Gradient {
+    pub fn linear(from: (f32, f32), to: (f32, f32), start: u32, end: u32) -> Self {
+        Self {
+            shape: GradientShape::Linear { from, to },
+            stops: vec![(0.0, start), (1.0, end)],
+        }
+    }
+    pub fn radial(center: (f32, f32), radius: f32, inner: u32, outer: u32) -> Self {
+        Self {
+            shape: GradientShape::Radial { center, radius },
+            stops: vec![(0.0, inner), (1.0, outer)],
+        }
+    }
+    pub fn add_stop(&mut self, offset: f32, pixel: u32) -> &mut Self {
+        let i = self.stops.partition_point(|&(o, _)| o <= offset);
+        self.stops.insert(i, (offset, pixel));
+        self
+    }
+    pub fn offset(&self, x: f32, y: f32) -> f32 {
+        match self.shape {
+            GradientShape::Linear { from, to } => {
+                let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+                let length_square = dx * dx + dy * dy;
+                if length_square == 0.0 {
+                    return 0.0;
+                }
+                ((x - from.0) * dx + (y - from.1) * dy) / length_square
+            }
+            GradientShape::Radial { center, radius } => {
+                let d = ((x - center.0).powi(2) + (y - center.1).powi(2)).sqrt();
+                d / radius.max(f32::EPSILON)
+            }
+        }
+    }
+    // the color at pixel center (x, y)
+    pub fn color(&self, x: u32, y: u32) -> u32 {
+        self.color_at(x as f32 + 0.5, y as f32 + 0.5)
+    }
+    pub fn color_at(&self, x: f32, y: f32) -> u32 {
+        let t = self.offset(x, y);
+        let i = self.stops.partition_point(|&(o, _)| o <= t);
+        if i == 0 {
+            return self.stops.first().map_or(0, |s| s.1);
+        }
+        if i == self.stops.len() {
+            return self.stops[i - 1].1;
+        }
+        let ((o0, a), (o1, b)) = (self.stops[i - 1], self.stops[i]);
+        let f = (t - o0) / (o1 - o0);
+        (0..4).fold(0, |pixel, i| {
+            let ca = ((a >> (8 * i)) & 0xff) as f32;
+            let cb = ((b >> (8 * i)) & 0xff) as f32;
+            pixel | ((ca + (cb - ca) * f).round() as u32) << (8 * i)
+        })
+    }
+}
+
+#[derive(Clone, Default, Debug)]
+struct SubPath {
+    points: Vec<(f32, f32)>,
+    closed: bool,
+}
+
+// canvas style path in screen space, curves are flattened as they are added
+#[derive(Clone, Default, Debug)]
+pub struct Path {
+    subpaths: Vec<SubPath>,
+}
+
+impl Path {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn move_to(&mut self, x: f32, y: f32) -> &mut Self {
+        self.subpaths.push(SubPath {
+            points: vec![(x, y)],
+            closed: false,
+        });
+        self
+    }
+    pub fn line_to(&mut self, x: f32, y: f32) -> &mut Self {
+        match self.subpaths.last_mut() {
+            Some(subpath) if !subpath.closed => subpath.points.push((x, y)),
+            _ => {
+                self.move_to(x, y);
+            }
+        }
+        self
+    }
+    pub fn quad_to(&mut self, cx: f32, cy: f32, x: f32, y: f32) -> &mut Self {
+        let (x0, y0) = self.current();
+        let n = segments_for(&[(x0, y0), (cx, cy), (x, y)]);
+        for i in 1..=n {
+            let t = i as f32 / n as f32;
+            let mt = 1.0 - t;
+            self.line_to(
+                mt * mt * x0 + 2.0 * mt * t * cx + t * t * x,
+                mt * mt * y0 + 2.0 * mt * t * cy + t * t * y,
+            );
+        }
+        self
+    }
+    pub fn curve_to(
+        &mut self,
+        c1x: f32,
+        c1y: f32,
+        c2x: f32,
+        c2y: f32,
+        x: f32,
+        y: f32,
+    ) -> &mut Self {
+        let (x0, y0) = self.current();
+        let n = segments_for(&[(x0, y0), (c1x, c1y), (c2x, c2y), (x, y)]);
+        for i in 1..=n {
+            let t = i as f32 / n as f32;
+            let mt = 1.0 - t;
+            let (a, b, c, d) = (mt * mt * mt, 3.0 * mt * mt * t, 3.0 * mt * t * t, t * t * t);
+            self.line_to(
+                a * x0 + b * c1x + c * c2x + d * x,
+                a * y0 + b * c1y + c * c2y + d * y,
+            );
+        }
+        self
+    }
+    pub fn close(&mut self) -> &mut Self {
+        if let Some(subpath) = self.subpaths.last_mut() {
+            subpath.closed = true;
+        }
+        self
+    }
+    pub fn rect(&mut self, x: f32, y: f32, w: f32, h: f32) -> &mut Self {
+        self.move_to(x, y)
+            .line_to(x + w, y)
+            .line_to(x + w, y + h)
+            .line_to(x, y + h)
+            .close()
+    }
+    pub fn circle(&mut self, cx: f32, cy: f32, r: f32) -> &mut Self {
+        let k = r * KAPPA;
+        self.move_to(cx + r, cy)
+            .curve_to(cx + r, cy + k, cx + k, cy + r, cx, cy + r)
+            .curve_to(cx - k, cy + r, cx - r, cy + k, cx - r, cy)
+            .curve_to(cx - r, cy - k, cx - k, cy - r, cx, cy - r)
+            .curve_to(cx + k, cy - r, cx + r, cy - k, cx + r, cy)
+            .close()
+    }
+    fn current(&self) -> (f32, f32) {
+        self.subpaths
+            .last()
+            .and_then(|subpath| subpath.points.last().copied())
+            .unwrap_or((0.0, 0.0))
+    }
+    // cut every subpath into the "on" pieces of the dash pattern
+    pub fn dashed(&self, pattern: &[f32], offset: f32) -> Path {
+        let total: f32 = pattern.iter().sum();
+        assert!(
+            total > 0.0 && pattern.iter().all(|&d| d >= 0.0),
+            "invalid dash pattern"
+        );
+        let mut dashes = Path::new();
+        for subpath in self.subpaths.iter() {
+            let mut points = subpath.points.clone();
+            if subpath.closed {
+                points.push(points[0]);
+            }
+            // skip into the pattern by the offset
+            let mut index = 0;
+            let mut left = pattern[0];
+            let mut skip = offset.rem_euclid(total);
+            while skip > 0.0 {
+                if skip < left {
+                    left -= skip;
+                    break;
+                }
+                skip -= left;
+                index = (index + 1) % pattern.len();
+                left = pattern[index];
+            }
+            let mut pen_down = false;
+            if index % 2 == 0 {
+                dashes.move_to(points[0].0, points[0].1);
+                pen_down = true;
+            }
+            for w in points.windows(2) {
+                let ((x0, y0), (x1, y1)) = (w[0], w[1]);
+                let length = ((x1 - x0).powi(2) + (y1 - y0).powi(2)).sqrt();
+                let mut done = 0.0;
+                while length - done > left {
+                    done += left;
+                    let t = done / length;
+                    let (x, y) = (x0 + (x1 - x0) * t, y0 + (y1 - y0) * t);
+                    if pen_down {
+                        dashes.line_to(x, y);
+                    } else {
+                        dashes.move_to(x, y);
+                    }
+                    pen_down = !pen_down;
+                    index = (index + 1) % pattern.len();
+                    left = pattern[index];
+                }
+                left -= length - done;
+                if pen_down {
+                    dashes.line_to(x1, y1);
+                }
+            }
+        }
+        dashes
+    }
+    // the points through the renderer's `transform_2d`
+    fn transformed(&self, renderer: &Renderer) -> Path {
+        if !renderer.has_transform_2d() {
+            return self.clone();
+        }
+        self.transform(&renderer.transform_2d)
+    }
+    pub fn transform(&self, transform: &Matrix3) -> Path {
+        let mut path = self.clone();
+        for subpath in path.subpaths.iter_mut() {
+            for point in subpath.points.iter_mut() {
+                *point = transform.apply_2d(point.0, point.1);
+            }
+        }
+        path
+    }
+    pub fn fill(&self, renderer: &mut Renderer, pixel: u32, rule: FillRule) {
+        self.fill_with(renderer, rule, |_, _| pixel);
+    }
+    // `fill` with the color of each pixel from `gradient`, which goes through the
+    // renderer's `transform_2d` with the path
+    pub fn fill_gradient(&self, renderer: &mut Renderer, gradient: &Gradient, rule: FillRule) {
+        let Some(inverse) = renderer.transform_2d.inverse() else {
+            return;
+        };
+        self.fill_with(renderer, rule, |x, y| {
+            let (x, y) = inverse.apply_2d(x as f32 + 0.5, y as f32 + 0.5);
+            gradient.color_at(x, y)
+        });
+    }
+    // `fill` with `pixel(x, y)` for the color of each pixel
+    pub fn fill_with(
+        &self,
+        renderer: &mut Renderer,
+        rule: FillRule,
+        mut pixel: impl FnMut(u32, u32) -> u32,
+    ) {
+        let path = self.transformed(renderer);
+        // (x0, y0, x1, y1, direction) with y0 < y1, every subpath is closed implicitly
+        let mut edges = Vec::new();
+        for subpath in path.subpaths.iter() {
+            let points = &subpath.points;
+            for i in 0..points.len() {
+                let (x0, y0) = points[i];
+                let (x1, y1) = points[(i + 1) % points.len()];
+                if y0 < y1 {
+                    edges.push((x0, y0, x1, y1, 1));
+                } else if y1 < y0 {
+                    edges.push((x1, y1, x0, y0, -1));
+                }
+            }
+        }
+        if edges.is_empty() {
+            return;
+        }
+        let y_min = edges.iter().map(|e| e.1).fold(f32::MAX, f32::min);
+        let y_max = edges.iter().map(|e| e.3).fold(f32::MIN, f32::max);
+        let y_min = (y_min.floor() as i32).clamp(0, renderer.height as i32) as u32;
+        let y_max = (y_max.ceil() as i32).clamp(0, renderer.height as i32) as u32;
+
+        let mut coverage = vec![0.0; renderer.width as usize];
+        let mut crossings = Vec::new();
+        for y in y_min..y_max {
+            coverage.fill(0.0);
+            for s in 0..SUBSAMPLES {
+                let sy = y as f32 + (s as f32 + 0.5) / SUBSAMPLES as f32;
+                crossings.clear();
+                for &(x0, y0, x1, y1, direction) in edges.iter() {
+                    if y0 <= sy && sy < y1 {
+                        crossings.push((x0 + (sy - y0) * (x1 - x0) / (y1 - y0), direction));
+                    }
+                }
+                crossings.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+                let mut winding = 0;
+                for i in 0..crossings.len().saturating_sub(1) {
+                    winding += crossings[i].1;
+                    let inside = match rule {
+                        FillRule::NonZero => winding != 0,
+                        FillRule::EvenOdd => winding % 2 != 0,
+                    };
+                    if inside {
+                        add_span(
+                            &mut coverage,
+                            crossings[i].0,
+                            crossings[i + 1].0,
+                            1.0 / SUBSAMPLES as f32,
+                        );
+                    }
+                }
+            }
+            for (x, &c) in coverage.iter().enumerate() {
+                if c > 0.0 {
+                    let x = x as u32;
+                    renderer.blend_pixel_unchecked(x, y, pixel(x, y), c);
+                }
+            }
+        }
+    }
+    // round caps and joins, coverage comes from the distance to the centerline
+    // so overlapping segments don't blend twice
+    pub fn stroke(&self, renderer: &mut Renderer, pixel: u32, style: &StrokeStyle) {
+        let path = if style.dash.is_empty() {
+            self.transformed(renderer)
+        } else {
+            self.dashed(&style.dash, style.dash_offset)
+                .transformed(renderer)
+        };
+        let mut segments = Vec::new();
+        for subpath in path.subpaths.iter() {
+            let points = &subpath.points;
+            if points.len() == 1 {
+                segments.push((points[0], points[0]));
+            }
+            for w in points.windows(2) {
+                segments.push((w[0], w[1]));
+            }
+            if subpath.closed && points.len() > 2 {
+                segments.push((*points.last().unwrap(), points[0]));
+            }
+        }
+        if segments.is_empty() {
+            return;
+        }
+        let half = style.width * renderer.transform_2d.area_scale_2d().sqrt() / 2.0;
+        let reach = half + 1.0;
+        let clamp_x = |x: f32| (x as i32).clamp(0, renderer.width as i32) as u32;
+        let clamp_y = |y: f32| (y as i32).clamp(0, renderer.height as i32) as u32;
+        let bounds = |(a, b): &((f32, f32), (f32, f32))| {
+            (
+                clamp_x(a.0.min(b.0) - reach),
+                clamp_y(a.1.min(b.1) - reach),
+                clamp_x(a.0.max(b.0) + reach + 1.0),
+                clamp_y(a.1.max(b.1) + reach + 1.0),
+            )
+        };
+        let (x_min, y_min, x_max, y_max) =
+            segments
+                .iter()
+                .map(bounds)
+                .fold((u32::MAX, u32::MAX, 0, 0), |acc, b| {
+                    (
+                        acc.0.min(b.0),
+                        acc.1.min(b.1),
+                        acc.2.max(b.2),
+                        acc.3.max(b.3),
+                    )
+                });
+        if x_min >= x_max || y_min >= y_max {
+            return;
+        }
+        let w = (x_max - x_min) as usize;
+        let mut coverage = vec![0.0f32; w * (y_max - y_min) as usize];
+        for segment in segments.iter() {
+            let (sx0, sy0, sx1, sy1) = bounds(segment);
+            for y in sy0..sy1 {
+                for x in sx0..sx1 {
+                    let d = distance_to_segment((x as f32 + 0.5, y as f32 + 0.5), segment);
+                    let c = (half + 0.5 - d).clamp(0.0, 1.0);
+                    let i = (x - x_min) as usize + (y - y_min) as usize * w;
+                    coverage[i] = coverage[i].max(c);
+                }
+            }
+        }
+        for y in y_min..y_max {
+            for x in x_min..x_max {
+                let c = coverage[(x - x_min) as usize + (y - y_min) as usize * w];
+                if c > 0.0 {
+                    renderer.blend_pixel_unchecked(x, y, pixel, c);
+                }
+            }
+        }
+    }
+}
+
+// roughly one segment every 3 pixels of control polygon
+fn segments_for(points: &[(f32, f32)]) -> usize {
+    let length: f32 = points
+        .windows(2)
+        .map(|w| ((w[1].0 - w[0].0).powi(2) + (w[1].1 - w[0].1).powi(2)).sqrt())
+        .sum();
+    ((length / 3.0).ceil() as usize).clamp(2, 64)
+}
+
+fn add_span(coverage: &mut [f32], x0: f32, x1: f32, weight: f32) {
+    let end = coverage.len() as f32;
+    let (x0, x1) = (x0.clamp(0.0, end), x1.clamp(0.0, end));
+    if x1 <= x0 {
+        return;
+    }
+    let (i0, i1) = (x0.floor() as usize, x1.floor() as usize);
+    if i0 == i1 {
+        coverage[i0] += (x1 - x0) * weight;
+        return;
+    }
+    coverage[i0] += (i0 as f32 + 1.0 - x0) * weight;
+    for c in coverage[i0 + 1..i1].iter_mut() {
+        *c += weight;
+    }
+    if i1 < coverage.len() {
+        coverage[i1] += (x1 - i1 as f32) * weight;
+    }
+}
+
+fn distance_to_segment(
+    (px, py): (f32, f32),
+    &((x0, y0), (x1, y1)): &((f32, f32), (f32, f32)),
+) -> f32 {
+    let (dx, dy) = (x1 - x0, y1 - y0);
+    let length_square = dx * dx + dy * dy;
+    let t = if length_square > 0.0 {
+        (((px - x0) * dx + (py - y0) * dy) / length_square).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    let (cx, cy) = (x0 + dx * t - px, y0 + dy * t - py);
+    (cx * cx + cy * cy).sqrt()
+}
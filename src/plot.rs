@@ -0,0 +1,244 @@
+use std::collections::VecDeque;
+
+use crate::{
+    path::{FillRule, Path, StrokeStyle},
+    renderer::Renderer,
+    text::{measure_text, GLYPH_HEIGHT},
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub w: u32,
+    pub h: u32,
+}
+
+impl Rect {
+    pub fn new(x: i32, y: i32, w: u32, h: u32) -> Self {
+        Self { x, y, w, h }
+    }
+}
+
+pub struct Series<'a> {
+    pub values: &'a [f32],
+    pub color: u32,
+}
+
+// a graph drawn into `rect` of the framebuffer, values are mapped bottom (min)
+// to top (max) of the rect
+pub struct Plot {
+    pub rect: Rect,
+    // None fits the range to the data
+    pub range: Option<(f32, f32)>,
+    pub background: u32,
+    pub grid_color: u32,
+    // horizontal grid lines between min and max
+    pub grid_lines: u32,
+    pub label_color: u32,
+    // print min and max in the corner
+    pub labels: bool,
+}
+
+impl Plot {
+    pub fn new(rect: Rect) -> Self {
+        Self {
+            rect,
+            range: None,
+            background: 0xa0000000,
+            grid_color: 0x60ffffff,
+            grid_lines: 3,
+            label_color: 0xffc0c0c0,
+            labels: true,
+        }
+    }
+    fn resolve_range<'a>(
+        &self,
+        values: impl Iterator<Item = &'a f32>,
+        include_zero: bool,
+    ) -> (f32, f32) {
+        if let Some(range) = self.range {
+            return range;
+        }
+        let (mut min, mut max) = if include_zero {
+            (0.0, 0.0)
+        } else {
+            (f32::MAX, f32::MIN)
+        };
+        for &v in values.filter(|v| v.is_finite()) {
+            min = min.min(v);
+            max = max.max(v);
+        }
+        if min > max {
+            (0.0, 1.0)
+        } else if (max - min).abs() < f32::EPSILON {
+            (min - 0.5, max + 0.5)
+        } else {
+            (min, max)
+        }
+    }
+    fn y_of(&self, value: f32, (min, max): (f32, f32)) -> f32 {
+        let t = ((value - min) / (max - min)).clamp(0.0, 1.0);
+        self.rect.y as f32 + (1.0 - t) * self.rect.h as f32
+    }
+    fn frame(&self, renderer: &mut Renderer, range: (f32, f32)) {
+        let Rect { x, y, w, h } = self.rect;
+        let (x, y, w, h) = (x as f32, y as f32, w as f32, h as f32);
+        Path::new()
+            .rect(x, y, w, h)
+            .fill(renderer, self.background, FillRule::NonZero);
+        let mut grid = Path::new();
+        for i in 1..=self.grid_lines {
+            let gy = (y + h * i as f32 / (self.grid_lines + 1) as f32).floor() + 0.5;
+            grid.move_to(x, gy).line_to(x + w, gy);
+        }
+        grid.stroke(
+            renderer,
+            self.grid_color,
+            &StrokeStyle::dashed(1.0, &[2.0, 3.0]),
+        );
+        if self.labels {
+            let (min, max) = range;
+            let right = self.rect.x + self.rect.w as i32 - 2;
+            let max_label = format_value(max);
+            let min_label = format_value(min);
+            let (max_w, _) = measure_text(&max_label, 1);
+            let (min_w, _) = measure_text(&min_label, 1);
+            renderer.draw_text(
+                right - max_w as i32,
+                self.rect.y + 2,
+                &max_label,
+                self.label_color,
+                1,
+            );
+            let bottom = self.rect.y + self.rect.h as i32 - GLYPH_HEIGHT as i32 - 1;
+            renderer.draw_text(
+                right - min_w as i32,
+                bottom,
+                &min_label,
+                self.label_color,
+                1,
+            );
+        }
+    }
+    // every series is spread over the whole width
+    pub fn lines(&self, renderer: &mut Renderer, series: &[Series]) {
+        let range = self.resolve_range(series.iter().flat_map(|s| s.values.iter()), false);
+        self.frame(renderer, range);
+        let Rect { x, w, .. } = self.rect;
+        for s in series.iter() {
+            let n = s.values.len();
+            if n == 0 {
+                continue;
+            }
+            let mut line = Path::new();
+            for (i, &value) in s.values.iter().enumerate() {
+                let t = if n > 1 {
+                    i as f32 / (n - 1) as f32
+                } else {
+                    0.5
+                };
+                let px = x as f32 + t * w as f32;
+                let py = self.y_of(value, range);
+                if i == 0 {
+                    line.move_to(px, py);
+                } else {
+                    line.line_to(px, py);
+                }
+            }
+            line.stroke(renderer, s.color, &StrokeStyle::new(1.5));
+        }
+    }
+    // bars grow from zero (or the bottom of the range if zero is outside it)
+    pub fn bars(&self, renderer: &mut Renderer, values: &[f32], color: u32) {
+        let range = self.resolve_range(values.iter(), true);
+        self.frame(renderer, range);
+        if values.is_empty() {
+            return;
+        }
+        let Rect { x, w, .. } = self.rect;
+        let slot = w as f32 / values.len() as f32;
+        let gap = if slot >= 4.0 { 1.0 } else { 0.0 };
+        let base = self.y_of(0.0, range);
+        let mut bars = Path::new();
+        for (i, &value) in values.iter().enumerate() {
+            let top = self.y_of(value, range);
+            let left = x as f32 + i as f32 * slot + gap;
+            bars.rect(left, top.min(base), slot - 2.0 * gap, (top - base).abs());
+        }
+        bars.fill(renderer, color, FillRule::NonZero);
+    }
+}
+
+// fixed size ring of the latest samples, e.g. frame times for a HUD
+pub struct TimeSeries {
+    values: VecDeque<f32>,
+    pub capacity: usize,
+}
+
+impl TimeSeries {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            values: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+    // `capacity` may have shrunk since the last push, and 0 still keeps one
+    pub fn push(&mut self, value: f32) {
+        while self.values.len() >= self.capacity.max(1) {
+            self.values.pop_front();
+        }
+        self.values.push_back(value);
+    }
+    pub fn values(&self) -> impl Iterator<Item = f32> + '_ {
+        self.values.iter().copied()
+    }
+    pub fn latest(&self) -> Option<f32> {
+        self.values.back().copied()
+    }
+    pub fn min(&self) -> f32 {
+        self.values().fold(f32::MAX, f32::min)
+    }
+    pub fn max(&self) -> f32 {
+        self.values().fold(f32::MIN, f32::max)
+    }
+    pub fn mean(&self) -> f32 {
+        if self.values.is_empty() {
+            0.0
+        } else {
+            self.values().sum::<f32>() / self.values.len() as f32
+        }
+    }
+    // newest sample at the right edge, the graph scrolls left as samples come in
+    pub fn draw(&self, plot: &Plot, renderer: &mut Renderer, color: u32) {
+        let range = plot.resolve_range(self.values.iter(), false);
+        plot.frame(renderer, range);
+        let Rect { x, w, .. } = plot.rect;
+        let first_slot = self.capacity.saturating_sub(self.values.len());
+        let mut line = Path::new();
+        for (i, value) in self.values().enumerate() {
+            let t = (first_slot + i) as f32 / (self.capacity.max(2) - 1) as f32;
+            let (px, py) = (x as f32 + t * w as f32, plot.y_of(value, range));
+            if i == 0 {
+                line.move_to(px, py);
+            } else {
+                line.line_to(px, py);
+            }
+        }
+        line.stroke(renderer, color, &StrokeStyle::new(1.5));
+        if let (true, Some(latest)) = (plot.labels, self.latest()) {
+            let label = format_value(latest);
+            renderer.draw_text(x + 2, plot.rect.y + 2, &label, color, 1);
+        }
+    }
+}
+
+fn format_value(value: f32) -> String {
+    if value.abs() >= 100.0 {
+        format!("{value:.0}")
+    } else if value.abs() >= 1.0 {
+        format!("{value:.1}")
+    } else {
+        format!("{value:.3}")
+    }
+}
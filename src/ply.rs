@@ -0,0 +1,282 @@
+use std::{f32::consts::PI, fs, path::Path};
+
+use crate::{
+    geometry::{Vector2, Vector3},
+    model::Model,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Type {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl Type {
+    fn parse(name: &str) -> Self {
+        match name {
+            "char" | "int8" => Type::I8,
+            "uchar" | "uint8" => Type::U8,
+            "short" | "int16" => Type::I16,
+            "ushort" | "uint16" => Type::U16,
+            "int" | "int32" => Type::I32,
+            "uint" | "uint32" => Type::U32,
+            "float" | "float32" => Type::F32,
+            "double" | "float64" => Type::F64,
+            _ => panic!("unknown ply type {name}"),
+        }
+    }
+    fn size(self) -> usize {
+        match self {
+            Type::I8 | Type::U8 => 1,
+            Type::I16 | Type::U16 => 2,
+            Type::I32 | Type::U32 | Type::F32 => 4,
+            Type::F64 => 8,
+        }
+    }
+    // color channels are stored either as integers or as [0, 1] floats
+    fn to_channel(self, value: f64) -> u32 {
+        let value = match self {
+            Type::F32 | Type::F64 => value * 255.0,
+            Type::U16 => value / 257.0,
+            _ => value,
+        };
+        value.round().clamp(0.0, 255.0) as u32
+    }
+}
+
+struct Property {
+    name: String,
+    // Some(count type) for list properties
+    list: Option<Type>,
+    ty: Type,
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+struct Reader<'a> {
+    format: Format,
+    body: &'a [u8],
+    offset: usize,
+    tokens: std::str::SplitAsciiWhitespace<'a>,
+}
+
+impl Reader<'_> {
+    fn read(&mut self, ty: Type) -> f64 {
+        if self.format == Format::Ascii {
+            return self.tokens.next().unwrap().parse().unwrap();
+        }
+        let bytes = &self.body[self.offset..self.offset + ty.size()];
+        self.offset += ty.size();
+        macro_rules! decode {
+            ($t:ty) => {{
+                let bytes = bytes.try_into().unwrap();
+                if self.format == Format::BinaryLittleEndian {
+                    <$t>::from_le_bytes(bytes) as f64
+                } else {
+                    <$t>::from_be_bytes(bytes) as f64
+                }
+            }};
+        }
+        match ty {
+            Type::I8 => decode!(i8),
+            Type::U8 => decode!(u8),
+            Type::I16 => decode!(i16),
+            Type::U16 => decode!(u16),
+            Type::I32 => decode!(i32),
+            Type::U32 => decode!(u32),
+            Type::F32 => decode!(f32),
+            Type::F64 => decode!(f64),
+        }
+    }
+}
+
+impl Model {
+    // ascii or binary ply with positions and optional normals, uvs and vertex colors,
+    // missing normals are generated smooth
+    pub fn from_ply(path: impl AsRef<Path>) -> Self {
+        let bytes = fs::read(path).unwrap();
+        let header_end = bytes
+            .windows(10)
+            .position(|w| w == b"end_header")
+            .expect("ply header is missing end_header");
+        let body_start = bytes[header_end..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .map(|i| header_end + i + 1)
+            .unwrap_or(bytes.len());
+        let header = std::str::from_utf8(&bytes[..header_end]).unwrap();
+
+        let mut format = Format::Ascii;
+        let mut elements: Vec<Element> = Vec::new();
+        for line in header.lines() {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            match parts.first() {
+                Some(&"format") => {
+                    format = match parts[1] {
+                        "ascii" => Format::Ascii,
+                        "binary_little_endian" => Format::BinaryLittleEndian,
+                        "binary_big_endian" => Format::BinaryBigEndian,
+                        other => panic!("unknown ply format {other}"),
+                    }
+                }
+                Some(&"element") => elements.push(Element {
+                    name: parts[1].to_string(),
+                    count: parts[2].parse().unwrap(),
+                    properties: Vec::new(),
+                }),
+                Some(&"property") => {
+                    let property = if parts[1] == "list" {
+                        Property {
+                            name: parts[4].to_string(),
+                            list: Some(Type::parse(parts[2])),
+                            ty: Type::parse(parts[3]),
+                        }
+                    } else {
+                        Property {
+                            name: parts[2].to_string(),
+                            list: None,
+                            ty: Type::parse(parts[1]),
+                        }
+                    };
+                    elements.last_mut().unwrap().properties.push(property);
+                }
+                _ => {}
+            }
+        }
+
+        let body = &bytes[body_start..];
+        let text = if format == Format::Ascii {
+            std::str::from_utf8(body).unwrap()
+        } else {
+            ""
+        };
+        let mut reader = Reader {
+            format,
+            body,
+            offset: 0,
+            tokens: text.split_ascii_whitespace(),
+        };
+
+        let mut verts = Vec::new();
+        let mut norms = Vec::new();
+        let mut tex_coord = Vec::new();
+        let mut colors = Vec::new();
+        let mut facet_vrt = Vec::new();
+        for element in elements.iter() {
+            let has = |names: &[&str]| {
+                element
+                    .properties
+                    .iter()
+                    .any(|p| names.contains(&p.name.as_str()))
+            };
+            let has_normals = has(&["nx"]);
+            let has_uvs = has(&["u", "s", "texture_u", "texture_s"]);
+            let has_colors = has(&["red", "diffuse_red", "r"]);
+            for _ in 0..element.count {
+                let mut v = Vector3::zero();
+                let mut n = Vector3::zero();
+                let mut uv = Vector2::zero();
+                let mut color = 0xff000000u32;
+                let mut face = Vec::new();
+                for property in element.properties.iter() {
+                    if let Some(count_type) = property.list {
+                        let count = reader.read(count_type) as usize;
+                        let values: Vec<f64> =
+                            (0..count).map(|_| reader.read(property.ty)).collect();
+                        if matches!(property.name.as_str(), "vertex_indices" | "vertex_index") {
+                            face = values.into_iter().map(|i| i as usize).collect();
+                        }
+                        continue;
+                    }
+                    let value = reader.read(property.ty);
+                    match property.name.as_str() {
+                        "x" => v[0] = value as f32,
+                        "y" => v[1] = value as f32,
+                        "z" => v[2] = value as f32,
+                        "nx" => n[0] = value as f32,
+                        "ny" => n[1] = value as f32,
+                        "nz" => n[2] = value as f32,
+                        "u" | "s" | "texture_u" | "texture_s" => uv[0] = value as f32,
+                        "v" | "t" | "texture_v" | "texture_t" => uv[1] = value as f32,
+                        "red" | "diffuse_red" | "r" => color |= property.ty.to_channel(value),
+                        "green" | "diffuse_green" | "g" => {
+                            color |= property.ty.to_channel(value) << 8
+                        }
+                        "blue" | "diffuse_blue" | "b" => {
+                            color |= property.ty.to_channel(value) << 16
+                        }
+                        "alpha" | "a" => {
+                            color = (color & 0x00ffffff) | property.ty.to_channel(value) << 24
+                        }
+                        _ => {}
+                    }
+                }
+                match element.name.as_str() {
+                    "vertex" => {
+                        verts.push(v);
+                        if has_normals {
+                            norms.push(n);
+                        }
+                        if has_uvs {
+                            tex_coord.push(uv);
+                        }
+                        if has_colors {
+                            colors.push(color);
+                        }
+                    }
+                    "face" => {
+                        for i in 1..face.len().saturating_sub(1) {
+                            facet_vrt.extend([face[0], face[i], face[i + 1]]);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        eprintln!(
+            "# v# {} f# {} vt# {} vn# {} vc# {}",
+            verts.len(),
+            facet_vrt.len() / 3,
+            tex_coord.len(),
+            norms.len(),
+            colors.len()
+        );
+
+        let facet_tex = if tex_coord.is_empty() {
+            tex_coord.push(Vector2::zero());
+            vec![0; facet_vrt.len()]
+        } else {
+            facet_vrt.clone()
+        };
+        let generate_normals = norms.is_empty();
+        if generate_normals {
+            norms = vec![Vector3::zero(); verts.len()];
+        }
+        let facet_nrm = facet_vrt.clone();
+        let mut model = Self::from_parts(verts, tex_coord, norms, facet_vrt, facet_tex, facet_nrm);
+        if generate_normals {
+            model.generate_normals(PI);
+        }
+        if !colors.is_empty() {
+            model.set_colors(colors);
+        }
+        model
+    }
+}
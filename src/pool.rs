@@ -0,0 +1,102 @@
+use std::{
+    any::Any,
+    cell::Cell,
+    panic::{catch_unwind, resume_unwind, AssertUnwindSafe},
+    sync::{
+        mpsc::{channel, Sender},
+        Mutex, OnceLock,
+    },
+    thread,
+};
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+// worker threads started on first use and kept for the rest of the process,
+// the renderer deals the rows of large triangles out to them. More are
+// started whenever a caller asks for more than there are
+struct WorkerPool {
+    workers: Mutex<Vec<Sender<Job>>>,
+}
+
+static POOL: OnceLock<WorkerPool> = OnceLock::new();
+
+thread_local! {
+    static IN_WORKER: Cell<bool> = const { Cell::new(false) };
+}
+
+// runs `local` on the calling thread and `jobs` on workers of the pool, and
+// returns their results in that order. Jobs may borrow from the caller since
+// this only returns once all of them are done, and a panic in any of them is
+// raised here after that. Called from a worker, e.g. a shader drawing with a
+// renderer of its own, the jobs run one after another on it instead
+pub(crate) fn run<'a, T: Send + 'static>(
+    local: impl FnOnce() -> T,
+    jobs: Vec<Box<dyn FnOnce() -> T + Send + 'a>>,
+) -> Vec<T> {
+    if jobs.is_empty() || IN_WORKER.with(Cell::get) {
+        let first = local();
+        return core::iter::once(first)
+            .chain(jobs.into_iter().map(|job| job()))
+            .collect();
+    }
+    let (done, results) = channel::<(usize, thread::Result<T>)>();
+    let sent = {
+        let pool = POOL.get_or_init(|| WorkerPool {
+            workers: Mutex::new(Vec::new()),
+        });
+        let mut workers = pool.workers.lock().unwrap_or_else(|e| e.into_inner());
+        while workers.len() < jobs.len() {
+            let (sender, receiver) = channel::<Job>();
+            thread::Builder::new()
+                .name(format!("olive3d-worker-{}", workers.len()))
+                .spawn(move || {
+                    IN_WORKER.with(|w| w.set(true));
+                    for job in receiver {
+                        job();
+                    }
+                })
+                .unwrap();
+            workers.push(sender);
+        }
+        let mut sent = 0;
+        for ((i, job), worker) in jobs.into_iter().enumerate().zip(workers.iter()) {
+            let done = done.clone();
+            let job = Box::new(move || {
+                // the job and what it borrows are gone before the caller hears
+                // back and returns
+                let result = catch_unwind(AssertUnwindSafe(job));
+                let _ = done.send((i + 1, result));
+            });
+            // SAFETY: `run` waits below for every job sent to report back, so
+            // nothing the job borrows for 'a is touched after it is dropped
+            let job: Job =
+                unsafe { core::mem::transmute::<Box<dyn FnOnce() + Send + 'a>, Job>(job) };
+            worker.send(job).unwrap();
+            sent += 1;
+        }
+        sent
+    };
+    drop(done);
+    let first = catch_unwind(AssertUnwindSafe(local));
+    let mut out: Vec<Option<thread::Result<T>>> = (0..=sent).map(|_| None).collect();
+    out[0] = Some(first);
+    for _ in 0..sent {
+        let (i, result) = results.recv().unwrap();
+        out[i] = Some(result);
+    }
+    let mut panic: Option<Box<dyn Any + Send>> = None;
+    let values = out
+        .into_iter()
+        .filter_map(|result| match result.unwrap() {
+            Ok(value) => Some(value),
+            Err(payload) => {
+                panic.get_or_insert(payload);
+                None
+            }
+        })
+        .collect();
+    if let Some(payload) = panic {
+        resume_unwind(payload);
+    }
+    values
+}
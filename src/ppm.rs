@@ -4,6 +4,8 @@ use std::{
     path::Path,
 };
 
+pub use crate::image::Image;
+
 pub fn save_buffer_to_ppm_file(
     buffer: &[u32],
     width: u32,
@@ -12,8 +14,19 @@ pub fn save_buffer_to_ppm_file(
     path: impl AsRef<Path>,
 ) -> std::io::Result<()> {
     let file = File::create(path)?;
-    let mut file = BufWriter::new(file);
-    write!(file, "P6\n{} {} 255\n", width, height)?;
+    write_ppm(buffer, width, height, stride, &mut BufWriter::new(file))
+}
+
+// one binary ppm image; several in a row make a stream video tools can read,
+// e.g. `ffmpeg -f image2pipe -c:v ppm -i - out.mp4`
+pub fn write_ppm(
+    buffer: &[u32],
+    width: u32,
+    height: u32,
+    stride: u32,
+    out: &mut impl Write,
+) -> std::io::Result<()> {
+    write!(out, "P6\n{} {} 255\n", width, height)?;
     for y in 0..height {
         for x in 0..width {
             let pixel = buffer[(y * stride + x) as usize];
@@ -22,7 +35,7 @@ pub fn save_buffer_to_ppm_file(
                 ((pixel >> 8) & 0xFF) as u8,
                 ((pixel >> (8 * 2)) & 0xFF) as u8,
             ];
-            file.write_all(&rgb)?;
+            out.write_all(&rgb)?;
         }
     }
     Ok(())
@@ -76,7 +89,7 @@ pub fn load_ppm_file_to_buffer(path: impl AsRef<Path>) -> Image {
             }
         }
     }
-    println!("load ppm, width: {width}, height: {height}, max_val: {max_val}");
+    eprintln!("load ppm, width: {width}, height: {height}, max_val: {max_val}");
     let mut buffer: Vec<u32> = Vec::with_capacity((width * height) as usize);
     let mut rgb = [0u8; 3];
     while file.read_exact(&mut rgb).is_ok() {
@@ -93,21 +106,3 @@ pub fn load_ppm_file_to_buffer(path: impl AsRef<Path>) -> Image {
         height,
     }
 }
-
-pub struct Image {
-    pub buffer: Vec<u32>,
-    pub width: u32,
-    pub height: u32,
-}
-
-impl Image {
-    pub fn vflip(&mut self) {
-        for y in 0..self.height / 2 {
-            for x in 0..self.width {
-                let a = x + y * self.width;
-                let b = x + (self.height - 1 - y) * self.width;
-                self.buffer.swap(a as usize, b as usize);
-            }
-        }
-    }
-}
@@ -0,0 +1,240 @@
+use std::{collections::HashMap, f32::consts::PI};
+
+use crate::{
+    geometry::{Vector2, Vector3},
+    model::Model,
+};
+
+// all primitives are centered on the origin and fit in [-1, 1] like a normalized
+// obj, faces wind counter-clockwise seen from outside
+#[derive(Default)]
+struct MeshBuilder {
+    verts: Vec<Vector3>,
+    tex_coord: Vec<Vector2>,
+    norms: Vec<Vector3>,
+    facets: Vec<usize>,
+}
+
+impl MeshBuilder {
+    fn vertex(&mut self, pos: Vector3, uv: Vector2, normal: Vector3) -> usize {
+        self.verts.push(pos);
+        self.tex_coord.push(uv);
+        self.norms.push(normal);
+        self.verts.len() - 1
+    }
+    // a (cols + 1) x (rows + 1) patch, `surface(u, v)` returns (position, normal)
+    // with d/du x d/dv pointing outwards
+    fn grid(&mut self, cols: usize, rows: usize, surface: impl Fn(f32, f32) -> (Vector3, Vector3)) {
+        let start = self.verts.len();
+        for r in 0..=rows {
+            for c in 0..=cols {
+                let u = c as f32 / cols as f32;
+                let v = r as f32 / rows as f32;
+                let (pos, normal) = surface(u, v);
+                self.vertex(pos, Vector2::new(u, v), normal);
+            }
+        }
+        for r in 0..rows {
+            for c in 0..cols {
+                let a = start + c + r * (cols + 1);
+                let b = a + 1;
+                let d = a + cols + 1;
+                let e = d + 1;
+                self.facets.extend([a, b, d, b, e, d]);
+            }
+        }
+    }
+    fn build(self) -> Model {
+        // drop the zero area triangles grids produce at poles
+        let facets: Vec<usize> = self
+            .facets
+            .chunks(3)
+            .filter(|f| {
+                let (a, b, c) = (&self.verts[f[0]], &self.verts[f[1]], &self.verts[f[2]]);
+                (b - a).cross(&(c - a)).length_square() > 1e-12
+            })
+            .flatten()
+            .copied()
+            .collect();
+        Model::from_parts(
+            self.verts,
+            self.tex_coord,
+            self.norms,
+            facets.clone(),
+            facets.clone(),
+            facets,
+        )
+    }
+}
+
+impl Model {
+    pub fn cube() -> Self {
+        let x = Vector3::new(1.0, 0.0, 0.0);
+        let y = Vector3::new(0.0, 1.0, 0.0);
+        let z = Vector3::new(0.0, 0.0, 1.0);
+        // (normal, u axis, v axis) with u x v == normal
+        let faces = [
+            (x.clone(), -&z, y.clone()),
+            (-&x, z.clone(), y.clone()),
+            (y.clone(), x.clone(), -&z),
+            (-&y, x.clone(), z.clone()),
+            (z.clone(), x.clone(), y.clone()),
+            (-&z, -&x, y.clone()),
+        ];
+        let mut builder = MeshBuilder::default();
+        for (n, u_axis, v_axis) in faces {
+            builder.grid(1, 1, |u, v| {
+                let pos = &n + &u_axis * (2.0 * u - 1.0) + &v_axis * (2.0 * v - 1.0);
+                (pos, n.clone())
+            });
+        }
+        builder.build()
+    }
+    // the xz plane facing +y
+    pub fn plane(subdivisions: usize) -> Self {
+        let n = subdivisions.max(1);
+        let mut builder = MeshBuilder::default();
+        builder.grid(n, n, |u, v| {
+            (
+                Vector3::new(2.0 * u - 1.0, 0.0, 1.0 - 2.0 * v),
+                Vector3::new(0.0, 1.0, 0.0),
+            )
+        });
+        builder.build()
+    }
+    pub fn uv_sphere(stacks: usize, slices: usize) -> Self {
+        let mut builder = MeshBuilder::default();
+        builder.grid(slices.max(3), stacks.max(2), |u, v| {
+            let theta = PI * (1.0 - v);
+            let phi = 2.0 * PI * u;
+            let n = Vector3::new(
+                theta.sin() * phi.sin(),
+                theta.cos(),
+                theta.sin() * phi.cos(),
+            );
+            (n.clone(), n)
+        });
+        builder.build()
+    }
+    // subdivided icosahedron, evenly spread triangles unlike the uv sphere
+    pub fn icosphere(subdivisions: usize) -> Self {
+        let t = (1.0 + 5f32.sqrt()) / 2.0;
+        let mut verts: Vec<Vector3> = [
+            (-1.0, t, 0.0),
+            (1.0, t, 0.0),
+            (-1.0, -t, 0.0),
+            (1.0, -t, 0.0),
+            (0.0, -1.0, t),
+            (0.0, 1.0, t),
+            (0.0, -1.0, -t),
+            (0.0, 1.0, -t),
+            (t, 0.0, -1.0),
+            (t, 0.0, 1.0),
+            (-t, 0.0, -1.0),
+            (-t, 0.0, 1.0),
+        ]
+        .into_iter()
+        .map(|(x, y, z)| Vector3::new(x, y, z).normalize())
+        .collect();
+        #[rustfmt::skip]
+        let mut faces: Vec<[usize; 3]> = vec![
+            [0, 11, 5], [0, 5, 1], [0, 1, 7], [0, 7, 10], [0, 10, 11],
+            [1, 5, 9], [5, 11, 4], [11, 10, 2], [10, 7, 6], [7, 1, 8],
+            [3, 9, 4], [3, 4, 2], [3, 2, 6], [3, 6, 8], [3, 8, 9],
+            [4, 9, 5], [2, 4, 11], [6, 2, 10], [8, 6, 7], [9, 8, 1],
+        ];
+        for _ in 0..subdivisions {
+            let mut midpoints = HashMap::new();
+            let mut midpoint = |a: usize, b: usize| {
+                *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                    verts.push(((&verts[a] + &verts[b]) * 0.5).normalize());
+                    verts.len() - 1
+                })
+            };
+            faces = faces
+                .into_iter()
+                .flat_map(|[a, b, c]| {
+                    let ab = midpoint(a, b);
+                    let bc = midpoint(b, c);
+                    let ca = midpoint(c, a);
+                    [[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]
+                })
+                .collect();
+        }
+        // spherical uvs per corner so triangles across the seam can wrap
+        let mut tex_coord = Vec::with_capacity(faces.len() * 3);
+        for face in faces.iter() {
+            let mut uvs = face.map(|i| {
+                let p = &verts[i];
+                Vector2::new(
+                    0.5 + p.x().atan2(p.z()) / (2.0 * PI),
+                    0.5 + p.y().clamp(-1.0, 1.0).asin() / PI,
+                )
+            });
+            let max_u = uvs.iter().map(|uv| uv.x()).fold(f32::MIN, f32::max);
+            for uv in uvs.iter_mut() {
+                if max_u - uv.x() > 0.5 {
+                    uv[0] += 1.0;
+                }
+            }
+            tex_coord.extend(uvs);
+        }
+        let facet_vrt: Vec<usize> = faces.into_iter().flatten().collect();
+        let facet_tex = (0..facet_vrt.len()).collect();
+        let norms = verts.clone();
+        Model::from_parts(
+            verts,
+            tex_coord,
+            norms,
+            facet_vrt.clone(),
+            facet_tex,
+            facet_vrt,
+        )
+    }
+    // around the y axis, `major_radius + minor_radius` should stay within 1
+    pub fn torus(major_radius: f32, minor_radius: f32, rings: usize, sides: usize) -> Self {
+        let mut builder = MeshBuilder::default();
+        builder.grid(rings.max(3), sides.max(3), |u, v| {
+            let alpha = 2.0 * PI * u;
+            let beta = 2.0 * PI * v;
+            let n = Vector3::new(
+                beta.cos() * alpha.sin(),
+                beta.sin(),
+                beta.cos() * alpha.cos(),
+            );
+            let center = Vector3::new(alpha.sin(), 0.0, alpha.cos()) * major_radius;
+            (center + &n * minor_radius, n)
+        });
+        builder.build()
+    }
+    // radius 1 along the y axis from -1 to 1, capped
+    pub fn cylinder(segments: usize) -> Self {
+        let segments = segments.max(3);
+        let mut builder = MeshBuilder::default();
+        builder.grid(segments, 1, |u, v| {
+            let alpha = 2.0 * PI * u;
+            let n = Vector3::new(alpha.sin(), 0.0, alpha.cos());
+            (Vector3::new(n.x(), 2.0 * v - 1.0, n.z()), n)
+        });
+        for y in [1.0f32, -1.0] {
+            let n = Vector3::new(0.0, y, 0.0);
+            let center = builder.vertex(n.clone(), Vector2::new(0.5, 0.5), n.clone());
+            let rim: Vec<usize> = (0..=segments)
+                .map(|i| {
+                    let alpha = 2.0 * PI * i as f32 / segments as f32;
+                    let (x, z) = (alpha.sin(), alpha.cos());
+                    let uv = Vector2::new(0.5 + 0.5 * x, 0.5 - 0.5 * z * y);
+                    builder.vertex(Vector3::new(x, y, z), uv, n.clone())
+                })
+                .collect();
+            for w in rim.windows(2) {
+                if y > 0.0 {
+                    builder.facets.extend([center, w[0], w[1]]);
+                } else {
+                    builder.facets.extend([center, w[1], w[0]]);
+                }
+            }
+        }
+        builder.build()
+    }
+}
@@ -0,0 +1,272 @@
+use crate::{
+    geometry::{m2v, v2m, Matrix4, Vector3},
+    model::Model,
+    renderer::{lookat, projection, viewport, Renderer, Shader},
+    shaders::srgb_to_linear,
+};
+
+// +x, -x, +y, -y, +z, -z
+const FACE_DIRS: [[f32; 3]; 6] = [
+    [1.0, 0.0, 0.0],
+    [-1.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0],
+    [0.0, -1.0, 0.0],
+    [0.0, 0.0, 1.0],
+    [0.0, 0.0, -1.0],
+];
+
+// forward, right and up of a cube face, the same frame `lookat` builds
+fn face_frame(face: usize) -> (Vector3, Vector3, Vector3) {
+    let [x, y, z] = FACE_DIRS[face];
+    let forward = Vector3::new(x, y, z);
+    let up = if face == 2 || face == 3 {
+        Vector3::new(0.0, 0.0, -y)
+    } else {
+        Vector3::new(0.0, 1.0, 0.0)
+    };
+    let back = -&forward;
+    let right = up.cross(&back).normalize();
+    let up = back.cross(&right).normalize();
+    (forward, right, up)
+}
+
+// one face being captured, hands out the transform for the scene and culls
+// triangles reaching behind the probe, which a 90 degree frustum can't project
+pub struct ProbeFace {
+    pub index: usize,
+    pub transform: Matrix4,
+    pub eye: Vector3,
+    pub forward: Vector3,
+    pub near: f32,
+}
+
+impl ProbeFace {
+    // `model` in world space, `shader` projecting it with `self.transform`
+    pub fn draw(&self, renderer: &mut Renderer, model: &Model, shader: &mut impl Shader) {
+        for i in 0..model.nfaces() {
+            let in_front =
+                (0..3).all(|j| (&model.vert(i, j) - &self.eye).dot(&self.forward) > self.near);
+            if !in_front {
+                continue;
+            }
+            let screen_coords = [0, 1, 2].map(|j| shader.vertex(i, j));
+            renderer.fill_triangle(&screen_coords, shader);
+        }
+    }
+    // like `draw` for `model` placed in the world by `world`
+    pub fn draw_placed(
+        &self,
+        renderer: &mut Renderer,
+        model: &Model,
+        world: &Matrix4,
+        shader: &mut impl Shader,
+    ) {
+        for i in 0..model.nfaces() {
+            let in_front = (0..3).all(|j| {
+                let vert = m2v(&(world * v2m(&model.vert(i, j))));
+                (&vert - &self.eye).dot(&self.forward) > self.near
+            });
+            if !in_front {
+                continue;
+            }
+            let screen_coords = [0, 1, 2].map(|j| shader.vertex(i, j));
+            renderer.fill_triangle(&screen_coords, shader);
+        }
+    }
+}
+
+// six square faces in linear rgb, each with a chain of box filtered levels
+// standing in for increasingly rough reflections
+pub struct CubeMap {
+    pub size: u32,
+    // levels[lod][face], level lod is (size >> lod) pixels wide
+    levels: Vec<[Vec<Vector3>; 6]>,
+}
+
+impl CubeMap {
+    pub fn new(size: u32) -> Self {
+        let size = size.max(1);
+        let mut cube = Self {
+            size,
+            levels: Vec::new(),
+        };
+        cube.levels.push(std::array::from_fn(|_| {
+            vec![Vector3::zero(); (size * size) as usize]
+        }));
+        cube
+    }
+    pub fn lods(&self) -> usize {
+        self.levels.len()
+    }
+    // replace face `face` from an sRGB framebuffer of size x size pixels
+    pub fn set_face(&mut self, face: usize, pixels: &[u32]) {
+        self.levels.truncate(1);
+        self.levels[0][face] = pixels.iter().map(|&p| srgb_to_linear(p)).collect();
+    }
+    // rebuild the lower levels after the faces changed
+    pub fn build_lods(&mut self) {
+        self.levels.truncate(1);
+        let mut size = self.size;
+        while size > 1 {
+            let half = size / 2;
+            let prev = self.levels.last().unwrap();
+            let next = std::array::from_fn(|face| {
+                let src = &prev[face];
+                let mut dst = vec![Vector3::zero(); (half * half) as usize];
+                for y in 0..half {
+                    for x in 0..half {
+                        let at =
+                            |dx: u32, dy: u32| &src[((x * 2 + dx) + (y * 2 + dy) * size) as usize];
+                        dst[(x + y * half) as usize] =
+                            (at(0, 0) + at(1, 0) + at(0, 1) + at(1, 1)) * 0.25;
+                    }
+                }
+                dst
+            });
+            self.levels.push(next);
+            size = half;
+        }
+    }
+    // `dir` needn't be normalized, `lod` in [0, lods - 1] blends between levels
+    pub fn sample(&self, dir: &Vector3, lod: f32) -> Vector3 {
+        let lod = lod.clamp(0.0, (self.lods() - 1) as f32);
+        let lo = lod.floor() as usize;
+        let hi = (lo + 1).min(self.lods() - 1);
+        let t = lod - lo as f32;
+        let a = self.sample_level(dir, lo);
+        if t == 0.0 || lo == hi {
+            return a;
+        }
+        a * (1.0 - t) + self.sample_level(dir, hi) * t
+    }
+    fn sample_level(&self, dir: &Vector3, lod: usize) -> Vector3 {
+        let abs = [dir.x().abs(), dir.y().abs(), dir.z().abs()];
+        let axis = if abs[0] >= abs[1] && abs[0] >= abs[2] {
+            0
+        } else if abs[1] >= abs[2] {
+            1
+        } else {
+            2
+        };
+        let face = axis * 2 + (dir[axis] < 0.0) as usize;
+        let (forward, right, up) = face_frame(face);
+        let depth = dir.dot(&forward);
+        // same mapping as viewport * projection in `face_transform`
+        let s = (dir.dot(&right) / depth + 1.0) * 0.5;
+        let t = (1.0 - dir.dot(&up) / depth) * 0.5;
+        let size = (self.size >> lod).max(1);
+        let texels = &self.levels[lod][face];
+        let fx = (s * size as f32 - 0.5).clamp(0.0, (size - 1) as f32);
+        let fy = (t * size as f32 - 0.5).clamp(0.0, (size - 1) as f32);
+        let (x0, y0) = (fx as u32, fy as u32);
+        let (x1, y1) = ((x0 + 1).min(size - 1), (y0 + 1).min(size - 1));
+        let (tx, ty) = (fx - x0 as f32, fy - y0 as f32);
+        let at = |x: u32, y: u32| &texels[(x + y * size) as usize];
+        let top = at(x0, y0) * (1.0 - tx) + at(x1, y0) * tx;
+        let bottom = at(x0, y1) * (1.0 - tx) + at(x1, y1) * tx;
+        top * (1.0 - ty) + bottom * ty
+    }
+    // world to screen for face `face` seen from `eye`, with a 90 degree fov
+    pub fn face_transform(&self, face: usize, eye: &Vector3, depth: f32) -> Matrix4 {
+        let (forward, _, up) = face_frame(face);
+        // the center one unit away makes `projection(-1.0)` divide by the distance
+        let center = eye + &forward;
+        let size = self.size as f32;
+        viewport(0.0, 0.0, size, size, depth) * projection(-1.0) * lookat(eye, &center, &up)
+    }
+}
+
+// a cube map captured at `position`, lighting reflections of objects within `radius`
+pub struct ReflectionProbe {
+    pub position: Vector3,
+    pub radius: f32,
+    pub cube: CubeMap,
+    pub near: f32,
+}
+
+impl ReflectionProbe {
+    pub fn new(position: Vector3, radius: f32, size: u32) -> Self {
+        Self {
+            position,
+            radius,
+            cube: CubeMap::new(size),
+            near: 0.05,
+        }
+    }
+    // render the six faces, `draw` is called once per face with a cleared renderer
+    // and should draw the scene (in world space) through `ProbeFace::draw`
+    pub fn capture(&mut self, background: u32, mut draw: impl FnMut(&mut Renderer, &ProbeFace)) {
+        let size = self.cube.size;
+        let mut buffer = vec![0; (size * size) as usize];
+        let mut z_buffer = vec![f32::MIN; (size * size) as usize];
+        for face in 0..6 {
+            z_buffer.fill(f32::MIN);
+            let mut renderer = Renderer::new(&mut buffer, &mut z_buffer, size, size);
+            renderer.fill(background);
+            let probe_face = ProbeFace {
+                index: face,
+                transform: self.cube.face_transform(face, &self.position, 255.0),
+                eye: self.position.clone(),
+                forward: face_frame(face).0,
+                near: self.near,
+            };
+            draw(&mut renderer, &probe_face);
+            self.cube.set_face(face, &buffer);
+        }
+        self.cube.build_lods();
+    }
+    // 1 at the probe, fading out towards the edge of its influence
+    pub fn weight(&self, pos: &Vector3) -> f32 {
+        let d = (pos - &self.position).length() / self.radius;
+        (1.0 - d).clamp(0.0, 1.0)
+    }
+    // linear radiance seen along `dir`, blurrier as `roughness` goes to 1
+    pub fn sample(&self, dir: &Vector3, roughness: f32) -> Vector3 {
+        let lod = roughness.sqrt() * (self.cube.lods() - 1) as f32;
+        self.cube.sample(dir, lod)
+    }
+}
+
+// the reflection probes placed in a scene
+#[derive(Default)]
+pub struct ProbeSet {
+    pub probes: Vec<ReflectionProbe>,
+}
+
+impl ProbeSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn add(&mut self, probe: ReflectionProbe) -> usize {
+        self.probes.push(probe);
+        self.probes.len() - 1
+    }
+    // up to two probes covering an object centered at `pos`, weights summing to 1;
+    // outside every probe the nearest one is used alone
+    pub fn blend_for(&self, pos: &Vector3) -> Vec<(&ReflectionProbe, f32)> {
+        let mut weighted: Vec<(&ReflectionProbe, f32)> = self
+            .probes
+            .iter()
+            .map(|p| (p, p.weight(pos)))
+            .filter(|(_, w)| *w > 0.0)
+            .collect();
+        weighted.sort_by(|a, b| b.1.total_cmp(&a.1));
+        weighted.truncate(2);
+        let total: f32 = weighted.iter().map(|(_, w)| w).sum();
+        if total > 0.0 {
+            for (_, w) in weighted.iter_mut() {
+                *w /= total;
+            }
+            return weighted;
+        }
+        self.probes
+            .iter()
+            .min_by(|a, b| {
+                let da = (pos - &a.position).length_square();
+                let db = (pos - &b.position).length_square();
+                da.total_cmp(&db)
+            })
+            .map(|p| vec![(p, 1.0)])
+            .unwrap_or_default()
+    }
+}
@@ -0,0 +1,213 @@
+use crate::{
+    bvh::Bvh,
+    geometry::{m2v, m3v, normal_matrix, v2m, Matrix3, Matrix4, Vector3},
+    material::Material,
+    model::Model,
+    renderer::interpolate,
+    scene::{Camera, Light, Scene},
+};
+
+// `dir` is kept normalized, so distances along the ray are world distances
+#[derive(Clone, Debug)]
+pub struct Ray {
+    pub origin: Vector3,
+    pub dir: Vector3,
+}
+
+impl Ray {
+    pub fn new(origin: Vector3, dir: Vector3) -> Self {
+        Self {
+            origin,
+            dir: dir.normalize(),
+        }
+    }
+    pub fn at(&self, t: f32) -> Vector3 {
+        &self.origin + &(&self.dir * t)
+    }
+}
+
+// a triangle of a model, `barycentric` weighs its three corners
+#[derive(Clone, Debug)]
+pub struct Hit {
+    pub t: f32,
+    pub face: usize,
+    pub barycentric: Vector3,
+}
+
+// Moller-Trumbore, the distance to a hit in front of the ray and the weights
+// of `b` and `c` there
+pub fn intersect_triangle(
+    ray: &Ray,
+    a: &Vector3,
+    b: &Vector3,
+    c: &Vector3,
+) -> Option<(f32, f32, f32)> {
+    let e1 = b - a;
+    let e2 = c - a;
+    let p = ray.dir.cross(&e2);
+    let det = e1.dot(&p);
+    if det.abs() < 1e-12 {
+        return None;
+    }
+    let s = &ray.origin - a;
+    let u = s.dot(&p) / det;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let q = s.cross(&e1);
+    let v = ray.dir.dot(&q) / det;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    let t = e2.dot(&q) / det;
+    (t > 0.0).then_some((t, u, v))
+}
+
+// a node's model with its triangles in world space
+struct Object<'a> {
+    model: &'a Model,
+    material: &'a Material,
+    normal: Matrix3,
+    bvh: Bvh,
+}
+
+// reference renderer for the scene the rasterizer draws with `draw_scene`: the
+// same lambert shading, plus the shadows and mirror reflections a rasterizer
+// can't do directly. Colors stay in the 0..1 sRGB values the shaders use
+pub struct RayTracer<'a> {
+    // reflection bounces
+    pub max_depth: u32,
+    pub background: u32,
+    ambient: f32,
+    lights: Vec<Light>,
+    objects: Vec<Object<'a>>,
+}
+
+impl<'a> RayTracer<'a> {
+    // the visible nodes of `scene` as they are posed now
+    pub fn new(scene: &'a Scene) -> Self {
+        let mut objects = Vec::new();
+        for (id, node) in scene.nodes.iter().enumerate() {
+            let Some(model) = node.model else {
+                continue;
+            };
+            if !scene.visible(id) {
+                continue;
+            }
+            let model = &scene.models[model];
+            let world = if node.skin.is_some() {
+                Matrix4::identity()
+            } else {
+                scene.world_transform(id)
+            };
+            let material = match node.material {
+                Some(material) => &scene.materials[material],
+                None => model.material(),
+            };
+            let faces = (0..model.nfaces())
+                .map(|i| [0, 1, 2].map(|j| m2v(&(&world * v2m(&model.vert(i, j))))))
+                .collect();
+            objects.push(Object {
+                model,
+                material,
+                normal: normal_matrix(&world),
+                bvh: Bvh::from_triangles(faces),
+            });
+        }
+        Self {
+            max_depth: 3,
+            background: 0xff000000,
+            ambient: scene.ambient,
+            lights: scene.lights(),
+            objects,
+        }
+    }
+    // the closest hit, with the object it belongs to
+    fn intersect(&self, ray: &Ray) -> Option<(&Object<'a>, Hit)> {
+        self.objects
+            .iter()
+            .filter_map(|object| Some((object, object.bvh.intersect(ray)?)))
+            .min_by(|a, b| a.1.t.total_cmp(&b.1.t))
+    }
+    pub fn occluded(&self, ray: &Ray, max_t: f32) -> bool {
+        self.objects
+            .iter()
+            .any(|object| object.bvh.occluded(ray, max_t))
+    }
+    // rgb seen along `ray`, `depth` is the number of bounces so far
+    pub fn trace(&self, ray: &Ray, depth: u32) -> Vector3 {
+        let Some((object, hit)) = self.intersect(ray) else {
+            return unpack(self.background);
+        };
+        let (model, bc, face) = (object.model, &hit.barycentric, hit.face);
+        let p = ray.at(hit.t);
+        let [a, b, c] = object.bvh.triangle(face);
+        // secondary rays leave from the side the ray came from
+        let mut side = (b - a).cross(&(c - a)).normalize();
+        if side.dot(&ray.dir) > 0.0 {
+            side = -side;
+        }
+        let origin = &p + &(side * (1e-4 * (1.0 + p.length())));
+        let normals = [0, 1, 2].map(|j| m3v(&object.normal, &model.normal_vert(face, j)));
+        let n = interpolate(bc, [&normals[0], &normals[1], &normals[2]]).normalize();
+        let uvs = [0, 1, 2].map(|j| model.uv(face, j));
+        let uv = interpolate(bc, [&uvs[0], &uvs[1], &uvs[2]]);
+        let colors = [0, 1, 2].map(|j| unpack(model.color(face, j)));
+        let color = interpolate(bc, [&colors[0], &colors[1], &colors[2]]);
+        let mut light = Vector3::new(self.ambient, self.ambient, self.ambient);
+        for l in self.lights.iter() {
+            let diffuse = (-n.dot(&l.dir)).max(0.0);
+            if diffuse > 0.0 && !self.occluded(&Ray::new(origin.clone(), -&l.dir), f32::MAX) {
+                light = light + &l.color * diffuse;
+            }
+        }
+        let texel = unpack(object.material.diffuse(&uv));
+        let mut result = Vector3::zero();
+        for i in 0..3 {
+            result[i] = texel[i] * color[i] * light[i];
+        }
+        let mirror = object.material.metallic * (1.0 - object.material.roughness);
+        if mirror > 0.0 && depth < self.max_depth {
+            let reflected = &ray.dir - &(&n * (2.0 * ray.dir.dot(&n)));
+            let bounce = self.trace(&Ray::new(origin, reflected), depth + 1);
+            result = result * (1.0 - mirror) + bounce * mirror;
+        }
+        result
+    }
+    // one ray through the center of every pixel, framed like the rasterizer
+    pub fn render(&self, camera: &Camera, buffer: &mut [u32], width: u32, height: u32) {
+        assert_eq!((width * height) as usize, buffer.len());
+        for y in 0..height {
+            for x in 0..width {
+                let ray = camera.screen_to_ray(width, height, x as f32 + 0.5, y as f32 + 0.5);
+                buffer[(x + y * width) as usize] = pack(&self.trace(&ray, 0));
+            }
+        }
+    }
+}
+
+// whether the ray passes through the sphere before `max_t`, or starts inside it
+pub fn hits_sphere(ray: &Ray, max_t: f32, center: &Vector3, radius: f32) -> bool {
+    let oc = center - &ray.origin;
+    if oc.length_square() <= radius * radius {
+        return true;
+    }
+    let t = oc.dot(&ray.dir);
+    if t < 0.0 || t - radius > max_t {
+        return false;
+    }
+    (oc.length_square() - t * t) <= radius * radius
+}
+
+fn unpack(pixel: u32) -> Vector3 {
+    let channel = |i: u32| ((pixel >> (8 * i)) & 0xff) as f32 / 255.0;
+    Vector3::new(channel(0), channel(1), channel(2))
+}
+
+fn pack(color: &Vector3) -> u32 {
+    let mut pixel = 0xff000000;
+    for i in 0..3 {
+        pixel |= ((color[i].clamp(0.0, 1.0) * 255.0) as u32) << (8 * i);
+    }
+    pixel
+}
@@ -0,0 +1,234 @@
+use std::sync::Arc;
+
+use crate::{
+    buffer::VertexBuffer,
+    geometry::{m2v, v2m, Matrix4, Vector2, Vector3},
+    image::Image,
+    lut::Lut,
+    material::Material,
+    model::Model,
+    probe::ReflectionProbe,
+    renderer::{interpolate, EdgeDetect, IndexedShader, Renderer},
+    scene::Camera,
+    shaders::{PbrShader, PbrVarying},
+    shadow::ShadowMap,
+};
+
+const DEPTH: f32 = 255.0;
+const BACKGROUND: u32 = 0xff302820;
+
+fn solid(color: u32) -> Image {
+    Image {
+        buffer: vec![color],
+        width: 1,
+        height: 1,
+    }
+}
+
+fn checker(size: u32, cells: u32, a: u32, b: u32) -> Image {
+    let cell = (size / cells).max(1);
+    let buffer = (0..size * size)
+        .map(|i| {
+            let (x, y) = (i % size / cell, i / size / cell);
+            if (x + y) % 2 == 0 {
+                a
+            } else {
+                b
+            }
+        })
+        .collect();
+    Image {
+        buffer,
+        width: size,
+        height: size,
+    }
+}
+
+// tangent space normals of a field of round bumps
+fn bumps(size: u32, cells: u32) -> Image {
+    let buffer = (0..size * size)
+        .map(|i| {
+            let u = (i % size) as f32 / size as f32 * cells as f32;
+            let v = (i / size) as f32 / size as f32 * cells as f32;
+            let (fu, fv) = (u.fract() - 0.5, v.fract() - 0.5);
+            let r2 = fu * fu + fv * fv;
+            let n = if r2 < 0.16 {
+                Vector3::new(fu * 2.0, fv * 2.0, 1.0).normalize()
+            } else {
+                Vector3::new(0.0, 0.0, 1.0)
+            };
+            let mut pixel = 0xff000000;
+            for c in 0..3 {
+                pixel |= (((n[c] + 1.0) * 0.5 * 255.0).round() as u32) << (8 * c);
+            }
+            pixel
+        })
+        .collect();
+    Image {
+        buffer,
+        width: size,
+        height: size,
+    }
+}
+
+fn place(mut model: Model, scale: f32, offset: Vector3) -> Model {
+    let n = model.nverts();
+    for v in model.verts_mut(0..n) {
+        *v = &(&*v * scale) + &offset;
+    }
+    model.refresh();
+    model
+}
+
+// a warm grade lifting the shadows a little, made up in place of one loaded
+// from a file
+fn warm_grade() -> Lut {
+    let mut lut = Lut::identity(17);
+    for entry in lut.table.iter_mut() {
+        entry[0] = entry[0].powf(0.9);
+        entry[2] = entry[2] * 0.85 + 0.04;
+    }
+    lut
+}
+
+// an object's shader with where the frame before had its vertices on screen,
+// for the velocity attachment and `motion_blur`
+struct Moving<'a> {
+    shader: PbrShader<'a>,
+    previous: Matrix4,
+}
+
+impl IndexedShader for Moving<'_> {
+    type Varying = (PbrVarying, Vector3);
+    fn vertex(&mut self, vertices: &VertexBuffer, index: usize) -> (Vector3, Self::Varying) {
+        let (screen, varying) = self.shader.vertex(vertices, index);
+        let previous = m2v(&(&self.previous * v2m(&vertices.positions[index])));
+        (screen, (varying, previous))
+    }
+    fn fregment(&mut self, varyings: [&Self::Varying; 3], bc: &Vector3) -> Option<u32> {
+        IndexedShader::fregment(&mut self.shader, varyings.map(|v| &v.0), bc)
+    }
+    fn previous_position(
+        &mut self,
+        varyings: [&Self::Varying; 3],
+        bc: &Vector3,
+    ) -> Option<Vector2> {
+        let p = interpolate(bc, varyings.map(|v| &v.1));
+        Some(Vector2::new(p.x(), p.y()))
+    }
+}
+
+struct Object {
+    model: Model,
+    material: Material,
+    tangent_space: bool,
+}
+
+impl Object {
+    fn shader<'a>(
+        &'a self,
+        transform: Matrix4,
+        light_dir: &Vector3,
+        eye: &Vector3,
+        shadow: &'a ShadowMap,
+    ) -> PbrShader<'a> {
+        let mut shader = PbrShader::new(&self.model, transform, light_dir.clone(), eye.clone());
+        shader.material = &self.material;
+        shader.tangent_space = self.tangent_space;
+        shader.shadow = Some(shadow);
+        shader
+    }
+}
+
+// a fixed scene going through most of the pipeline: procedural diffuse and tangent
+// space normal maps, pbr shading, a shadow map, a reflection probe, and the post
+// passes: motion blur on the sphere moving, edge lines where the depth jumps, and
+// a color grade. Nothing in it depends on files, time or
+// randomness, so CI and snapshot tests can compare the result against a stored
+// image, see tests/reference.rs and `olive3d reference`
+pub fn render_reference_scene(width: u32, height: u32) -> Image {
+    let light_dir = Vector3::new(-1.0, -1.6, -0.7).normalize();
+    let camera = Camera::new(Vector3::new(1.0, 1.3, 3.0), Vector3::new(0.0, -0.2, 0.0));
+
+    let floor = place(Model::plane(8), 1.4, Vector3::new(0.0, -0.6, 0.0));
+    let mut tiles = Material::new("tiles");
+    tiles.diffuse_map = Some(Arc::new(checker(256, 8, 0xffd8d8d8, 0xff505050)));
+    tiles.metallic = 0.0;
+    tiles.roughness = 0.8;
+    let mut torus = place(
+        Model::torus(1.0, 0.4, 48, 24),
+        0.45,
+        Vector3::new(-0.35, -0.2, 0.0),
+    );
+    torus.compute_tangents();
+    let mut plastic = Material::new("plastic");
+    plastic.diffuse_map = Some(Arc::new(solid(0xff2080e0)));
+    plastic.normal_map = Some(Arc::new(bumps(256, 16)));
+    plastic.metallic = 0.0;
+    plastic.roughness = 0.45;
+    let sphere_center = Vector3::new(0.55, -0.25, 0.45);
+    let sphere = place(Model::uv_sphere(24, 48), 0.35, sphere_center.clone());
+    let mut metal = Material::new("metal");
+    metal.roughness = 0.3;
+    let objects = [
+        Object {
+            model: floor,
+            material: tiles,
+            tangent_space: false,
+        },
+        Object {
+            model: torus,
+            material: plastic,
+            tangent_space: true,
+        },
+        Object {
+            model: sphere,
+            material: metal,
+            tangent_space: false,
+        },
+    ];
+
+    let mut shadow = ShadowMap::new(512, 512, &light_dir, &Vector3::zero(), 2.2, DEPTH);
+    for object in objects.iter() {
+        shadow.render(&object.model);
+    }
+
+    // the metal sphere reflects the rest of the scene
+    let mut probe = ReflectionProbe::new(sphere_center, 2.0, 32);
+    probe.capture(BACKGROUND, |renderer, face| {
+        for object in objects[..2].iter() {
+            let mut shader = object.shader(face.transform.clone(), &light_dir, &face.eye, &shadow);
+            face.draw(renderer, &object.model, &mut shader);
+        }
+    });
+
+    let size = (width * height) as usize;
+    let mut buffer = vec![0; size];
+    let mut z_buffer = vec![f32::MIN; size];
+    let mut velocity = vec![(0.0, 0.0); size];
+    let mut renderer = Renderer::new(&mut buffer, &mut z_buffer, width, height);
+    renderer.attach_velocity(&mut velocity);
+    renderer.fill(BACKGROUND);
+    let transform = camera.transform(width, height, DEPTH);
+    for object in objects[..2].iter() {
+        let mut shader = object.shader(transform.clone(), &light_dir, &camera.eye, &shadow);
+        renderer.draw_model(&object.model, &mut shader);
+    }
+    let mut shader = objects[2].shader(transform.clone(), &light_dir, &camera.eye, &shadow);
+    shader.probes = vec![(&probe, 1.0)];
+    // the sphere came from the left
+    let previous = transform * Matrix4::translation(&Vector3::new(-0.15, 0.0, 0.0));
+    renderer.draw_model(&objects[2].model, &mut Moving { shader, previous });
+    renderer.motion_blur(8, 1.0);
+    renderer.draw_edges(&EdgeDetect {
+        depth_threshold: DEPTH / 16.0,
+        ..EdgeDetect::new(0xff000000)
+    });
+    renderer.color_grade(&warm_grade(), 0.6);
+
+    Image {
+        buffer,
+        width,
+        height,
+    }
+}
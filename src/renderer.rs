@@ -1,17 +1,453 @@
-#![allow(clippy::too_many_arguments)]
+#[cfg(feature = "ppm")]
 use std::path::Path;
 
+#[cfg(not(feature = "std"))]
+use crate::compat::*;
+#[cfg(feature = "model")]
+use crate::model::Model;
+#[cfg(feature = "std")]
+use crate::pool;
+#[cfg(feature = "ppm")]
+use crate::ppm::save_buffer_to_ppm_file;
 use crate::{
-    geometry::{Line2D, Matrix, Matrix4, Ray, Vector3},
-    ppm::save_buffer_to_ppm_file,
+    buffer::{IndexBuffer, VertexBuffer},
+    geometry::{
+        Line2D, LineIter, Matrix, Matrix3, Matrix4, Plane, Vector, Vector2, Vector3, Vector4,
+    },
+    hiz::{HiZ, TILE_SIZE},
+    image::Image,
+    interlace::{Field, Interlace},
+    lut::Lut,
+    oit::{accumulate, WeightedOit},
+};
+
+// counters for the draws of one renderer, reset them with `RenderStats::default()`
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RenderStats {
+    pub triangles: usize,
+    // vertex shader runs, and the runs the indexed draws saved by reusing a result
+    pub vertices_shaded: usize,
+    pub vertex_cache_hits: usize,
+    // fragments that passed the depth test
+    pub fragments_shaded: usize,
+    // triangles and tiles of triangles the coarse depth rejected unshaded
+    pub occluded_triangles: usize,
+    pub occluded_tiles: usize,
+    // triangles without area or between the pixel centers, dropped unshaded
+    pub degenerate_triangles: usize,
+    // triangles over a few pixels, filled without the tiles
+    pub small_triangles: usize,
+}
+
+// how often the pixels of a frame were shaded, from `Renderer::overdraw_stats`
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct OverdrawStats {
+    // pixels shaded at least once
+    pub covered: usize,
+    pub fragments: usize,
+    pub max: u16,
+    // covered pixels shaded once, twice, .. and 8 or more times
+    pub histogram: [usize; 8],
+}
+
+impl OverdrawStats {
+    // fragments per covered pixel, 1 is no overdraw at all
+    pub fn average(&self) -> f32 {
+        if self.covered == 0 {
+            0.0
+        } else {
+            self.fragments as f32 / self.covered as f32
+        }
+    }
+}
+
+impl core::fmt::Display for OverdrawStats {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "overdraw {:.2}x over {} pixels, max {}",
+            self.average(),
+            self.covered,
+            self.max
+        )
+    }
+}
+
+// the counts of renderers that drew parts of a frame on other threads, added up
+impl core::ops::AddAssign for RenderStats {
+    fn add_assign(&mut self, other: Self) {
+        self.triangles += other.triangles;
+        self.vertices_shaded += other.vertices_shaded;
+        self.vertex_cache_hits += other.vertex_cache_hits;
+        self.fragments_shaded += other.fragments_shaded;
+        self.occluded_triangles += other.occluded_triangles;
+        self.occluded_tiles += other.occluded_tiles;
+        self.degenerate_triangles += other.degenerate_triangles;
+        self.small_triangles += other.small_triangles;
+    }
+}
+
+impl RenderStats {
+    pub fn cache_hit_rate(&self) -> f32 {
+        let total = self.vertices_shaded + self.vertex_cache_hits;
+        if total == 0 {
+            0.0
+        } else {
+            self.vertex_cache_hits as f32 / total as f32
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Compare {
+    Never,
+    Less,
+    LessEqual,
+    Equal,
+    NotEqual,
+    Greater,
+    GreaterEqual,
+    Always,
+}
+
+impl Compare {
+    // the fragment's `z` against the `stored` depth, or the stencil reference
+    // against the stored stencil
+    #[inline]
+    pub fn passes<T: PartialOrd>(self, z: T, stored: T) -> bool {
+        match self {
+            Compare::Never => false,
+            Compare::Less => z < stored,
+            Compare::LessEqual => z <= stored,
+            Compare::Equal => z == stored,
+            Compare::NotEqual => z != stored,
+            Compare::Greater => z > stored,
+            Compare::GreaterEqual => z >= stored,
+            Compare::Always => true,
+        }
+    }
+    // no depth in `z` passes against any depth in `stored`, both (min, max)
+    fn rejects(self, z: (f32, f32), stored: (f32, f32)) -> bool {
+        match self {
+            Compare::Never => true,
+            Compare::Less => z.0 >= stored.1,
+            Compare::LessEqual => z.0 > stored.1,
+            Compare::Greater => z.1 <= stored.0,
+            Compare::GreaterEqual => z.1 < stored.0,
+            Compare::Equal | Compare::NotEqual | Compare::Always => false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DepthState {
+    pub compare: Compare,
+    pub write: bool,
+    // window depth of the nearest and the farthest points, for `viewport`
+    pub near: f32,
+    pub far: f32,
+    // what `Renderer::fill` resets the z buffer to
+    pub clear: f32,
+}
+
+impl DepthState {
+    // the renderer's own convention: greater is closer over 0..=255 and the
+    // z buffer starts at f32::MIN
+    pub fn new() -> Self {
+        Self {
+            compare: Compare::Greater,
+            write: true,
+            near: 255.0,
+            far: 0.0,
+            clear: f32::MIN,
+        }
+    }
+    // what most pipelines use: 0 near, 1 far, smaller passes, cleared to 1
+    pub fn standard() -> Self {
+        Self {
+            compare: Compare::Less,
+            write: true,
+            near: 0.0,
+            far: 1.0,
+            clear: 1.0,
+        }
+    }
+    // 1 near, 0 far, cleared to 0. Floats are densest around 0, where the
+    // distant geometry that needs the precision ends up
+    pub fn reversed() -> Self {
+        Self {
+            compare: Compare::Greater,
+            write: true,
+            near: 1.0,
+            far: 0.0,
+            clear: 0.0,
+        }
+    }
+    // `viewport` with the depth mapped onto near..far
+    #[rustfmt::skip]
+    pub fn viewport(&self, x: f32, y: f32, w: f32, h: f32) -> Matrix4 {
+        let (n, f) = (self.near, self.far);
+        [
+            [w/2.0,    0.0,       0.0,   x+w/2.0],
+            [  0.0, -h/2.0,       0.0,   y+h/2.0],
+            [  0.0,    0.0, (n-f)/2.0, (n+f)/2.0],
+            [  0.0,    0.0,       0.0,       1.0],
+        ]
+        .into()
+    }
+    // the barycentric correction divides by depth as it was on 0..=255 with 255
+    // near, which keeps it independent of the range. Not possible with a vertex
+    // right on the far end, like the flat z = 0 of 2D draws
+    fn perspective_depths(&self, verts: &[Vector3; 3]) -> Option<[f32; 3]> {
+        let scale = 255.0 / (self.near - self.far);
+        let depths = [0, 1, 2].map(|i| (verts[i].z() - self.far) * scale);
+        depths.iter().all(|&z| z != 0.0).then_some(depths)
+    }
+    // undoes the perspective correction of the `bc` a fragment of the triangle
+    // `verts` gets, giving the weights of its screen position
+    pub fn screen_barycentric(&self, verts: &[Vector3; 3], bc: &Vector3) -> Vector3 {
+        let Some(depths) = self.perspective_depths(verts) else {
+            return bc.clone();
+        };
+        let mut screen = bc.clone();
+        let mut sum = 0.0;
+        for i in 0..3 {
+            screen[i] *= depths[i];
+            sum += screen[i];
+        }
+        screen / sum
+    }
+    // 0 at far, 1 at near
+    #[inline]
+    pub fn normalized(&self, z: f32) -> f32 {
+        ((z - self.far) / (self.near - self.far)).clamp(0.0, 1.0)
+    }
+    // grows towards the near plane whichever way the range runs
+    #[inline]
+    pub fn nearness(&self, z: f32) -> f32 {
+        if self.near < self.far {
+            -z
+        } else {
+            z
+        }
+    }
+}
+
+impl Default for DepthState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// what happens to the stored stencil value
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StencilOp {
+    Keep,
+    Zero,
+    // set it to the reference
+    Replace,
+    // saturating at 255 and 0
+    Increment,
+    Decrement,
+    IncrementWrap,
+    DecrementWrap,
+    Invert,
+}
+
+impl StencilOp {
+    fn apply(self, stored: u8, reference: u8) -> u8 {
+        match self {
+            StencilOp::Keep => stored,
+            StencilOp::Zero => 0,
+            StencilOp::Replace => reference,
+            StencilOp::Increment => stored.saturating_add(1),
+            StencilOp::Decrement => stored.saturating_sub(1),
+            StencilOp::IncrementWrap => stored.wrapping_add(1),
+            StencilOp::DecrementWrap => stored.wrapping_sub(1),
+            StencilOp::Invert => !stored,
+        }
+    }
+}
+
+// the test runs before the depth test, only with a stencil buffer attached
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StencilState {
+    // `reference & read_mask` against `stored & read_mask`
+    pub compare: Compare,
+    pub reference: u8,
+    pub read_mask: u8,
+    // the bits the ops may change
+    pub write_mask: u8,
+    // the stencil test failed
+    pub fail: StencilOp,
+    // the stencil test passed and the depth test failed
+    pub depth_fail: StencilOp,
+    // both passed
+    pub pass: StencilOp,
+}
+
+impl StencilState {
+    // always passes and never writes
+    pub fn new() -> Self {
+        Self {
+            compare: Compare::Always,
+            reference: 0,
+            read_mask: 0xff,
+            write_mask: 0xff,
+            fail: StencilOp::Keep,
+            depth_fail: StencilOp::Keep,
+            pass: StencilOp::Keep,
+        }
+    }
+    #[inline]
+    pub fn passes(&self, stored: u8) -> bool {
+        self.compare
+            .passes(self.reference & self.read_mask, stored & self.read_mask)
+    }
+    #[inline]
+    pub fn update(&self, stored: u8, op: StencilOp) -> u8 {
+        let new = op.apply(stored, self.reference);
+        (stored & !self.write_mask) | (new & self.write_mask)
+    }
+}
+
+impl Default for StencilState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// how the triangle fill writes the fragment colors
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Blend {
+    Replace,
+    // source over with the fragment's alpha, back to front is up to the caller
+    Alpha,
+    // into the weighted sums of `WeightedOit`, order free until `resolve_oit`
+    WeightedOit,
+}
+
+// how `draw_scene` composites translucent materials over the opaque nodes
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Transparency {
+    // whole nodes sorted back to front by view depth, alpha blended
+    Sorted,
+    WeightedOit,
+}
+
+// what `Renderer::pick` finds under a pixel: the object set with `set_object`,
+// the triangle drawn for it, counted from 0, and where on that triangle
+#[derive(Clone, Debug, PartialEq)]
+pub struct PickResult {
+    pub object: usize,
+    pub face: usize,
+    pub barycentric: Vector3,
+}
+
+// what `Renderer::debug_view` draws in place of the shaded colors
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DebugView {
+    // the shaders' colors
+    Shaded,
+    // in false color from blue at the far plane to red at the near one
+    Depth,
+    // the shader's `normal` or else the facing of the triangle on screen, xyz
+    // mapped from -1..1 to rgb
+    Normals,
+    // fragments shaded per pixel, blue for one to red for 8 or more
+    Overdraw,
+    // the shader's `uv` as red and green over an 8x8 checker, magenta where
+    // the shader doesn't say
+    Uv,
+    // the edges of every triangle in white over a gray changing per triangle
+    BarycentricWireframe,
+}
+
+// what `Renderer::draw_edges` takes for an edge and how it draws them
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EdgeDetect {
+    // of the sobel gradient of the depth, per pixel in the z of the screen
+    // coordinates, e.g. 0..255 with `Camera::transform(.., 255.0)`
+    pub depth_threshold: f32,
+    // of the sobel gradient of the normal attachment, about 0.5 for a 30 degree
+    // crease. Unused without the attachment
+    pub normal_threshold: f32,
+    // of the lines in pixels
+    pub width: u32,
+    pub color: u32,
+}
+
+impl EdgeDetect {
+    pub fn new(color: u32) -> Self {
+        Self {
+            depth_threshold: 4.0,
+            normal_threshold: 0.5,
+            width: 1,
+            color,
+        }
+    }
+}
+
+// the settings draws are placed, tested and blended with, from
+// `Renderer::state`, to give another renderer, e.g. one on a worker thread,
+// the same
+#[derive(Clone, Debug, PartialEq)]
+pub struct RenderState {
+    pub depth: DepthState,
+    pub stencil: StencilState,
+    pub blend: Blend,
+    pub transparency: Transparency,
+    pub clip_plane: Option<Vector4>,
+    pub jitter: (f32, f32),
+    pub field: Option<Field>,
+}
+
+// a renderer holds its target and attachments by unique borrow, so it can be
+// moved to the thread that draws with it, one per target or part of a frame
+const _: () = {
+    const fn send<T: Send>() {}
+    send::<Renderer<'static>>();
 };
 
 pub struct Renderer<'b> {
     buffer: &'b mut [u32],
     z_buffer: &'b mut [f32],
+    stencil: Option<&'b mut [u8]>,
+    ids: Option<&'b mut [Option<PickResult>]>,
+    velocity: Option<&'b mut [(f32, f32)]>,
+    normals: Option<&'b mut [[f32; 3]]>,
     pub width: u32,
     pub height: u32,
     pub stride: u32,
+    pub stats: RenderStats,
+    pub depth: DepthState,
+    pub stencil_state: StencilState,
+    pub blend: Blend,
+    pub transparency: Transparency,
+    // (a, b, c, d) in screen space, fragments with a x + b y + c z + d < 0 are
+    // dropped before any test. See `clip_plane` for one given in world space
+    pub clip_plane: Option<Vector4>,
+    // (x, y) in pixels added to every triangle before it is rasterized, the sub
+    // pixel offsets of an `Accumulator`
+    pub jitter: (f32, f32),
+    // Some to draw only the pixels it covers, see `Interlacer`
+    pub field: Option<Field>,
+    // threads sharing the rows of tall triangles whose shaders `fork`, the
+    // calling one among them. 1 fills every triangle on the calling thread
+    pub threads: usize,
+    // applied to the 2D drawing: lines, pixels, paths, text and images. See
+    // `Matrix3::translation_2d` and the rest, or `translate` and the rest here
+    pub transform_2d: Matrix3,
+    transforms: Vec<Matrix3>,
+    hiz: HiZ,
+    // allocated on the first `Blend::WeightedOit` fragment
+    oit: Option<WeightedOit>,
+    debug: DebugView,
+    // fragments per pixel since the colors were cleared, empty unless tracked
+    overdraw: Vec<u16>,
+    // for the id attachment, `face` counts the triangles since `set_object`
+    object: usize,
+    face: usize,
 }
 impl<'b> Renderer<'b> {
     pub fn new(buffer: &'b mut [u32], z_buffer: &'b mut [f32], width: u32, height: u32) -> Self {
@@ -20,21 +456,294 @@ impl<'b> Renderer<'b> {
         Self {
             buffer,
             z_buffer,
+            stencil: None,
+            ids: None,
+            velocity: None,
+            normals: None,
             width,
             height,
             stride: width,
+            stats: RenderStats::default(),
+            depth: DepthState::new(),
+            stencil_state: StencilState::new(),
+            blend: Blend::Replace,
+            transparency: Transparency::Sorted,
+            clip_plane: None,
+            jitter: (0.0, 0.0),
+            field: None,
+            threads: 1,
+            transform_2d: Matrix3::identity(),
+            transforms: Vec::new(),
+            hiz: HiZ::new(width, height),
+            oit: None,
+            debug: DebugView::Shaded,
+            overdraw: Vec::new(),
+            object: 0,
+            face: 0,
+        }
+    }
+    pub fn state(&self) -> RenderState {
+        RenderState {
+            depth: self.depth,
+            stencil: self.stencil_state,
+            blend: self.blend,
+            transparency: self.transparency,
+            clip_plane: self.clip_plane.clone(),
+            jitter: self.jitter,
+            field: self.field,
+        }
+    }
+    pub fn set_state(&mut self, state: &RenderState) {
+        self.depth = state.depth;
+        self.stencil_state = state.stencil;
+        self.blend = state.blend;
+        self.transparency = state.transparency;
+        self.clip_plane = state.clip_plane.clone();
+        self.jitter = state.jitter;
+        self.field = state.field;
+    }
+    // an 8 bit stencil buffer, one value per pixel like the z buffer
+    pub fn attach_stencil(&mut self, stencil: &'b mut [u8]) {
+        assert_eq!(stencil.len(), self.buffer.len());
+        self.stencil = Some(stencil);
+    }
+    pub fn stencil(&self) -> Option<&[u8]> {
+        self.stencil.as_deref()
+    }
+    // per pixel what was drawn there last, for every fragment that passed the
+    // depth test and wasn't discarded
+    pub fn attach_ids(&mut self, ids: &'b mut [Option<PickResult>]) {
+        assert_eq!(ids.len(), self.buffer.len());
+        self.ids = Some(ids);
+    }
+    // per pixel how far, in pixels, the surface drawn there moved on screen since
+    // the frame before: where it is minus where the shader's `previous_position`
+    // says it was. Zero where nothing moved or the shader doesn't say
+    pub fn attach_velocity(&mut self, velocity: &'b mut [(f32, f32)]) {
+        assert_eq!(velocity.len(), self.buffer.len());
+        self.velocity = Some(velocity);
+    }
+    pub fn velocity(&self) -> Option<&[(f32, f32)]> {
+        self.velocity.as_deref()
+    }
+    // per pixel the normal of the surface drawn there, the shader's `normal` or
+    // else the facing of the triangle on screen. Zero where nothing was drawn
+    pub fn attach_normals(&mut self, normals: &'b mut [[f32; 3]]) {
+        assert_eq!(normals.len(), self.buffer.len());
+        self.normals = Some(normals);
+    }
+    pub fn normals(&self) -> Option<&[[f32; 3]]> {
+        self.normals.as_deref()
+    }
+    // false color diagnostics in place of what the shaders return, see
+    // `DebugView`. The depth test and the attachments work as usual, blending
+    // is skipped
+    pub fn debug_view(&mut self, view: DebugView) {
+        self.debug = view;
+        if view == DebugView::Overdraw {
+            self.track_overdraw();
+        }
+    }
+    // count the fragments shaded per pixel from here on, for `overdraw`,
+    // `overdraw_stats` and `draw_overdraw_heatmap`. Clearing the colors resets
+    // the counts, a fragment counts whether or not the shader discards it
+    pub fn track_overdraw(&mut self) {
+        self.overdraw.resize(self.buffer.len(), 0);
+    }
+    pub fn overdraw(&self) -> Option<&[u16]> {
+        (!self.overdraw.is_empty()).then_some(&self.overdraw[..])
+    }
+    pub fn overdraw_stats(&self) -> Option<OverdrawStats> {
+        let overdraw = self.overdraw()?;
+        let mut stats = OverdrawStats::default();
+        for y in 0..self.height {
+            let row = (y * self.stride) as usize;
+            for &count in overdraw[row..row + self.width as usize].iter() {
+                if count == 0 {
+                    continue;
+                }
+                stats.covered += 1;
+                stats.fragments += count as usize;
+                stats.max = stats.max.max(count);
+                stats.histogram[(count as usize - 1).min(7)] += 1;
+            }
         }
+        Some(stats)
+    }
+    // the triangles from here on belong to `object`, their faces count from 0
+    pub fn set_object(&mut self, object: usize) {
+        self.object = object;
+        self.face = 0;
+    }
+    pub fn pick(&self, x: u32, y: u32) -> Option<PickResult> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        self.ids.as_deref()?[(x + y * self.stride) as usize].clone()
+    }
+    // the transform stack: `push_transform` keeps `transform_2d` to go back to
+    // with `pop_transform`, the others apply before it like a canvas does
+    pub fn push_transform(&mut self) {
+        self.transforms.push(self.transform_2d.clone());
+    }
+    // back to the last pushed, the identity when there is none
+    pub fn pop_transform(&mut self) {
+        self.transform_2d = self.transforms.pop().unwrap_or_else(Matrix3::identity);
+    }
+    pub fn reset_transform(&mut self) {
+        self.transforms.clear();
+        self.transform_2d = Matrix3::identity();
+    }
+    pub fn translate(&mut self, x: f32, y: f32) {
+        self.transform_2d = &self.transform_2d * &Matrix3::translation_2d(x, y);
+    }
+    pub fn rotate(&mut self, angle: f32) {
+        self.transform_2d = &self.transform_2d * &Matrix3::rotation_2d(angle);
+    }
+    pub fn scale(&mut self, x: f32, y: f32) {
+        self.transform_2d = &self.transform_2d * &Matrix3::scaling_2d(x, y);
+    }
+    pub fn skew(&mut self, x: f32, y: f32) {
+        self.transform_2d = &self.transform_2d * &Matrix3::skew_2d(x, y);
+    }
+    pub fn has_transform_2d(&self) -> bool {
+        self.transform_2d != Matrix3::identity()
+    }
+    // the pixel the center of pixel (x, y) lands in
+    fn apply_2d(&self, x: i32, y: i32) -> (i32, i32) {
+        if !self.has_transform_2d() {
+            return (x, y);
+        }
+        let (x, y) = self.transform_2d.apply_2d(x as f32 + 0.5, y as f32 + 0.5);
+        (x.floor() as i32, y.floor() as i32)
+    }
+    // the pixels a w x h rectangle at (x, y) can reach through `transform_2d`,
+    // (x_min, y_min, x_max, y_max) with the max excluded
+    pub(crate) fn bounds_2d(&self, x: f32, y: f32, w: f32, h: f32) -> (u32, u32, u32, u32) {
+        let corners = [(0.0, 0.0), (w, 0.0), (0.0, h), (w, h)]
+            .map(|(u, v)| self.transform_2d.apply_2d(x + u, y + v));
+        let (lo, hi) = corners.iter().fold(
+            ((f32::MAX, f32::MAX), (f32::MIN, f32::MIN)),
+            |(lo, hi), &(x, y)| ((lo.0.min(x), lo.1.min(y)), (hi.0.max(x), hi.1.max(y))),
+        );
+        // whole pixels, the cast rounds down what is left above 0
+        let clamp = |v: f32, max: u32| (v.max(0.0) as u32).min(max);
+        (
+            clamp(lo.0, self.width),
+            clamp(lo.1, self.height),
+            clamp(hi.0 + 1.0, self.width),
+            clamp(hi.1 + 1.0, self.height),
+        )
+    }
+    // `pixel` at (x, y) through `transform_2d` with the renderer's blend,
+    // nothing off the target. For overlays there is no depth to sort by,
+    // `Blend::WeightedOit` blends like `Blend::Alpha`
+    pub fn draw_pixel(&mut self, x: i32, y: i32, pixel: u32) {
+        let (x, y) = self.apply_2d(x, y);
+        let Some((x, y)) = self.inside(x, y) else {
+            return;
+        };
+        match self.blend {
+            Blend::Replace => self.draw_pixel_unchecked(x, y, pixel),
+            Blend::Alpha | Blend::WeightedOit => self.blend_pixel_unchecked(x, y, pixel, 1.0),
+        }
+    }
+    // `blend_pixel_unchecked` through `transform_2d`, nothing off the target
+    pub fn blend_pixel(&mut self, x: i32, y: i32, pixel: u32, coverage: f32) {
+        let (x, y) = self.apply_2d(x, y);
+        let Some((x, y)) = self.inside(x, y) else {
+            return;
+        };
+        self.blend_pixel_unchecked(x, y, pixel, coverage);
+    }
+    // `pixel` stored at (x, y) of the target as it is, no transform or blend,
+    // nothing off the target. The other half of `get_pixel`
+    pub fn put_pixel(&mut self, x: i32, y: i32, pixel: u32) {
+        if let Some((x, y)) = self.inside(x, y) {
+            self.draw_pixel_unchecked(x, y, pixel);
+        }
+    }
+    // None off the target
+    pub fn get_pixel(&self, x: i32, y: i32) -> Option<u32> {
+        let (x, y) = self.inside(x, y)?;
+        Some(self.pixel(x, y))
+    }
+    #[inline]
+    fn inside(&self, x: i32, y: i32) -> Option<(u32, u32)> {
+        let (x, y) = (u32::try_from(x).ok()?, u32::try_from(y).ok()?);
+        (x < self.width && y < self.height).then_some((x, y))
     }
     #[inline]
     pub fn draw_pixel_unchecked(&mut self, x: u32, y: u32, pixel: u32) {
         self.buffer[(y * self.stride + x) as usize] = pixel;
     }
     #[inline]
+    pub fn pixel(&self, x: u32, y: u32) -> u32 {
+        self.buffer[(y * self.stride + x) as usize]
+    }
+    // source over, `coverage` scales the alpha of `pixel`. Unchecked like
+    // `draw_pixel_unchecked`
+    pub fn blend_pixel_unchecked(&mut self, x: u32, y: u32, pixel: u32, coverage: f32) {
+        let dst = self.pixel(x, y);
+        self.draw_pixel_unchecked(x, y, source_over(dst, pixel, coverage));
+    }
+    #[inline]
     pub fn fill(&mut self, pixel: u32) {
+        self.clear_color(pixel);
+        self.clear_depth();
+        self.clear_stencil(0);
+        self.clear_ids();
+        self.clear_velocity();
+        self.clear_normals();
+    }
+    // the colors alone, the depth is kept for the next pass
+    #[inline]
+    pub fn clear_color(&mut self, pixel: u32) {
         self.buffer.fill(pixel);
-        self.z_buffer.fill(f32::MIN);
+        self.overdraw.fill(0);
+    }
+    // a color per pixel, for gradients and skies behind the scene. The depth is
+    // kept like with `clear_color`
+    pub fn clear_with(&mut self, mut pixel: impl FnMut(u32, u32) -> u32) {
+        self.overdraw.fill(0);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                self.draw_pixel_unchecked(x, y, pixel(x, y));
+            }
+        }
+    }
+    // the depth alone to `depth.clear`, the colors are kept
+    pub fn clear_depth(&mut self) {
+        self.clear_depth_to(self.depth.clear);
+    }
+    // `clear_depth` to some other value, e.g. for a pass testing the other way
+    pub fn clear_depth_to(&mut self, value: f32) {
+        self.z_buffer.fill(value);
+        self.hiz.reset(value);
+    }
+    pub fn clear_ids(&mut self) {
+        if let Some(ids) = self.ids.as_deref_mut() {
+            ids.fill(None);
+        }
+    }
+    pub fn clear_velocity(&mut self) {
+        if let Some(velocity) = self.velocity.as_deref_mut() {
+            velocity.fill((0.0, 0.0));
+        }
+    }
+    pub fn clear_normals(&mut self) {
+        if let Some(normals) = self.normals.as_deref_mut() {
+            normals.fill([0.0; 3]);
+        }
+    }
+    pub fn clear_stencil(&mut self, value: u8) {
+        if let Some(stencil) = self.stencil.as_deref_mut() {
+            stencil.fill(value);
+        }
     }
     pub fn draw_line(&mut self, x0: i32, y0: i32, x1: i32, y1: i32, pixel: u32) {
+        let (x0, y0) = self.apply_2d(x0, y0);
+        let (x1, y1) = self.apply_2d(x1, y1);
         let (x0, y0, x1, y1) = if let Some(Line2D { x0, y0, x1, y1 }) = (Line2D {
             x0: x0 as f32,
             y0: y0 as f32,
@@ -51,77 +760,1419 @@ impl<'b> Renderer<'b> {
         } else {
             return;
         };
-        self.draw_pixel_unchecked(x1 as u32, y1 as u32, pixel);
-
-        let mut ray = Ray::new(x0, y0, x1, y1);
-        while !ray.reached {
-            let (x, y) = ray.next_xy();
+        for (x, y) in LineIter::new(x0, y0, x1, y1) {
             self.draw_pixel_unchecked(x as u32, y as u32, pixel)
         }
     }
-    pub fn fill_triangle(&mut self, verts: &[Vector3], shader: &mut impl Shader) {
-        let ((x_min, y_min), (x_max, y_max)) = triangle_bunding_box(verts);
-        let x_min = (x_min.round() as i32).clamp(0, self.width as i32) as u32;
-        let y_min = (y_min.round() as i32).clamp(0, self.height as i32) as u32;
-        let x_max = (x_max.round() as i32).clamp(0, self.width as i32) as u32;
-        let y_max = (y_max.round() as i32).clamp(0, self.height as i32) as u32;
-        for y in y_min..y_max {
-            for x in x_min..x_max {
-                // TODO: why it doesn't consider z
-                let mut bc = barycentric(
-                    x as f32 + 0.5,
-                    y as f32 + 0.5,
-                    verts[0].x(),
-                    verts[0].y(),
-                    verts[1].x(),
-                    verts[1].y(),
-                    verts[2].x(),
-                    verts[2].y(),
-                );
-                if bc.x() < 0.0 || bc.y() < 0.0 || bc.z() < 0.0 {
+    // paint bucket: the 4-connected pixels of the color at (x, y), through
+    // `transform_2d`, become `pixel`, returns how many. A span of a row at a
+    // time off a stack of seeds, so a large area doesn't recurse
+    pub fn flood_fill(&mut self, x: i32, y: i32, pixel: u32) -> usize {
+        let (x, y) = self.apply_2d(x, y);
+        let Some(seed) = self.inside(x, y) else {
+            return 0;
+        };
+        let target = self.pixel(seed.0, seed.1);
+        if target == pixel {
+            return 0;
+        }
+        let mut filled = 0;
+        let mut seeds = vec![seed];
+        while let Some((x, y)) = seeds.pop() {
+            if self.pixel(x, y) != target {
+                continue;
+            }
+            let mut left = x;
+            while left > 0 && self.pixel(left - 1, y) == target {
+                left -= 1;
+            }
+            let mut right = x + 1;
+            while right < self.width && self.pixel(right, y) == target {
+                right += 1;
+            }
+            for x in left..right {
+                self.draw_pixel_unchecked(x, y, pixel);
+            }
+            filled += (right - left) as usize;
+            // a seed per run of the target color in the rows above and below
+            for row in [y.wrapping_sub(1), y + 1] {
+                if row >= self.height {
                     continue;
                 }
-                let mut z = 0.0;
+                let mut in_run = false;
+                for x in left..right {
+                    let matches = self.pixel(x, row) == target;
+                    if matches && !in_run {
+                        seeds.push((x, row));
+                    }
+                    in_run = matches;
+                }
+            }
+        }
+        filled
+    }
+    pub fn fill_triangle(&mut self, verts: &[Vector3; 3], shader: &mut impl Shader) {
+        // the caller ran the vertex shader for every corner
+        self.stats.vertices_shaded += 3;
+        let wanted = self.outputs_wanted();
+        self.rasterize(verts, &mut FlatFragments { shader, wanted });
+    }
+    // `fill_triangle` with a bare fragment closure, for quick shading where the
+    // vertices are already on screen. The closure gives only the color
+    pub fn fill_triangle_with(
+        &mut self,
+        verts: &[Vector3; 3],
+        fregment: impl FnMut(&Vector3) -> Option<u32>,
+    ) {
+        self.fill_triangle(verts, &mut FnShader(fregment));
+    }
+    // nothing per pixel but the colors and the depth, for the quads of the
+    // simd fill
+    #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+    fn simple_state(&self) -> bool {
+        self.stencil.is_none()
+            && self.ids.is_none()
+            && self.velocity.is_none()
+            && self.normals.is_none()
+            && self.clip_plane.is_none()
+            && self.blend == Blend::Replace
+            && self.debug == DebugView::Shaded
+            && self.overdraw.is_empty()
+    }
+    // `draw_indexed` with the model's own buffers, shared corners are shaded once
+    #[cfg(feature = "model")]
+    pub fn draw_model<S: IndexedShader>(&mut self, model: &Model, shader: &mut S) {
+        let (vertices, indices) = model.buffers();
+        self.draw_indexed(vertices, indices, shader);
+    }
+    // every vertex of `vertices` that a triangle uses goes through the vertex
+    // shader once, triangles sharing it reuse the result
+    pub fn draw_indexed<S: IndexedShader>(
+        &mut self,
+        vertices: &VertexBuffer,
+        indices: &IndexBuffer,
+        shader: &mut S,
+    ) {
+        let mut cache: Vec<Option<(Vector3, S::Varying)>> = vec![None; vertices.len()];
+        for triangle in indices.indices.chunks_exact(3) {
+            for &i in triangle {
+                if cache[i].is_none() {
+                    cache[i] = Some(shader.vertex(vertices, i));
+                    self.stats.vertices_shaded += 1;
+                } else {
+                    self.stats.vertex_cache_hits += 1;
+                }
+            }
+            let [a, b, c] = [0, 1, 2].map(|j| cache[triangle[j]].as_ref().unwrap());
+            let verts = [a.0.clone(), b.0.clone(), c.0.clone()];
+            let mut fragments = IndexedFragments {
+                shader: &mut *shader,
+                varyings: [&a.1, &b.1, &c.1],
+                wanted: self.outputs_wanted(),
+            };
+            self.rasterize(&verts, &mut fragments);
+        }
+    }
+    // which of the shader's `previous_position`, `normal` and `uv` a fragment
+    // needs to be asked for
+    fn outputs_wanted(&self) -> Wanted {
+        Wanted {
+            motion: self.velocity.is_some(),
+            normal: self.normals.is_some() || self.debug == DebugView::Normals,
+            uv: self.debug == DebugView::Uv,
+        }
+    }
+    // the edge walk of a triangle and the pixels it can cover, None when it
+    // covers none
+    fn setup(&mut self, verts: &[Vector3; 3]) -> Option<Setup> {
+        self.stats.triangles += 1;
+        let face = self.face;
+        self.face += 1;
+        // past the guard band the fixed point edge functions could overflow
+        let mut p = [(0, 0); 3];
+        for (p, v) in p.iter_mut().zip(verts) {
+            *p = (snap(v.x() + self.jitter.0)?, snap(v.y() + self.jitter.1)?);
+        }
+        let area = edge(p[0], p[1], p[2]);
+        if area == 0 {
+            self.stats.degenerate_triangles += 1;
+            return None;
+        }
+        // the clip distance is affine in screen space like depth
+        let clip = match &self.clip_plane {
+            Some(plane) => {
+                let d = [0, 1, 2].map(|i| {
+                    plane.dot(&Vector4::new(verts[i].x(), verts[i].y(), verts[i].z(), 1.0))
+                });
+                if d.iter().all(|&d| d < 0.0) {
+                    return None;
+                }
+                d.iter().any(|&d| d < 0.0).then_some(d)
+            }
+            None => None,
+        };
+        // either winding is drawn, inside is where every edge agrees with the area
+        let sign = area.signum();
+        // the edge opposite each vertex, its edge function is that vertex's weight
+        let edges = [(p[1], p[2]), (p[2], p[0]), (p[0], p[1])];
+        let owns_boundary = edges.map(|(a, b)| is_top_left(a, b, sign));
+        let (lo, hi) = p.iter().fold(
+            ((i64::MAX, i64::MAX), (i64::MIN, i64::MIN)),
+            |(lo, hi), v| {
+                (
+                    (lo.0.min(v.0), lo.1.min(v.1)),
+                    (hi.0.max(v.0), hi.1.max(v.1)),
+                )
+            },
+        );
+        // pixels whose center is inside the bounds. A sliver or a triangle
+        // smaller than a pixel can fall between the centers and cover none
+        let first = |lo: i64, max: u32| {
+            (lo - SUBPIXEL / 2 + SUBPIXEL - 1)
+                .div_euclid(SUBPIXEL)
+                .clamp(0, max as i64) as u32
+        };
+        let end = |hi: i64, max: u32| {
+            ((hi - SUBPIXEL / 2).div_euclid(SUBPIXEL) + 1).clamp(0, max as i64) as u32
+        };
+        let (x_min, y_min) = (first(lo.0, self.width), first(lo.1, self.height));
+        let (x_max, y_max) = (end(hi.0, self.width), end(hi.1, self.height));
+        if x_min >= x_max || y_min >= y_max {
+            self.stats.degenerate_triangles += 1;
+            return None;
+        }
+        // edge functions at the first pixel center, then stepped a pixel at a time
+        let center = |x: u32, y: u32| {
+            (
+                x as i64 * SUBPIXEL + SUBPIXEL / 2,
+                y as i64 * SUBPIXEL + SUBPIXEL / 2,
+            )
+        };
+        let origin = edges.map(|(a, b)| edge(a, b, center(x_min, y_min)) * sign);
+        let step_y = edges.map(|(a, b)| (b.0 - a.0) * SUBPIXEL * sign);
+        let walk = EdgeWalk {
+            step_x: edges.map(|(a, b)| -(b.1 - a.1) * SUBPIXEL * sign),
+            // top-left rule: a pixel center exactly on a shared edge belongs to
+            // only one of the two triangles
+            bias: owns_boundary.map(|owns| if owns { -1 } else { 0 }),
+            total: area * sign,
+            z: [0, 1, 2].map(|i| verts[i].z()),
+            xy: [0, 1, 2].map(|i| (verts[i].x(), verts[i].y())),
+            normal: self.outputs_wanted().normal.then(|| self.facing(verts)),
+            heights: (self.debug == DebugView::BarycentricWireframe).then(|| {
+                edges.map(|(a, b)| {
+                    let length = (((b.0 - a.0).pow(2) + (b.1 - a.1).pow(2)) as f32).sqrt();
+                    (area.abs() as f32 / length / SUBPIXEL as f32).max(f32::EPSILON)
+                })
+            }),
+            face,
+            correction: self.depth.perspective_depths(verts),
+            clip,
+            // the edge functions are linear, so the box corners bound them
+            #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+            fits_i32: [x_min, x_max + 4].iter().all(|&x| {
+                [y_min, y_max].iter().all(|&y| {
+                    edges
+                        .iter()
+                        .all(|&(a, b)| edge(a, b, center(x, y)).abs() < i32::MAX as i64)
+                })
+            }),
+        };
+        Some(Setup {
+            walk,
+            origin,
+            step_y,
+            x_min,
+            x_max,
+            y_min,
+            y_max,
+        })
+    }
+    fn rasterize(&mut self, verts: &[Vector3; 3], fregment: &mut impl Fragments) {
+        let Some(setup) = self.setup(verts) else {
+            return;
+        };
+        let Setup {
+            ref walk,
+            origin,
+            step_y,
+            x_min,
+            x_max,
+            y_min,
+            y_max,
+        } = setup;
+        if self.blend == Blend::WeightedOit && self.oit.is_none() {
+            self.oit = Some(WeightedOit::new(self.buffer.len()));
+        }
+        // most of a dense mesh covers a pixel or two: their centers are tested
+        // straight away, the tiles and the coarse depth cost more than they skip
+        if (x_max - x_min) * (y_max - y_min) <= SMALL_TRIANGLE {
+            self.stats.small_triangles += 1;
+            let mut row = origin;
+            for y in y_min..y_max {
+                self.walk_row(walk, y, x_min, x_max, row, fregment);
                 for i in 0..3 {
-                    bc[i] /= verts[i].z();
-                    z += bc[i];
+                    row[i] += step_y[i];
                 }
-                let z = 1.0 / z;
-                if self.z_buffer[(x + y * self.stride) as usize] < z {
+            }
+            return;
+        }
+        // the corners bound the depth of every pixel as long as the corrected
+        // barycentrics can't go negative
+        let z_range = match walk.correction {
+            Some(depths) if depths.iter().any(|&d| d < 0.0) => (f32::MIN, f32::MAX),
+            _ => walk
+                .z
+                .iter()
+                .fold((f32::MAX, f32::MIN), |(lo, hi), &z| (lo.min(z), hi.max(z))),
+        };
+        // skipping pixels that fail the depth test also skips their stencil ops
+        let early_depth = self.stencil.is_none()
+            || (self.stencil_state.fail == StencilOp::Keep
+                && self.stencil_state.depth_fail == StencilOp::Keep);
+        #[cfg(feature = "std")]
+        if self.threads > 1 && y_max - y_min >= self.threads as u32 * PARALLEL_ROWS {
+            let forks: Option<Vec<_>> = (1..self.threads).map(|_| fregment.fork()).collect();
+            if let Some(forks) = forks {
+                self.rasterize_parallel(&setup, z_range, early_depth, fregment, forks);
+                return;
+            }
+        }
+        let mut occluded = true;
+        for ty in y_min / TILE_SIZE..=(y_max - 1) / TILE_SIZE {
+            for tx in x_min / TILE_SIZE..=(x_max - 1) / TILE_SIZE {
+                let tile = self.hiz.range(tx, ty, self.z_buffer, self.stride);
+                if early_depth && self.depth.compare.rejects(z_range, tile) {
+                    self.stats.occluded_tiles += 1;
+                    continue;
+                }
+                occluded = false;
+                let x0 = (tx * TILE_SIZE).max(x_min);
+                let x1 = ((tx + 1) * TILE_SIZE).min(x_max);
+                let y0 = (ty * TILE_SIZE).max(y_min);
+                let y1 = ((ty + 1) * TILE_SIZE).min(y_max);
+                let (dx, dy) = ((x0 - x_min) as i64, (y0 - y_min) as i64);
+                let mut row = [0, 1, 2].map(|i| origin[i] + dx * walk.step_x[i] + dy * step_y[i]);
+                for y in y0..y1 {
+                    self.walk_row(walk, y, x0, x1, row, fregment);
                     for i in 0..3 {
-                        bc[i] *= z;
+                        row[i] += step_y[i];
+                    }
+                }
+            }
+        }
+        if occluded {
+            self.stats.occluded_triangles += 1;
+        }
+    }
+    // the rows of a tall triangle dealt out in turn to the caller and the
+    // workers of the pool, each shading with one of `forks` but the caller with
+    // `fregment`. The same pixels as the tiles of `rasterize`, a row at a time
+    #[cfg(feature = "std")]
+    fn rasterize_parallel<'f>(
+        &mut self,
+        setup: &Setup,
+        z_range: (f32, f32),
+        early_depth: bool,
+        fregment: &mut impl Fragments,
+        forks: Vec<Box<dyn Shader + Send + 'f>>,
+    ) {
+        let (x_min, x_max, y_min, y_max) = (setup.x_min, setup.x_max, setup.y_min, setup.y_max);
+        // which tiles the coarse depth leaves to fill, row of tiles by row
+        let tiles = (x_min / TILE_SIZE, (x_max - 1) / TILE_SIZE);
+        let mut visible = Vec::new();
+        for ty in y_min / TILE_SIZE..=(y_max - 1) / TILE_SIZE {
+            for tx in tiles.0..=tiles.1 {
+                let tile = self.hiz.range(tx, ty, self.z_buffer, self.stride);
+                let rejected = early_depth && self.depth.compare.rejects(z_range, tile);
+                self.stats.occluded_tiles += rejected as usize;
+                visible.push(!rejected);
+            }
+        }
+        if !visible.contains(&true) {
+            self.stats.occluded_triangles += 1;
+            return;
+        }
+        let (state, wanted) = (&self.pixel_state(), self.outputs_wanted());
+        // row y to part y % parts, so the rows of every part of the triangle
+        // are shared out evenly
+        let parts = forks.len() + 1;
+        let mut rows: Vec<Vec<Row>> = (0..parts).map(|_| Vec::new()).collect();
+        for (i, row) in self.rows(y_min, y_max).enumerate() {
+            rows[i % parts].push(row);
+        }
+        let mut rows = rows.into_iter();
+        let mut local = rows.next().unwrap();
+        let visible = &visible[..];
+        let jobs = rows
+            .zip(forks)
+            .map(|(mut rows, mut shader)| {
+                Box::new(move || {
+                    let mut fregment = FlatFragments {
+                        shader: &mut *shader,
+                        wanted,
+                    };
+                    fill_rows(&mut rows, setup, state, visible, &mut fregment)
+                }) as Box<dyn FnOnce() -> _ + Send + '_>
+            })
+            .collect();
+        let done = pool::run(
+            || fill_rows(&mut local, setup, state, visible, fregment),
+            jobs,
+        );
+        for (y, shaded, written) in done.into_iter().flatten() {
+            self.row_done(y, shaded, written);
+        }
+    }
+    // pixels [x0, x1) of row y, `w` holds the edge functions at x0
+    fn walk_row(
+        &mut self,
+        walk: &EdgeWalk,
+        y: u32,
+        x0: u32,
+        x1: u32,
+        w: [i64; 3],
+        fregment: &mut impl Fragments,
+    ) {
+        let state = self.pixel_state();
+        let (shaded, written) = {
+            let mut row = self.rows(y, y + 1).next().unwrap();
+            row.walk(&state, walk, x0, x1, w, fregment);
+            (row.shaded, row.written)
+        };
+        self.row_done(y, shaded, written);
+    }
+    // what a walked row leaves for the renderer: the count of fragments shaded
+    // and the tiles whose depth changed
+    fn row_done(&mut self, y: u32, shaded: usize, written: Option<(u32, u32)>) {
+        self.stats.fragments_shaded += shaded;
+        if let Some((lo, hi)) = written {
+            for tx in lo / TILE_SIZE..=hi / TILE_SIZE {
+                self.hiz.mark(tx * TILE_SIZE, y);
+            }
+        }
+    }
+    // rows [y0, y1) of the target and every attachment
+    fn rows(&mut self, y0: u32, y1: u32) -> impl Iterator<Item = Row<'_>> {
+        let (width, stride) = (self.width as usize, self.stride as usize);
+        let (start, end) = (y0 as usize * stride, (y1 - 1) as usize * stride + width);
+        // the next row off the front of what is left of an attachment
+        fn next<'a, T>(rest: &mut &'a mut [T], stride: usize, width: usize) -> &'a mut [T] {
+            let rows = core::mem::take(rest);
+            let (row, others) = rows.split_at_mut(stride.min(rows.len()));
+            *rest = others;
+            &mut row[..width]
+        }
+        let mut color = &mut self.buffer[start..end];
+        let mut depth = &mut self.z_buffer[start..end];
+        let mut stencil = self.stencil.as_deref_mut().map(|s| &mut s[start..end]);
+        let mut ids = self.ids.as_deref_mut().map(|s| &mut s[start..end]);
+        let mut velocity = self.velocity.as_deref_mut().map(|s| &mut s[start..end]);
+        let mut normals = self.normals.as_deref_mut().map(|s| &mut s[start..end]);
+        let mut overdraw = (!self.overdraw.is_empty()).then(|| &mut self.overdraw[start..end]);
+        let mut oit = self.oit.as_mut().map(|oit| {
+            let (accum, revealage) = oit.sums();
+            (&mut accum[start..end], &mut revealage[start..end])
+        });
+        (y0..y1).map(move |y| Row {
+            y,
+            color: next(&mut color, stride, width),
+            depth: next(&mut depth, stride, width),
+            stencil: stencil.as_mut().map(|s| next(s, stride, width)),
+            ids: ids.as_mut().map(|s| next(s, stride, width)),
+            velocity: velocity.as_mut().map(|s| next(s, stride, width)),
+            normals: normals.as_mut().map(|s| next(s, stride, width)),
+            overdraw: overdraw.as_mut().map(|s| next(s, stride, width)),
+            oit: oit
+                .as_mut()
+                .map(|(a, r)| (next(a, stride, width), next(r, stride, width))),
+            shaded: 0,
+            written: None,
+        })
+    }
+    // what the rows need of the renderer's state for shading a pixel
+    fn pixel_state(&self) -> PixelState {
+        PixelState {
+            depth: self.depth,
+            stencil: self.stencil_state,
+            blend: self.blend,
+            debug: self.debug,
+            field: self.field,
+            object: self.object,
+            #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+            quads: self.simple_state(),
+        }
+    }
+    // another target of the same size drawn with the same depth state, e.g. a part
+    // of a split `CommandBuffer`: its pixels replace ours where they pass the depth
+    // test against what is here
+    pub fn composite(&mut self, buffer: &[u32], z_buffer: &[f32]) {
+        assert_eq!(buffer.len(), self.buffer.len());
+        assert_eq!(z_buffer.len(), self.z_buffer.len());
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let i = (x + y * self.stride) as usize;
+                if self.depth.compare.passes(z_buffer[i], self.z_buffer[i]) {
+                    self.buffer[i] = buffer[i];
+                    self.z_buffer[i] = z_buffer[i];
+                    self.hiz.mark(x, y);
+                }
+            }
+        }
+    }
+    // `image` with its top left at (x, y) through `transform_2d`, nearest texel,
+    // with the renderer's blend like `draw_pixel`. The depth is left alone
+    pub fn draw_image(&mut self, x: i32, y: i32, image: &Image) {
+        let Some(inverse) = self.transform_2d.inverse() else {
+            return;
+        };
+        let (w, h) = (image.width as f32, image.height as f32);
+        let (x_min, y_min, x_max, y_max) = self.bounds_2d(x as f32, y as f32, w, h);
+        for sy in y_min..y_max {
+            for sx in x_min..x_max {
+                let (u, v) = inverse.apply_2d(sx as f32 + 0.5, sy as f32 + 0.5);
+                let (u, v) = ((u - x as f32).floor(), (v - y as f32).floor());
+                if u < 0.0 || v < 0.0 || u >= w || v >= h {
+                    continue;
+                }
+                let texel = image.buffer[(u as u32 + v as u32 * image.width) as usize];
+                match self.blend {
+                    Blend::Replace => self.draw_pixel_unchecked(sx, sy, texel),
+                    Blend::Alpha | Blend::WeightedOit => {
+                        self.blend_pixel_unchecked(sx, sy, texel, 1.0)
+                    }
+                }
+            }
+        }
+    }
+    // a `width` wide image and its depth copied in with the top left at (x, y),
+    // no test or blending, and no `transform_2d`
+    pub fn blit(&mut self, x: u32, y: u32, width: u32, buffer: &[u32], z_buffer: &[f32]) {
+        assert_eq!(buffer.len(), z_buffer.len());
+        let height = buffer.len() as u32 / width.max(1);
+        assert!(x + width <= self.width && y + height <= self.height);
+        for row in 0..height {
+            for column in 0..width {
+                let src = (column + row * width) as usize;
+                let dst = ((x + column) + (y + row) * self.stride) as usize;
+                self.buffer[dst] = buffer[src];
+                self.z_buffer[dst] = z_buffer[src];
+                self.hiz.mark(x + column, y + row);
+            }
+        }
+    }
+    // composite the `Blend::WeightedOit` fragments drawn so far over the colors
+    pub fn resolve_oit(&mut self) {
+        if let Some(oit) = self.oit.as_mut() {
+            oit.resolve(self.buffer);
+        }
+    }
+    #[inline]
+    pub fn z_buffer(&self) -> &[f32] {
+        self.z_buffer
+    }
+    // screen-space outline pass: mark pixels where the depth jumps by more than
+    // `threshold` to a neighbour, or where the geometry meets the background
+    pub fn outline(&mut self, threshold: f32, pixel: u32) {
+        let depth_at = |x: u32, y: u32| self.z_buffer[(x + y * self.stride) as usize];
+        let mut edges = Vec::new();
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let z = depth_at(x, y);
+                let neighbours = [
+                    (x.wrapping_sub(1), y),
+                    (x + 1, y),
+                    (x, y.wrapping_sub(1)),
+                    (x, y + 1),
+                ];
+                let is_edge = neighbours
+                    .into_iter()
+                    .filter(|&(nx, ny)| nx < self.width && ny < self.height)
+                    .any(|(nx, ny)| {
+                        let n = depth_at(nx, ny);
+                        // only the nearer side of a discontinuity gets the line
+                        if z == self.depth.clear {
+                            false
+                        } else if n == self.depth.clear {
+                            true
+                        } else {
+                            self.depth.nearness(z) - self.depth.nearness(n) > threshold
+                        }
+                    });
+                if is_edge {
+                    edges.push((x, y));
+                }
+            }
+        }
+        for (x, y) in edges {
+            self.draw_pixel_unchecked(x, y, pixel);
+        }
+    }
+    // post pass smearing every pixel along its velocity over `samples` taps
+    // centered on it. `shutter` is the part of the frame the shutter is open:
+    // 1 blurs over the whole distance moved, 0.5 over half. Does nothing
+    // without a velocity attachment
+    pub fn motion_blur(&mut self, samples: u32, shutter: f32) {
+        let Some(velocity) = self.velocity.as_deref() else {
+            return;
+        };
+        let source = self.buffer.to_vec();
+        let samples = samples.max(1);
+        let (w, h) = (self.width as f32, self.height as f32);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let i = (x + y * self.stride) as usize;
+                let (vx, vy) = velocity[i];
+                if vx == 0.0 && vy == 0.0 {
+                    continue;
+                }
+                let mut sum = [0.0; 4];
+                for k in 0..samples {
+                    let t = ((k as f32 + 0.5) / samples as f32 - 0.5) * shutter;
+                    let sx = (x as f32 - vx * t).round().clamp(0.0, w - 1.0) as u32;
+                    let sy = (y as f32 - vy * t).round().clamp(0.0, h - 1.0) as u32;
+                    let pixel = source[(sx + sy * self.stride) as usize];
+                    for (c, sum) in sum.iter_mut().enumerate() {
+                        *sum += ((pixel >> (8 * c)) & 0xff) as f32;
+                    }
+                }
+                let mut new_pixel = 0;
+                for (c, sum) in sum.iter().enumerate() {
+                    let part = (sum / samples as f32).round() as u32;
+                    new_pixel |= (part & 0xff) << (8 * c);
+                }
+                self.buffer[i] = new_pixel;
+            }
+        }
+    }
+    // fills the pixels outside `field`, the ones that weren't drawn, from
+    // `history`, the frame before. Each is taken from where it was a frame ago
+    // by the velocity of its nearest drawn neighbour, or from the same place
+    // without a velocity attachment, then clamped to the colors of its drawn
+    // neighbours so what has gone since doesn't linger. The depth and velocity
+    // are the neighbour's
+    pub fn reconstruct(&mut self, field: Field, history: &[u32]) {
+        assert_eq!(history.len(), self.buffer.len());
+        let neighbours: &[(i32, i32)] = match field.pattern {
+            Interlace::Scanlines => &[(0, -1), (0, 1)],
+            Interlace::Checkerboard => &[(-1, 0), (1, 0), (0, -1), (0, 1)],
+        };
+        let (w, h) = (self.width as i32, self.height as i32);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if field.covers(x, y) {
+                    continue;
+                }
+                let mut lo = [255; 4];
+                let mut hi = [0; 4];
+                let mut nearest: Option<usize> = None;
+                for (dx, dy) in neighbours {
+                    let (nx, ny) = (x as i32 + dx, y as i32 + dy);
+                    if !(0..w).contains(&nx) || !(0..h).contains(&ny) {
+                        continue;
                     }
-                    self.z_buffer[(x + y * self.stride) as usize] = z;
-                    if let Some(color) = shader.fregment(&bc) {
-                        self.draw_pixel_unchecked(x, y, color);
+                    let n = (nx + ny * self.stride as i32) as usize;
+                    for c in 0..4 {
+                        let part = (self.buffer[n] >> (8 * c)) & 0xff;
+                        lo[c] = lo[c].min(part);
+                        hi[c] = hi[c].max(part);
                     }
+                    let nearer = |k: usize| {
+                        self.depth.nearness(self.z_buffer[n])
+                            > self.depth.nearness(self.z_buffer[k])
+                    };
+                    if nearest.map_or(true, nearer) {
+                        nearest = Some(n);
+                    }
+                }
+                // a single row has nothing drawn next to the other scanlines
+                let Some(nearest) = nearest else {
+                    continue;
+                };
+                let i = (x + y * self.stride) as usize;
+                let motion = self.velocity.as_deref().map(|velocity| velocity[nearest]);
+                let (vx, vy) = motion.unwrap_or((0.0, 0.0));
+                let (px, py) = ((x as f32 - vx).round(), (y as f32 - vy).round());
+                let previous = if (0.0..w as f32).contains(&px) && (0.0..h as f32).contains(&py) {
+                    history[(px as u32 + py as u32 * self.stride) as usize]
+                } else {
+                    self.buffer[nearest]
+                };
+                let mut pixel = 0;
+                for c in 0..4 {
+                    let part = ((previous >> (8 * c)) & 0xff).clamp(lo[c], hi[c]);
+                    pixel |= part << (8 * c);
+                }
+                self.buffer[i] = pixel;
+                self.z_buffer[i] = self.z_buffer[nearest];
+                self.hiz.mark(x, y);
+                if let (Some(velocity), Some(motion)) = (self.velocity.as_deref_mut(), motion) {
+                    velocity[i] = motion;
                 }
             }
         }
     }
+    // the unit normal of a triangle on screen, x right, y down and z towards
+    // the viewer
+    fn facing(&self, verts: &[Vector3; 3]) -> [f32; 3] {
+        let [a, b, c] = [0, 1, 2].map(|i| {
+            Vector3::new(
+                verts[i].x(),
+                verts[i].y(),
+                self.depth.nearness(verts[i].z()),
+            )
+        });
+        let mut normal = (&b - &a).cross(&(&c - &a));
+        if normal.z() < 0.0 {
+            normal = -normal;
+        }
+        let length = normal.length();
+        if length == 0.0 {
+            return [0.0, 0.0, 1.0];
+        }
+        [
+            normal.x() / length,
+            normal.y() / length,
+            normal.z() / length,
+        ]
+    }
+    // post pass like `outline` with a sobel filter, over the depth and, when
+    // attached, the normals, so creases get a line too and not just silhouettes.
+    // Pixels next to the background are always edges
+    pub fn draw_edges(&mut self, edges: &EdgeDetect) {
+        let (w, h) = (self.width as i64, self.height as i64);
+        let stride = self.stride as i64;
+        let index = |x: i64, y: i64| (x.clamp(0, w - 1) + y.clamp(0, h - 1) * stride) as usize;
+        // squared, scaled to the difference between neighbours
+        let sobel = |v: [f32; 9]| {
+            let gx = (v[2] + 2.0 * v[5] + v[8]) - (v[0] + 2.0 * v[3] + v[6]);
+            let gy = (v[6] + 2.0 * v[7] + v[8]) - (v[0] + 2.0 * v[1] + v[2]);
+            (gx * gx + gy * gy) / 16.0
+        };
+        let mut is_edge = vec![false; (self.width * self.height) as usize];
+        for y in 0..h {
+            for x in 0..w {
+                if self.z_buffer[index(x, y)] == self.depth.clear {
+                    continue;
+                }
+                let taps: [usize; 9] =
+                    core::array::from_fn(|k| index(x + k as i64 % 3 - 1, y + k as i64 / 3 - 1));
+                let edge = taps.iter().any(|&i| self.z_buffer[i] == self.depth.clear)
+                    || sobel(taps.map(|i| self.depth.nearness(self.z_buffer[i])))
+                        > edges.depth_threshold * edges.depth_threshold
+                    || self.normals.as_deref().is_some_and(|normals| {
+                        (0..3)
+                            .map(|c| sobel(taps.map(|i| normals[i][c])))
+                            .sum::<f32>()
+                            > edges.normal_threshold * edges.normal_threshold
+                    });
+                is_edge[(x + y * w) as usize] = edge;
+            }
+        }
+        // lines grow from the edge pixels to `width`
+        let (before, after) = ((edges.width.max(1) as i64 - 1) / 2, edges.width as i64 / 2);
+        let mut line = vec![false; is_edge.len()];
+        for y in 0..h {
+            for x in 0..w {
+                if !is_edge[(x + y * w) as usize] {
+                    continue;
+                }
+                for ly in (y - before).max(0)..=(y + after).min(h - 1) {
+                    for lx in (x - before).max(0)..=(x + after).min(w - 1) {
+                        line[(lx + ly * w) as usize] = true;
+                    }
+                }
+            }
+        }
+        for (i, _) in line.iter().enumerate().filter(|(_, &line)| line) {
+            let (x, y) = (i as u32 % self.width, i as u32 / self.width);
+            self.draw_pixel_unchecked(x, y, edges.color);
+        }
+    }
+    // post pass coloring every pixel shaded since the colors were cleared by how
+    // often it was, blue for once to red for `max` or more times. Needs
+    // `track_overdraw`, the pixels never shaded are kept
+    pub fn draw_overdraw_heatmap(&mut self, max: u16) {
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let i = (x + y * self.stride) as usize;
+                match self.overdraw.get(i) {
+                    Some(&count) if count > 0 => self.buffer[i] = overdraw_heat(count, max),
+                    _ => {}
+                }
+            }
+        }
+    }
+    // post pass looking every pixel up in `lut`, `strength` 0 keeps the colors
+    // and 1 takes the graded ones
+    pub fn color_grade(&mut self, lut: &Lut, strength: f32) {
+        for y in 0..self.height {
+            let row = (y * self.stride) as usize;
+            for pixel in self.buffer[row..row + self.width as usize].iter_mut() {
+                let graded = lut.apply(*pixel);
+                if strength >= 1.0 {
+                    *pixel = graded;
+                    continue;
+                }
+                let t = strength.max(0.0);
+                let mut new_pixel = *pixel & 0xff000000;
+                for i in 0..3 {
+                    let a = ((*pixel >> (8 * i)) & 0xff) as f32;
+                    let b = ((graded >> (8 * i)) & 0xff) as f32;
+                    new_pixel |= (((a + (b - a) * t).round() as u32) & 0xff) << (8 * i);
+                }
+                *pixel = new_pixel;
+            }
+        }
+    }
+    #[cfg(feature = "ppm")]
     pub fn save_to_ppm_file(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
         save_buffer_to_ppm_file(self.buffer, self.width, self.height, self.stride, path)
     }
 }
 
-fn triangle_bunding_box(verts: &[Vector3]) -> ((f32, f32), (f32, f32)) {
-    let mut xs: Vec<f32> = verts.iter().map(|v| v.x()).collect();
-    let mut ys: Vec<f32> = verts.iter().map(|v| v.y()).collect();
-    xs.sort_by(|a, b| a.partial_cmp(b).unwrap());
-    ys.sort_by(|a, b| a.partial_cmp(b).unwrap());
-    ((xs[0], ys[0]), (xs[2], ys[2]))
+// 0 blue, through cyan, green and yellow, to 1 red
+fn heat(t: f32) -> u32 {
+    let t = t.clamp(0.0, 1.0) * 4.0;
+    let rgb = if t < 1.0 {
+        [0.0, t, 1.0]
+    } else if t < 2.0 {
+        [0.0, 1.0, 2.0 - t]
+    } else if t < 3.0 {
+        [t - 2.0, 1.0, 0.0]
+    } else {
+        [1.0, 4.0 - t, 0.0]
+    };
+    pack_rgb(rgb)
+}
+
+// one fragment blue, `max` and more red
+fn overdraw_heat(count: u16, max: u16) -> u32 {
+    heat((count as f32 - 1.0) / (max.max(2) - 1) as f32)
 }
 
-// return (u, v, w)
-#[inline]
-fn barycentric(x: f32, y: f32, x0: f32, y0: f32, x1: f32, y1: f32, x2: f32, y2: f32) -> Vector3 {
-    // Barycentric coordinate system
-    // https://github.com/ssloy/tinyrenderer/wiki/Lesson-2:-Triangle-rasterization-and-back-face-culling#:~:text=It%20means%20that%20we%20are%20looking%20for%20a%20vector%20(u%2Cv%2C1)%20that%20is%20orthogonal%20to%20(ABx%2CACx%2CPAx)%20and%20(ABy%2CACy%2CPAy)%20at%20the%20same%20time!
-    let u = Vector3::new(x2 - x0, x1 - x0, x0 - x).cross(&Vector3::new(y2 - y0, y1 - y0, y0 - y));
-    if u.z().abs() < 1.0 {
-        return Vector3::new(-1.0, 1.0, 1.0);
+// opaque, from 0..1 per channel
+fn pack_rgb(rgb: [f32; 3]) -> u32 {
+    let mut pixel = 0xff000000;
+    for (i, c) in rgb.iter().enumerate() {
+        pixel |= ((c.clamp(0.0, 1.0) * 255.0).round() as u32) << (8 * i);
     }
-    Vector3::new(1.0 - (u.x() + u.y()) / u.z(), u.y() / u.z(), u.x() / u.z())
+    pixel
+}
+
+// vertices are snapped to a 28.4 fixed point grid before rasterizing: coverage
+// is exact integer math, so edges shared by two triangles are watertight, and
+// vertices move in sixteenths of a pixel instead of popping between pixels
+const SUBPIXEL: i64 = 16;
+// snapped coordinates must stay below this (2^22 pixels) for the edge functions
+// to fit in an i64; triangles reaching further out are dropped
+const GUARD_BAND: f32 = (1i64 << 26) as f32;
+// pixels in the bounds of a triangle for the fill to skip the tiles
+const SMALL_TRIANGLE: u32 = 4;
+// rows per thread a triangle needs for its rows to be shared out to the worker
+// pool, below that the threads cost more than they save
+#[cfg(feature = "std")]
+const PARALLEL_ROWS: u32 = 32;
+
+fn snap(v: f32) -> Option<i64> {
+    let v = (v * SUBPIXEL as f32).round();
+    (v.abs() < GUARD_BAND).then_some(v as i64)
+}
+
+// what the shader says beside the color, asked only with the attachment taking it
+#[derive(Default)]
+struct Outputs {
+    previous: Option<Vector2>,
+    normal: Option<Vector3>,
+    uv: Option<Vector2>,
+}
+
+#[derive(Clone, Copy)]
+struct Wanted {
+    motion: bool,
+    normal: bool,
+    uv: bool,
+}
+
+// a shader of either kind as the rasterizer calls it
+trait Fragments {
+    fn shade(&mut self, bc: &Vector3, outputs: &mut Outputs) -> Option<u32>;
+    // four pixels without any outputs
+    #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+    fn shade4(&mut self, bc: &[Vector3; 4]) -> [Option<u32>; 4];
+    // see `Shader::fork`
+    #[cfg(feature = "std")]
+    fn fork<'f>(&self) -> Option<Box<dyn Shader + Send + 'f>>
+    where
+        Self: 'f;
+}
+
+// a fragment closure as a shader, for `fill_triangle_with`
+struct FnShader<F>(F);
+
+impl<F: FnMut(&Vector3) -> Option<u32>> Shader for FnShader<F> {
+    fn vertex(&mut self, _iface: usize, _nthvert: usize) -> Vector3 {
+        unreachable!("fill_triangle_with takes the vertices on screen")
+    }
+    fn fregment(&mut self, bc: &Vector3) -> Option<u32> {
+        (self.0)(bc)
+    }
+}
+
+struct FlatFragments<'s, S: ?Sized> {
+    shader: &'s mut S,
+    wanted: Wanted,
+}
+
+impl<S: Shader + ?Sized> Fragments for FlatFragments<'_, S> {
+    #[inline]
+    fn shade(&mut self, bc: &Vector3, outputs: &mut Outputs) -> Option<u32> {
+        let color = self.shader.fregment(bc);
+        if self.wanted.motion {
+            outputs.previous = self.shader.previous_position(bc);
+        }
+        if self.wanted.normal {
+            outputs.normal = self.shader.normal(bc);
+        }
+        if self.wanted.uv {
+            outputs.uv = self.shader.uv(bc);
+        }
+        color
+    }
+    #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+    #[inline]
+    fn shade4(&mut self, bc: &[Vector3; 4]) -> [Option<u32>; 4] {
+        self.shader.fregment4(bc)
+    }
+    #[cfg(feature = "std")]
+    fn fork<'f>(&self) -> Option<Box<dyn Shader + Send + 'f>>
+    where
+        Self: 'f,
+    {
+        self.shader.fork()
+    }
+}
+
+struct IndexedFragments<'s, 'v, S: IndexedShader> {
+    shader: &'s mut S,
+    varyings: [&'v S::Varying; 3],
+    wanted: Wanted,
+}
+
+impl<S: IndexedShader> Fragments for IndexedFragments<'_, '_, S> {
+    #[inline]
+    fn shade(&mut self, bc: &Vector3, outputs: &mut Outputs) -> Option<u32> {
+        let varyings = self.varyings;
+        let color = self.shader.fregment(varyings, bc);
+        if self.wanted.motion {
+            outputs.previous = self.shader.previous_position(varyings, bc);
+        }
+        if self.wanted.normal {
+            outputs.normal = self.shader.normal(varyings, bc);
+        }
+        if self.wanted.uv {
+            outputs.uv = self.shader.uv(varyings, bc);
+        }
+        color
+    }
+    #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+    #[inline]
+    fn shade4(&mut self, bc: &[Vector3; 4]) -> [Option<u32>; 4] {
+        self.shader.fregment4(self.varyings, bc)
+    }
+    #[cfg(feature = "std")]
+    fn fork<'f>(&self) -> Option<Box<dyn Shader + Send + 'f>>
+    where
+        Self: 'f,
+    {
+        self.shader.fork(self.varyings)
+    }
+}
+
+// a triangle ready to walk: the edge functions at the center of pixel (x_min,
+// y_min) and their step a row down. Pixels [x_min, x_max) x [y_min, y_max)
+struct Setup {
+    walk: EdgeWalk,
+    origin: [i64; 3],
+    step_y: [i64; 3],
+    x_min: u32,
+    x_max: u32,
+    y_min: u32,
+    y_max: u32,
+}
+
+// per triangle constants for walking its pixels. `w > bias` is inside an edge,
+// the bias is -1 for edges that own their boundary
+struct EdgeWalk {
+    step_x: [i64; 3],
+    bias: [i64; 3],
+    total: i64,
+    z: [f32; 3],
+    // the corners on screen before any jitter, for the velocity attachment
+    xy: [(f32, f32); 3],
+    // the facing on screen, for the normal attachment
+    normal: Option<[f32; 3]>,
+    // over each edge in pixels, for `DebugView::BarycentricWireframe`
+    heights: Option<[f32; 3]>,
+    face: usize,
+    // per corner depths dividing the barycentrics for perspective, if any
+    correction: Option<[f32; 3]>,
+    // per corner distances to the clip plane, when it cuts the triangle
+    clip: Option<[f32; 3]>,
+    // small enough for the 32 bit lanes of the simd walk
+    #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+    fits_i32: bool,
+}
+
+impl EdgeWalk {
+    // the edge functions over twice the area
+    #[cfg(not(feature = "fixed"))]
+    #[inline]
+    fn barycentric(&self, w: [i64; 3]) -> Vector3 {
+        Vector3::new(
+            w[0] as f32 / self.total as f32,
+            w[1] as f32 / self.total as f32,
+            w[2] as f32 / self.total as f32,
+        )
+    }
+    // rounded to 16.16 with integer division, so coverage and weights don't
+    // depend on the float unit
+    #[cfg(feature = "fixed")]
+    #[inline]
+    fn barycentric(&self, w: [i64; 3]) -> Vector3 {
+        let [a, b, c] = w.map(|w| crate::fixed::Fixed::ratio(w, self.total).to_f32());
+        Vector3::new(a, b, c)
+    }
+    // the screen space barycentrics corrected for perspective, if the depth
+    // state asks for it
+    #[inline]
+    fn perspective(&self, mut bc: Vector3) -> Vector3 {
+        if let Some(depths) = self.correction {
+            let mut sum = 0.0;
+            for i in 0..3 {
+                bc[i] /= depths[i];
+                sum += bc[i];
+            }
+            for i in 0..3 {
+                bc[i] /= sum;
+            }
+        }
+        bc
+    }
+    // 1 / sum(bc / z) over the depths the correction uses, which is the same as
+    // weighing the corners' depths by the corrected barycentrics
+    #[inline]
+    fn depth(&self, bc: &Vector3) -> f32 {
+        let bc = self.perspective(bc.clone());
+        bc[0] * self.z[0] + bc[1] * self.z[1] + bc[2] * self.z[2]
+    }
+    #[inline]
+    fn clipped(&self, bc: &Vector3) -> bool {
+        self.clip
+            .is_some_and(|d| bc[0] * d[0] + bc[1] * d[1] + bc[2] * d[2] < 0.0)
+    }
+}
+
+// what a walked row leaves for `Renderer::row_done`: its y, the fragments
+// shaded and the first and last pixel whose depth was written
+#[cfg(feature = "std")]
+type RowDone = (u32, usize, Option<(u32, u32)>);
+
+// the visible tiles' pixels of each of `rows` of a triangle, `visible` holds
+// the tiles of its bounds a row of tiles after another
+#[cfg(feature = "std")]
+fn fill_rows(
+    rows: &mut [Row],
+    setup: &Setup,
+    state: &PixelState,
+    visible: &[bool],
+    fregment: &mut impl Fragments,
+) -> Vec<RowDone> {
+    let Setup {
+        ref walk,
+        origin,
+        step_y,
+        x_min,
+        x_max,
+        y_min,
+        ..
+    } = *setup;
+    let columns = ((x_max - 1) / TILE_SIZE - x_min / TILE_SIZE + 1) as usize;
+    for row in rows.iter_mut() {
+        let ty = (row.y / TILE_SIZE - y_min / TILE_SIZE) as usize;
+        let tiles = &visible[ty * columns..][..columns];
+        for (tx, _) in (x_min / TILE_SIZE..).zip(tiles).filter(|(_, &v)| v) {
+            let x0 = (tx * TILE_SIZE).max(x_min);
+            let x1 = ((tx + 1) * TILE_SIZE).min(x_max);
+            let (dx, dy) = ((x0 - x_min) as i64, (row.y - y_min) as i64);
+            let w = [0, 1, 2].map(|i| origin[i] + dx * walk.step_x[i] + dy * step_y[i]);
+            row.walk(state, walk, x0, x1, w, fregment);
+        }
+    }
+    rows.iter()
+        .map(|row| (row.y, row.shaded, row.written))
+        .collect()
+}
+
+// the renderer's state a pixel is shaded with, copied out for the rows
+#[derive(Clone, Copy)]
+struct PixelState {
+    depth: DepthState,
+    stencil: StencilState,
+    blend: Blend,
+    debug: DebugView,
+    field: Option<Field>,
+    object: usize,
+    // whole quads go to the shader together when nothing else is per pixel
+    #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+    quads: bool,
+}
+
+// row y of the target and its attachments, what walking a triangle writes. A
+// renderer's rows can be filled on as many threads
+struct Row<'a> {
+    y: u32,
+    color: &'a mut [u32],
+    depth: &'a mut [f32],
+    stencil: Option<&'a mut [u8]>,
+    ids: Option<&'a mut [Option<PickResult>]>,
+    velocity: Option<&'a mut [(f32, f32)]>,
+    normals: Option<&'a mut [[f32; 3]]>,
+    overdraw: Option<&'a mut [u16]>,
+    // the sums of `WeightedOit`
+    oit: Option<(&'a mut [[f32; 4]], &'a mut [f32])>,
+    // fragments shaded, and the first and last pixel whose depth was written
+    shaded: usize,
+    written: Option<(u32, u32)>,
+}
+
+impl Row<'_> {
+    // pixels [x0, x1), `w` holds the edge functions at x0
+    fn walk(
+        &mut self,
+        state: &PixelState,
+        walk: &EdgeWalk,
+        x0: u32,
+        x1: u32,
+        mut w: [i64; 3],
+        fregment: &mut impl Fragments,
+    ) {
+        let (mut x, y) = (x0, self.y);
+        // every other pixel of a checkerboard field, rows of the other
+        // scanlines not at all
+        let mut skip = 1;
+        if let Some(field) = state.field {
+            if field.pattern == Interlace::Checkerboard {
+                skip = 2;
+            }
+            if !field.covers(x, y) {
+                if field.pattern == Interlace::Scanlines {
+                    return;
+                }
+                x += 1;
+                for (w, step) in w.iter_mut().zip(walk.step_x) {
+                    *w += step;
+                }
+            }
+        }
+        // the stencil, the clip plane and fields go pixel by pixel
+        // fixed point weights come from the scalar loop
+        #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+        if walk.fits_i32
+            && self.stencil.is_none()
+            && walk.clip.is_none()
+            && state.field.is_none()
+            && !cfg!(feature = "fixed")
+        {
+            // SAFETY: sse2 is part of the x86_64 baseline
+            x = unsafe { self.walk_sse2(state, walk, x, x1, &mut w, fregment) };
+        }
+        while x < x1 {
+            if (0..3).all(|i| w[i] > walk.bias[i]) {
+                let bc = walk.barycentric(w);
+                let z = walk.depth(&bc);
+                let index = x as usize;
+                let stencil_state = state.stencil;
+                if walk.clipped(&bc) {
+                    // gone before the stencil and depth tests
+                } else if let Some(stencil) = self.stencil.as_deref_mut() {
+                    let stored = stencil[index];
+                    let depth_passes = state.depth.compare.passes(z, self.depth[index]);
+                    let (op, shade) = if !stencil_state.passes(stored) {
+                        (stencil_state.fail, false)
+                    } else if !depth_passes {
+                        (stencil_state.depth_fail, false)
+                    } else {
+                        (stencil_state.pass, true)
+                    };
+                    stencil[index] = stencil_state.update(stored, op);
+                    if shade {
+                        self.shade_pixel(state, x, bc, z, walk, fregment);
+                    }
+                } else if state.depth.compare.passes(z, self.depth[index]) {
+                    self.shade_pixel(state, x, bc, z, walk, fregment);
+                }
+            }
+            for (w, step) in w.iter_mut().zip(walk.step_x) {
+                *w += step * skip as i64;
+            }
+            x += skip;
+        }
+    }
+    // four pixels at a time while they fit, returns where it stopped and
+    // leaves `w` there for the scalar loop to finish the row
+    #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+    unsafe fn walk_sse2(
+        &mut self,
+        state: &PixelState,
+        walk: &EdgeWalk,
+        mut x: u32,
+        x_max: u32,
+        w: &mut [i64; 3],
+        fregment: &mut impl Fragments,
+    ) -> u32 {
+        use core::arch::x86_64::*;
+        let lanes = |v: i64, step: i64| {
+            let v = v as i32;
+            let step = step as i32;
+            _mm_setr_epi32(v, v + step, v + 2 * step, v + 3 * step)
+        };
+        let mut ws = [0, 1, 2].map(|i| lanes(w[i], walk.step_x[i]));
+        let steps = walk.step_x.map(|step| _mm_set1_epi32(4 * step as i32));
+        let bias = walk.bias.map(|bias| _mm_set1_epi32(bias as i32));
+        let total = _mm_set1_ps(walk.total as f32);
+        let zs = [0, 1, 2].map(|i| _mm_set1_ps(walk.z[i]));
+        let corrections = walk.correction.map(|d| d.map(|d| _mm_set1_ps(d)));
+        while x + 4 <= x_max {
+            let inside = _mm_and_si128(
+                _mm_and_si128(
+                    _mm_cmpgt_epi32(ws[0], bias[0]),
+                    _mm_cmpgt_epi32(ws[1], bias[1]),
+                ),
+                _mm_cmpgt_epi32(ws[2], bias[2]),
+            );
+            if _mm_movemask_epi8(inside) != 0 {
+                let bc = ws.map(|w| _mm_div_ps(_mm_cvtepi32_ps(w), total));
+                // `EdgeWalk::depth` four at a time
+                let weights = match corrections {
+                    Some(d) => {
+                        let divided = [0, 1, 2].map(|i| _mm_div_ps(bc[i], d[i]));
+                        let sum = _mm_add_ps(_mm_add_ps(divided[0], divided[1]), divided[2]);
+                        divided.map(|b| _mm_div_ps(b, sum))
+                    }
+                    None => bc,
+                };
+                let z = _mm_add_ps(
+                    _mm_add_ps(_mm_mul_ps(weights[0], zs[0]), _mm_mul_ps(weights[1], zs[1])),
+                    _mm_mul_ps(weights[2], zs[2]),
+                );
+                let index = x as usize;
+                let depth = _mm_loadu_ps(self.depth[index..index + 4].as_ptr());
+                let compare = match state.depth.compare {
+                    Compare::Never => _mm_setzero_ps(),
+                    Compare::Less => _mm_cmplt_ps(z, depth),
+                    Compare::LessEqual => _mm_cmple_ps(z, depth),
+                    Compare::Equal => _mm_cmpeq_ps(z, depth),
+                    Compare::NotEqual => _mm_cmpneq_ps(z, depth),
+                    Compare::Greater => _mm_cmpgt_ps(z, depth),
+                    Compare::GreaterEqual => _mm_cmpge_ps(z, depth),
+                    Compare::Always => _mm_castsi128_ps(_mm_set1_epi32(-1)),
+                };
+                let pass = _mm_and_ps(_mm_castsi128_ps(inside), compare);
+                let mask = _mm_movemask_ps(pass);
+                if mask != 0 {
+                    let mut bc_lanes = [[0.0; 4]; 3];
+                    let mut z_lanes = [0.0; 4];
+                    for i in 0..3 {
+                        _mm_storeu_ps(bc_lanes[i].as_mut_ptr(), bc[i]);
+                    }
+                    _mm_storeu_ps(z_lanes.as_mut_ptr(), z);
+                    if mask == 0xf && state.quads {
+                        self.shade_quad(state, x, bc_lanes, z_lanes, walk, fregment);
+                    } else {
+                        for k in 0..4 {
+                            if mask & (1 << k) != 0 {
+                                let bc =
+                                    Vector3::new(bc_lanes[0][k], bc_lanes[1][k], bc_lanes[2][k]);
+                                self.shade_pixel(
+                                    state,
+                                    x + k as u32,
+                                    bc,
+                                    z_lanes[k],
+                                    walk,
+                                    fregment,
+                                );
+                            }
+                        }
+                    }
+                }
+            }
+            for i in 0..3 {
+                ws[i] = _mm_add_epi32(ws[i], steps[i]);
+                w[i] += 4 * walk.step_x[i];
+            }
+            x += 4;
+        }
+        x
+    }
+    // four covered pixels from `x` that all passed the depth test, for the
+    // simple state
+    #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+    #[inline]
+    fn shade_quad(
+        &mut self,
+        state: &PixelState,
+        x: u32,
+        bc: [[f32; 4]; 3],
+        z: [f32; 4],
+        walk: &EdgeWalk,
+        fregment: &mut impl Fragments,
+    ) {
+        let bc = [0, 1, 2, 3].map(|k| walk.perspective(Vector3::new(bc[0][k], bc[1][k], bc[2][k])));
+        self.shaded += 4;
+        for (k, color) in fregment.shade4(&bc).into_iter().enumerate() {
+            let Some(color) = color else {
+                continue;
+            };
+            let x = x + k as u32;
+            if state.depth.write {
+                self.depth[x as usize] = z[k];
+                self.wrote_depth(x);
+            }
+            self.color[x as usize] = color;
+        }
+    }
+    // a covered pixel that passed the depth test
+    #[inline]
+    fn shade_pixel(
+        &mut self,
+        state: &PixelState,
+        x: u32,
+        bc: Vector3,
+        z: f32,
+        walk: &EdgeWalk,
+        fregment: &mut impl Fragments,
+    ) {
+        // in pixels to the nearest edge, before the barycentrics are corrected
+        let wire = walk
+            .heights
+            .map(|h| (0..3).fold(f32::MAX, |d, i| d.min(bc[i] * h[i])));
+        let bc = walk.perspective(bc);
+        let index = x as usize;
+        self.shaded += 1;
+        if let Some(overdraw) = self.overdraw.as_deref_mut() {
+            overdraw[index] = overdraw[index].saturating_add(1);
+        }
+        // a discarded fragment, e.g. an alpha tested texel, leaves the depth alone
+        let mut outputs = Outputs::default();
+        let Some(color) = fregment.shade(&bc, &mut outputs) else {
+            return;
+        };
+        if state.depth.write {
+            self.depth[index] = z;
+            self.wrote_depth(x);
+        }
+        if let Some(velocity) = self.velocity.as_deref_mut() {
+            velocity[index] = match outputs.previous {
+                Some(previous) => {
+                    let [a, b, c] = walk.xy;
+                    let sx = bc[0] * a.0 + bc[1] * b.0 + bc[2] * c.0;
+                    let sy = bc[0] * a.1 + bc[1] * b.1 + bc[2] * c.1;
+                    (sx - previous.x(), sy - previous.y())
+                }
+                None => (0.0, 0.0),
+            };
+        }
+        let normal = match &outputs.normal {
+            Some(normal) => [normal.x(), normal.y(), normal.z()],
+            None => walk.normal.unwrap_or_default(),
+        };
+        if let Some(normals) = self.normals.as_deref_mut() {
+            normals[index] = normal;
+        }
+        if let Some(ids) = self.ids.as_deref_mut() {
+            ids[index] = Some(PickResult {
+                object: state.object,
+                face: walk.face,
+                barycentric: bc,
+            });
+        }
+        if state.debug != DebugView::Shaded {
+            self.color[index] = match state.debug {
+                DebugView::Depth => heat(state.depth.normalized(z)),
+                DebugView::Normals => pack_rgb(normal.map(|n| n * 0.5 + 0.5)),
+                DebugView::Overdraw => {
+                    overdraw_heat(self.overdraw.as_deref().map_or(0, |o| o[index]), 8)
+                }
+                DebugView::Uv => match outputs.uv {
+                    Some(uv) => {
+                        let (u, v) = (uv.x() - uv.x().floor(), uv.y() - uv.y().floor());
+                        let cell = (u * 8.0) as u32 + (v * 8.0) as u32;
+                        let shade = if cell % 2 == 0 { 1.0 } else { 0.7 };
+                        pack_rgb([u * shade, v * shade, 0.0])
+                    }
+                    None => 0xffff00ff,
+                },
+                DebugView::BarycentricWireframe => {
+                    let line = (1.5 - wire.unwrap_or(f32::MAX)).clamp(0.0, 1.0);
+                    let gray = 0.15 + 0.05 * (walk.face % 4) as f32;
+                    pack_rgb([gray + (1.0 - gray) * line; 3])
+                }
+                DebugView::Shaded => color,
+            };
+            return;
+        }
+        match state.blend {
+            Blend::Replace => self.color[index] = color,
+            Blend::Alpha => self.color[index] = source_over(self.color[index], color, 1.0),
+            Blend::WeightedOit => {
+                if let Some((accum, revealage)) = self.oit.as_mut() {
+                    let nearness = state.depth.normalized(z);
+                    accumulate(&mut accum[index], &mut revealage[index], color, nearness);
+                }
+            }
+        }
+    }
+    #[inline]
+    fn wrote_depth(&mut self, x: u32) {
+        self.written = Some(match self.written {
+            Some((lo, hi)) => (lo.min(x), hi.max(x)),
+            None => (x, x),
+        });
+    }
+}
+
+// `pixel` over `dst`, `coverage` scales the alpha of `pixel`
+fn source_over(dst: u32, pixel: u32, coverage: f32) -> u32 {
+    let alpha = ((pixel >> 24) & 0xff) as f32 / 255.0 * coverage.clamp(0.0, 1.0);
+    if alpha <= 0.0 {
+        return dst;
+    }
+    let mut new_pixel = 0;
+    for i in 0..4 {
+        let s = if i == 3 {
+            255.0
+        } else {
+            ((pixel >> (8 * i)) & 0xff) as f32
+        };
+        let d = ((dst >> (8 * i)) & 0xff) as f32;
+        let part = s * alpha + d * (1.0 - alpha);
+        new_pixel |= ((part.round() as u32) & 0xff) << (8 * i);
+    }
+    new_pixel
+}
+
+// twice the signed area of (a, b, p) in 1/256 pixels, positive with p below
+// a -> b going right
+fn edge(a: (i64, i64), b: (i64, i64), p: (i64, i64)) -> i64 {
+    (b.0 - a.0) * (p.1 - a.1) - (b.1 - a.1) * (p.0 - a.0)
+}
+
+// y grows downwards: a top edge is horizontal with the inside below it, a left
+// edge has the inside to its right. `sign` is the sign of the triangle's area
+fn is_top_left(a: (i64, i64), b: (i64, i64), sign: i64) -> bool {
+    let (dx, dy) = ((b.0 - a.0) * sign, (b.1 - a.1) * sign);
+    (dy == 0 && dx > 0) || dy < 0
 }
 
 #[rustfmt::skip]
@@ -143,6 +2194,14 @@ pub fn projection(coeff: f32) -> Matrix4 {
     projection
 }
 
+// `plane` in the space `transform` takes to the screen, as `Renderer::clip_plane`
+// keeping its inner side. None if `transform` can't be inverted
+pub fn clip_plane(transform: &Matrix4, plane: &Plane) -> Option<Vector4> {
+    let n = &plane.normal;
+    let row = Matrix::from_rows([[n.x(), n.y(), n.z(), plane.d]]);
+    Some((&row * &transform.inverse()?).to_row_vector())
+}
+
 #[rustfmt::skip]
 pub fn lookat(eye: &Vector3, center: &Vector3, up: &Vector3) -> Matrix4 {
     let z = (eye - center).normalize();
@@ -156,16 +2215,126 @@ pub fn lookat(eye: &Vector3, center: &Vector3, up: &Vector3) -> Matrix4 {
         [ 0.0,  0.0,  0.0, 1.0],
     ]);
     // move center to [0,0,0]
-    let tr = Matrix::from_rows([
-        [1.0, 0.0, 0.0, -center[0]],
-        [0.0, 1.0, 0.0, -center[1]],
-        [0.0, 0.0, 1.0, -center[2]],
-        [0.0, 0.0, 0.0,        1.0],
-    ]);
-    minv * tr
+    minv * Matrix4::translation(&-center)
 }
 
+// a fragment stage returning None discards the pixel, its depth included
 pub trait Shader {
     fn vertex(&mut self, iface: usize, nthvert: usize) -> Vector3;
     fn fregment(&mut self, bc: &Vector3) -> Option<u32>;
+    // four pixels of a row at once, for the simd fill to batch the texture
+    // lookups with `Sampler::sample4`. The same colors as four `fregment`s
+    fn fregment4(&mut self, bc: &[Vector3; 4]) -> [Option<u32>; 4] {
+        bc.each_ref().map(|bc| self.fregment(bc))
+    }
+    // where the point at `bc` was on screen the frame before, asked only with a
+    // velocity attachment. None is a point that didn't move
+    fn previous_position(&mut self, _bc: &Vector3) -> Option<Vector2> {
+        None
+    }
+    // the surface normal at `bc`, asked only with a normal attachment. Any space
+    // works as long as the shaders of a frame agree, None is the facing of the
+    // triangle on screen
+    fn normal(&mut self, _bc: &Vector3) -> Option<Vector3> {
+        None
+    }
+    // the texture coordinates at `bc`, asked only by `DebugView::Uv`
+    fn uv(&mut self, _bc: &Vector3) -> Option<Vector2> {
+        None
+    }
+    // a copy to shade part of a tall triangle on another thread when the
+    // renderer has `threads` to spare, e.g. `Some(Box::new(self.clone()))`.
+    // None fills every triangle on the calling thread. What a copy changes in
+    // itself is dropped with it
+    #[cfg(feature = "std")]
+    fn fork<'f>(&self) -> Option<Box<dyn Shader + Send + 'f>>
+    where
+        Self: 'f,
+    {
+        None
+    }
+}
+
+// shaders for `Renderer::draw_indexed`: the vertex stage returns the screen
+// position and whatever the fragments interpolate, the fragment stage gets the
+// varyings of the triangle's three vertices
+pub trait IndexedShader {
+    type Varying: Clone;
+    fn vertex(&mut self, vertices: &VertexBuffer, index: usize) -> (Vector3, Self::Varying);
+    fn fregment(&mut self, varyings: [&Self::Varying; 3], bc: &Vector3) -> Option<u32>;
+    // see `Shader::fregment4`
+    fn fregment4(&mut self, varyings: [&Self::Varying; 3], bc: &[Vector3; 4]) -> [Option<u32>; 4] {
+        bc.each_ref().map(|bc| self.fregment(varyings, bc))
+    }
+    // see `Shader::previous_position`
+    fn previous_position(
+        &mut self,
+        _varyings: [&Self::Varying; 3],
+        _bc: &Vector3,
+    ) -> Option<Vector2> {
+        None
+    }
+    // see `Shader::normal`
+    fn normal(&mut self, _varyings: [&Self::Varying; 3], _bc: &Vector3) -> Option<Vector3> {
+        None
+    }
+    // see `Shader::uv`
+    fn uv(&mut self, _varyings: [&Self::Varying; 3], _bc: &Vector3) -> Option<Vector2> {
+        None
+    }
+    // see `Shader::fork`, a copy for the triangle with `varyings` at its corners.
+    // `TriangleShader` makes one of a clone
+    #[cfg(feature = "std")]
+    fn fork<'f>(&self, _varyings: [&Self::Varying; 3]) -> Option<Box<dyn Shader + Send + 'f>>
+    where
+        Self: 'f,
+    {
+        None
+    }
+}
+
+// an indexed shader with the varyings of one triangle, the shader of its
+// fragments for `IndexedShader::fork`
+pub struct TriangleShader<S: IndexedShader> {
+    pub shader: S,
+    pub varyings: [S::Varying; 3],
+}
+
+impl<S: IndexedShader> TriangleShader<S> {
+    pub fn new(shader: S, varyings: [&S::Varying; 3]) -> Self {
+        Self {
+            shader,
+            varyings: varyings.map(Clone::clone),
+        }
+    }
+}
+
+impl<S: IndexedShader> Shader for TriangleShader<S> {
+    fn vertex(&mut self, _iface: usize, _nthvert: usize) -> Vector3 {
+        unreachable!("the vertices of a TriangleShader are shaded already")
+    }
+    fn fregment(&mut self, bc: &Vector3) -> Option<u32> {
+        self.shader.fregment(self.varyings.each_ref(), bc)
+    }
+    fn fregment4(&mut self, bc: &[Vector3; 4]) -> [Option<u32>; 4] {
+        self.shader.fregment4(self.varyings.each_ref(), bc)
+    }
+    fn previous_position(&mut self, bc: &Vector3) -> Option<Vector2> {
+        self.shader.previous_position(self.varyings.each_ref(), bc)
+    }
+    fn normal(&mut self, bc: &Vector3) -> Option<Vector3> {
+        self.shader.normal(self.varyings.each_ref(), bc)
+    }
+    fn uv(&mut self, bc: &Vector3) -> Option<Vector2> {
+        self.shader.uv(self.varyings.each_ref(), bc)
+    }
+}
+
+// barycentric blend of three per vertex values
+pub fn interpolate<const D: usize>(bc: &Vector3, values: [&Vector<D>; 3]) -> Vector<D> {
+    let mut result = Vector::zero();
+    for i in 0..D {
+        result[i] = bc[0] * values[0][i] + bc[1] * values[1][i] + bc[2] * values[2][i];
+    }
+    result
 }
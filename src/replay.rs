@@ -0,0 +1,221 @@
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::Path,
+};
+
+const HEADER: &str = "olive3d replay 1";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Button {
+    Left,
+    Right,
+    Middle,
+}
+
+impl Button {
+    fn name(self) -> &'static str {
+        match self {
+            Button::Left => "left",
+            Button::Right => "right",
+            Button::Middle => "middle",
+        }
+    }
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "left" => Some(Button::Left),
+            "right" => Some(Button::Right),
+            "middle" => Some(Button::Middle),
+            _ => None,
+        }
+    }
+}
+
+// what a frame's input did, to the window showing `view`. Positions are in
+// pixels of that view
+#[derive(Clone, Debug, PartialEq)]
+pub enum Input {
+    PointerMoved {
+        view: usize,
+        x: f32,
+        y: f32,
+    },
+    PointerButton {
+        view: usize,
+        x: f32,
+        y: f32,
+        button: Button,
+        pressed: bool,
+    },
+    Scroll {
+        view: usize,
+        dx: f32,
+        dy: f32,
+    },
+    Screenshot {
+        view: usize,
+    },
+    Close {
+        view: usize,
+    },
+}
+
+// everything a frame depends on besides the app itself: how long the frame
+// before took and the input that came in meanwhile
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Frame {
+    pub dt: f32,
+    pub inputs: Vec<Input>,
+}
+
+// writes frames as text, a `dt` line per frame followed by its inputs:
+//
+//   olive3d replay 1
+//   dt 0.016666668
+//   move 0 120.0 44.5
+//   button 0 left down 120.0 44.5
+//
+// floats are written so they read back to the same bits
+pub struct Recorder<W: Write> {
+    out: W,
+}
+
+impl Recorder<BufWriter<File>> {
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::new(BufWriter::new(File::create(path)?))
+    }
+}
+
+impl<W: Write> Recorder<W> {
+    pub fn new(mut out: W) -> io::Result<Self> {
+        writeln!(out, "{HEADER}")?;
+        Ok(Self { out })
+    }
+    // flushed every frame, so a run that is killed still replays up to there
+    pub fn record(&mut self, frame: &Frame) -> io::Result<()> {
+        writeln!(self.out, "dt {:?}", frame.dt)?;
+        for input in frame.inputs.iter() {
+            match *input {
+                Input::PointerMoved { view, x, y } => {
+                    writeln!(self.out, "move {view} {x:?} {y:?}")?
+                }
+                Input::PointerButton {
+                    view,
+                    x,
+                    y,
+                    button,
+                    pressed,
+                } => {
+                    let state = if pressed { "down" } else { "up" };
+                    let button = button.name();
+                    writeln!(self.out, "button {view} {button} {state} {x:?} {y:?}")?
+                }
+                Input::Scroll { view, dx, dy } => {
+                    writeln!(self.out, "scroll {view} {dx:?} {dy:?}")?
+                }
+                Input::Screenshot { view } => writeln!(self.out, "screenshot {view}")?,
+                Input::Close { view } => writeln!(self.out, "close {view}")?,
+            }
+        }
+        self.out.flush()
+    }
+}
+
+// the frames of a recording, in order
+pub struct Replay {
+    frames: VecDeque<Frame>,
+}
+
+impl Replay {
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::read(BufReader::new(File::open(path)?))
+    }
+    pub fn read(input: impl BufRead) -> io::Result<Self> {
+        let mut lines = input.lines();
+        if lines.next().transpose()?.as_deref() != Some(HEADER) {
+            return Err(invalid(1, "not a replay"));
+        }
+        let mut frames: VecDeque<Frame> = VecDeque::new();
+        for (i, line) in lines.enumerate() {
+            let line = line?;
+            // after the header, counted from 1
+            let number = i + 2;
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words.as_slice() {
+                [] => {}
+                ["dt", dt] => frames.push_back(Frame {
+                    dt: parse(number, dt)?,
+                    inputs: Vec::new(),
+                }),
+                words => {
+                    let input = parse_input(number, words)?;
+                    let Some(frame) = frames.back_mut() else {
+                        return Err(invalid(number, "input before the first frame"));
+                    };
+                    frame.inputs.push(input);
+                }
+            }
+        }
+        Ok(Self { frames })
+    }
+    // frames left
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+}
+
+impl Iterator for Replay {
+    type Item = Frame;
+    fn next(&mut self) -> Option<Frame> {
+        self.frames.pop_front()
+    }
+}
+
+fn parse_input(number: usize, words: &[&str]) -> io::Result<Input> {
+    let input = match *words {
+        ["move", view, x, y] => Input::PointerMoved {
+            view: parse(number, view)?,
+            x: parse(number, x)?,
+            y: parse(number, y)?,
+        },
+        ["button", view, button, state, x, y] => Input::PointerButton {
+            view: parse(number, view)?,
+            x: parse(number, x)?,
+            y: parse(number, y)?,
+            button: Button::from_name(button).ok_or_else(|| invalid(number, button))?,
+            pressed: match state {
+                "down" => true,
+                "up" => false,
+                _ => return Err(invalid(number, state)),
+            },
+        },
+        ["scroll", view, dx, dy] => Input::Scroll {
+            view: parse(number, view)?,
+            dx: parse(number, dx)?,
+            dy: parse(number, dy)?,
+        },
+        ["screenshot", view] => Input::Screenshot {
+            view: parse(number, view)?,
+        },
+        ["close", view] => Input::Close {
+            view: parse(number, view)?,
+        },
+        _ => return Err(invalid(number, &words.join(" "))),
+    };
+    Ok(input)
+}
+
+fn parse<T: std::str::FromStr>(number: usize, word: &str) -> io::Result<T> {
+    word.parse().map_err(|_| invalid(number, word))
+}
+
+fn invalid(number: usize, what: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("replay line {number}: {what}"),
+    )
+}
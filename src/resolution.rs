@@ -0,0 +1,110 @@
+#[cfg(not(feature = "std"))]
+use crate::compat::*;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Upscale {
+    Nearest,
+    Bilinear,
+}
+
+// the scene drawn into a buffer `scale` times the output's width and height,
+// then scaled up to it with `upscale`. A quarter of the pixels at 0.5 keeps
+// slower machines at frame rate
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RenderScale {
+    pub scale: f32,
+    pub filter: Upscale,
+    // Some to have `adjust` move the scale towards drawing a frame in this many
+    // seconds, within `min_scale..=max_scale`
+    pub target_frame_time: Option<f32>,
+    pub min_scale: f32,
+    pub max_scale: f32,
+}
+
+impl RenderScale {
+    pub fn new(scale: f32, filter: Upscale) -> Self {
+        Self {
+            scale,
+            filter,
+            target_frame_time: None,
+            min_scale: 0.25,
+            max_scale: 1.0,
+        }
+    }
+    // starts at full size and adjusts towards `target_frame_time`
+    pub fn dynamic(target_frame_time: f32, filter: Upscale) -> Self {
+        Self {
+            target_frame_time: Some(target_frame_time),
+            ..Self::new(1.0, filter)
+        }
+    }
+    // of the buffer to draw into for a `width` x `height` output
+    pub fn size(&self, width: u32, height: u32) -> (u32, u32) {
+        let scale = |size: u32| ((size as f32 * self.scale).round() as u32).clamp(1, size);
+        (scale(width), scale(height))
+    }
+    // `frame_time` is what the last frame took to draw, in seconds
+    pub fn adjust(&mut self, frame_time: f32) {
+        let Some(target) = self.target_frame_time else {
+            return;
+        };
+        if frame_time <= 0.0 {
+            return;
+        }
+        // the time goes with the pixels, the square of the scale
+        let ideal = self.scale * (target / frame_time).sqrt();
+        // only when off by more than 10% and a little at a time, so the size
+        // settles instead of following every hiccup
+        if (ideal / self.scale - 1.0).abs() > 0.1 {
+            let step = (ideal - self.scale).clamp(-0.05, 0.05);
+            self.scale = (self.scale + step).clamp(self.min_scale, self.max_scale);
+        }
+    }
+}
+
+// `src` stretched over all of `dst`, both row by row without padding
+pub fn upscale(
+    src: &[u32],
+    src_width: u32,
+    src_height: u32,
+    dst: &mut [u32],
+    dst_width: u32,
+    dst_height: u32,
+    filter: Upscale,
+) {
+    assert_eq!(src.len(), (src_width * src_height) as usize);
+    assert_eq!(dst.len(), (dst_width * dst_height) as usize);
+    let (sx, sy) = (
+        src_width as f32 / dst_width as f32,
+        src_height as f32 / dst_height as f32,
+    );
+    let at = |x: u32, y: u32| src[(x + y * src_width) as usize];
+    for y in 0..dst_height {
+        for x in 0..dst_width {
+            // the source position of the pixel center
+            let (fx, fy) = ((x as f32 + 0.5) * sx, (y as f32 + 0.5) * sy);
+            dst[(x + y * dst_width) as usize] = match filter {
+                Upscale::Nearest => at(
+                    (fx as u32).min(src_width - 1),
+                    (fy as u32).min(src_height - 1),
+                ),
+                Upscale::Bilinear => {
+                    let fx = (fx - 0.5).clamp(0.0, (src_width - 1) as f32);
+                    let fy = (fy - 0.5).clamp(0.0, (src_height - 1) as f32);
+                    let (x0, y0) = (fx as u32, fy as u32);
+                    let (x1, y1) = ((x0 + 1).min(src_width - 1), (y0 + 1).min(src_height - 1));
+                    let (tx, ty) = (fx - x0 as f32, fy - y0 as f32);
+                    let mut pixel = 0;
+                    for i in 0..4 {
+                        let c = |p: u32| ((p >> (8 * i)) & 0xff) as f32;
+                        let top = c(at(x0, y0)) * (1.0 - tx) + c(at(x1, y0)) * tx;
+                        let bottom = c(at(x0, y1)) * (1.0 - tx) + c(at(x1, y1)) * tx;
+                        let value = top * (1.0 - ty) + bottom * ty;
+                        pixel |= ((value.round() as u32) & 0xff) << (8 * i);
+                    }
+                    pixel
+                }
+            };
+        }
+    }
+}
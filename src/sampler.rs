@@ -0,0 +1,152 @@
+#[cfg(not(feature = "std"))]
+use crate::compat::*;
+use crate::{geometry::Vector2, image::Image};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Filter {
+    // the texel the uv falls in
+    #[default]
+    Nearest,
+    // the four texels around the uv weighed by how close it is to their
+    // centers, in 1/256ths
+    Bilinear,
+}
+
+// texel lookups in `image` by uv, u to the right and v along the rows. A uv
+// outside 0..=1 is clamped to the edge
+#[derive(Clone, Copy)]
+pub struct Sampler<'a> {
+    pub image: &'a Image,
+    pub filter: Filter,
+}
+
+impl<'a> Sampler<'a> {
+    pub fn new(image: &'a Image, filter: Filter) -> Self {
+        Self { image, filter }
+    }
+    pub fn sample(&self, uv: &Vector2) -> u32 {
+        match self.filter {
+            Filter::Nearest => self.nearest(uv),
+            Filter::Bilinear => {
+                let (x, y) = self.fixed(uv.x(), uv.y());
+                self.bilinear(x, y)
+            }
+        }
+    }
+    // four lookups at once, for shaders working on four pixels of a row. The
+    // same texels as four `sample`s; the bilinear weights are worked out four
+    // at a time with the simd feature on x86_64
+    pub fn sample4(&self, uvs: &[Vector2; 4]) -> [u32; 4] {
+        match self.filter {
+            Filter::Nearest => uvs.each_ref().map(|uv| self.nearest(uv)),
+            #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+            // SAFETY: sse2 is part of the x86_64 baseline
+            Filter::Bilinear => unsafe { self.bilinear4_sse2(uvs) },
+            #[cfg(not(all(feature = "simd", target_arch = "x86_64")))]
+            Filter::Bilinear => uvs.each_ref().map(|uv| self.sample(uv)),
+        }
+    }
+    #[inline]
+    fn nearest(&self, uv: &Vector2) -> u32 {
+        let (w, h) = (self.image.width as i32, self.image.height as i32);
+        // uv = 1 is the far edge, not the next texel
+        let x = ((uv.x() * w as f32) as i32).clamp(0, w - 1);
+        let y = ((uv.y() * h as f32) as i32).clamp(0, h - 1);
+        self.image.buffer[(x + y * w) as usize]
+    }
+    // the position among the texel centers in 24.8 fixed point
+    #[inline]
+    fn fixed(&self, u: f32, v: f32) -> (i32, i32) {
+        let at = |t: f32, size: u32| ((t.clamp(0.0, 1.0) * size as f32 - 0.5) * 256.0).floor();
+        (
+            at(u, self.image.width) as i32,
+            at(v, self.image.height) as i32,
+        )
+    }
+    // the four texels around a 24.8 position and its fractions
+    #[inline]
+    fn quad(&self, x: i32, y: i32) -> ([u32; 4], u32, u32) {
+        let (w, h) = (self.image.width as i32, self.image.height as i32);
+        let (x0, y0) = (x >> 8, y >> 8);
+        let [x0, x1] = [x0, x0 + 1].map(|x| x.clamp(0, w - 1));
+        let [y0, y1] = [y0, y0 + 1].map(|y| y.clamp(0, h - 1));
+        let texel = |x: i32, y: i32| self.image.buffer[(x + y * w) as usize];
+        (
+            [texel(x0, y0), texel(x1, y0), texel(x0, y1), texel(x1, y1)],
+            (x & 0xff) as u32,
+            (y & 0xff) as u32,
+        )
+    }
+    fn bilinear(&self, x: i32, y: i32) -> u32 {
+        let ([a, b, c, d], tx, ty) = self.quad(x, y);
+        // at most 255 * 256, the sums fit the 16 bit lanes of the simd version
+        let lerp = |a: u32, b: u32, t: u32| (a * (256 - t) + b * t) >> 8;
+        let mut pixel = 0;
+        for i in 0..4 {
+            let channel = |p: u32| (p >> (8 * i)) & 0xff;
+            let top = lerp(channel(a), channel(b), tx);
+            let bottom = lerp(channel(c), channel(d), tx);
+            pixel |= lerp(top, bottom, ty) << (8 * i);
+        }
+        pixel
+    }
+    #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+    unsafe fn bilinear4_sse2(&self, uvs: &[Vector2; 4]) -> [u32; 4] {
+        use core::arch::x86_64::*;
+        // the same float steps as `fixed`, four lanes at a time
+        let at = |t: __m128, size: u32| {
+            let t = _mm_min_ps(_mm_max_ps(t, _mm_setzero_ps()), _mm_set1_ps(1.0));
+            let t = _mm_sub_ps(_mm_mul_ps(t, _mm_set1_ps(size as f32)), _mm_set1_ps(0.5));
+            let t = _mm_mul_ps(t, _mm_set1_ps(256.0));
+            // sse2 only truncates, one less where that rounded up
+            let truncated = _mm_cvttps_epi32(t);
+            let above = _mm_cmpgt_ps(_mm_cvtepi32_ps(truncated), t);
+            let mut lanes = [0i32; 4];
+            _mm_storeu_si128(
+                lanes.as_mut_ptr() as *mut __m128i,
+                _mm_add_epi32(truncated, _mm_castps_si128(above)),
+            );
+            lanes
+        };
+        let [u0, u1, u2, u3] = uvs.each_ref().map(|uv| uv.x());
+        let [v0, v1, v2, v3] = uvs.each_ref().map(|uv| uv.y());
+        let xs = at(_mm_setr_ps(u0, u1, u2, u3), self.image.width);
+        let ys = at(_mm_setr_ps(v0, v1, v2, v3), self.image.height);
+        let quads = [0, 1, 2, 3].map(|k| self.quad(xs[k], ys[k]));
+        let corner = |i: usize| {
+            let [a, b, c, d] = quads.each_ref().map(|q| q.0[i] as i32);
+            _mm_setr_epi32(a, b, c, d)
+        };
+        // each lane's fraction over the four channels of its texel, lanes 0
+        // and 1 in the low register, 2 and 3 in the high one
+        let weights = |t: [u32; 4]| {
+            let t = t.map(|t| t as i16);
+            (
+                _mm_setr_epi16(t[0], t[0], t[0], t[0], t[1], t[1], t[1], t[1]),
+                _mm_setr_epi16(t[2], t[2], t[2], t[2], t[3], t[3], t[3], t[3]),
+            )
+        };
+        let tx = weights(quads.each_ref().map(|q| q.1));
+        let ty = weights(quads.each_ref().map(|q| q.2));
+        let zero = _mm_setzero_si128();
+        let full = _mm_set1_epi16(256);
+        let lerp = |a: __m128i, b: __m128i, t: __m128i| {
+            let a = _mm_mullo_epi16(a, _mm_sub_epi16(full, t));
+            _mm_srli_epi16(_mm_add_epi16(a, _mm_mullo_epi16(b, t)), 8)
+        };
+        let [a, b, c, d] = [0, 1, 2, 3].map(corner);
+        let half = |unpack: fn(__m128i, __m128i) -> __m128i, tx: __m128i, ty: __m128i| {
+            let top = lerp(unpack(a, zero), unpack(b, zero), tx);
+            let bottom = lerp(unpack(c, zero), unpack(d, zero), tx);
+            lerp(top, bottom, ty)
+        };
+        let low = half(|a, b| _mm_unpacklo_epi8(a, b), tx.0, ty.0);
+        let high = half(|a, b| _mm_unpackhi_epi8(a, b), tx.1, ty.1);
+        let mut pixels = [0u32; 4];
+        _mm_storeu_si128(
+            pixels.as_mut_ptr() as *mut __m128i,
+            _mm_packus_epi16(low, high),
+        );
+        pixels
+    }
+}
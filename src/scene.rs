@@ -0,0 +1,864 @@
+use crate::{
+    geometry::{
+        m2v, m3v, normal_matrix, project, unproject, v2m, Aabb, Frustum, Line3D, Matrix, Matrix3,
+        Matrix4, Plane, Quaternion, Vector2, Vector3,
+    },
+    image::Image,
+    material::Material,
+    model::Model,
+    occlusion::OcclusionBuffer,
+    raytrace::Ray,
+    renderer::{
+        clip_plane, lookat, projection, viewport, Blend, DepthState, Renderer, Shader, Transparency,
+    },
+    skin::Skin,
+};
+
+// what a scene draws with is only read while drawing, so renderers on any
+// number of threads can share one
+const _: () = {
+    const fn sync<T: Send + Sync>() {}
+    sync::<Scene>();
+    sync::<Model>();
+    sync::<Material>();
+};
+
+// local translation, rotation and scale of a node, applied scale first
+#[derive(Clone, Debug)]
+pub struct Transform {
+    pub translation: Vector3,
+    pub rotation: Quaternion,
+    pub scale: Vector3,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            translation: Vector3::zero(),
+            rotation: Quaternion::identity(),
+            scale: Vector3::new(1.0, 1.0, 1.0),
+        }
+    }
+}
+
+impl Transform {
+    pub fn from_translation(translation: Vector3) -> Self {
+        Self {
+            translation,
+            ..Default::default()
+        }
+    }
+    // rotate by `angle` radians about `axis`, on top of the current rotation
+    pub fn rotate(&mut self, axis: &Vector3, angle: f32) -> &mut Self {
+        self.rotation = (Quaternion::from_axis_angle(axis, angle) * self.rotation).normalize();
+        self
+    }
+    // `p` through `to_matrix4()`
+    pub fn transform_point(&self, p: &Vector3) -> Vector3 {
+        let mut scaled = p.clone();
+        for i in 0..3 {
+            scaled[i] *= self.scale[i];
+        }
+        &self.rotation.rotate(&scaled) + &self.translation
+    }
+    // `child` first, then this one, like multiplying the matrices. The result is
+    // exact as long as this scale is uniform or `child` doesn't rotate, otherwise
+    // the shear a matrix would pick up is lost
+    pub fn compose(&self, child: &Transform) -> Transform {
+        let mut scale = child.scale.clone();
+        for i in 0..3 {
+            scale[i] *= self.scale[i];
+        }
+        Transform {
+            translation: self.transform_point(&child.translation),
+            rotation: (self.rotation * child.rotation).normalize(),
+            scale,
+        }
+    }
+    // exact for uniform scales, see `compose`
+    pub fn inverse(&self) -> Transform {
+        let rotation = self.rotation.conjugate();
+        let mut scale = Vector3::zero();
+        for i in 0..3 {
+            scale[i] = 1.0 / self.scale[i];
+        }
+        let mut translation = rotation.rotate(&-&self.translation);
+        for i in 0..3 {
+            translation[i] *= scale[i];
+        }
+        Transform {
+            translation,
+            rotation,
+            scale,
+        }
+    }
+    // linear for translation and scale, slerp along the shorter arc for the rotation
+    pub fn lerp(&self, other: &Transform, t: f32) -> Transform {
+        Transform {
+            translation: &self.translation * (1.0 - t) + &other.translation * t,
+            rotation: self.rotation.slerp(&other.rotation, t),
+            scale: &self.scale * (1.0 - t) + &other.scale * t,
+        }
+    }
+    pub fn to_matrix4(&self) -> Matrix4 {
+        Matrix4::trs(&self.translation, &self.rotation, &self.scale)
+    }
+    // see `Matrix4::decompose`
+    pub fn from_matrix(m: &Matrix4) -> Self {
+        let (translation, rotation, scale) = m.decompose();
+        Self {
+            translation,
+            rotation,
+            scale,
+        }
+    }
+}
+
+// directional light, `dir` is where the light travels in the node's space
+#[derive(Clone, Debug)]
+pub struct Light {
+    pub dir: Vector3,
+    pub color: Vector3,
+}
+
+impl Light {
+    pub fn new(dir: Vector3, intensity: f32) -> Self {
+        Self {
+            dir: dir.normalize(),
+            color: Vector3::new(intensity, intensity, intensity),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Camera {
+    pub eye: Vector3,
+    pub center: Vector3,
+    pub up: Vector3,
+}
+
+impl Camera {
+    pub fn new(eye: Vector3, center: Vector3) -> Self {
+        Self {
+            eye,
+            center,
+            up: Vector3::new(0.0, 1.0, 0.0),
+        }
+    }
+    // world to screen, framed like the examples with a margin of an eighth
+    pub fn transform(&self, width: u32, height: u32, depth: f32) -> Matrix4 {
+        let (w, h) = (width as f32, height as f32);
+        viewport(w / 8.0, h / 8.0, w * 3.0 / 4.0, h * 3.0 / 4.0, depth)
+            * projection(-1.0 / (&self.eye - &self.center).length())
+            * lookat(&self.eye, &self.center, &self.up)
+    }
+    // where `transform` puts a world point: x and y in pixels, z the depth the
+    // renderer compares
+    pub fn project(&self, width: u32, height: u32, p: &Vector3) -> Vector3 {
+        project(&self.transform(width, height, 255.0), p)
+    }
+    // the world point at screen (x, y) and depth z, e.g. read back from the z
+    // buffer to orbit around or place something at the clicked surface
+    pub fn unproject(&self, width: u32, height: u32, screen: &Vector3) -> Vector3 {
+        unproject(&self.transform(width, height, 255.0), screen).unwrap()
+    }
+    // the ray from the eye through screen point (x, y), aimed at the plane
+    // through `center`
+    pub fn screen_to_ray(&self, width: u32, height: u32, x: f32, y: f32) -> Ray {
+        let depth = self.project(width, height, &self.center).z();
+        let target = self.unproject(width, height, &Vector3::new(x, y, depth));
+        Ray::new(self.eye.clone(), &target - &self.eye)
+    }
+    // what `transform` puts on a width x height screen
+    #[rustfmt::skip]
+    pub fn frustum(&self, width: u32, height: u32) -> Frustum {
+        // screen back to -1..1 over the whole buffer, margins included
+        let (w, h) = (width as f32, height as f32);
+        let to_ndc = Matrix::from_rows([
+            [2.0 / w,      0.0, 0.0, -1.0],
+            [    0.0, -2.0 / h, 0.0,  1.0],
+            [    0.0,      0.0, 1.0,  0.0],
+            [    0.0,      0.0, 0.0,  1.0],
+        ]);
+        Frustum::from_matrix_without_depth(&(to_ndc * self.transform(width, height, 255.0)))
+    }
+}
+
+// coarser stand-ins for a node's model, e.g. from `Model::lod_chain`:
+// `models[i]` is drawn once the model's bounding sphere spans fewer than
+// `sizes[i]` pixels on screen, so sizes go down level by level
+#[derive(Clone, Debug)]
+pub struct Lod {
+    pub models: Vec<usize>,
+    pub sizes: Vec<f32>,
+}
+
+impl Lod {
+    pub fn new(models: Vec<usize>, sizes: Vec<f32>) -> Self {
+        assert_eq!(models.len(), sizes.len());
+        Self { models, sizes }
+    }
+}
+
+pub struct Node {
+    pub name: String,
+    pub transform: Transform,
+    pub parent: Option<usize>,
+    pub children: Vec<usize>,
+    // index into `Scene::models`, models can be shared between nodes
+    pub model: Option<usize>,
+    // index into `Scene::materials`, None draws the model's own material
+    pub material: Option<usize>,
+    // index into `Scene::skins`; the model is posed by the joints, which already
+    // place it in the world, so the node's own transform is ignored
+    pub skin: Option<usize>,
+    pub light: Option<Light>,
+    pub camera: Option<Camera>,
+    // hides the node and everything below it
+    pub visible: bool,
+    // ignored for skinned nodes
+    pub lod: Option<Lod>,
+}
+
+pub struct Scene {
+    pub nodes: Vec<Node>,
+    pub models: Vec<Model>,
+    pub materials: Vec<Material>,
+    pub skins: Vec<Skin>,
+    pub ambient: f32,
+}
+
+impl Default for Scene {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Scene {
+    pub fn new() -> Self {
+        Self {
+            nodes: Vec::new(),
+            models: Vec::new(),
+            materials: Vec::new(),
+            skins: Vec::new(),
+            ambient: 0.1,
+        }
+    }
+    pub fn add_model(&mut self, model: Model) -> usize {
+        self.models.push(model);
+        self.models.len() - 1
+    }
+    pub fn add_material(&mut self, material: Material) -> usize {
+        self.materials.push(material);
+        self.materials.len() - 1
+    }
+    pub fn add_skin(&mut self, skin: Skin) -> usize {
+        self.skins.push(skin);
+        self.skins.len() - 1
+    }
+    pub fn add_node(&mut self, name: &str, parent: Option<usize>) -> usize {
+        let id = self.nodes.len();
+        self.nodes.push(Node {
+            name: name.to_string(),
+            transform: Transform::default(),
+            parent,
+            children: Vec::new(),
+            model: None,
+            material: None,
+            skin: None,
+            light: None,
+            camera: None,
+            visible: true,
+            lod: None,
+        });
+        if let Some(parent) = parent {
+            self.nodes[parent].children.push(id);
+        }
+        id
+    }
+    pub fn node(&self, id: usize) -> &Node {
+        &self.nodes[id]
+    }
+    pub fn node_mut(&mut self, id: usize) -> &mut Node {
+        &mut self.nodes[id]
+    }
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.nodes.iter().position(|n| n.name == name)
+    }
+    pub fn set_parent(&mut self, id: usize, parent: Option<usize>) {
+        let mut ancestor = parent;
+        while let Some(a) = ancestor {
+            assert_ne!(a, id, "node can't become its own descendant");
+            ancestor = self.nodes[a].parent;
+        }
+        if let Some(old) = self.nodes[id].parent {
+            self.nodes[old].children.retain(|&c| c != id);
+        }
+        self.nodes[id].parent = parent;
+        if let Some(parent) = parent {
+            self.nodes[parent].children.push(id);
+        }
+    }
+    // node to world
+    pub fn world_transform(&self, id: usize) -> Matrix4 {
+        let mut m = self.nodes[id].transform.to_matrix4();
+        let mut parent = self.nodes[id].parent;
+        while let Some(p) = parent {
+            m = self.nodes[p].transform.to_matrix4() * m;
+            parent = self.nodes[p].parent;
+        }
+        m
+    }
+    // the model to draw for node `id` seen through `camera` on a width x height
+    // screen, one of its `lod` levels when it is small enough
+    pub fn lod_model(&self, id: usize, camera: &Camera, width: u32, height: u32) -> Option<usize> {
+        let node = &self.nodes[id];
+        let model = node.model?;
+        let Some(lod) = node.lod.as_ref().filter(|_| node.skin.is_none()) else {
+            return Some(model);
+        };
+        let world = self.world_transform(id);
+        let (center, radius) = self.models[model].bounding_sphere();
+        let scale = (0..3)
+            .map(|c| Vector3::new(world[0][c], world[1][c], world[2][c]).length())
+            .fold(0.0, f32::max);
+        let center = m2v(&(&world * v2m(&center)));
+        // a radius across the view direction, measured on screen
+        let right = camera.up.cross(&(&camera.eye - &camera.center)).normalize();
+        let edge = &center + &(right * (radius * scale));
+        let view = camera.transform(width, height, 255.0);
+        let (a, b) = (m2v(&(&view * v2m(&center))), m2v(&(&view * v2m(&edge))));
+        let size = 2.0 * (b.x() - a.x()).hypot(b.y() - a.y());
+        Some(
+            lod.models
+                .iter()
+                .zip(lod.sizes.iter())
+                .take_while(|(_, &min)| size < min)
+                .last()
+                .map_or(model, |(&m, _)| m),
+        )
+    }
+    // the node and all its ancestors are visible
+    pub fn visible(&self, id: usize) -> bool {
+        let mut node = Some(id);
+        while let Some(n) = node {
+            if !self.nodes[n].visible {
+                return false;
+            }
+            node = self.nodes[n].parent;
+        }
+        true
+    }
+    // pose every skinned model from where its joints are now, call after moving
+    // or animating them and before drawing. A skinned model can't be shared
+    pub fn update_skins(&mut self) {
+        for id in 0..self.nodes.len() {
+            let (Some(model), Some(skin)) = (self.nodes[id].model, self.nodes[id].skin) else {
+                continue;
+            };
+            let matrices = self.skins[skin].joint_matrices(self);
+            self.models[model].skin(&matrices);
+        }
+    }
+    // the lights of visible nodes, directions in world space
+    pub fn lights(&self) -> Vec<Light> {
+        (0..self.nodes.len())
+            .filter(|&id| self.visible(id))
+            .filter_map(|id| {
+                let light = self.nodes[id].light.as_ref()?;
+                let m = self.world_transform(id);
+                let mut dir = Vector3::zero();
+                for i in 0..3 {
+                    dir[i] =
+                        m[i][0] * light.dir.x() + m[i][1] * light.dir.y() + m[i][2] * light.dir.z();
+                }
+                Some(Light {
+                    dir: dir.normalize(),
+                    color: light.color.clone(),
+                })
+            })
+            .collect()
+    }
+    // the camera attached to node `id`, in world space
+    pub fn camera(&self, id: usize) -> Option<Camera> {
+        let camera = self.nodes[id].camera.as_ref()?;
+        let m = self.world_transform(id);
+        let to_world = |p: &Vector3| m2v(&(&m * v2m(p)));
+        let eye = to_world(&camera.eye);
+        let up = &to_world(&(&camera.eye + &camera.up)) - &eye;
+        Some(Camera {
+            center: to_world(&camera.center),
+            eye,
+            up: up.normalize(),
+        })
+    }
+}
+
+// diffuse map times vertex color, lambert lit by the scene lights and scaled by
+// the lightmap
+#[derive(Clone)]
+pub(crate) struct SceneShader<'a> {
+    model: &'a Model,
+    material: &'a Material,
+    transform: Matrix4,
+    normal: Matrix3,
+    lights: &'a [Light],
+    ambient: f32,
+    varying_uv: Matrix<3, 2>,
+    varying_uv1: Matrix<3, 2>,
+    varying_nrm: Matrix3,
+    varying_color: Matrix3,
+}
+
+impl<'a> SceneShader<'a> {
+    // `model` placed by `world` and seen through `view`, like `draw_scene` does
+    pub(crate) fn new(
+        model: &'a Model,
+        material: &'a Material,
+        view: &Matrix4,
+        world: &Matrix4,
+        lights: &'a [Light],
+        ambient: f32,
+    ) -> Self {
+        Self {
+            model,
+            material,
+            transform: view * world,
+            normal: normal_matrix(world),
+            lights,
+            ambient,
+            varying_uv: Matrix::zero(),
+            varying_uv1: Matrix::zero(),
+            varying_nrm: Matrix::zero(),
+            varying_color: Matrix::zero(),
+        }
+    }
+    // `pixel` of the diffuse map lit at `bc`
+    fn lit(&self, bc: &Vector3, pixel: u32) -> u32 {
+        let bc_row = Matrix::from_row_vector(bc.clone());
+        let n = (&bc_row * &self.varying_nrm).to_row_vector().normalize();
+
+        let color = (&bc_row * &self.varying_color).to_row_vector();
+        let mut light = Vector3::new(self.ambient, self.ambient, self.ambient);
+        for l in self.lights.iter() {
+            light = light + &l.color * (-n.dot(&l.dir)).max(0.0);
+        }
+        let baked = self
+            .material
+            .lightmap(&(&bc_row * &self.varying_uv1).to_row_vector());
+        for i in 0..3 {
+            light[i] *= baked[i];
+        }
+        let mut new_pixel = pixel & 0xff000000;
+        for i in 0..3 {
+            let part = ((pixel >> (8 * i)) & 0xff) as f32 * color[i] * light[i];
+            new_pixel |= ((part.clamp(0.0, 255.0) as u32) & 0xff) << (8 * i)
+        }
+        new_pixel
+    }
+}
+
+impl Shader for SceneShader<'_> {
+    fn vertex(&mut self, iface: usize, nthvert: usize) -> Vector3 {
+        self.varying_uv
+            .set_row(nthvert, self.model.uv(iface, nthvert));
+        self.varying_uv1
+            .set_row(nthvert, self.model.uv1(iface, nthvert));
+        let n = m3v(&self.normal, &self.model.normal_vert(iface, nthvert));
+        self.varying_nrm.set_row(nthvert, n);
+        let color = self.model.color(iface, nthvert);
+        let mut rgb = Vector3::zero();
+        for i in 0..3 {
+            rgb[i] = ((color >> (8 * i)) & 0xff) as f32 / 255.0;
+        }
+        self.varying_color.set_row(nthvert, rgb);
+        let v = self.model.vert(iface, nthvert);
+        m2v(&(&self.transform * v2m(&v)))
+    }
+
+    fn fregment(&mut self, bc: &Vector3) -> Option<u32> {
+        let uv = (Matrix::from_row_vector(bc.clone()) * &self.varying_uv).to_row_vector();
+        Some(self.lit(bc, self.material.diffuse(&uv)))
+    }
+    fn fregment4(&mut self, bc: &[Vector3; 4]) -> [Option<u32>; 4] {
+        let uvs = bc
+            .each_ref()
+            .map(|bc| (Matrix::from_row_vector(bc.clone()) * &self.varying_uv).to_row_vector());
+        let pixels = self.material.diffuse4(&uvs);
+        [0, 1, 2, 3].map(|k| Some(self.lit(&bc[k], pixels[k])))
+    } // in world space
+    fn normal(&mut self, bc: &Vector3) -> Option<Vector3> {
+        let bc_row = Matrix::from_row_vector(bc.clone());
+        Some((&bc_row * &self.varying_nrm).to_row_vector().normalize())
+    }
+    fn uv(&mut self, bc: &Vector3) -> Option<Vector2> {
+        Some((Matrix::from_row_vector(bc.clone()) * &self.varying_uv).to_row_vector())
+    }
+    fn fork<'f>(&self) -> Option<Box<dyn Shader + Send + 'f>>
+    where
+        Self: 'f,
+    {
+        Some(Box::new(self.clone()))
+    }
+}
+
+// a node showing the scene reflected about `plane`, e.g. a floor. Only what is on
+// the inner side of the plane gets reflected, the camera should be there too
+#[derive(Clone, Debug)]
+pub struct Mirror {
+    pub node: usize,
+    // world space, the model of the node is expected to lie in it
+    pub plane: Plane,
+    // how much of the surface color the reflection replaces
+    pub strength: f32,
+}
+
+impl Mirror {
+    pub fn new(node: usize, plane: Plane, strength: f32) -> Self {
+        Self {
+            node,
+            plane,
+            strength,
+        }
+    }
+}
+
+// the scene shader's color with the mirrored pass rendered at the same screen
+// position mixed in
+#[derive(Clone)]
+struct MirrorShader<'a> {
+    surface: SceneShader<'a>,
+    reflection: &'a Image,
+    strength: f32,
+    depth: DepthState,
+    screen: [Vector3; 3],
+}
+
+impl Shader for MirrorShader<'_> {
+    fn vertex(&mut self, iface: usize, nthvert: usize) -> Vector3 {
+        let v = self.surface.vertex(iface, nthvert);
+        self.screen[nthvert] = v.clone();
+        v
+    }
+
+    fn fregment(&mut self, bc: &Vector3) -> Option<u32> {
+        let pixel = self.surface.fregment(bc)?;
+        let bc = self.depth.screen_barycentric(&self.screen, bc);
+        let (w, h) = (self.reflection.width, self.reflection.height);
+        let at = |k: usize, size: u32| {
+            let p =
+                bc[0] * self.screen[0][k] + bc[1] * self.screen[1][k] + bc[2] * self.screen[2][k];
+            (p.max(0.0) as u32).min(size - 1)
+        };
+        let mirrored = self.reflection.buffer[(at(0, w) + at(1, h) * w) as usize];
+        let mut new_pixel = pixel & 0xff000000;
+        for i in 0..3 {
+            let a = ((pixel >> (8 * i)) & 0xff) as f32;
+            let b = ((mirrored >> (8 * i)) & 0xff) as f32;
+            let part = a + (b - a) * self.strength;
+            new_pixel |= ((part.round().clamp(0.0, 255.0) as u32) & 0xff) << (8 * i);
+        }
+        Some(new_pixel)
+    }
+    fn fork<'f>(&self) -> Option<Box<dyn Shader + Send + 'f>>
+    where
+        Self: 'f,
+    {
+        Some(Box::new(self.clone()))
+    }
+}
+
+// texels with less alpha than this are cut out of billboards
+const ALPHA_CUTOFF: u32 = 128;
+
+// a camera facing quad, unlit: corners counter-clockwise from the bottom left
+#[derive(Clone)]
+struct BillboardShader<'a> {
+    material: &'a Material,
+    transform: Matrix4,
+    corners: [Vector3; 4],
+    varying_uv: Matrix<3, 2>,
+}
+
+impl Shader for BillboardShader<'_> {
+    fn vertex(&mut self, iface: usize, nthvert: usize) -> Vector3 {
+        let corner = [[0, 1, 2], [0, 2, 3]][iface][nthvert];
+        let uv = Vector2::new([0.0, 1.0, 1.0, 0.0][corner], [0.0, 0.0, 1.0, 1.0][corner]);
+        self.varying_uv.set_row(nthvert, uv);
+        m2v(&(&self.transform * v2m(&self.corners[corner])))
+    }
+
+    fn fregment(&mut self, bc: &Vector3) -> Option<u32> {
+        let bc_row = Matrix::from_row_vector(bc.clone());
+        let uv = (&bc_row * &self.varying_uv).to_row_vector();
+        let pixel = self.material.diffuse(&uv);
+        (pixel >> 24 >= ALPHA_CUTOFF).then_some(pixel)
+    }
+    fn fork<'f>(&self) -> Option<Box<dyn Shader + Send + 'f>>
+    where
+        Self: 'f,
+    {
+        Some(Box::new(self.clone()))
+    }
+}
+
+impl Renderer<'_> {
+    // every visible node with a model, lit by the scene lights; the buffers aren't cleared.
+    // Nodes whose bounds fall outside the view are skipped, returns how many were drawn.
+    // Translucent materials go last without writing depth, composited as `self.transparency`
+    pub fn draw_scene(&mut self, scene: &Scene, camera: &Camera) -> usize {
+        self.draw_nodes(scene, camera, None, None, None)
+    }
+    // `draw_scene` skipping the nodes `occlusion` has behind its occluders. The
+    // occluders are drawn like any node if they are in the scene
+    pub fn draw_scene_occluded(
+        &mut self,
+        scene: &Scene,
+        camera: &Camera,
+        occlusion: &OcclusionBuffer,
+    ) -> usize {
+        self.draw_nodes(scene, camera, None, None, Some(occlusion))
+    }
+    // a world space line as `camera` sees it, cut to the view first so an end
+    // behind the eye doesn't flip across the screen. No depth test
+    pub fn draw_line_3d(&mut self, line: &Line3D, pixel: u32, camera: &Camera) {
+        let Some(line) = line.clip_to_planes(&camera.frustum(self.width, self.height).planes)
+        else {
+            return;
+        };
+        let [start, end] = [&line.start, &line.end].map(|p| {
+            let p = camera.project(self.width, self.height, p);
+            (p.x().round() as i32, p.y().round() as i32)
+        });
+        self.draw_line(start.0, start.1, end.0, end.1, pixel);
+    }
+    // `draw_scene` with `mirror` showing the reflection, which is rendered into an
+    // offscreen image cleared to `background` first. The mirror isn't counted
+    pub fn draw_scene_with_mirror(
+        &mut self,
+        scene: &Scene,
+        camera: &Camera,
+        mirror: &Mirror,
+        background: u32,
+    ) -> usize {
+        let size = (self.width * self.height) as usize;
+        let mut reflection = Image {
+            buffer: vec![background; size],
+            width: self.width,
+            height: self.height,
+        };
+        let mut z_buffer = vec![self.depth.clear; size];
+        {
+            let mut offscreen = Renderer::new(
+                &mut reflection.buffer,
+                &mut z_buffer,
+                self.width,
+                self.height,
+            );
+            offscreen.depth = self.depth;
+            offscreen.transparency = self.transparency;
+            offscreen.draw_mirrored(scene, camera, mirror);
+        }
+        let drawn = self.draw_nodes(scene, camera, None, Some(mirror.node), None);
+        self.draw_mirror(scene, camera, mirror, &reflection);
+        drawn
+    }
+    // the scene as seen in `mirror`, for a target the size of the one the mirror
+    // gets drawn to. The mirror node itself is left out and whatever is behind
+    // the plane is clipped away
+    pub fn draw_mirrored(&mut self, scene: &Scene, camera: &Camera, mirror: &Mirror) -> usize {
+        let reflection = mirror.plane.reflection();
+        let view = camera.transform(self.width, self.height, 255.0);
+        let clip = self.clip_plane.take();
+        self.clip_plane = clip_plane(&(&view * &reflection), &mirror.plane);
+        let drawn = self.draw_nodes(scene, camera, Some(&reflection), Some(mirror.node), None);
+        self.clip_plane = clip;
+        drawn
+    }
+    // the mirror node lit like `draw_scene` with `reflection`, from `draw_mirrored`,
+    // mixed in at the pixels it covers
+    pub fn draw_mirror(
+        &mut self,
+        scene: &Scene,
+        camera: &Camera,
+        mirror: &Mirror,
+        reflection: &Image,
+    ) {
+        assert_eq!(
+            (reflection.width, reflection.height),
+            (self.width, self.height)
+        );
+        let node = &scene.nodes[mirror.node];
+        let Some(model) = node.model else {
+            return;
+        };
+        let model = &scene.models[model];
+        let world = if node.skin.is_some() {
+            Matrix4::identity()
+        } else {
+            scene.world_transform(mirror.node)
+        };
+        let material = match node.material {
+            Some(material) => &scene.materials[material],
+            None => model.material(),
+        };
+        let lights = scene.lights();
+        let mut shader = MirrorShader {
+            surface: SceneShader {
+                model,
+                material,
+                transform: &camera.transform(self.width, self.height, 255.0) * &world,
+                normal: normal_matrix(&world),
+                lights: &lights,
+                ambient: scene.ambient,
+                varying_uv: Matrix::zero(),
+                varying_uv1: Matrix::zero(),
+                varying_nrm: Matrix::zero(),
+                varying_color: Matrix::zero(),
+            },
+            reflection,
+            strength: mirror.strength,
+            depth: self.depth,
+            screen: [Vector3::zero(), Vector3::zero(), Vector3::zero()],
+        };
+        self.set_object(mirror.node);
+        for i in 0..model.nfaces() {
+            let screen_coords = [0, 1, 2].map(|j| shader.vertex(i, j));
+            self.fill_triangle(&screen_coords, &mut shader);
+        }
+    }
+    // `reflection` mirrors the whole scene and its lights, `skip` leaves a node out
+    fn draw_nodes(
+        &mut self,
+        scene: &Scene,
+        camera: &Camera,
+        reflection: Option<&Matrix4>,
+        skip: Option<usize>,
+        occlusion: Option<&OcclusionBuffer>,
+    ) -> usize {
+        let view = camera.transform(self.width, self.height, 255.0);
+        let frustum = camera.frustum(self.width, self.height);
+        let mut lights = scene.lights();
+        if let Some(reflection) = reflection {
+            let normal = normal_matrix(reflection);
+            for light in lights.iter_mut() {
+                light.dir = m3v(&normal, &light.dir).normalize();
+            }
+        }
+        let mut drawn = 0;
+        let mut translucent = Vec::new();
+        for (id, node) in scene.nodes.iter().enumerate() {
+            if !scene.visible(id) || skip == Some(id) {
+                continue;
+            }
+            let Some(model) = scene.lod_model(id, camera, self.width, self.height) else {
+                continue;
+            };
+            let model = &scene.models[model];
+            let world = if node.skin.is_some() {
+                Matrix4::identity()
+            } else {
+                scene.world_transform(id)
+            };
+            let world = match reflection {
+                Some(reflection) => reflection * &world,
+                None => world,
+            };
+            let (min, max) = model.aabb();
+            let bounds = Aabb::new(min, max);
+            let corners = bounds.corners().map(|c| m2v(&(&world * v2m(&c))));
+            if !frustum.intersects_box(&corners) {
+                continue;
+            }
+            if occlusion.is_some_and(|o| !o.is_visible(&bounds, &world)) {
+                continue;
+            }
+            drawn += 1;
+            // lod levels are drawn with the material of the full model
+            let material = match node.material {
+                Some(material) => &scene.materials[material],
+                None => scene.models[node.model.unwrap()].material(),
+            };
+            let mut shader = SceneShader {
+                model,
+                material,
+                transform: &view * &world,
+                normal: normal_matrix(&world),
+                lights: &lights,
+                ambient: scene.ambient,
+                varying_uv: Matrix::zero(),
+                varying_uv1: Matrix::zero(),
+                varying_nrm: Matrix::zero(),
+                varying_color: Matrix::zero(),
+            };
+            if material.is_transparent() {
+                let center = corners.iter().fold(Vector3::zero(), |a, c| a + c) / 8.0;
+                let depth = self.depth.nearness(m2v(&(&view * v2m(&center))).z());
+                translucent.push((depth, id, shader));
+                continue;
+            }
+            // picking reports node ids
+            self.set_object(id);
+            self.draw_faces(&mut shader);
+        }
+        if translucent.is_empty() {
+            return drawn;
+        }
+        let (blend, write) = (self.blend, self.depth.write);
+        self.depth.write = false;
+        self.blend = match self.transparency {
+            Transparency::Sorted => Blend::Alpha,
+            Transparency::WeightedOit => Blend::WeightedOit,
+        };
+        // farthest first, which also leaves the nearest in the id attachment
+        translucent.sort_by(|a, b| a.0.total_cmp(&b.0));
+        for (_, id, shader) in translucent.iter_mut() {
+            self.set_object(*id);
+            self.draw_faces(shader);
+        }
+        if self.transparency == Transparency::WeightedOit {
+            self.resolve_oit();
+        }
+        self.blend = blend;
+        self.depth.write = write;
+        drawn
+    }
+    // a `size` (width, height) quad centered on `center` in world space, kept
+    // parallel to the screen, showing the material's diffuse map unlit, e.g.
+    // labels, flares or sprites of plants. It is depth tested like any triangle;
+    // texels with alpha under half are cut out and leave the depth alone, the
+    // rest are drawn as `self.blend`
+    pub fn draw_billboard(
+        &mut self,
+        center: &Vector3,
+        size: &Vector2,
+        material: &Material,
+        camera: &Camera,
+    ) {
+        let z = (&camera.eye - &camera.center).normalize();
+        let right = camera.up.cross(&z).normalize();
+        let up = z.cross(&right);
+        let (dx, dy) = (right * (size.x() / 2.0), up * (size.y() / 2.0));
+        let mut shader = BillboardShader {
+            material,
+            transform: camera.transform(self.width, self.height, 255.0),
+            corners: [
+                &(center - &dx) - &dy,
+                &(center + &dx) - &dy,
+                &(center + &dx) + &dy,
+                &(center - &dx) + &dy,
+            ],
+            varying_uv: Matrix::zero(),
+        };
+        for i in 0..2 {
+            let screen_coords = [0, 1, 2].map(|j| shader.vertex(i, j));
+            self.fill_triangle(&screen_coords, &mut shader);
+        }
+    }
+    pub(crate) fn draw_faces(&mut self, shader: &mut SceneShader) {
+        for i in 0..shader.model.nfaces() {
+            let screen_coords = [0, 1, 2].map(|j| shader.vertex(i, j));
+            self.fill_triangle(&screen_coords, shader);
+        }
+    }
+}
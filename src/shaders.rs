@@ -0,0 +1,507 @@
+use std::{f32::consts::PI, ops::Neg};
+
+use crate::{
+    buffer::VertexBuffer,
+    geometry::{m2v, v2m, Matrix, Matrix3, Matrix4, Vector2, Vector3, Vector4},
+    material::Material,
+    model::Model,
+    probe::ReflectionProbe,
+    renderer::{interpolate, IndexedShader, Shader, TriangleShader},
+    shadow::ShadowMap,
+};
+
+#[derive(Clone)]
+pub struct ToonShader<'a> {
+    pub model: &'a Model,
+    // the model's own material unless replaced
+    pub material: &'a Material,
+    pub transform: Matrix4,
+    pub light_dir: Vector3,
+    pub bands: u32,
+    varying_uv: Matrix<3, 2>,
+    varying_intensity: Vector3,
+    varying_color: Matrix3,
+}
+impl<'a> ToonShader<'a> {
+    pub fn new(model: &'a Model, transform: Matrix4, light_dir: Vector3, bands: u32) -> Self {
+        Self {
+            model,
+            material: model.material(),
+            transform,
+            light_dir: light_dir.normalize(),
+            bands: bands.max(1),
+            varying_uv: Matrix::zero(),
+            varying_intensity: Vector3::zero(),
+            varying_color: Matrix::zero(),
+        }
+    }
+}
+impl Shader for ToonShader<'_> {
+    fn vertex(&mut self, iface: usize, nthvert: usize) -> Vector3 {
+        self.varying_uv
+            .set_row(nthvert, self.model.uv(iface, nthvert));
+        let n = self.model.normal_vert(iface, nthvert).normalize();
+        self.varying_intensity[nthvert] = n.dot(&self.light_dir).neg().max(0.0);
+        self.varying_color
+            .set_row(nthvert, unpack_color(self.model.color(iface, nthvert)));
+        let v = self.model.vert(iface, nthvert);
+        m2v(&(&self.transform * v2m(&v)))
+    }
+
+    fn fregment(&mut self, bc: &Vector3) -> Option<u32> {
+        let uv = (Matrix::from_row_vector(bc.clone()) * &self.varying_uv).to_row_vector();
+        let intensity = self.varying_intensity.dot(bc);
+        let color = (Matrix::from_row_vector(bc.clone()) * &self.varying_color).to_row_vector();
+        Some(self.shade(&uv, intensity, &color))
+    }
+    fn uv(&mut self, bc: &Vector3) -> Option<Vector2> {
+        Some((Matrix::from_row_vector(bc.clone()) * &self.varying_uv).to_row_vector())
+    }
+    fn fork<'f>(&self) -> Option<Box<dyn Shader + Send + 'f>>
+    where
+        Self: 'f,
+    {
+        Some(Box::new(self.clone()))
+    }
+}
+
+#[derive(Clone)]
+pub struct ToonVarying {
+    uv: Vector2,
+    intensity: f32,
+    color: Vector3,
+    normal: Vector3,
+}
+
+impl IndexedShader for ToonShader<'_> {
+    type Varying = ToonVarying;
+    fn vertex(&mut self, vertices: &VertexBuffer, index: usize) -> (Vector3, ToonVarying) {
+        let n = vertices.normals[index].normalize();
+        let varying = ToonVarying {
+            uv: vertices.uvs[index].clone(),
+            intensity: n.dot(&self.light_dir).neg().max(0.0),
+            color: unpack_color(vertices.colors[index]),
+            normal: n,
+        };
+        let v = &vertices.positions[index];
+        (m2v(&(&self.transform * v2m(v))), varying)
+    }
+
+    fn fregment(&mut self, varyings: [&ToonVarying; 3], bc: &Vector3) -> Option<u32> {
+        let [a, b, c] = varyings;
+        let uv = interpolate(bc, [&a.uv, &b.uv, &c.uv]);
+        let intensity = bc[0] * a.intensity + bc[1] * b.intensity + bc[2] * c.intensity;
+        let color = interpolate(bc, [&a.color, &b.color, &c.color]);
+        Some(self.shade(&uv, intensity, &color))
+    }
+    // in model space
+    fn normal(&mut self, varyings: [&ToonVarying; 3], bc: &Vector3) -> Option<Vector3> {
+        let [a, b, c] = varyings;
+        Some(interpolate(bc, [&a.normal, &b.normal, &c.normal]).normalize())
+    }
+    fn uv(&mut self, varyings: [&ToonVarying; 3], bc: &Vector3) -> Option<Vector2> {
+        let [a, b, c] = varyings;
+        Some(interpolate(bc, [&a.uv, &b.uv, &c.uv]))
+    }
+    fn fork<'f>(&self, varyings: [&ToonVarying; 3]) -> Option<Box<dyn Shader + Send + 'f>>
+    where
+        Self: 'f,
+    {
+        Some(Box::new(TriangleShader::new(self.clone(), varyings)))
+    }
+}
+
+impl ToonShader<'_> {
+    fn shade(&self, uv: &Vector2, intensity: f32, color: &Vector3) -> u32 {
+        // quantize into `bands` flat steps, the darkest band is kept slightly lit
+        let bands = self.bands as f32;
+        let level = ((intensity * bands).ceil() / bands).max(0.5 / bands);
+        let pixel = self.material.diffuse(uv);
+        let mut new_pixel = 0xff000000;
+        for i in 0..3 {
+            let part = ((pixel >> (8 * i)) & 0xff) as f32 * color[i] * level;
+            new_pixel |= ((part.clamp(0.0, 255.0) as u32) & 0xff) << (8 * i)
+        }
+        new_pixel
+    }
+}
+
+// the model's vertex colors, alpha included so they can be blended, optionally
+// lambert lit. Meshes without colors come out white
+#[derive(Clone)]
+pub struct VertexColorShader<'a> {
+    pub model: &'a Model,
+    pub transform: Matrix4,
+    // unlit when None
+    pub light_dir: Option<Vector3>,
+    pub ambient: f32,
+    varying_color: Matrix<3, 4>,
+    varying_intensity: Vector3,
+}
+impl<'a> VertexColorShader<'a> {
+    pub fn new(model: &'a Model, transform: Matrix4) -> Self {
+        Self {
+            model,
+            transform,
+            light_dir: None,
+            ambient: 0.2,
+            varying_color: Matrix::zero(),
+            varying_intensity: Vector3::zero(),
+        }
+    }
+    fn intensity(&self, normal: &Vector3) -> f32 {
+        match self.light_dir {
+            Some(ref l) => self.ambient + normal.normalize().dot(l).neg().max(0.0),
+            None => 1.0,
+        }
+    }
+}
+impl Shader for VertexColorShader<'_> {
+    fn vertex(&mut self, iface: usize, nthvert: usize) -> Vector3 {
+        self.varying_color
+            .set_row(nthvert, unpack_rgba(self.model.color(iface, nthvert)));
+        self.varying_intensity[nthvert] = self.intensity(&self.model.normal_vert(iface, nthvert));
+        let v = self.model.vert(iface, nthvert);
+        m2v(&(&self.transform * v2m(&v)))
+    }
+
+    fn fregment(&mut self, bc: &Vector3) -> Option<u32> {
+        let color = (Matrix::from_row_vector(bc.clone()) * &self.varying_color).to_row_vector();
+        Some(pack_rgba(&color, self.varying_intensity.dot(bc)))
+    }
+    fn fork<'f>(&self) -> Option<Box<dyn Shader + Send + 'f>>
+    where
+        Self: 'f,
+    {
+        Some(Box::new(self.clone()))
+    }
+}
+
+#[derive(Clone)]
+pub struct VertexColorVarying {
+    color: Vector4,
+    intensity: f32,
+}
+
+impl IndexedShader for VertexColorShader<'_> {
+    type Varying = VertexColorVarying;
+    fn vertex(&mut self, vertices: &VertexBuffer, index: usize) -> (Vector3, VertexColorVarying) {
+        let varying = VertexColorVarying {
+            color: unpack_rgba(vertices.colors[index]),
+            intensity: self.intensity(&vertices.normals[index]),
+        };
+        let v = &vertices.positions[index];
+        (m2v(&(&self.transform * v2m(v))), varying)
+    }
+
+    fn fregment(&mut self, varyings: [&VertexColorVarying; 3], bc: &Vector3) -> Option<u32> {
+        let [a, b, c] = varyings;
+        let color = interpolate(bc, [&a.color, &b.color, &c.color]);
+        let intensity = bc[0] * a.intensity + bc[1] * b.intensity + bc[2] * c.intensity;
+        Some(pack_rgba(&color, intensity))
+    }
+    fn fork<'f>(&self, varyings: [&VertexColorVarying; 3]) -> Option<Box<dyn Shader + Send + 'f>>
+    where
+        Self: 'f,
+    {
+        Some(Box::new(TriangleShader::new(self.clone(), varyings)))
+    }
+}
+
+// Cook-Torrance: GGX distribution, Smith-Schlick geometry, Fresnel-Schlick,
+// plus a Lambert diffuse lobe, metallic/roughness following the glTF convention
+#[derive(Clone)]
+pub struct PbrShader<'a> {
+    pub model: &'a Model,
+    // the model's own material unless replaced
+    pub material: &'a Material,
+    pub transform: Matrix4,
+    pub light_dir: Vector3,
+    pub light_color: Vector3,
+    pub eye: Vector3,
+    pub ambient: f32,
+    // interpret the normal map in tangent space instead of model space
+    pub tangent_space: bool,
+    // image based specular from nearby probes and their blend weights, see `ProbeSet::blend_for`
+    pub probes: Vec<(&'a ReflectionProbe, f32)>,
+    // darkens the direct light where the map says the point is covered
+    pub shadow: Option<&'a ShadowMap>,
+    varying_uv: Matrix<3, 2>,
+    varying_uv1: Matrix<3, 2>,
+    varying_pos: Matrix3,
+    varying_nrm: Matrix3,
+    varying_tan: Matrix3,
+    varying_btn: Matrix3,
+    varying_color: Matrix3,
+}
+impl<'a> PbrShader<'a> {
+    pub fn new(model: &'a Model, transform: Matrix4, light_dir: Vector3, eye: Vector3) -> Self {
+        Self {
+            model,
+            material: model.material(),
+            transform,
+            light_dir: light_dir.normalize(),
+            light_color: Vector3::new(3.0, 3.0, 3.0),
+            eye,
+            ambient: 0.03,
+            tangent_space: false,
+            probes: Vec::new(),
+            shadow: None,
+            varying_uv: Matrix::zero(),
+            varying_uv1: Matrix::zero(),
+            varying_pos: Matrix::zero(),
+            varying_nrm: Matrix::zero(),
+            varying_tan: Matrix::zero(),
+            varying_btn: Matrix::zero(),
+            varying_color: Matrix::zero(),
+        }
+    }
+}
+impl Shader for PbrShader<'_> {
+    fn vertex(&mut self, iface: usize, nthvert: usize) -> Vector3 {
+        self.varying_uv
+            .set_row(nthvert, self.model.uv(iface, nthvert));
+        self.varying_uv1
+            .set_row(nthvert, self.model.uv1(iface, nthvert));
+        self.varying_nrm
+            .set_row(nthvert, self.model.normal_vert(iface, nthvert));
+        if self.tangent_space {
+            self.varying_tan
+                .set_row(nthvert, self.model.tangent(iface, nthvert));
+            self.varying_btn
+                .set_row(nthvert, self.model.bitangent(iface, nthvert));
+        }
+        // vertex colors are sRGB like the diffuse map, interpolate them in linear space
+        self.varying_color
+            .set_row(nthvert, srgb_to_linear(self.model.color(iface, nthvert)));
+        let v = self.model.vert(iface, nthvert);
+        self.varying_pos.set_row(nthvert, v.clone());
+        m2v(&(&self.transform * v2m(&v)))
+    }
+
+    fn fregment(&mut self, bc: &Vector3) -> Option<u32> {
+        let bc_row = Matrix::from_row_vector(bc.clone());
+        let varying = PbrVarying {
+            uv: (&bc_row * &self.varying_uv).to_row_vector(),
+            uv1: (&bc_row * &self.varying_uv1).to_row_vector(),
+            pos: (&bc_row * &self.varying_pos).to_row_vector(),
+            normal: (&bc_row * &self.varying_nrm).to_row_vector(),
+            tangent: (&bc_row * &self.varying_tan).to_row_vector(),
+            bitangent: (&bc_row * &self.varying_btn).to_row_vector(),
+            color: (&bc_row * &self.varying_color).to_row_vector(),
+        };
+        Some(self.shade(&varying))
+    }
+    fn fork<'f>(&self) -> Option<Box<dyn Shader + Send + 'f>>
+    where
+        Self: 'f,
+    {
+        Some(Box::new(self.clone()))
+    }
+}
+
+// also what a fragment gets, interpolated
+#[derive(Clone)]
+pub struct PbrVarying {
+    uv: Vector2,
+    // lightmap coordinates
+    uv1: Vector2,
+    pos: Vector3,
+    normal: Vector3,
+    tangent: Vector3,
+    bitangent: Vector3,
+    // linear
+    color: Vector3,
+}
+
+impl IndexedShader for PbrShader<'_> {
+    type Varying = PbrVarying;
+    fn vertex(&mut self, vertices: &VertexBuffer, index: usize) -> (Vector3, PbrVarying) {
+        let v = &vertices.positions[index];
+        let varying = PbrVarying {
+            uv: vertices.uvs[index].clone(),
+            uv1: vertices.uvs1[index].clone(),
+            pos: v.clone(),
+            normal: vertices.normals[index].clone(),
+            tangent: vertices.tangents[index].clone(),
+            bitangent: vertices.bitangents[index].clone(),
+            color: srgb_to_linear(vertices.colors[index]),
+        };
+        (m2v(&(&self.transform * v2m(v))), varying)
+    }
+
+    fn fregment(&mut self, varyings: [&PbrVarying; 3], bc: &Vector3) -> Option<u32> {
+        let [a, b, c] = varyings;
+        let varying = PbrVarying {
+            uv: interpolate(bc, [&a.uv, &b.uv, &c.uv]),
+            uv1: interpolate(bc, [&a.uv1, &b.uv1, &c.uv1]),
+            pos: interpolate(bc, [&a.pos, &b.pos, &c.pos]),
+            normal: interpolate(bc, [&a.normal, &b.normal, &c.normal]),
+            tangent: interpolate(bc, [&a.tangent, &b.tangent, &c.tangent]),
+            bitangent: interpolate(bc, [&a.bitangent, &b.bitangent, &c.bitangent]),
+            color: interpolate(bc, [&a.color, &b.color, &c.color]),
+        };
+        Some(self.shade(&varying))
+    }
+    fn fork<'f>(&self, varyings: [&PbrVarying; 3]) -> Option<Box<dyn Shader + Send + 'f>>
+    where
+        Self: 'f,
+    {
+        Some(Box::new(TriangleShader::new(self.clone(), varyings)))
+    }
+}
+
+impl PbrShader<'_> {
+    fn shade(&self, varying: &PbrVarying) -> u32 {
+        let PbrVarying {
+            uv,
+            uv1,
+            pos,
+            normal: vertex_normal,
+            tangent: t,
+            bitangent: b,
+            color: vertex_color,
+        } = varying;
+        let n = if !self.material.has_normal_map() {
+            vertex_normal.clone()
+        } else if self.tangent_space {
+            tangent_to_model(&tbn(vertex_normal, t, b), &self.material.normal_uv(uv))
+        } else {
+            self.material.normal_uv(uv)
+        }
+        .normalize();
+        let v = (&self.eye - pos).normalize();
+        let l = -&self.light_dir;
+        let h = (&v + &l).normalize();
+
+        let mut base_color = srgb_to_linear(self.material.diffuse(uv));
+        for i in 0..3 {
+            base_color[i] *= vertex_color[i];
+        }
+        let (metallic, roughness) = self.material.metallic_roughness(uv);
+        let metallic = metallic.clamp(0.0, 1.0);
+        let roughness = roughness.clamp(0.04, 1.0);
+
+        let n_dot_l = n.dot(&l).max(0.0);
+        let n_dot_v = n.dot(&v).max(1e-4);
+        let n_dot_h = n.dot(&h).max(0.0);
+        let h_dot_v = h.dot(&v).max(0.0);
+
+        let a = roughness * roughness;
+        let a2 = a * a;
+        let denom = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+        let distribution = a2 / (PI * denom * denom);
+        let k = (roughness + 1.0) * (roughness + 1.0) / 8.0;
+        let geometry =
+            (n_dot_v / (n_dot_v * (1.0 - k) + k)) * (n_dot_l / (n_dot_l * (1.0 - k) + k));
+        let fresnel_weight = (1.0 - h_dot_v).powi(5);
+
+        // probes are in world space, so this assumes the model is too
+        let mut reflection = Vector3::zero();
+        if !self.probes.is_empty() {
+            let r = &n * (2.0 * n.dot(&v)) - &v;
+            for (probe, weight) in self.probes.iter() {
+                reflection = reflection + probe.sample(&r, roughness) * *weight;
+            }
+        }
+
+        let visibility = self.shadow.map_or(1.0, |shadow| shadow.visibility(pos));
+        let baked = self.material.lightmap(uv1);
+        let mut color = Vector3::zero();
+        for i in 0..3 {
+            let f0 = 0.04 * (1.0 - metallic) + base_color[i] * metallic;
+            let fresnel = f0 + (1.0 - f0) * fresnel_weight;
+            let specular = distribution * geometry * fresnel / (4.0 * n_dot_v * n_dot_l.max(1e-4));
+            let diffuse = (1.0 - fresnel) * (1.0 - metallic) * base_color[i] / PI;
+            color[i] = (diffuse + specular) * self.light_color[i] * n_dot_l * visibility
+                + self.ambient * base_color[i]
+                + env_brdf(f0, roughness, n_dot_v) * reflection[i];
+            color[i] *= baked[i];
+        }
+        linear_to_srgb(&color)
+    }
+}
+
+// tangent frame as columns: maps tangent space vectors (e.g. normal map samples) to model space
+#[rustfmt::skip]
+pub fn tbn(normal: &Vector3, tangent: &Vector3, bitangent: &Vector3) -> Matrix3 {
+    let n = normal.normalize();
+    // re-orthogonalize, interpolation skews the frame across a face
+    let t = (tangent - &n * n.dot(tangent)).normalize();
+    let b = if n.cross(&t).dot(bitangent) < 0.0 { -n.cross(&t) } else { n.cross(&t) };
+    Matrix::from_rows([
+        [t.x(), b.x(), n.x()],
+        [t.y(), b.y(), n.y()],
+        [t.z(), b.z(), n.z()],
+    ])
+}
+
+pub fn tangent_to_model(tbn: &Matrix3, v: &Vector3) -> Vector3 {
+    let mut result = Vector3::zero();
+    for i in 0..3 {
+        result[i] = tbn[i][0] * v.x() + tbn[i][1] * v.y() + tbn[i][2] * v.z();
+    }
+    result
+}
+
+// Karis' analytic fit of the split sum environment brdf, scales prefiltered radiance
+fn env_brdf(f0: f32, roughness: f32, n_dot_v: f32) -> f32 {
+    let r = [
+        -roughness + 1.0,
+        -0.0275 * roughness + 0.0425,
+        -0.572 * roughness + 1.04,
+        0.022 * roughness - 0.04,
+    ];
+    let a004 = (r[0] * r[0]).min((-9.28 * n_dot_v).exp2()) * r[0] + r[1];
+    let scale = -1.04 * a004 + r[2];
+    let bias = 1.04 * a004 + r[3];
+    f0 * scale + bias
+}
+
+// 0xAABBGGRR to rgb in [0, 1]
+fn unpack_color(pixel: u32) -> Vector3 {
+    let mut color = Vector3::zero();
+    for i in 0..3 {
+        color[i] = ((pixel >> (8 * i)) & 0xff) as f32 / 255.0;
+    }
+    color
+}
+
+// 0xAABBGGRR to rgba in [0, 1]
+fn unpack_rgba(pixel: u32) -> Vector4 {
+    let mut color = Vector4::zero();
+    for i in 0..4 {
+        color[i] = ((pixel >> (8 * i)) & 0xff) as f32 / 255.0;
+    }
+    color
+}
+
+// rgb scaled by `intensity`, alpha as is
+fn pack_rgba(color: &Vector4, intensity: f32) -> u32 {
+    let mut pixel = 0;
+    for i in 0..4 {
+        let scale = if i < 3 { intensity } else { 1.0 };
+        let part = (color[i] * scale * 255.0).round();
+        pixel |= (part.clamp(0.0, 255.0) as u32) << (8 * i);
+    }
+    pixel
+}
+
+pub fn srgb_to_linear(pixel: u32) -> Vector3 {
+    let mut color = Vector3::zero();
+    for i in 0..3 {
+        color[i] = (((pixel >> (8 * i)) & 0xff) as f32 / 255.0).powf(2.2);
+    }
+    color
+}
+
+// reinhard tone mapping followed by gamma encoding
+fn linear_to_srgb(color: &Vector3) -> u32 {
+    let mut pixel = 0xff000000;
+    for i in 0..3 {
+        let c = color[i] / (1.0 + color[i]);
+        let c = c.max(0.0).powf(1.0 / 2.2) * 255.0;
+        pixel |= ((c.clamp(0.0, 255.0) as u32) & 0xff) << (8 * i);
+    }
+    pixel
+}
@@ -0,0 +1,95 @@
+use crate::{
+    geometry::{m2v, v2m, Matrix4, Vector3},
+    model::Model,
+    renderer::{lookat, viewport, Renderer, Shader},
+};
+
+// depth map rendered from a directional light, used to answer "is this point lit"
+pub struct ShadowMap {
+    pub width: u32,
+    pub height: u32,
+    pub transform: Matrix4,
+    pub bias: f32,
+    depth: Vec<f32>,
+    color: Vec<u32>,
+}
+
+pub(crate) struct DepthShader<'a> {
+    pub(crate) model: &'a Model,
+    pub(crate) transform: &'a Matrix4,
+}
+impl Shader for DepthShader<'_> {
+    fn vertex(&mut self, iface: usize, nthvert: usize) -> Vector3 {
+        let v = self.model.vert(iface, nthvert);
+        m2v(&(self.transform * v2m(&v)))
+    }
+    fn fregment(&mut self, _bc: &Vector3) -> Option<u32> {
+        Some(0xffffffff)
+    }
+}
+
+impl ShadowMap {
+    // orthographic light covering the sphere (`center`, `radius`)
+    pub fn new(
+        width: u32,
+        height: u32,
+        light_dir: &Vector3,
+        center: &Vector3,
+        radius: f32,
+        depth: f32,
+    ) -> Self {
+        let eye = center - light_dir.normalize();
+        let up = if light_dir.normalize().y().abs() > 0.99 {
+            Vector3::new(0.0, 0.0, 1.0)
+        } else {
+            Vector3::new(0.0, 1.0, 0.0)
+        };
+        let scale = Matrix4::scaling(&Vector3::new(1.0 / radius, 1.0 / radius, 1.0 / radius));
+        let transform = viewport(0.0, 0.0, width as f32, height as f32, depth)
+            * scale
+            * lookat(&eye, center, &up);
+        let size = (width * height) as usize;
+        Self {
+            width,
+            height,
+            transform,
+            bias: depth / 64.0,
+            depth: vec![f32::MIN; size],
+            color: vec![0; size],
+        }
+    }
+    pub fn clear(&mut self) {
+        self.depth.fill(f32::MIN);
+    }
+    pub fn depth_buffer(&self) -> &[f32] {
+        &self.depth
+    }
+    // accumulate the occluders of `model` (in world space) into the map
+    pub fn render(&mut self, model: &Model) {
+        let mut renderer = Renderer::new(&mut self.color, &mut self.depth, self.width, self.height);
+        let mut shader = DepthShader {
+            model,
+            transform: &self.transform,
+        };
+        for i in 0..model.nfaces() {
+            let screen_coords = [0, 1, 2].map(|j| shader.vertex(i, j));
+            renderer.fill_triangle(&screen_coords, &mut shader);
+        }
+    }
+    // 1.0 if `pos` (world space) is lit, 0.0 if something closer to the light covers it
+    pub fn visibility(&self, pos: &Vector3) -> f32 {
+        let p = m2v(&(&self.transform * v2m(pos)));
+        if p.x() < 0.0 || p.y() < 0.0 {
+            return 1.0;
+        }
+        let (x, y) = (p.x() as u32, p.y() as u32);
+        if x >= self.width || y >= self.height {
+            return 1.0;
+        }
+        if self.depth[(x + y * self.width) as usize] > p.z() + self.bias {
+            0.0
+        } else {
+            1.0
+        }
+    }
+}
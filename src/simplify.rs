@@ -0,0 +1,356 @@
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap},
+};
+
+use crate::{geometry::Vector3, model::Model};
+
+// symmetric 4x4 error quadric, upper triangle row by row
+#[derive(Clone, Copy, Default)]
+struct Quadric([f64; 10]);
+
+impl Quadric {
+    // squared distance to the plane n.p + d = 0, times `weight`
+    fn plane(n: [f64; 3], d: f64, weight: f64) -> Self {
+        let p = [n[0], n[1], n[2], d];
+        let mut q = [0.0; 10];
+        let mut k = 0;
+        for i in 0..4 {
+            for j in i..4 {
+                q[k] = p[i] * p[j] * weight;
+                k += 1;
+            }
+        }
+        Self(q)
+    }
+    fn add(&mut self, other: &Self) {
+        for (a, b) in self.0.iter_mut().zip(other.0.iter()) {
+            *a += b;
+        }
+    }
+    fn error(&self, p: [f64; 3]) -> f64 {
+        let q = &self.0;
+        let [x, y, z] = p;
+        q[0] * x * x
+            + 2.0 * q[1] * x * y
+            + 2.0 * q[2] * x * z
+            + 2.0 * q[3] * x
+            + q[4] * y * y
+            + 2.0 * q[5] * y * z
+            + 2.0 * q[6] * y
+            + q[7] * z * z
+            + 2.0 * q[8] * z
+            + q[9]
+    }
+    // the point of least error, None when the quadric is flat in some direction
+    fn minimum(&self) -> Option<[f64; 3]> {
+        let q = &self.0;
+        let a = [[q[0], q[1], q[2]], [q[1], q[4], q[5]], [q[2], q[5], q[7]]];
+        let b = [-q[3], -q[6], -q[8]];
+        let det = a[0][0] * (a[1][1] * a[2][2] - a[1][2] * a[2][1])
+            - a[0][1] * (a[1][0] * a[2][2] - a[1][2] * a[2][0])
+            + a[0][2] * (a[1][0] * a[2][1] - a[1][1] * a[2][0]);
+        if det.abs() < 1e-12 {
+            return None;
+        }
+        // cramer's rule
+        let solve = |c: usize| {
+            let mut m = a;
+            for r in 0..3 {
+                m[r][c] = b[r];
+            }
+            (m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+                - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+                + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0]))
+                / det
+        };
+        Some([solve(0), solve(1), solve(2)])
+    }
+}
+
+// an edge collapse waiting in the queue, stale once either end changed
+struct Collapse {
+    cost: f64,
+    a: usize,
+    b: usize,
+    versions: (u32, u32),
+    target: [f64; 3],
+}
+
+impl PartialEq for Collapse {
+    fn eq(&self, other: &Self) -> bool {
+        self.cost == other.cost
+    }
+}
+impl Eq for Collapse {}
+impl PartialOrd for Collapse {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for Collapse {
+    // cheapest first out of the max heap
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.cost.total_cmp(&self.cost)
+    }
+}
+
+// open borders weigh this much more than the surface so the outline stays put
+const BORDER_WEIGHT: f64 = 1e3;
+
+struct Simplifier {
+    positions: Vec<[f64; 3]>,
+    quadrics: Vec<Quadric>,
+    faces: Vec<[usize; 3]>,
+    alive: Vec<bool>,
+    vert_faces: Vec<Vec<usize>>,
+    versions: Vec<u32>,
+    heap: BinaryHeap<Collapse>,
+}
+
+fn sub(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn cross(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+impl Simplifier {
+    fn new(model: &Model) -> Self {
+        let positions: Vec<[f64; 3]> = model
+            .verts()
+            .iter()
+            .map(|v| [v.x() as f64, v.y() as f64, v.z() as f64])
+            .collect();
+        let faces: Vec<[usize; 3]> = (0..model.nfaces())
+            .map(|i| [0, 1, 2].map(|j| model.vert_index(i, j)))
+            .collect();
+        let mut quadrics = vec![Quadric::default(); positions.len()];
+        let mut vert_faces = vec![Vec::new(); positions.len()];
+        let mut edges = HashMap::new();
+        for (f, face) in faces.iter().enumerate() {
+            let [a, b, c] = face.map(|v| positions[v]);
+            let n = cross(sub(b, a), sub(c, a));
+            let area = dot(n, n).sqrt();
+            for &v in face {
+                vert_faces[v].push(f);
+            }
+            if area <= 0.0 {
+                continue;
+            }
+            let n = n.map(|x| x / area);
+            let q = Quadric::plane(n, -dot(n, a), area / 2.0);
+            for &v in face {
+                quadrics[v].add(&q);
+            }
+            for k in 0..3 {
+                let (u, v) = (face[k], face[(k + 1) % 3]);
+                edges.entry((u.min(v), u.max(v))).or_insert((0, n, u, v)).0 += 1;
+            }
+        }
+        // a plane through each border edge, standing on its face
+        for (_, (count, n, u, v)) in edges.iter() {
+            if *count != 1 {
+                continue;
+            }
+            let e = sub(positions[*v], positions[*u]);
+            let side = cross(e, *n);
+            let length = dot(side, side).sqrt();
+            if length <= 0.0 {
+                continue;
+            }
+            let side = side.map(|x| x / length);
+            let q = Quadric::plane(side, -dot(side, positions[*u]), BORDER_WEIGHT * dot(e, e));
+            quadrics[*u].add(&q);
+            quadrics[*v].add(&q);
+        }
+        let mut simplifier = Self {
+            alive: vec![true; faces.len()],
+            versions: vec![0; positions.len()],
+            positions,
+            quadrics,
+            faces,
+            vert_faces,
+            heap: BinaryHeap::new(),
+        };
+        for &(a, b) in edges.keys() {
+            simplifier.push(a, b);
+        }
+        simplifier
+    }
+    fn push(&mut self, a: usize, b: usize) {
+        let mut q = self.quadrics[a];
+        q.add(&self.quadrics[b]);
+        let (pa, pb) = (self.positions[a], self.positions[b]);
+        let middle = [0, 1, 2].map(|i| (pa[i] + pb[i]) / 2.0);
+        let target = q
+            .minimum()
+            .into_iter()
+            .chain([pa, pb, middle])
+            .min_by(|x, y| q.error(*x).total_cmp(&q.error(*y)))
+            .unwrap();
+        self.heap.push(Collapse {
+            cost: q.error(target),
+            a,
+            b,
+            versions: (self.versions[a], self.versions[b]),
+            target,
+        });
+    }
+    fn neighbours(&self, v: usize) -> Vec<usize> {
+        let mut n: Vec<usize> = self.vert_faces[v]
+            .iter()
+            .filter(|&&f| self.alive[f])
+            .flat_map(|&f| self.faces[f])
+            .filter(|&u| u != v)
+            .collect();
+        n.sort_unstable();
+        n.dedup();
+        n
+    }
+    // keeps the surface a manifold and no face turning over
+    fn can_collapse(&self, a: usize, b: usize, target: [f64; 3]) -> bool {
+        let (na, nb) = (self.neighbours(a), self.neighbours(b));
+        let shared = na.iter().filter(|v| nb.binary_search(v).is_ok()).count();
+        let edge_faces = self.vert_faces[a]
+            .iter()
+            .filter(|&&f| self.alive[f] && self.faces[f].contains(&b))
+            .count();
+        if shared != edge_faces {
+            return false;
+        }
+        for &v in [a, b].iter() {
+            for &f in self.vert_faces[v].iter() {
+                let face = self.faces[f];
+                if !self.alive[f] || (face.contains(&a) && face.contains(&b)) {
+                    continue;
+                }
+                let old = face.map(|u| self.positions[u]);
+                let new = face.map(|u| if u == v { target } else { self.positions[u] });
+                let n0 = cross(sub(old[1], old[0]), sub(old[2], old[0]));
+                let n1 = cross(sub(new[1], new[0]), sub(new[2], new[0]));
+                if dot(n0, n1) <= 0.0 {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+    fn run(&mut self, target_faces: usize) {
+        let mut faces = self.alive.iter().filter(|&&a| a).count();
+        while faces > target_faces {
+            let Some(c) = self.heap.pop() else {
+                break;
+            };
+            let (a, b) = (c.a, c.b);
+            if c.versions != (self.versions[a], self.versions[b])
+                || !self.can_collapse(a, b, c.target)
+            {
+                continue;
+            }
+            // b goes into a
+            self.positions[a] = c.target;
+            let q = self.quadrics[b];
+            self.quadrics[a].add(&q);
+            for f in std::mem::take(&mut self.vert_faces[b]) {
+                if !self.alive[f] {
+                    continue;
+                }
+                if self.faces[f].contains(&a) {
+                    self.alive[f] = false;
+                    faces -= 1;
+                    continue;
+                }
+                for u in self.faces[f].iter_mut() {
+                    if *u == b {
+                        *u = a;
+                    }
+                }
+                self.vert_faces[a].push(f);
+            }
+            self.vert_faces[a].retain(|&f| self.alive[f]);
+            self.versions[a] += 1;
+            self.versions[b] += 1;
+            for n in self.neighbours(a) {
+                self.push(a, n);
+            }
+        }
+    }
+}
+
+impl Model {
+    // quadric error metric edge collapses (Garland and Heckbert 1997) down to
+    // about `target_faces` triangles. Corners keep their uv and normal, open
+    // borders are kept in place, vertex colors and second uvs follow their vertex. The material
+    // isn't copied
+    pub fn simplify(&self, target_faces: usize) -> Self {
+        let mut simplifier = Simplifier::new(self);
+        simplifier.run(target_faces);
+        let mut vertex_colors = vec![0xffffffff; self.nverts()];
+        for f in 0..self.nfaces() {
+            for j in 0..3 {
+                vertex_colors[self.vert_index(f, j)] = self.color(f, j);
+            }
+        }
+        let mut remap = vec![usize::MAX; self.nverts()];
+        let mut verts = Vec::new();
+        let mut colors = Vec::new();
+        let mut uvs1 = Vec::new();
+        let (mut facet_vrt, mut facet_tex, mut facet_nrm) = (Vec::new(), Vec::new(), Vec::new());
+        for (f, face) in simplifier.faces.iter().enumerate() {
+            if !simplifier.alive[f] {
+                continue;
+            }
+            for (j, &v) in face.iter().enumerate() {
+                if remap[v] == usize::MAX {
+                    remap[v] = verts.len();
+                    let p = simplifier.positions[v];
+                    verts.push(Vector3::new(p[0] as f32, p[1] as f32, p[2] as f32));
+                    colors.push(vertex_colors[v]);
+                    if self.has_uv1() {
+                        uvs1.push(self.uv1(f, j));
+                    }
+                }
+                let (_, t, n) = self.corner(f, j);
+                facet_vrt.push(remap[v]);
+                facet_tex.push(t);
+                facet_nrm.push(n);
+            }
+        }
+        let mut model = Self::from_parts(
+            verts,
+            self.uvs().to_vec(),
+            self.normals().to_vec(),
+            facet_vrt,
+            facet_tex,
+            facet_nrm,
+        );
+        if self.has_colors() {
+            model.set_colors(colors);
+        }
+        if self.has_uv1() {
+            model.set_uv1(uvs1);
+        }
+        model
+    }
+    // `levels` models, each with about `ratio` of the triangles of the previous
+    // one, starting from a simplified copy of this model
+    pub fn lod_chain(&self, levels: usize, ratio: f32) -> Vec<Self> {
+        let mut chain: Vec<Self> = Vec::with_capacity(levels);
+        for _ in 0..levels {
+            let source = chain.last().unwrap_or(self);
+            let target = (source.nfaces() as f32 * ratio) as usize;
+            chain.push(source.simplify(target.max(1)));
+        }
+        chain
+    }
+}
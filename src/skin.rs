@@ -0,0 +1,30 @@
+use crate::{geometry::Matrix4, scene::Scene};
+
+// the joints of a skinned mesh. Joints are scene nodes, so the skeleton is posed
+// (and animated) like any other part of the hierarchy
+pub struct Skin {
+    pub name: String,
+    // node ids, joint j of the model's skin weights is `joints[j]`
+    pub joints: Vec<usize>,
+    // mesh space to the space of each joint in the bind pose
+    pub inverse_bind: Vec<Matrix4>,
+}
+
+impl Skin {
+    pub fn new(name: &str, joints: Vec<usize>, inverse_bind: Vec<Matrix4>) -> Self {
+        assert_eq!(joints.len(), inverse_bind.len());
+        Self {
+            name: name.to_string(),
+            joints,
+            inverse_bind,
+        }
+    }
+    // for `Model::skin`: bind pose to world with the joints where they are now
+    pub fn joint_matrices(&self, scene: &Scene) -> Vec<Matrix4> {
+        self.joints
+            .iter()
+            .zip(self.inverse_bind.iter())
+            .map(|(&joint, inverse_bind)| &scene.world_transform(joint) * inverse_bind)
+            .collect()
+    }
+}
@@ -0,0 +1,90 @@
+use crate::{
+    renderer::Renderer,
+    scene::{Camera, Scene},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StereoMode {
+    // red from the left eye, green and blue from the right, for red-cyan glasses
+    Anaglyph,
+    // each eye squeezed into half the width, left eye on the left, the way 3D
+    // TVs and headsets take a half side-by-side frame
+    SideBySide,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Stereo {
+    // distance between the eyes in world units, about 0.064 in meters
+    pub ipd: f32,
+    pub mode: StereoMode,
+}
+
+impl Stereo {
+    pub fn new(ipd: f32, mode: StereoMode) -> Self {
+        Self { ipd, mode }
+    }
+}
+
+impl Camera {
+    // the left and right eye, `ipd` apart across the view and both aimed at
+    // `center`, which ends up at the depth of the screen
+    pub fn eyes(&self, ipd: f32) -> (Camera, Camera) {
+        let forward = &self.center - &self.eye;
+        let right = forward.cross(&self.up).normalize() * (ipd / 2.0);
+        let eye = |offset| Camera {
+            eye: &self.eye + &offset,
+            ..self.clone()
+        };
+        (eye(-&right), eye(right.clone()))
+    }
+}
+
+impl Renderer<'_> {
+    // `draw_scene` once per eye, composited as `stereo.mode`
+    pub fn draw_scene_stereo(&mut self, scene: &Scene, camera: &Camera, stereo: &Stereo) {
+        self.draw_stereo(camera, stereo, |renderer, eye| {
+            renderer.draw_scene(scene, eye);
+        });
+    }
+    // `draw` renders the frame from the camera it is given, once per eye into a
+    // target of its own that is cleared to black first. The depth state, blend
+    // and transparency are the ones set here. The colors and depth of the
+    // composite replace what is drawn here
+    pub fn draw_stereo(
+        &mut self,
+        camera: &Camera,
+        stereo: &Stereo,
+        mut draw: impl FnMut(&mut Renderer, &Camera),
+    ) {
+        let width = match stereo.mode {
+            StereoMode::Anaglyph => self.width,
+            StereoMode::SideBySide => self.width / 2,
+        };
+        let size = (width * self.height) as usize;
+        let (left, right) = camera.eyes(stereo.ipd);
+        let mut eyes = [left, right].map(|eye| {
+            let mut buffer = vec![0xff000000; size];
+            let mut z_buffer = vec![self.depth.clear; size];
+            let mut renderer = Renderer::new(&mut buffer, &mut z_buffer, width, self.height);
+            renderer.depth = self.depth;
+            renderer.blend = self.blend;
+            renderer.transparency = self.transparency;
+            draw(&mut renderer, &eye);
+            (buffer, z_buffer)
+        });
+        match stereo.mode {
+            StereoMode::Anaglyph => {
+                let [(left, left_z), (right, _)] = &mut eyes;
+                for (pixel, right) in left.iter_mut().zip(right.iter()) {
+                    *pixel = 0xff000000 | (*pixel & 0xff) | (right & 0xffff00);
+                }
+                self.blit(0, 0, width, left, left_z);
+            }
+            StereoMode::SideBySide => {
+                let [(left, left_z), (right, right_z)] = &eyes;
+                self.blit(0, 0, width, left, left_z);
+                self.blit(width, 0, width, right, right_z);
+            }
+        }
+    }
+}
@@ -0,0 +1,122 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use crate::{
+    geometry::{Vector2, Vector3},
+    model::Model,
+};
+
+impl Model {
+    // binary or ascii stl, one flat normal per facet and no texture coordinates
+    pub fn from_stl(path: impl AsRef<Path>) -> Self {
+        let bytes = fs::read(path).unwrap();
+        let triangles = if is_binary_stl(&bytes) {
+            parse_binary(&bytes)
+        } else {
+            parse_ascii(std::str::from_utf8(&bytes).unwrap())
+        };
+
+        // stl repeats every corner, weld identical positions back together
+        let mut verts = Vec::new();
+        let mut norms = Vec::with_capacity(triangles.len());
+        let mut facet_vrt = Vec::with_capacity(triangles.len() * 3);
+        let mut facet_nrm = Vec::with_capacity(triangles.len() * 3);
+        let mut welded: HashMap<[u32; 3], usize> = HashMap::new();
+        for (normal, corners) in triangles {
+            let computed = (&corners[1] - &corners[0]).cross(&(&corners[2] - &corners[0]));
+            // plenty of exporters write zero normals
+            let normal = if normal.length_square() > 0.0 {
+                normal.normalize()
+            } else if computed.length_square() > 0.0 {
+                computed.normalize()
+            } else {
+                Vector3::new(0.0, 0.0, 1.0)
+            };
+            norms.push(normal);
+            for v in corners {
+                let key = [v.x().to_bits(), v.y().to_bits(), v.z().to_bits()];
+                let index = *welded.entry(key).or_insert_with(|| {
+                    verts.push(v);
+                    verts.len() - 1
+                });
+                facet_vrt.push(index);
+                facet_nrm.push(norms.len() - 1);
+            }
+        }
+        eprintln!(
+            "# v# {} f# {} vn# {}",
+            verts.len(),
+            facet_vrt.len() / 3,
+            norms.len()
+        );
+        let facet_tex = vec![0; facet_vrt.len()];
+        Self::from_parts(
+            verts,
+            vec![Vector2::zero()],
+            norms,
+            facet_vrt,
+            facet_tex,
+            facet_nrm,
+        )
+    }
+}
+
+// ascii files start with "solid" too, but binary ones say exactly how long they are
+fn is_binary_stl(bytes: &[u8]) -> bool {
+    if bytes.len() < 84 {
+        return false;
+    }
+    let count = u32::from_le_bytes(bytes[80..84].try_into().unwrap()) as usize;
+    bytes.len() == 84 + count * 50 || !bytes.starts_with(b"solid")
+}
+
+fn parse_binary(bytes: &[u8]) -> Vec<(Vector3, [Vector3; 3])> {
+    // a truncated file keeps the triangles it has whole records for
+    let count = u32::from_le_bytes(bytes[80..84].try_into().unwrap()) as usize;
+    let count = count.min((bytes.len() - 84) / 50);
+    let read_vector = |offset: usize| {
+        let f = |i: usize| {
+            let at = offset + i * 4;
+            f32::from_le_bytes(bytes[at..at + 4].try_into().unwrap())
+        };
+        Vector3::new(f(0), f(1), f(2))
+    };
+    (0..count)
+        .map(|i| {
+            // normal, 3 corners, 2 bytes of attributes
+            let offset = 84 + i * 50;
+            let normal = read_vector(offset);
+            let corners = [1, 2, 3].map(|j| read_vector(offset + j * 12));
+            (normal, corners)
+        })
+        .collect()
+}
+
+fn parse_ascii(text: &str) -> Vec<(Vector3, [Vector3; 3])> {
+    let mut triangles = Vec::new();
+    let mut normal = Vector3::zero();
+    let mut corners = Vec::with_capacity(3);
+    for line in text.lines() {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        let vector = |start: usize| {
+            let mut v = Vector3::zero();
+            for i in 0..3 {
+                v[i] = parts[start + i].parse().unwrap();
+            }
+            v
+        };
+        match parts.first() {
+            Some(&"facet") => {
+                normal = vector(2);
+                corners.clear();
+            }
+            Some(&"vertex") => corners.push(vector(1)),
+            Some(&"endfacet") => {
+                assert_eq!(corners.len(), 3, "stl facets must be triangles");
+                let corners: [Vector3; 3] = std::mem::take(&mut corners).try_into().unwrap();
+                triangles.push((normal.clone(), corners));
+            }
+            _ => {}
+        }
+    }
+    triangles
+}
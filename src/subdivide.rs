@@ -0,0 +1,252 @@
+use std::{collections::HashMap, f32::consts::PI};
+
+use crate::{
+    geometry::{Vector2, Vector3},
+    model::Model,
+};
+
+// the parts of a mesh loop subdivision carries along, uvs by their own indices so
+// seams stay seams
+struct Mesh {
+    verts: Vec<Vector3>,
+    uvs: Vec<Vector2>,
+    colors: Vec<u32>,
+    // per vertex like the colors, empty if the model has none
+    uvs1: Vec<Vector2>,
+    faces: Vec<[usize; 3]>,
+    uv_faces: Vec<[usize; 3]>,
+}
+
+fn edge_key(a: usize, b: usize) -> (usize, usize) {
+    (a.min(b), a.max(b))
+}
+
+fn mix_colors(a: u32, b: u32) -> u32 {
+    (0..4).fold(0, |pixel, i| {
+        let ca = (a >> (8 * i)) & 0xff;
+        let cb = (b >> (8 * i)) & 0xff;
+        pixel | (ca + cb).div_ceil(2) << (8 * i)
+    })
+}
+
+impl Mesh {
+    // the faces each edge borders, with the corner opposite to it
+    fn edges(&self) -> HashMap<(usize, usize), Vec<usize>> {
+        let mut edges: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+        for face in self.faces.iter() {
+            for k in 0..3 {
+                edges
+                    .entry(edge_key(face[k], face[(k + 1) % 3]))
+                    .or_default()
+                    .push(face[(k + 2) % 3]);
+            }
+        }
+        edges
+    }
+    // one level: every triangle splits in four, new edge points weigh in the two
+    // opposite corners and old points move towards their neighbours
+    fn refine(&self) -> Mesh {
+        let edges = self.edges();
+        let n = self.verts.len();
+        let mut neighbours: Vec<Vec<usize>> = vec![Vec::new(); n];
+        let mut border: Vec<Vec<usize>> = vec![Vec::new(); n];
+        for (&(a, b), opposite) in edges.iter() {
+            neighbours[a].push(b);
+            neighbours[b].push(a);
+            if opposite.len() == 1 {
+                border[a].push(b);
+                border[b].push(a);
+            }
+        }
+        let mut verts: Vec<Vector3> = (0..n)
+            .map(|v| {
+                let p = &self.verts[v];
+                if border[v].len() == 2 {
+                    // creases along open borders only follow the border
+                    p * 0.75 + (&self.verts[border[v][0]] + &self.verts[border[v][1]]) * 0.125
+                } else if !border[v].is_empty() || neighbours[v].len() < 3 {
+                    p.clone()
+                } else {
+                    let k = neighbours[v].len() as f32;
+                    let c = 0.375 + 0.25 * (2.0 * PI / k).cos();
+                    let beta = (0.625 - c * c) / k;
+                    let sum = neighbours[v]
+                        .iter()
+                        .fold(Vector3::zero(), |sum, &u| sum + &self.verts[u]);
+                    p * (1.0 - k * beta) + sum * beta
+                }
+            })
+            .collect();
+        let mut colors = self.colors.clone();
+        let mut uvs1 = self.uvs1.clone();
+        let mut edge_points = HashMap::with_capacity(edges.len());
+        for (&(a, b), opposite) in edges.iter() {
+            let ends = &self.verts[a] + &self.verts[b];
+            let p = if opposite.len() == 2 {
+                ends * 0.375 + (&self.verts[opposite[0]] + &self.verts[opposite[1]]) * 0.125
+            } else {
+                ends * 0.5
+            };
+            verts.push(p);
+            if !self.colors.is_empty() {
+                colors.push(mix_colors(self.colors[a], self.colors[b]));
+            }
+            if !self.uvs1.is_empty() {
+                uvs1.push((&self.uvs1[a] + &self.uvs1[b]) * 0.5);
+            }
+            edge_points.insert((a, b), verts.len() - 1);
+        }
+        // uvs are interpolated, not smoothed
+        let mut uvs = self.uvs.clone();
+        let mut uv_points = HashMap::new();
+        let mut uv_point = |a: usize, b: usize| {
+            *uv_points.entry(edge_key(a, b)).or_insert_with(|| {
+                uvs.push((&self.uvs[a] + &self.uvs[b]) * 0.5);
+                uvs.len() - 1
+            })
+        };
+        let mut faces = Vec::with_capacity(self.faces.len() * 4);
+        let mut uv_faces = Vec::with_capacity(self.faces.len() * 4);
+        for (face, uv) in self.faces.iter().zip(self.uv_faces.iter()) {
+            let [a, b, c] = *face;
+            let [ab, bc, ca] = [(a, b), (b, c), (c, a)].map(|(u, v)| edge_points[&edge_key(u, v)]);
+            let [ta, tb, tc] = *uv;
+            let [tab, tbc, tca] = [(ta, tb), (tb, tc), (tc, ta)].map(|(u, v)| uv_point(u, v));
+            faces.extend([[a, ab, ca], [ab, b, bc], [ca, bc, c], [ab, bc, ca]]);
+            uv_faces.extend([
+                [ta, tab, tca],
+                [tab, tb, tbc],
+                [tca, tbc, tc],
+                [tab, tbc, tca],
+            ]);
+        }
+        Mesh {
+            verts,
+            uvs,
+            colors,
+            uvs1,
+            faces,
+            uv_faces,
+        }
+    }
+    // normals of the limit surface: tangents from the one ring weighted by
+    // cos and sin around it. Border and irregular rings get the area weighted
+    // average of their faces
+    fn limit_normals(&self) -> Vec<Vector3> {
+        let mut average = vec![Vector3::zero(); self.verts.len()];
+        // the ring of each vertex as directed edges, the faces wind around it
+        let mut next: Vec<HashMap<usize, usize>> = vec![HashMap::new(); self.verts.len()];
+        for &[a, b, c] in self.faces.iter() {
+            let n = (&self.verts[b] - &self.verts[a]).cross(&(&self.verts[c] - &self.verts[a]));
+            for (v, from, to) in [(a, b, c), (b, c, a), (c, a, b)] {
+                average[v] = &average[v] + &n;
+                next[v].insert(from, to);
+            }
+        }
+        (0..self.verts.len())
+            .map(|v| {
+                let fallback = if average[v].length_square() > 0.0 {
+                    average[v].normalize()
+                } else {
+                    Vector3::new(0.0, 0.0, 1.0)
+                };
+                let Some(&start) = next[v].keys().next() else {
+                    return fallback;
+                };
+                let mut ring = vec![start];
+                while let Some(&u) = next[v].get(ring.last().unwrap()) {
+                    if u == start || ring.len() > next[v].len() {
+                        break;
+                    }
+                    ring.push(u);
+                }
+                // open or tangled rings
+                if ring.len() != next[v].len() || next[v].get(ring.last().unwrap()) != Some(&start)
+                {
+                    return fallback;
+                }
+                let k = ring.len() as f32;
+                let (mut t1, mut t2) = (Vector3::zero(), Vector3::zero());
+                for (i, &u) in ring.iter().enumerate() {
+                    let angle = 2.0 * PI * i as f32 / k;
+                    t1 = t1 + &self.verts[u] * angle.cos();
+                    t2 = t2 + &self.verts[u] * angle.sin();
+                }
+                let n = t1.cross(&t2);
+                if n.length_square() == 0.0 {
+                    fallback
+                } else if n.dot(&fallback) < 0.0 {
+                    -n.normalize()
+                } else {
+                    n.normalize()
+                }
+            })
+            .collect()
+    }
+}
+
+impl Model {
+    // `levels` rounds of loop subdivision (Loop 1987), each splitting every
+    // triangle in four, with normals of the limit surface. Expects shared
+    // vertices (see `weld_vertices`); open borders are kept as creases and uvs and
+    // vertex colors are interpolated. Authored normals and skins are dropped
+    pub fn subdivide(&mut self, levels: usize) {
+        if levels == 0 {
+            return;
+        }
+        let faces = (0..self.nfaces())
+            .map(|i| [0, 1, 2].map(|j| self.vert_index(i, j)))
+            .collect();
+        let uv_faces = (0..self.nfaces())
+            .map(|i| [0, 1, 2].map(|j| self.corner(i, j).1))
+            .collect();
+        let mut colors = Vec::new();
+        if self.has_colors() {
+            colors = vec![0xffffffff; self.nverts()];
+            for i in 0..self.nfaces() {
+                for j in 0..3 {
+                    colors[self.vert_index(i, j)] = self.color(i, j);
+                }
+            }
+        }
+        let mut uvs1 = Vec::new();
+        if self.has_uv1() {
+            uvs1 = vec![Vector2::zero(); self.nverts()];
+            for i in 0..self.nfaces() {
+                for j in 0..3 {
+                    uvs1[self.vert_index(i, j)] = self.uv1(i, j);
+                }
+            }
+        }
+        let mut mesh = Mesh {
+            verts: self.verts().to_vec(),
+            uvs: self.uvs().to_vec(),
+            colors,
+            uvs1,
+            faces,
+            uv_faces,
+        };
+        for _ in 0..levels {
+            mesh = mesh.refine();
+        }
+        let normals = mesh.limit_normals();
+        let facet_vrt: Vec<usize> = mesh.faces.into_iter().flatten().collect();
+        let facet_tex = mesh.uv_faces.into_iter().flatten().collect();
+        let mut model = Self::from_parts(
+            mesh.verts,
+            mesh.uvs,
+            normals,
+            facet_vrt.clone(),
+            facet_tex,
+            facet_vrt,
+        );
+        if !mesh.colors.is_empty() {
+            model.set_colors(mesh.colors);
+        }
+        if !mesh.uvs1.is_empty() {
+            model.set_uv1(mesh.uvs1);
+        }
+        model.set_material(std::mem::take(self.material_mut()));
+        *self = model;
+    }
+}
@@ -0,0 +1,134 @@
+use crate::{
+    geometry::{Vector2, Vector3},
+    image::Image,
+    model::Model,
+};
+
+// a landscape from a grayscale heightmap: the image lies on the xz plane
+// centered on the origin, its top row towards -z, and the red channel lifts each
+// texel from 0 up to `height`. Coarser levels keep every 2^level-th row and
+// column, plus the last ones so the borders stay where they are
+pub struct Terrain {
+    pub size: f32,
+    pub height: f32,
+    // each level doubles the distance the next coarser one takes over at
+    pub lod_distance: f32,
+    width: u32,
+    depth: u32,
+    heights: Vec<f32>,
+    levels: Vec<Model>,
+}
+
+impl Terrain {
+    // `size` is the side along x, z is scaled to keep the texels square
+    pub fn new(heightmap: &Image, size: f32, height: f32, levels: usize) -> Self {
+        assert!(heightmap.width >= 2 && heightmap.height >= 2);
+        let heights = heightmap
+            .buffer
+            .iter()
+            .map(|pixel| (pixel & 0xff) as f32 / 255.0 * height)
+            .collect();
+        let mut terrain = Self {
+            size,
+            height,
+            lod_distance: size,
+            width: heightmap.width,
+            depth: heightmap.height,
+            heights,
+            levels: Vec::new(),
+        };
+        terrain.levels = (0..levels.max(1)).map(|l| terrain.build(1 << l)).collect();
+        terrain
+    }
+    pub fn levels(&self) -> usize {
+        self.levels.len()
+    }
+    pub fn model(&self, level: usize) -> &Model {
+        &self.levels[level.min(self.levels.len() - 1)]
+    }
+    // the level to draw with the eye `distance` away from the terrain's center
+    pub fn level_for(&self, distance: f32) -> usize {
+        let mut level = 0;
+        let mut threshold = self.lod_distance;
+        while distance > threshold && level + 1 < self.levels.len() {
+            level += 1;
+            threshold *= 2.0;
+        }
+        level
+    }
+    fn spacing(&self) -> f32 {
+        self.size / (self.width - 1) as f32
+    }
+    fn texel(&self, x: u32, y: u32) -> f32 {
+        self.heights[(x.min(self.width - 1) + y.min(self.depth - 1) * self.width) as usize]
+    }
+    // world position of texel (x, y)
+    fn position(&self, x: u32, y: u32) -> Vector3 {
+        let spacing = self.spacing();
+        Vector3::new(
+            x as f32 * spacing - self.size / 2.0,
+            self.texel(x, y),
+            y as f32 * spacing - (self.depth - 1) as f32 * spacing / 2.0,
+        )
+    }
+    // bilinear height of the full resolution surface under (x, z), clamped to the
+    // edges, e.g. to keep a fly-through camera above the ground
+    pub fn height_at(&self, x: f32, z: f32) -> f32 {
+        let spacing = self.spacing();
+        let fx = ((x + self.size / 2.0) / spacing).clamp(0.0, (self.width - 1) as f32);
+        let fy = (z / spacing + (self.depth - 1) as f32 / 2.0).clamp(0.0, (self.depth - 1) as f32);
+        let (x0, y0) = (fx.floor() as u32, fy.floor() as u32);
+        let (tx, ty) = (fx - x0 as f32, fy - y0 as f32);
+        let top = self.texel(x0, y0) * (1.0 - tx) + self.texel(x0 + 1, y0) * tx;
+        let bottom = self.texel(x0, y0 + 1) * (1.0 - tx) + self.texel(x0 + 1, y0 + 1) * tx;
+        top * (1.0 - ty) + bottom * ty
+    }
+    fn build(&self, step: u32) -> Model {
+        let samples = |n: u32| {
+            let mut samples: Vec<u32> = (0..n - 1).step_by(step as usize).collect();
+            samples.push(n - 1);
+            samples
+        };
+        let (xs, ys) = (samples(self.width), samples(self.depth));
+        let mut verts = Vec::with_capacity(xs.len() * ys.len());
+        let mut tex_coord = Vec::with_capacity(verts.capacity());
+        let mut norms = Vec::with_capacity(verts.capacity());
+        for &y in ys.iter() {
+            for &x in xs.iter() {
+                verts.push(self.position(x, y));
+                // v = 0 is the bottom row of the image like the material maps
+                tex_coord.push(Vector2::new(
+                    x as f32 / (self.width - 1) as f32,
+                    1.0 - y as f32 / (self.depth - 1) as f32,
+                ));
+                // central differences over the spacing of this level
+                let (l, r) = (x.saturating_sub(step), (x + step).min(self.width - 1));
+                let (t, b) = (y.saturating_sub(step), (y + step).min(self.depth - 1));
+                let spacing = self.spacing();
+                let dx = (self.texel(r, y) - self.texel(l, y)) / ((r - l) as f32 * spacing);
+                let dz = (self.texel(x, b) - self.texel(x, t)) / ((b - t) as f32 * spacing);
+                norms.push(Vector3::new(-dx, 1.0, -dz).normalize());
+            }
+        }
+        let cols = xs.len();
+        let mut facets = Vec::with_capacity((cols - 1) * (ys.len() - 1) * 6);
+        for r in 0..ys.len() - 1 {
+            for c in 0..cols - 1 {
+                let a = c + r * cols;
+                let b = a + 1;
+                let d = a + cols;
+                let e = d + 1;
+                // counter-clockwise seen from above
+                facets.extend([a, d, b, b, d, e]);
+            }
+        }
+        Model::from_parts(
+            verts,
+            tex_coord,
+            norms,
+            facets.clone(),
+            facets.clone(),
+            facets,
+        )
+    }
+}
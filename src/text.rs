@@ -0,0 +1,840 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufRead, BufReader},
+    path::Path,
+};
+
+use lazy_static::lazy_static;
+
+use crate::renderer::Renderer;
+#[cfg(feature = "model")]
+use crate::{
+    geometry::{m2v, v2m, Matrix3, Vector3},
+    scene::Camera,
+};
+
+// metrics of the builtin font
+pub const GLYPH_WIDTH: u32 = 5;
+pub const GLYPH_HEIGHT: u32 = 8;
+// one column of spacing between glyphs
+pub const ADVANCE: u32 = GLYPH_WIDTH + 1;
+
+// printable ascii (0x20..=0x7e), one byte per column, bit 0 is the top row
+#[rustfmt::skip]
+const FONT: [[u8; 5]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00], [0x00, 0x00, 0x5f, 0x00, 0x00], [0x00, 0x07, 0x00, 0x07, 0x00],
+    [0x14, 0x7f, 0x14, 0x7f, 0x14], [0x24, 0x2a, 0x7f, 0x2a, 0x12], [0x23, 0x13, 0x08, 0x64, 0x62],
+    [0x36, 0x49, 0x56, 0x20, 0x50], [0x00, 0x08, 0x07, 0x03, 0x00], [0x00, 0x1c, 0x22, 0x41, 0x00],
+    [0x00, 0x41, 0x22, 0x1c, 0x00], [0x2a, 0x1c, 0x7f, 0x1c, 0x2a], [0x08, 0x08, 0x3e, 0x08, 0x08],
+    [0x00, 0x80, 0x70, 0x30, 0x00], [0x08, 0x08, 0x08, 0x08, 0x08], [0x00, 0x00, 0x60, 0x60, 0x00],
+    [0x20, 0x10, 0x08, 0x04, 0x02], [0x3e, 0x51, 0x49, 0x45, 0x3e], [0x00, 0x42, 0x7f, 0x40, 0x00],
+    [0x72, 0x49, 0x49, 0x49, 0x46], [0x21, 0x41, 0x49, 0x4d, 0x33], [0x18, 0x14, 0x12, 0x7f, 0x10],
+    [0x27, 0x45, 0x45, 0x45, 0x39], [0x3c, 0x4a, 0x49, 0x49, 0x31], [0x41, 0x21, 0x11, 0x09, 0x07],
+    [0x36, 0x49, 0x49, 0x49, 0x36], [0x46, 0x49, 0x49, 0x29, 0x1e], [0x00, 0x00, 0x14, 0x00, 0x00],
+    [0x00, 0x40, 0x34, 0x00, 0x00], [0x00, 0x08, 0x14, 0x22, 0x41], [0x14, 0x14, 0x14, 0x14, 0x14],
+    [0x00, 0x41, 0x22, 0x14, 0x08], [0x02, 0x01, 0x59, 0x09, 0x06], [0x3e, 0x41, 0x5d, 0x59, 0x4e],
+    [0x7c, 0x12, 0x11, 0x12, 0x7c], [0x7f, 0x49, 0x49, 0x49, 0x36], [0x3e, 0x41, 0x41, 0x41, 0x22],
+    [0x7f, 0x41, 0x41, 0x41, 0x3e], [0x7f, 0x49, 0x49, 0x49, 0x41], [0x7f, 0x09, 0x09, 0x09, 0x01],
+    [0x3e, 0x41, 0x41, 0x51, 0x73], [0x7f, 0x08, 0x08, 0x08, 0x7f], [0x00, 0x41, 0x7f, 0x41, 0x00],
+    [0x20, 0x40, 0x41, 0x3f, 0x01], [0x7f, 0x08, 0x14, 0x22, 0x41], [0x7f, 0x40, 0x40, 0x40, 0x40],
+    [0x7f, 0x02, 0x1c, 0x02, 0x7f], [0x7f, 0x04, 0x08, 0x10, 0x7f], [0x3e, 0x41, 0x41, 0x41, 0x3e],
+    [0x7f, 0x09, 0x09, 0x09, 0x06], [0x3e, 0x41, 0x51, 0x21, 0x5e], [0x7f, 0x09, 0x19, 0x29, 0x46],
+    [0x26, 0x49, 0x49, 0x49, 0x32], [0x03, 0x01, 0x7f, 0x01, 0x03], [0x3f, 0x40, 0x40, 0x40, 0x3f],
+    [0x1f, 0x20, 0x40, 0x20, 0x1f], [0x3f, 0x40, 0x38, 0x40, 0x3f], [0x63, 0x14, 0x08, 0x14, 0x63],
+    [0x03, 0x04, 0x78, 0x04, 0x03], [0x61, 0x59, 0x49, 0x4d, 0x43], [0x00, 0x7f, 0x41, 0x41, 0x41],
+    [0x02, 0x04, 0x08, 0x10, 0x20], [0x00, 0x41, 0x41, 0x41, 0x7f], [0x04, 0x02, 0x01, 0x02, 0x04],
+    [0x40, 0x40, 0x40, 0x40, 0x40], [0x00, 0x03, 0x07, 0x08, 0x00], [0x20, 0x54, 0x54, 0x78, 0x40],
+    [0x7f, 0x28, 0x44, 0x44, 0x38], [0x38, 0x44, 0x44, 0x44, 0x28], [0x38, 0x44, 0x44, 0x28, 0x7f],
+    [0x38, 0x54, 0x54, 0x54, 0x18], [0x00, 0x08, 0x7e, 0x09, 0x02], [0x18, 0xa4, 0xa4, 0x9c, 0x78],
+    [0x7f, 0x08, 0x04, 0x04, 0x78], [0x00, 0x44, 0x7d, 0x40, 0x00], [0x20, 0x40, 0x40, 0x3d, 0x00],
+    [0x7f, 0x10, 0x28, 0x44, 0x00], [0x00, 0x41, 0x7f, 0x40, 0x00], [0x7c, 0x04, 0x78, 0x04, 0x78],
+    [0x7c, 0x08, 0x04, 0x04, 0x78], [0x38, 0x44, 0x44, 0x44, 0x38], [0xfc, 0x18, 0x24, 0x24, 0x18],
+    [0x18, 0x24, 0x24, 0x18, 0xfc], [0x7c, 0x08, 0x04, 0x04, 0x08], [0x48, 0x54, 0x54, 0x54, 0x24],
+    [0x04, 0x04, 0x3f, 0x44, 0x24], [0x3c, 0x40, 0x40, 0x20, 0x7c], [0x1c, 0x20, 0x40, 0x20, 0x1c],
+    [0x3c, 0x40, 0x30, 0x40, 0x3c], [0x44, 0x28, 0x10, 0x28, 0x44], [0x4c, 0x90, 0x90, 0x90, 0x7c],
+    [0x44, 0x64, 0x54, 0x4c, 0x44], [0x00, 0x08, 0x36, 0x41, 0x00], [0x00, 0x00, 0x77, 0x00, 0x00],
+    [0x00, 0x41, 0x36, 0x08, 0x00], [0x02, 0x01, 0x02, 0x04, 0x02],
+];
+
+// combining marks for the builtin font, drawn like FONT but with no advance
+const MARKS: [(char, [u8; 5]); 7] = [
+    ('\u{300}', [0x00, 0x01, 0x02, 0x00, 0x00]),
+    ('\u{301}', [0x00, 0x00, 0x02, 0x01, 0x00]),
+    ('\u{302}', [0x00, 0x02, 0x01, 0x02, 0x00]),
+    ('\u{303}', [0x02, 0x01, 0x02, 0x01, 0x00]),
+    ('\u{308}', [0x00, 0x01, 0x00, 0x01, 0x00]),
+    ('\u{30a}', [0x00, 0x02, 0x05, 0x02, 0x00]),
+    ('\u{327}', [0x00, 0x00, 0x80, 0x40, 0x00]),
+];
+
+// precomposed latin letters a font doesn't have are drawn as base + mark
+const DECOMPOSITIONS: [(&str, &str, char); 7] = [
+    ("ÀÈÌÒÙàèìòù", "AEIOUaeiou", '\u{300}'),
+    ("ÁÉÍÓÚÝáéíóúý", "AEIOUYaeiouy", '\u{301}'),
+    ("ÂÊÎÔÛâêîôû", "AEIOUaeiou", '\u{302}'),
+    ("ÃÑÕãñõ", "ANOano", '\u{303}'),
+    ("ÄËÏÖÜäëïöüÿ", "AEIOUaeiouy", '\u{308}'),
+    ("Åå", "Aa", '\u{30a}'),
+    ("Çç", "Cc", '\u{327}'),
+];
+
+fn decompose(c: char) -> Option<(char, char)> {
+    DECOMPOSITIONS.iter().find_map(|(composed, bases, mark)| {
+        let i = composed.chars().position(|x| x == c)?;
+        Some((bases.chars().nth(i).unwrap(), *mark))
+    })
+}
+
+pub fn is_combining(c: char) -> bool {
+    matches!(c,
+        '\u{300}'..='\u{36f}'
+        | '\u{1ab0}'..='\u{1aff}'
+        | '\u{1dc0}'..='\u{1dff}'
+        | '\u{20d0}'..='\u{20ff}'
+        | '\u{fe20}'..='\u{fe2f}')
+}
+
+// marks hanging under the base rather than above it
+fn is_below_mark(c: char) -> bool {
+    matches!(c, '\u{316}'..='\u{319}' | '\u{31c}'..='\u{333}' | '\u{339}'..='\u{33c}')
+}
+
+// scripts written without spaces, a line may break between any two of these
+fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{2e80}'..='\u{9fff}'
+        | '\u{f900}'..='\u{faff}'
+        | '\u{ff00}'..='\u{ffef}'
+        | '\u{20000}'..='\u{2fa1f}')
+}
+
+pub struct Glyph {
+    pub width: u32,
+    pub height: u32,
+    // pen movement after the glyph, 0 for combining marks
+    pub advance: u32,
+    pub x_offset: i32,
+    // rows above the baseline
+    pub top: i32,
+    // row-major, `width` x `height`
+    bits: Vec<bool>,
+}
+
+impl Glyph {
+    pub fn new(
+        width: u32,
+        height: u32,
+        advance: u32,
+        x_offset: i32,
+        top: i32,
+        bits: Vec<bool>,
+    ) -> Self {
+        assert_eq!(bits.len(), (width * height) as usize);
+        Self {
+            width,
+            height,
+            advance,
+            x_offset,
+            top,
+            bits,
+        }
+    }
+    // column-major bytes, bit 0 is the top row, like the builtin table
+    fn from_columns(columns: &[u8; 5], advance: u32) -> Self {
+        let mut bits = vec![false; (GLYPH_WIDTH * GLYPH_HEIGHT) as usize];
+        for (col, column) in columns.iter().enumerate() {
+            for row in 0..GLYPH_HEIGHT as usize {
+                bits[col + row * GLYPH_WIDTH as usize] = column & (1 << row) != 0;
+            }
+        }
+        Self::new(
+            GLYPH_WIDTH,
+            GLYPH_HEIGHT,
+            advance,
+            0,
+            GLYPH_HEIGHT as i32 - 1,
+            bits,
+        )
+    }
+    #[inline]
+    pub fn pixel(&self, x: u32, y: u32) -> bool {
+        self.bits[(x + y * self.width) as usize]
+    }
+    // first and last rows/columns with any pixel set
+    fn ink_rows(&self) -> Option<(i32, i32)> {
+        let rows: Vec<u32> = (0..self.height)
+            .filter(|&y| (0..self.width).any(|x| self.pixel(x, y)))
+            .collect();
+        Some((*rows.first()? as i32, *rows.last()? as i32))
+    }
+    fn ink_cols(&self) -> Option<(i32, i32)> {
+        let cols: Vec<u32> = (0..self.width)
+            .filter(|&x| (0..self.height).any(|y| self.pixel(x, y)))
+            .collect();
+        Some((*cols.first()? as i32, *cols.last()? as i32))
+    }
+}
+
+// glyphs looked up by codepoint
+pub struct BitmapFont {
+    glyphs: HashMap<char, Glyph>,
+    pub ascent: i32,
+    pub descent: i32,
+}
+
+impl BitmapFont {
+    pub fn new(ascent: i32, descent: i32) -> Self {
+        Self {
+            glyphs: HashMap::new(),
+            ascent,
+            descent,
+        }
+    }
+    // printable ascii and a few combining marks, 5x7 with one row of descender
+    pub fn builtin() -> Self {
+        let mut font = Self::new(GLYPH_HEIGHT as i32 - 1, 1);
+        for (i, columns) in FONT.iter().enumerate() {
+            let c = char::from(0x20 + i as u8);
+            font.insert(c, Glyph::from_columns(columns, ADVANCE));
+        }
+        for (c, columns) in MARKS.iter() {
+            font.insert(*c, Glyph::from_columns(columns, 0));
+        }
+        font
+    }
+    // X11 bitmap distribution format, e.g. GNU Unifont or WenQuanYi for CJK
+    pub fn from_bdf(path: impl AsRef<Path>) -> Self {
+        let file = File::open(path).unwrap();
+        let file = BufReader::new(file);
+        let mut font = Self::new(0, 0);
+        let mut font_box = (0, 0, 0, 0);
+        let mut encoding = -1;
+        let mut advance = 0;
+        let mut bbx = (0, 0, 0, 0);
+        let mut bitmap: Option<Vec<bool>> = None;
+
+        for line in file.lines().map(|line| line.unwrap()) {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            if parts.is_empty() {
+                continue;
+            }
+            if let Some(bits) = bitmap.as_mut() {
+                if parts[0] != "ENDCHAR" {
+                    let row = parts[0];
+                    for x in 0..bbx.0 as usize {
+                        let byte = u8::from_str_radix(&row[x / 8 * 2..x / 8 * 2 + 2], 16).unwrap();
+                        bits.push(byte & (0x80 >> (x % 8)) != 0);
+                    }
+                    continue;
+                }
+                let bits = bitmap.take().unwrap();
+                if let Some(c) = u32::try_from(encoding).ok().and_then(char::from_u32) {
+                    let (width, height, x_offset, y_offset) = bbx;
+                    let glyph = Glyph::new(
+                        width,
+                        height,
+                        advance,
+                        x_offset,
+                        y_offset + height as i32,
+                        bits,
+                    );
+                    font.insert(c, glyph);
+                }
+                continue;
+            }
+            let int = |i: usize| parts[i].parse::<i32>().unwrap();
+            match parts[0] {
+                "FONTBOUNDINGBOX" => font_box = (int(1), int(2), int(3), int(4)),
+                "FONT_ASCENT" => font.ascent = int(1),
+                "FONT_DESCENT" => font.descent = int(1),
+                "STARTCHAR" => {
+                    encoding = -1;
+                    advance = font_box.0 as u32;
+                    bbx = (font_box.0 as u32, font_box.1 as u32, font_box.2, font_box.3);
+                }
+                "ENCODING" => encoding = int(1),
+                "DWIDTH" => advance = int(1).max(0) as u32,
+                "BBX" => bbx = (int(1) as u32, int(2) as u32, int(3), int(4)),
+                "BITMAP" => bitmap = Some(Vec::with_capacity((bbx.0 * bbx.1) as usize)),
+                _ => {}
+            }
+        }
+        if font.ascent == 0 && font.descent == 0 {
+            font.ascent = font_box.1 + font_box.3;
+            font.descent = -font_box.3;
+        }
+        font
+    }
+    pub fn insert(&mut self, c: char, glyph: Glyph) {
+        self.glyphs.insert(c, glyph);
+    }
+    pub fn glyph(&self, c: char) -> Option<&Glyph> {
+        self.glyphs.get(&c)
+    }
+}
+
+// a primary font followed by fallbacks, searched in order for every codepoint
+pub struct FontStack {
+    fonts: Vec<BitmapFont>,
+}
+
+impl FontStack {
+    pub fn new(primary: BitmapFont) -> Self {
+        Self {
+            fonts: vec![primary],
+        }
+    }
+    pub fn push_fallback(&mut self, font: BitmapFont) {
+        self.fonts.push(font);
+    }
+    pub fn glyph(&self, c: char) -> Option<&Glyph> {
+        self.fonts.iter().find_map(|font| font.glyph(c))
+    }
+    // drawn for codepoints no font covers
+    pub fn replacement(&self) -> &Glyph {
+        self.glyph('\u{fffd}').or_else(|| self.glyph('?')).unwrap()
+    }
+    pub fn ascent(&self) -> i32 {
+        self.fonts.iter().map(|font| font.ascent).max().unwrap()
+    }
+    pub fn descent(&self) -> i32 {
+        self.fonts.iter().map(|font| font.descent).max().unwrap()
+    }
+}
+
+lazy_static! {
+    static ref DEFAULT_FONT: FontStack = FontStack::new(BitmapFont::builtin());
+}
+
+pub fn default_font() -> &'static FontStack {
+    &DEFAULT_FONT
+}
+
+// how `Renderer::draw_label_3d` places a label
+#[derive(Clone, Debug)]
+pub struct LabelOptions {
+    // pixels from the projected point to the top left corner of the text
+    pub offset: (i32, i32),
+    // hide the parts of the label behind what is already drawn, the label itself
+    // never writes depth
+    pub depth_test: bool,
+    pub scale: u32,
+}
+
+impl Default for LabelOptions {
+    fn default() -> Self {
+        Self {
+            offset: (0, 0),
+            depth_test: false,
+            scale: 1,
+        }
+    }
+}
+
+impl Renderer<'_> {
+    // (x, y) is the top left corner of the bitmap, each font pixel becomes a
+    // `scale` x `scale` block
+    pub fn draw_glyph(&mut self, x: i32, y: i32, glyph: &Glyph, pixel: u32, scale: u32) {
+        self.draw_glyph_at_depth(x, y, glyph, pixel, scale, None);
+    }
+    // with a depth, pixels failing the depth test against it are skipped
+    fn draw_glyph_at_depth(
+        &mut self,
+        x: i32,
+        y: i32,
+        glyph: &Glyph,
+        pixel: u32,
+        scale: u32,
+        depth: Option<f32>,
+    ) {
+        let scale = scale.max(1);
+        if self.has_transform_2d() {
+            self.draw_glyph_transformed(x, y, glyph, pixel, scale, depth);
+            return;
+        }
+        for row in 0..glyph.height {
+            for col in 0..glyph.width {
+                if !glyph.pixel(col, row) {
+                    continue;
+                }
+                let px = x + (col * scale) as i32;
+                let py = y + (row * scale) as i32;
+                for dy in 0..scale as i32 {
+                    for dx in 0..scale as i32 {
+                        let (sx, sy) = (px + dx, py + dy);
+                        if sx < 0 || sy < 0 || sx as u32 >= self.width || sy as u32 >= self.height {
+                            continue;
+                        }
+                        self.draw_glyph_pixel(sx as u32, sy as u32, pixel, depth);
+                    }
+                }
+            }
+        }
+    }
+    // every pixel the transformed glyph box reaches, looked up in the glyph
+    fn draw_glyph_transformed(
+        &mut self,
+        x: i32,
+        y: i32,
+        glyph: &Glyph,
+        pixel: u32,
+        scale: u32,
+        depth: Option<f32>,
+    ) {
+        let Some(inverse) = self.transform_2d.inverse() else {
+            return;
+        };
+        let (w, h) = ((glyph.width * scale) as f32, (glyph.height * scale) as f32);
+        let (x_min, y_min, x_max, y_max) = self.bounds_2d(x as f32, y as f32, w, h);
+        for sy in y_min..y_max {
+            for sx in x_min..x_max {
+                let (u, v) = inverse.apply_2d(sx as f32 + 0.5, sy as f32 + 0.5);
+                let (u, v) = (u - x as f32, v - y as f32);
+                if u < 0.0 || v < 0.0 || u >= w || v >= h {
+                    continue;
+                }
+                if glyph.pixel(u as u32 / scale, v as u32 / scale) {
+                    self.draw_glyph_pixel(sx, sy, pixel, depth);
+                }
+            }
+        }
+    }
+    fn draw_glyph_pixel(&mut self, x: u32, y: u32, pixel: u32, depth: Option<f32>) {
+        if let Some(z) = depth {
+            let stored = self.z_buffer()[(x + y * self.stride) as usize];
+            if !self.depth.compare.passes(z, stored) {
+                return;
+            }
+        }
+        self.draw_pixel_unchecked(x, y, pixel);
+    }
+    // `text` in the builtin font next to where `camera` (framed like `draw_scene`)
+    // sees `world_pos`, e.g. to annotate vertices, joints or probes. Nothing is
+    // drawn for points behind the eye
+    #[cfg(feature = "model")]
+    pub fn draw_label_3d(
+        &mut self,
+        world_pos: &Vector3,
+        text: &str,
+        color: u32,
+        camera: &Camera,
+        options: &LabelOptions,
+    ) {
+        let forward = &camera.center - &camera.eye;
+        if (world_pos - &camera.eye).dot(&forward) <= 0.0 {
+            return;
+        }
+        let transform = camera.transform(self.width, self.height, 255.0);
+        let p = m2v(&(&transform * v2m(world_pos)));
+        let x = p.x().round() as i32 + options.offset.0;
+        let y = p.y().round() as i32 + options.offset.1;
+        let depth = options.depth_test.then_some(p.z());
+        // the label stays on its point whatever the 2D transform
+        let transform = std::mem::replace(&mut self.transform_2d, Matrix3::identity());
+        for glyph in TextLayout::default().layout(&[Span::scaled(text, color, options.scale)]) {
+            self.draw_glyph_at_depth(
+                x + glyph.x,
+                y + glyph.y,
+                glyph.glyph,
+                glyph.color,
+                glyph.scale,
+                depth,
+            );
+        }
+        self.transform_2d = transform;
+    }
+    // builtin font, (x, y) is the top left corner of the line
+    pub fn draw_char(&mut self, x: i32, y: i32, c: char, pixel: u32, scale: u32) {
+        let font = default_font();
+        let glyph = font.glyph(c).unwrap_or_else(|| font.replacement());
+        let scale = scale.max(1) as i32;
+        let y = y + (font.ascent() - glyph.top) * scale;
+        self.draw_glyph(x + glyph.x_offset * scale, y, glyph, pixel, scale as u32);
+    }
+    // no wrapping, see `TextLayout` for anything fancier
+    pub fn draw_text(&mut self, x: i32, y: i32, text: &str, pixel: u32, scale: u32) {
+        TextLayout::default().draw(self, x, y, &[Span::scaled(text, pixel, scale)]);
+    }
+}
+
+// a run of text sharing color and scale
+#[derive(Clone)]
+pub struct Span<'a> {
+    pub text: &'a str,
+    pub color: u32,
+    pub scale: u32,
+}
+
+impl<'a> Span<'a> {
+    pub fn new(text: &'a str, color: u32) -> Self {
+        Self {
+            text,
+            color,
+            scale: 1,
+        }
+    }
+    pub fn scaled(text: &'a str, color: u32, scale: u32) -> Self {
+        Self {
+            text,
+            color,
+            scale: scale.max(1),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+// everything in pixels, measured from the top left corner of the block
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TextMetrics {
+    pub width: u32,
+    pub height: u32,
+    // from the first baseline up to the top of the block
+    pub ascent: u32,
+    // from the last baseline down to the bottom of the block
+    pub descent: u32,
+    // y of every line's baseline
+    pub baselines: Vec<u32>,
+}
+
+// size of `text` drawn with `Renderer::draw_text` at `scale`
+pub fn measure_text(text: &str, scale: u32) -> (u32, u32) {
+    TextLayout::default().measure(&[Span::scaled(text, 0, scale)])
+}
+
+pub fn text_metrics(text: &str, scale: u32) -> TextMetrics {
+    TextLayout::default().metrics(&[Span::scaled(text, 0, scale)])
+}
+
+// (x, y) is the top left corner of the glyph bitmap
+#[derive(Clone)]
+pub struct PlacedGlyph<'f> {
+    pub x: i32,
+    pub y: i32,
+    pub c: char,
+    pub color: u32,
+    pub scale: u32,
+    pub glyph: &'f Glyph,
+}
+
+#[derive(Clone)]
+pub struct TextLayout<'f> {
+    // wrap lines at this many pixels, None only breaks on '\n'
+    pub width: Option<u32>,
+    pub align: Align,
+    // extra pixels between lines
+    pub line_spacing: u32,
+    // '\t' jumps to the next multiple of this many pixels, handy for tables
+    pub tab_stop: u32,
+    pub font: &'f FontStack,
+}
+
+impl Default for TextLayout<'_> {
+    fn default() -> Self {
+        Self {
+            width: None,
+            align: Align::Left,
+            line_spacing: 1,
+            tab_stop: ADVANCE * 8,
+            font: default_font(),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Item<'f> {
+    c: char,
+    color: u32,
+    scale: i32,
+    glyph: &'f Glyph,
+}
+
+struct Placed<'f> {
+    pen: i32,
+    // pixels the glyph was raised (or lowered) by to clear the glyph under a mark
+    lift: i32,
+    item: Item<'f>,
+}
+
+impl Placed<'_> {
+    // ink extent in pixels above the baseline as (top, bottom)
+    fn ink_heights(&self) -> Option<(i32, i32)> {
+        let (first, last) = self.item.glyph.ink_rows()?;
+        let top = self.item.glyph.top;
+        let s = self.item.scale;
+        Some((
+            (top - first) * s + self.lift,
+            (top - last - 1) * s + self.lift,
+        ))
+    }
+    // doubled ink center relative to the pen
+    fn ink_center2(&self) -> Option<i32> {
+        let (first, last) = self.item.glyph.ink_cols()?;
+        Some((2 * self.item.glyph.x_offset + first + last + 1) * self.item.scale)
+    }
+}
+
+struct Line<'f> {
+    glyphs: Vec<Placed<'f>>,
+    // pen position after the last base glyph
+    cursor: i32,
+    width: i32,
+    ascent: i32,
+    descent: i32,
+}
+
+impl<'f> Line<'f> {
+    fn new(font: &FontStack) -> Self {
+        Self {
+            glyphs: Vec::new(),
+            cursor: 0,
+            width: 0,
+            ascent: font.ascent(),
+            descent: font.descent(),
+        }
+    }
+    fn push(&mut self, pen: i32, lift: i32, item: Item<'f>) {
+        let glyph = item.glyph;
+        let s = item.scale;
+        if glyph.width > 0 {
+            self.width = self
+                .width
+                .max(pen + (glyph.x_offset + glyph.width as i32) * s);
+        }
+        self.ascent = self.ascent.max(glyph.top * s + lift);
+        self.descent = self
+            .descent
+            .max((glyph.height as i32 - glyph.top) * s - lift);
+        self.glyphs.push(Placed { pen, lift, item });
+    }
+    // centered over the last base glyph, moved vertically until it clears the
+    // base (or the marks already stacked on it)
+    fn mark_position(&self, mark: &Item) -> (i32, i32) {
+        let Some(base_index) = self.glyphs.iter().rposition(|p| !is_combining(p.item.c)) else {
+            return (self.cursor, 0);
+        };
+        let base = &self.glyphs[base_index];
+        let below = is_below_mark(mark.c);
+        let anchor = self.glyphs[base_index..]
+            .iter()
+            .rev()
+            .find(|p| !is_combining(p.item.c) || is_below_mark(p.item.c) == below)
+            .unwrap_or(base);
+        let probe = Placed {
+            pen: 0,
+            lift: 0,
+            item: *mark,
+        };
+        let pen = match (base.ink_center2(), probe.ink_center2()) {
+            (Some(base_center), Some(mark_center)) => base.pen + (base_center - mark_center) / 2,
+            _ => base.pen,
+        };
+        let lift = match (anchor.ink_heights(), probe.ink_heights()) {
+            (Some((anchor_top, anchor_bottom)), Some((mark_top, mark_bottom))) => {
+                let gap = mark.scale;
+                if below {
+                    (anchor_bottom - gap - mark_top).min(0)
+                } else {
+                    (anchor_top + gap - mark_bottom).max(0)
+                }
+            }
+            _ => 0,
+        };
+        (pen, lift)
+    }
+}
+
+impl<'f> TextLayout<'f> {
+    pub fn new(width: Option<u32>, align: Align) -> Self {
+        Self {
+            width,
+            align,
+            ..Default::default()
+        }
+    }
+    // resolve every character to a glyph, decomposing accented letters the fonts lack
+    fn itemize(&self, spans: &[Span]) -> Vec<Item<'f>> {
+        let font = self.font;
+        let mut items = Vec::new();
+        for span in spans {
+            let scale = span.scale.max(1) as i32;
+            let item = |c: char, glyph: &'f Glyph| Item {
+                c,
+                color: span.color,
+                scale,
+                glyph,
+            };
+            for c in span.text.chars() {
+                if let Some(glyph) = font.glyph(c) {
+                    items.push(item(c, glyph));
+                } else if let Some((base, mark)) = decompose(c).filter(|&(base, mark)| {
+                    font.glyph(base).is_some() && font.glyph(mark).is_some()
+                }) {
+                    items.push(item(base, font.glyph(base).unwrap()));
+                    items.push(item(mark, font.glyph(mark).unwrap()));
+                } else {
+                    items.push(item(c, font.replacement()));
+                }
+            }
+        }
+        items
+    }
+    fn break_lines(&self, spans: &[Span]) -> Vec<Line<'f>> {
+        let font = self.font;
+        let max_width = self.width.map(|w| w as i32).unwrap_or(i32::MAX);
+        let mut lines = vec![Line::new(font)];
+        // pending word and the whitespace (in pixels) in front of it
+        let mut word: Vec<Item> = Vec::new();
+        let mut gap = 0;
+
+        let place_word = |lines: &mut Vec<Line<'f>>, word: &mut Vec<Item<'f>>, gap: i32| {
+            let mut pen = 0;
+            let mut word_width = 0;
+            for item in word.iter().filter(|item| !is_combining(item.c)) {
+                let glyph = item.glyph;
+                word_width =
+                    word_width.max(pen + (glyph.x_offset + glyph.width as i32) * item.scale);
+                pen += glyph.advance as i32 * item.scale;
+            }
+            let line = lines.last_mut().unwrap();
+            let mut pen = if line.glyphs.is_empty() {
+                gap
+            } else {
+                line.cursor + gap
+            };
+            if !line.glyphs.is_empty() && pen + word_width > max_width {
+                lines.push(Line::new(font));
+                pen = 0;
+            }
+            for item in word.drain(..) {
+                if is_combining(item.c) {
+                    let line = lines.last_mut().unwrap();
+                    let (mark_pen, lift) = line.mark_position(&item);
+                    line.push(mark_pen, lift, item);
+                    continue;
+                }
+                // a word longer than the whole line gets split wherever it overflows
+                let extent = pen + (item.glyph.x_offset + item.glyph.width as i32) * item.scale;
+                if !lines.last().unwrap().glyphs.is_empty() && extent > max_width {
+                    lines.push(Line::new(font));
+                    pen = 0;
+                }
+                let line = lines.last_mut().unwrap();
+                line.push(pen, 0, item);
+                pen += item.glyph.advance as i32 * item.scale;
+                line.cursor = pen;
+            }
+        };
+
+        for item in self.itemize(spans) {
+            match item.c {
+                '\n' | ' ' | '\t' if !word.is_empty() => {
+                    place_word(&mut lines, &mut word, gap);
+                    gap = 0;
+                }
+                c if is_cjk(c) && !word.is_empty() => {
+                    place_word(&mut lines, &mut word, gap);
+                    gap = 0;
+                }
+                _ => {}
+            }
+            match item.c {
+                '\n' => {
+                    let line = lines.last_mut().unwrap();
+                    line.ascent = line.ascent.max(font.ascent() * item.scale);
+                    line.descent = line.descent.max(font.descent() * item.scale);
+                    lines.push(Line::new(font));
+                    gap = 0;
+                }
+                ' ' => gap += item.glyph.advance as i32 * item.scale,
+                '\t' => {
+                    let line = lines.last().unwrap();
+                    let tab_stop = self.tab_stop.max(1) as i32;
+                    let x = line.cursor + gap;
+                    gap = (x / tab_stop + 1) * tab_stop - line.cursor;
+                }
+                c if is_cjk(c) => {
+                    word.push(item);
+                    place_word(&mut lines, &mut word, gap);
+                    gap = 0;
+                }
+                _ => word.push(item),
+            }
+        }
+        if !word.is_empty() {
+            place_word(&mut lines, &mut word, gap);
+        }
+        lines
+    }
+    // glyph positions relative to the top left corner of the block
+    pub fn layout(&self, spans: &[Span]) -> Vec<PlacedGlyph<'f>> {
+        let lines = self.break_lines(spans);
+        let block_width = self
+            .width
+            .map(|w| w as i32)
+            .unwrap_or_else(|| lines.iter().map(|line| line.width).max().unwrap_or(0));
+        let mut glyphs = Vec::new();
+        let mut y = 0;
+        for line in lines.iter() {
+            let offset = match self.align {
+                Align::Left => 0,
+                Align::Center => (block_width - line.width).max(0) / 2,
+                Align::Right => (block_width - line.width).max(0),
+            };
+            for placed in line.glyphs.iter() {
+                let item = placed.item;
+                // every glyph sits on the line's baseline
+                glyphs.push(PlacedGlyph {
+                    x: placed.pen + item.glyph.x_offset * item.scale + offset,
+                    y: y + line.ascent - item.glyph.top * item.scale - placed.lift,
+                    c: item.c,
+                    color: item.color,
+                    scale: item.scale as u32,
+                    glyph: item.glyph,
+                });
+            }
+            y += line.ascent + line.descent + self.line_spacing as i32;
+        }
+        glyphs
+    }
+    pub fn metrics(&self, spans: &[Span]) -> TextMetrics {
+        let lines = self.break_lines(spans);
+        let mut baselines = Vec::with_capacity(lines.len());
+        let mut y = 0;
+        for line in lines.iter() {
+            baselines.push((y + line.ascent) as u32);
+            y += line.ascent + line.descent + self.line_spacing as i32;
+        }
+        let first = lines.first().unwrap();
+        let last = lines.last().unwrap();
+        TextMetrics {
+            width: lines.iter().map(|line| line.width).max().unwrap_or(0) as u32,
+            height: (y - self.line_spacing as i32).max(0) as u32,
+            ascent: first.ascent as u32,
+            descent: last.descent as u32,
+            baselines,
+        }
+    }
+    // (width, height) of the laid out block in pixels
+    pub fn measure(&self, spans: &[Span]) -> (u32, u32) {
+        let metrics = self.metrics(spans);
+        (metrics.width, metrics.height)
+    }
+    pub fn draw(&self, renderer: &mut Renderer, x: i32, y: i32, spans: &[Span]) {
+        for glyph in self.layout(spans) {
+            renderer.draw_glyph(
+                x + glyph.x,
+                y + glyph.y,
+                glyph.glyph,
+                glyph.color,
+                glyph.scale,
+            );
+        }
+    }
+}
@@ -0,0 +1,172 @@
+use std::{
+    collections::VecDeque,
+    fmt::Write as _,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+    time::{Duration, Instant},
+};
+
+// where the cpu time of a frame goes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pass {
+    Clear,
+    Vertex,
+    Raster,
+    Present,
+}
+
+impl Pass {
+    pub const ALL: [Pass; 4] = [Pass::Clear, Pass::Vertex, Pass::Raster, Pass::Present];
+    pub fn name(self) -> &'static str {
+        match self {
+            Pass::Clear => "clear",
+            Pass::Vertex => "vertex",
+            Pass::Raster => "raster",
+            Pass::Present => "present",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FrameTime {
+    // from the end of the frame before, waiting included
+    pub total: Duration,
+    // indexed like `Pass::ALL`
+    pub passes: [Duration; 4],
+}
+
+// frame times of the last `window` frames, for an overlay or to profile with.
+// Passes are timed with `time`, each frame is closed with `end_frame`
+pub struct FrameStats {
+    pub window: usize,
+    frames: VecDeque<FrameTime>,
+    current: FrameTime,
+    frame_start: Instant,
+    count: u64,
+    // a row per frame while recording, see `record_csv`
+    csv: Option<BufWriter<File>>,
+}
+
+impl FrameStats {
+    pub fn new(window: usize) -> Self {
+        Self {
+            window,
+            frames: VecDeque::with_capacity(window),
+            current: FrameTime::default(),
+            frame_start: Instant::now(),
+            count: 0,
+            csv: None,
+        }
+    }
+    // runs `f`, its time counted to `pass` of the current frame
+    pub fn time<R>(&mut self, pass: Pass, f: impl FnOnce() -> R) -> R {
+        let start = Instant::now();
+        let result = f();
+        self.add(pass, start.elapsed());
+        result
+    }
+    pub fn add(&mut self, pass: Pass, time: Duration) {
+        self.current.passes[pass as usize] += time;
+    }
+    pub fn end_frame(&mut self) {
+        let now = Instant::now();
+        self.current.total = now - self.frame_start;
+        self.frame_start = now;
+        if let Some(csv) = self.csv.as_mut() {
+            let ms = |d: Duration| d.as_secs_f64() * 1e3;
+            let mut row = format!("{},{:.3}", self.count, ms(self.current.total));
+            for pass in self.current.passes {
+                write!(row, ",{:.3}", ms(pass)).unwrap();
+            }
+            // a failing dump shouldn't stop the app, it just ends
+            if writeln!(csv, "{row}").is_err() {
+                self.csv = None;
+            }
+        }
+        if self.frames.len() == self.window {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(self.current);
+        self.current = FrameTime::default();
+        self.count += 1;
+    }
+    // from then on every frame is appended to `path` in milliseconds,
+    // as `frame,total,clear,vertex,raster,present`
+    pub fn record_csv(&mut self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut csv = BufWriter::new(File::create(path)?);
+        write!(csv, "frame,total")?;
+        for pass in Pass::ALL {
+            write!(csv, ",{}", pass.name())?;
+        }
+        writeln!(csv)?;
+        self.csv = Some(csv);
+        Ok(())
+    }
+    pub fn frames(&self) -> impl Iterator<Item = &FrameTime> {
+        self.frames.iter()
+    }
+    pub fn last(&self) -> Option<&FrameTime> {
+        self.frames.back()
+    }
+    pub fn average(&self) -> Duration {
+        self.mean(|frame| frame.total)
+    }
+    pub fn pass_average(&self, pass: Pass) -> Duration {
+        self.mean(|frame| frame.passes[pass as usize])
+    }
+    // the frame time `p` of the frames are at or below, e.g. 0.99
+    pub fn percentile(&self, p: f32) -> Duration {
+        let mut totals: Vec<Duration> = self.frames.iter().map(|frame| frame.total).collect();
+        if totals.is_empty() {
+            return Duration::ZERO;
+        }
+        totals.sort();
+        let i = (p.clamp(0.0, 1.0) * totals.len() as f32).ceil() as usize;
+        totals[i.saturating_sub(1)]
+    }
+    pub fn fps(&self) -> f32 {
+        let average = self.average().as_secs_f32();
+        if average > 0.0 {
+            1.0 / average
+        } else {
+            0.0
+        }
+    }
+    // how much one frame time differs from the next on average, even pacing
+    // is 0 and stutter at the same frame rate shows up here
+    pub fn pacing(&self) -> Duration {
+        if self.frames.len() < 2 {
+            return Duration::ZERO;
+        }
+        let jumps: Duration = self
+            .frames
+            .iter()
+            .zip(self.frames.iter().skip(1))
+            .map(|(a, b)| a.total.abs_diff(b.total))
+            .sum();
+        jumps / (self.frames.len() - 1) as u32
+    }
+    // two lines for an overlay, times in milliseconds
+    pub fn summary(&self) -> String {
+        let ms = |d: Duration| d.as_secs_f32() * 1e3;
+        let mut text = format!(
+            "{:.1} fps  avg {:.2}  p99 {:.2}  pacing {:.2}\n",
+            self.fps(),
+            ms(self.average()),
+            ms(self.percentile(0.99)),
+            ms(self.pacing()),
+        );
+        for pass in Pass::ALL {
+            write!(text, "{} {:.2}  ", pass.name(), ms(self.pass_average(pass))).unwrap();
+        }
+        text.truncate(text.trim_end().len());
+        text
+    }
+    fn mean(&self, time: impl Fn(&FrameTime) -> Duration) -> Duration {
+        if self.frames.is_empty() {
+            return Duration::ZERO;
+        }
+        self.frames.iter().map(time).sum::<Duration>() / self.frames.len() as u32
+    }
+}
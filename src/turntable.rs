@@ -0,0 +1,135 @@
+use std::{
+    f32::consts::PI,
+    fs,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    geometry::{m2v, v2m, Matrix4, Vector3},
+    ppm::{save_buffer_to_ppm_file, write_ppm},
+    renderer::Renderer,
+    scene::{Camera, Scene},
+};
+
+// what goes around during a turntable
+#[derive(Clone, Debug)]
+pub enum Motion {
+    // the eye circles the center
+    OrbitCamera,
+    // the node spins in place, on top of its own rotation
+    RotateNode(usize),
+}
+
+// a full turn about `axis` over `frames` frames of the scene, for showcase
+// clips. Frame i is at i / frames of the turn, so the clip loops seamlessly
+#[derive(Clone, Debug)]
+pub struct Turntable {
+    pub frames: u32,
+    pub width: u32,
+    pub height: u32,
+    pub motion: Motion,
+    pub axis: Vector3,
+    // the color the frames are cleared to
+    pub background: u32,
+}
+
+impl Turntable {
+    pub fn new(frames: u32, width: u32, height: u32, motion: Motion) -> Self {
+        Self {
+            frames,
+            width,
+            height,
+            motion,
+            axis: Vector3::new(0.0, 1.0, 0.0),
+            background: 0xff000000,
+        }
+    }
+    pub fn angle(&self, frame: u32) -> f32 {
+        2.0 * PI * frame as f32 / self.frames as f32
+    }
+    // draws one frame, the scene is left as it was
+    pub fn render_frame(
+        &self,
+        scene: &mut Scene,
+        camera: &Camera,
+        frame: u32,
+        buffer: &mut [u32],
+        z_buffer: &mut [f32],
+    ) {
+        let angle = self.angle(frame);
+        let mut camera = camera.clone();
+        let mut restore = None;
+        match self.motion {
+            Motion::OrbitCamera => {
+                let rotation = Matrix4::from_axis_angle(&self.axis, angle);
+                let offset = &camera.eye - &camera.center;
+                camera.eye = &camera.center + &m2v(&(rotation * v2m(&offset)));
+            }
+            Motion::RotateNode(id) => {
+                let transform = &mut scene.nodes[id].transform;
+                restore = Some(transform.clone());
+                transform.rotate(&self.axis, angle);
+            }
+        }
+        let mut renderer = Renderer::new(buffer, z_buffer, self.width, self.height);
+        renderer.fill(self.background);
+        renderer.draw_scene(scene, &camera);
+        if let (Motion::RotateNode(id), Some(transform)) = (&self.motion, restore) {
+            scene.nodes[*id].transform = transform;
+        }
+    }
+    // every frame, handed to `frame_done` as (index, pixels) once drawn
+    pub fn render(
+        &self,
+        scene: &mut Scene,
+        camera: &Camera,
+        mut frame_done: impl FnMut(u32, &[u32]) -> io::Result<()>,
+    ) -> io::Result<()> {
+        let size = (self.width * self.height) as usize;
+        let mut buffer = vec![0u32; size];
+        let mut z_buffer = vec![0f32; size];
+        for frame in 0..self.frames {
+            self.render_frame(scene, camera, frame, &mut buffer, &mut z_buffer);
+            frame_done(frame, &buffer)?;
+        }
+        Ok(())
+    }
+    // numbered images `frame_0000.ppm`, ... in `dir`, created if missing.
+    // `progress` gets (frames done, total) after each one
+    pub fn export_frames(
+        &self,
+        scene: &mut Scene,
+        camera: &Camera,
+        dir: impl AsRef<Path>,
+        mut progress: impl FnMut(u32, u32),
+    ) -> io::Result<Vec<PathBuf>> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+        let mut paths = Vec::with_capacity(self.frames as usize);
+        self.render(scene, camera, |frame, buffer| {
+            let path = dir.join(format!("frame_{frame:04}.ppm"));
+            save_buffer_to_ppm_file(buffer, self.width, self.height, self.width, &path)?;
+            paths.push(path);
+            progress(frame + 1, self.frames);
+            Ok(())
+        })?;
+        Ok(paths)
+    }
+    // the frames back to back as one ppm stream, see `write_ppm`
+    pub fn export_stream(
+        &self,
+        scene: &mut Scene,
+        camera: &Camera,
+        out: impl Write,
+        mut progress: impl FnMut(u32, u32),
+    ) -> io::Result<()> {
+        let mut out = BufWriter::new(out);
+        self.render(scene, camera, |frame, buffer| {
+            write_ppm(buffer, self.width, self.height, self.width, &mut out)?;
+            progress(frame + 1, self.frames);
+            Ok(())
+        })?;
+        out.flush()
+    }
+}
@@ -0,0 +1,32 @@
+// the reference scene against the image stored next to this file. Run with
+// OLIVE3D_BLESS=1 to store a new one after a change meant to alter the render
+use olive3d::{ppm, reference::render_reference_scene};
+
+const WIDTH: u32 = 160;
+const HEIGHT: u32 = 120;
+// float math differs a little between targets and features, more pixels than
+// this off by more than a few levels is a change in the render
+const TOLERANCE: usize = (WIDTH * HEIGHT / 200) as usize;
+
+#[test]
+fn reference_scene_matches_snapshot() {
+    let image = render_reference_scene(WIDTH, HEIGHT);
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/reference.ppm");
+    if std::env::var_os("OLIVE3D_BLESS").is_some() {
+        ppm::save_buffer_to_ppm_file(&image.buffer, WIDTH, HEIGHT, WIDTH, path).unwrap();
+        return;
+    }
+    let stored = ppm::load_ppm_file_to_buffer(path);
+    assert_eq!((stored.width, stored.height), (WIDTH, HEIGHT));
+    let channel = |pixel: u32, i: u32| ((pixel >> (8 * i)) & 0xff) as i32;
+    let off = image
+        .buffer
+        .iter()
+        .zip(stored.buffer.iter())
+        .filter(|&(&a, &b)| (0..3).any(|i| (channel(a, i) - channel(b, i)).abs() > 8))
+        .count();
+    assert!(
+        off <= TOLERANCE,
+        "{off} pixels differ from tests/reference.ppm"
+    );
+}